@@ -1,3 +1,88 @@
+use crate::editor::FilePositions;
+use crate::file_ops::archive::ArchiveKind;
+use crate::file_ops::DiskUsage;
+use crate::frecency::VisitedDirs;
+use crate::notifications::{Level, Notifications};
+use crate::ssh::client::shell_quote;
+use crate::tabs::Tabs;
+use crate::tui::columns::{default_columns, Column};
+use crate::tui::help::HelpOverlay;
+use crate::tui::icons::IconMode;
+use crate::tui::image::ImageInfo;
+use crate::tui::jump::JumpPrompt;
+use crate::tui::layout::PanelLayout;
+use crate::tui::palette::Palette;
+use crate::tui::shell_switcher::ShellSwitcher;
+use crate::tui::prompt::{Prompt, PromptHistory};
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How often to re-query disk usage while staying in one directory
+pub const DISK_USAGE_REFRESH: Duration = Duration::from_secs(60);
+
+/// How long entries that appeared on a refresh stay highlighted
+pub const NEW_ENTRY_HIGHLIGHT: Duration = Duration::from_secs(3);
+
+/// How often to time a round trip to the server for the header
+pub const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Round trips slower than this are shown as a warning
+pub const SLOW_LATENCY: Duration = Duration::from_millis(300);
+
+/// What the last connection health check found
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ConnectionHealth {
+    /// Not checked yet
+    #[default]
+    Unknown,
+    /// The server answered after this long
+    Responding(Duration),
+    /// No answer in time: the server is stuck or the network is gone
+    NoReply,
+    Disconnected,
+}
+
+impl ConnectionHealth {
+    /// Text for the header, if there is anything to show
+    pub fn label(&self) -> Option<String> {
+        match self {
+            ConnectionHealth::Unknown => None,
+            ConnectionHealth::Responding(latency) => Some(format!(" [{} ms]", latency.as_millis())),
+            ConnectionHealth::NoReply => Some(String::from(" [no reply]")),
+            ConnectionHealth::Disconnected => Some(String::from(" [disconnected]")),
+        }
+    }
+}
+
+/// Frames of the spinner shown while a slow operation runs
+const SPINNER_FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// How long each spinner frame is shown
+pub const SPINNER_FRAME_TIME: Duration = Duration::from_millis(80);
+
+/// A slow operation the footer shows a spinner for, e.g. "Loading /var/log"
+#[derive(Debug, Clone)]
+pub struct Loading {
+    pub label: String,
+    started: Instant,
+}
+
+impl Loading {
+    pub fn new(label: String) -> Self {
+        Self { label, started: Instant::now() }
+    }
+
+    /// The spinner frame for `now`
+    pub fn spinner(&self, now: Instant) -> char {
+        let frame = now.duration_since(self.started).as_millis() / SPINNER_FRAME_TIME.as_millis();
+        SPINNER_FRAMES[frame as usize % SPINNER_FRAMES.len()]
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct FileEntry {
     pub name: String,
@@ -5,17 +90,833 @@ pub struct FileEntry {
     pub is_dir: bool,
     pub size: u64,
     pub modified: Option<i64>,
+    pub accessed: Option<i64>,
+    /// Unix mode bits, including the file type
     pub permissions: Option<u32>,
+    /// Owner and group names, or numeric ids when the server sends no names
+    pub owner: Option<String>,
+    pub group: Option<String>,
+    /// Where the entry points if it is a symlink
+    pub link_target: Option<String>,
+    /// Symlink whose target does not exist
+    pub broken_link: bool,
+}
+
+impl FileEntry {
+    /// Entry whose metadata is unknown apart from its type
+    pub fn new(name: String, path: String, is_dir: bool) -> Self {
+        Self {
+            name,
+            path,
+            is_dir,
+            size: 0,
+            modified: None,
+            accessed: None,
+            permissions: None,
+            owner: None,
+            group: None,
+            link_target: None,
+            broken_link: false,
+        }
+    }
+
+    /// The `..` entry leading to the parent directory
+    /// Whether this looks like something to run: executable, or a `.sh` file
+    pub fn is_script(&self) -> bool {
+        !self.is_dir && (self.permissions.is_some_and(|mode| mode & 0o111 != 0) || self.name.ends_with(".sh"))
+    }
+
+    pub fn parent() -> Self {
+        Self::new(String::from(".."), String::from(".."), true)
+    }
+}
+
+/// Path segments as (label, absolute path), starting with the root
+pub fn breadcrumbs(path: &str) -> Vec<(String, String)> {
+    let mut crumbs = vec![(String::from("/"), String::from("/"))];
+    let mut current = String::new();
+    for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+        current.push('/');
+        current.push_str(segment);
+        crumbs.push((segment.to_string(), current.clone()));
+    }
+    crumbs
+}
+
+/// Listing order: directories first, then by name
+pub fn sort_entries(entries: &mut [FileEntry]) {
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+}
+
+/// Entries left after hiding dotfiles (unless `show_hidden`) and applying a
+/// fuzzy filter query. The parent entry never matches a query.
+pub fn visible_entries<'a>(
+    entries: &'a [FileEntry],
+    show_hidden: bool,
+    query: Option<&'a str>,
+) -> impl Iterator<Item = &'a FileEntry> {
+    entries
+        .iter()
+        .filter(move |file| show_hidden || !is_hidden(&file.name))
+        .filter(move |file| query.is_none_or(|query| file.name != ".." && fuzzy_match(&file.name, query)))
+}
+
+/// Single-line text being edited in a prompt
+#[derive(Debug, Clone, Default)]
+pub struct TextInput {
+    pub value: String,
+    /// Cursor position in characters
+    pub cursor: usize,
+}
+
+impl TextInput {
+    pub fn new(value: &str) -> Self {
+        Self {
+            value: value.to_string(),
+            cursor: value.chars().count(),
+        }
+    }
+
+    fn byte_index(&self) -> usize {
+        self.value
+            .char_indices()
+            .nth(self.cursor)
+            .map(|(i, _)| i)
+            .unwrap_or(self.value.len())
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        let idx = self.byte_index();
+        self.value.insert(idx, c);
+        self.cursor += 1;
+    }
+
+    pub fn delete_char(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            let idx = self.byte_index();
+            self.value.remove(idx);
+        }
+    }
+
+    pub fn delete_char_forward(&mut self) {
+        if self.cursor < self.value.chars().count() {
+            let idx = self.byte_index();
+            self.value.remove(idx);
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn move_right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.value.chars().count());
+    }
+
+    pub fn move_home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn move_end(&mut self) {
+        self.cursor = self.value.chars().count();
+    }
+
+    /// Insert `text` at the cursor as a separate word, adding a space
+    /// before it when it would run into the word there
+    pub fn insert_word(&mut self, text: &str) {
+        let before = self.value.chars().nth(self.cursor.wrapping_sub(1));
+        if before.is_some_and(|c| !c.is_whitespace()) {
+            self.insert_char(' ');
+        }
+        text.chars().for_each(|c| self.insert_char(c));
+    }
+
+    /// Apply a cursor-movement or editing key
+    pub fn edit(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Backspace => self.delete_char(),
+            KeyCode::Delete => self.delete_char_forward(),
+            KeyCode::Left => self.move_left(),
+            KeyCode::Right => self.move_right(),
+            KeyCode::Home => self.move_home(),
+            KeyCode::End => self.move_end(),
+            KeyCode::Char(c) => self.insert_char(c),
+            _ => {}
+        }
+    }
+
+    /// The value with a block cursor drawn at the cursor position
+    pub fn with_cursor(&self) -> String {
+        let mut rendered: String = self.value.chars().take(self.cursor).collect();
+        rendered.push('█');
+        rendered.extend(self.value.chars().skip(self.cursor));
+        rendered
+    }
+}
+
+/// In-progress rename of a file list entry
+pub struct RenameState {
+    pub index: usize,
+    pub original_name: String,
+    pub prompt: Prompt,
+}
+
+/// Change of owner and/or group for the selected entries
+pub struct ChownState {
+    pub entries: Vec<FileEntry>,
+    /// `owner[:group]` or `:group`
+    pub prompt: Prompt,
+}
+
+impl ChownState {
+    pub fn new(entries: Vec<FileEntry>) -> Self {
+        let current = entries
+            .first()
+            .map(|file| {
+                format!(
+                    "{}:{}",
+                    file.owner.as_deref().unwrap_or_default(),
+                    file.group.as_deref().unwrap_or_default()
+                )
+            })
+            .unwrap_or_default();
+        let prompt = Prompt::new(&format!("Owner of {} item(s)", entries.len()), &current)
+            .with_hint("owner, owner:group or :group  Esc cancel")
+            .with_validator(|value| parse_owner_spec(value).map(|_| ()));
+        Self { entries, prompt }
+    }
+}
+
+/// Archive name prompt for compressing the selected entries. The format
+/// follows the name's extension.
+pub struct CompressState {
+    pub entries: Vec<FileEntry>,
+    pub prompt: Prompt,
+}
+
+impl CompressState {
+    /// `check_name` rejects names that cannot be created in the directory
+    pub fn new(entries: Vec<FileEntry>, check_name: impl Fn(&str) -> Result<(), String> + 'static) -> Self {
+        let stem = match entries.as_slice() {
+            [entry] => entry.name.clone(),
+            _ => String::from("archive"),
+        };
+        let prompt = Prompt::new("Archive name", &format!("{}{}", stem, ArchiveKind::TarGz.extension()))
+            .with_hint("Tab changes the format  Esc cancel")
+            .with_validator(move |value| {
+                if ArchiveKind::from_name(value.trim()).is_none() {
+                    return Err(String::from("Name must end in .tar, .tar.gz, .tar.xz, .tar.bz2 or .zip"));
+                }
+                check_name(value)
+            });
+        Self { entries, prompt }
+    }
+
+    /// Selected format, if the name has a supported extension
+    pub fn kind(&self) -> Option<ArchiveKind> {
+        ArchiveKind::from_name(self.prompt.input.value.trim())
+    }
+
+    /// Swap the extension for the next supported format
+    pub fn cycle_format(&mut self) {
+        let input = &mut self.prompt.input;
+        let (stem, next) = match ArchiveKind::split_name(&input.value) {
+            Some((stem, kind)) => (stem.to_string(), kind.next()),
+            None => (input.value.clone(), ArchiveKind::TarGz),
+        };
+        *input = TextInput::new(&format!("{}{}", stem, next.extension()));
+    }
+}
+
+/// Prompt for the remote file that receives the local clipboard text
+pub struct PasteTextState {
+    pub prompt: Prompt,
+    /// Add to the end of an existing file instead of replacing it
+    pub append: bool,
+}
+
+impl PasteTextState {
+    /// `check_name` rejects names that cannot be written to
+    pub fn new(name: &str, check_name: impl Fn(&str) -> Result<(), String> + 'static) -> Self {
+        let prompt = Prompt::new("", name)
+            .with_hint("Tab append/overwrite  Esc cancel")
+            .with_validator(check_name);
+        let mut paste = Self { prompt, append: false };
+        paste.toggle_append();
+        paste
+    }
+
+    pub fn toggle_append(&mut self) {
+        self.append = !self.append;
+        let mode = if self.append { "append" } else { "overwrite" };
+        self.prompt.set_title(&format!("Write clipboard to [{}]", mode));
+    }
+}
+
+/// Which way a batch moves files
+#[derive(Debug, Clone, PartialEq)]
+pub enum Direction {
+    /// Into the local working directory
+    Download,
+    /// Into the remote directory `remote_dir`
+    Upload { remote_dir: String },
+}
+
+/// Files waiting to be transferred, paused while the user decides what to
+/// do about a file that would be overwritten
+#[derive(Debug, Clone)]
+pub struct TransferBatch {
+    pub direction: Direction,
+    /// Remaining files, remote for a download and local for an upload;
+    /// while paused, the first one is the conflict
+    pub queue: VecDeque<FileEntry>,
+    /// File the first queued transfer would replace, while paused
+    pub existing: Option<FileEntry>,
+    pub total: usize,
+    /// Overwrite existing files for the rest of the batch without asking
+    pub overwrite_all: bool,
+    /// Names written, with the offset each transfer resumed from
+    pub transferred: Vec<(String, u64)>,
+    pub skipped: usize,
+    /// Names that failed, with the error
+    pub failed: Vec<(String, String)>,
+}
+
+impl TransferBatch {
+    pub fn new(direction: Direction, files: Vec<FileEntry>) -> Self {
+        Self {
+            direction,
+            total: files.len(),
+            queue: files.into(),
+            existing: None,
+            overwrite_all: false,
+            transferred: Vec::new(),
+            skipped: 0,
+            failed: Vec::new(),
+        }
+    }
+
+    /// Note how the transfer of `file` to `target` went: the offset it
+    /// resumed from, or the error
+    pub fn record(&mut self, file: &FileEntry, target: &str, result: Result<u64, String>) {
+        match result {
+            Ok(offset) => self.transferred.push((target.to_string(), offset)),
+            Err(e) => self.failed.push((file.name.clone(), e)),
+        }
+    }
+
+    /// The file paused on and the one it would replace
+    pub fn conflict(&self) -> Option<(&FileEntry, &FileEntry)> {
+        self.queue.front().zip(self.existing.as_ref())
+    }
+
+    /// Status line once the batch has finished or been cancelled
+    pub fn summary(&self) -> String {
+        let (done, failed) = match self.direction {
+            Direction::Download => ("Downloaded", "Download failed"),
+            Direction::Upload { .. } => ("Uploaded", "Upload failed"),
+        };
+        if self.total == 1 {
+            if let Some((name, offset)) = self.transferred.first() {
+                return match offset {
+                    0 => format!("{}: {}", done, name),
+                    offset => format!("{}: {} (resumed at {} bytes)", done, name, offset),
+                };
+            }
+            if let Some((_, error)) = self.failed.first() {
+                return format!("{}: {}", failed, error);
+            }
+        }
+
+        let mut summary = if self.transferred.len() == self.total {
+            format!("{} {} files", done, self.total)
+        } else {
+            format!("{} {} of {} files", done, self.transferred.len(), self.total)
+        };
+        if self.skipped > 0 {
+            summary.push_str(&format!(", {} skipped", self.skipped));
+        }
+        if !self.failed.is_empty() {
+            let names: Vec<&str> = self.failed.iter().map(|(name, _)| name.as_str()).collect();
+            summary.push_str(&format!("; failed: {}", names.join(", ")));
+        }
+        summary
+    }
+}
+
+/// First of `name`, `stem (1).ext`, `stem (2).ext`, ... that is not taken
+pub fn unique_name(name: &str, taken: impl Fn(&str) -> bool) -> String {
+    if !taken(name) {
+        return name.to_string();
+    }
+    // Dotfiles like .bashrc have no extension
+    let (stem, extension) = match name.rfind('.') {
+        Some(dot) if dot > 0 => name.split_at(dot),
+        _ => (name, ""),
+    };
+    (1..)
+        .map(|n| format!("{} ({}){}", stem, n, extension))
+        .find(|candidate| !taken(candidate))
+        .unwrap_or_default()
+}
+
+/// Check a proposed entry name; `taken` tells whether another entry has it
+fn check_name(name: &str, taken: impl Fn(&str) -> bool) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("Name cannot be empty".to_string());
+    }
+    if name == "." || name == ".." {
+        return Err(format!("Invalid name: {}", name));
+    }
+    if name.contains('/') {
+        return Err("Name cannot contain '/'".to_string());
+    }
+    // Hidden and filtered out entries would be overwritten just the same
+    if taken(name) {
+        return Err(format!("'{}' already exists", name));
+    }
+    Ok(())
+}
+
+/// Split a chown spec (`user`, `user:group`, `:group`) into its parts
+pub fn parse_owner_spec(spec: &str) -> Result<(Option<String>, Option<String>), String> {
+    let (owner, group) = match spec.trim().split_once(':') {
+        Some((owner, group)) => (owner, group),
+        None => (spec.trim(), ""),
+    };
+
+    let non_empty = |part: &str| (!part.is_empty()).then(|| part.to_string());
+    let (owner, group) = (non_empty(owner), non_empty(group));
+
+    if owner.is_none() && group.is_none() {
+        return Err("Enter an owner, owner:group or :group".to_string());
+    }
+    Ok((owner, group))
+}
+
+/// Most directories remembered in each direction of the history
+const HISTORY_LIMIT: usize = 100;
+
+/// A visited directory and the entry that was selected there
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct HistoryEntry {
+    pub path: String,
+    pub selected: Option<String>,
+}
+
+/// Browser-style back/forward history of visited directories
+#[derive(Debug, Default)]
+pub struct DirHistory {
+    back: Vec<HistoryEntry>,
+    forward: Vec<HistoryEntry>,
+}
+
+impl DirHistory {
+    /// Record leaving `current` for a new directory; clears the forward history
+    pub fn visit(&mut self, current: HistoryEntry) {
+        if self.back.last() != Some(&current) {
+            self.back.push(current);
+        }
+        if self.back.len() > HISTORY_LIMIT {
+            self.back.remove(0);
+        }
+        self.forward.clear();
+    }
+
+    pub fn back_target(&self) -> Option<&HistoryEntry> {
+        self.back.last()
+    }
+
+    pub fn forward_target(&self) -> Option<&HistoryEntry> {
+        self.forward.last()
+    }
+
+    /// Complete a move back, remembering `current` for going forward again
+    pub fn commit_back(&mut self, current: HistoryEntry) {
+        if self.back.pop().is_some() {
+            self.forward.push(current);
+        }
+    }
+
+    /// Complete a move forward, remembering `current` for going back again
+    pub fn commit_forward(&mut self, current: HistoryEntry) {
+        if self.forward.pop().is_some() {
+            self.back.push(current);
+        }
+    }
+}
+
+/// Most directories whose selected entry is remembered
+const SELECTIONS_LIMIT: usize = 200;
+
+/// The entry last selected in each directory left, so coming back to one
+/// selects it again; least recently left first
+#[derive(Debug, Default)]
+pub struct DirSelections {
+    entries: Vec<HistoryEntry>,
+}
+
+impl DirSelections {
+    pub fn restore(entries: Vec<HistoryEntry>) -> Self {
+        let excess = entries.len().saturating_sub(SELECTIONS_LIMIT);
+        Self {
+            entries: entries.into_iter().skip(excess).collect(),
+        }
+    }
+
+    /// Remember where the selection was on leaving a directory
+    pub fn leave(&mut self, place: HistoryEntry) {
+        if place.selected.is_none() {
+            return;
+        }
+        self.entries.retain(|entry| entry.path != place.path);
+        self.entries.push(place);
+        let excess = self.entries.len().saturating_sub(SELECTIONS_LIMIT);
+        self.entries.drain(..excess);
+    }
+
+    /// Name of the entry that was selected in `path`
+    pub fn get(&self, path: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|entry| entry.path == path)
+            .and_then(|entry| entry.selected.as_deref())
+    }
+
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.entries
+    }
+}
+
+/// Incremental filter over the file list
+pub struct FilterState {
+    pub prompt: Prompt,
+    /// Entry selected before filtering, restored on cancel
+    pub previous_selection: Option<String>,
+}
+
+impl FilterState {
+    pub fn query(&self) -> &str {
+        &self.prompt.input.value
+    }
+}
+
+/// Permission editor: a 3x3 rwx grid for owner/group/other
+#[derive(Debug, Clone)]
+pub struct ChmodState {
+    pub entries: Vec<FileEntry>,
+    /// Permission bits being edited (0o777 range)
+    pub mode: u32,
+    /// Grid cursor: row is owner/group/other, column is r/w/x
+    pub row: usize,
+    pub col: usize,
+}
+
+impl ChmodState {
+    pub fn new(entries: Vec<FileEntry>) -> Self {
+        let mode = entries
+            .first()
+            .and_then(|file| file.permissions)
+            .map(|mode| mode & 0o777)
+            .unwrap_or(0o644);
+        Self {
+            entries,
+            mode,
+            row: 0,
+            col: 0,
+        }
+    }
+
+    fn bit(row: usize, col: usize) -> u32 {
+        1 << ((2 - row) * 3 + (2 - col))
+    }
+
+    pub fn is_set(&self, row: usize, col: usize) -> bool {
+        self.mode & Self::bit(row, col) != 0
+    }
+
+    pub fn toggle(&mut self) {
+        self.mode ^= Self::bit(self.row, self.col);
+    }
+
+    pub fn move_cursor(&mut self, row_delta: isize, col_delta: isize) {
+        self.row = (self.row as isize + row_delta).rem_euclid(3) as usize;
+        self.col = (self.col as isize + col_delta).rem_euclid(3) as usize;
+    }
+
+    /// Set the current row from an octal digit and move to the next row
+    pub fn set_digit(&mut self, digit: u32) {
+        let shift = (2 - self.row) * 3;
+        self.mode = (self.mode & !(0o7 << shift)) | ((digit & 0o7) << shift);
+        self.row = (self.row + 1) % 3;
+    }
+
+    /// New mode for an entry, keeping its setuid/setgid/sticky bits
+    pub fn mode_for(&self, entry: &FileEntry) -> u32 {
+        entry.permissions.unwrap_or(0) & 0o7000 | self.mode
+    }
+}
+
+/// Bytes read from the start of a file for the preview pane
+pub const PREVIEW_BYTES: usize = 16 * 1024;
+
+/// What the preview pane shows for the selected entry
+#[derive(Debug, Clone, PartialEq)]
+pub enum PreviewContent {
+    Text(String),
+    Binary,
+    /// Image metadata, with the file contents if small enough to draw inline
+    Image {
+        info: ImageInfo,
+        data: Option<Arc<Vec<u8>>>,
+    },
+    Directory,
+    Error(String),
+}
+
+impl PreviewContent {
+    /// Text if the bytes look like text, otherwise Binary
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        if bytes.contains(&0) {
+            return PreviewContent::Binary;
+        }
+        PreviewContent::Text(String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+/// Preview of one entry, keyed by path so it can be refreshed on selection change
+#[derive(Debug, Clone)]
+pub struct Preview {
+    pub path: String,
+    pub content: PreviewContent,
+    /// The file is larger than the preview
+    pub truncated: bool,
+}
+
+/// Whether pasting clipboard entries copies or moves them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClipboardMode {
+    Copy,
+    Move,
+}
+
+/// Entries yanked in the browser, waiting to be pasted into another directory
+#[derive(Debug, Clone)]
+pub struct FileClipboard {
+    pub mode: ClipboardMode,
+    pub entries: Vec<FileEntry>,
 }
 
 pub struct App {
     pub current_path: String,
+    /// Entries currently shown, after hiding dotfiles if requested
     pub files: Vec<FileEntry>,
+    /// Full listing of the current directory
+    pub all_files: Vec<FileEntry>,
+    pub show_hidden: bool,
+    /// Show permissions, owner and mtime columns
+    pub detailed_view: bool,
+    pub filter: Option<FilterState>,
+    pub history: DirHistory,
+    /// Entry selected in each directory left, for when it is entered again
+    pub selections: DirSelections,
+    /// Cursor position in each file edited lately, for when it is opened again
+    pub file_positions: FilePositions,
+    /// Every directory entered on the connection, for the `z` prompt
+    pub visited: VisitedDirs,
     pub selected_index: usize,
     pub should_quit: bool,
-    pub status_message: String,
+    pub notifications: Notifications,
     pub connection_string: String,
-    pub has_background_shell: bool,
+    /// Colour the saved connection asks for, used for the header
+    pub connection_color: Option<Color>,
+    /// Named session open, shown in the header
+    pub session_name: Option<String>,
+    /// Shells open on the connection, shown in the header
+    pub background_shells: usize,
+    pub socks_port: Option<u16>,
+    pub reconnecting: bool,
+    pub rename: Option<RenameState>,
+    /// Paths of entries marked for batch operations
+    pub marked: HashSet<String>,
+    /// Start of an in-progress visual range selection
+    pub visual_anchor: Option<usize>,
+    pub clipboard: Option<FileClipboard>,
+    /// Entries waiting for the user to confirm deletion
+    pub pending_delete: Option<Vec<FileEntry>>,
+    /// Glob batch download waiting for confirmation
+    pub pending_download: Option<Vec<FileEntry>>,
+    /// Transfer paused on a target file that already exists
+    pub pending_overwrite: Option<TransferBatch>,
+    /// The batch whose transfer is running in the background
+    pub transferring: Option<TransferBatch>,
+    /// Quit asked for while jobs are still running
+    pub confirm_quit: bool,
+    /// Batch command prompt, e.g. `rm *.log.1`
+    pub command: Option<Prompt>,
+    pub command_history: PromptHistory,
+    /// Remote command prompt, with `{}` standing for the selected entry
+    pub execute: Option<Prompt>,
+    pub execute_history: PromptHistory,
+    /// Picker over the configured commands
+    pub palette: Option<Palette>,
+    /// Prompt to jump to a visited directory
+    pub jump: Option<JumpPrompt>,
+    /// List of the connection's shells
+    pub shell_switcher: Option<ShellSwitcher>,
+    /// Script about to be run, with the prompt for its arguments
+    pub run_script: Option<(FileEntry, Prompt)>,
+    pub script_args_history: PromptHistory,
+    pub chmod: Option<ChmodState>,
+    pub chown: Option<ChownState>,
+    pub compress: Option<CompressState>,
+    pub paste_text: Option<PasteTextState>,
+    /// Prompt for the local file or directory to upload here
+    pub upload: Option<Prompt>,
+    /// Keybinding overlay opened with `?`
+    pub help: Option<HelpOverlay>,
+    /// Panels shown around the file list
+    pub layout: PanelLayout,
+    /// Directory tabs other than the one shown
+    pub tabs: Tabs,
+    /// Path and SHA-256 of the last file whose checksum was computed
+    pub checksum: Option<(String, String)>,
+    /// Glyphs for the file list, already resolved from `auto`
+    pub icons: IconMode,
+    /// File list columns from the config
+    pub columns: Vec<Column>,
+    /// `sftp://user@host:port` when entry names are drawn as OSC 8 links
+    pub hyperlink_base: Option<String>,
+    pub preview: Option<Preview>,
+    /// Space on the filesystem of the current directory, if known
+    pub disk_usage: Option<DiskUsage>,
+    /// Directory and time of the last disk usage query
+    disk_usage_checked: Option<(String, Instant)>,
+    pub health: ConnectionHealth,
+    health_checked: Option<Instant>,
+    /// First key of a browser key sequence such as `gg`
+    pub pending_key: Option<char>,
+    /// Spinners of the slow operations running, oldest first, with the
+    /// worker job each belongs to; the footer shows the newest
+    loading: Vec<(Option<u64>, Loading)>,
+    /// Paths that appeared on the last refresh, and when
+    new_entries: HashSet<String>,
+    new_entries_at: Option<Instant>,
+}
+
+/// Dotfiles are hidden; the parent entry always stays visible
+fn is_hidden(name: &str) -> bool {
+    name.starts_with('.') && name != ".."
+}
+
+/// Case-insensitive fuzzy match: every query character appears in order
+pub fn fuzzy_match(name: &str, query: &str) -> bool {
+    let mut name_chars = name.chars().flat_map(char::to_lowercase);
+    query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|q| name_chars.any(|c| c == q))
+}
+
+/// Shell-style glob match supporting `*`, `?` and `[...]` classes
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Pattern position after the last `*`, and the name position it resumes from
+    let mut star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        let step = match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+                continue;
+            }
+            Some('?') => Some(p + 1),
+            Some('[') => match match_class(&pattern, p, name[n]) {
+                Some((true, next)) => Some(next),
+                Some((false, _)) => None,
+                // An unterminated class is a literal '['
+                None => (name[n] == '[').then_some(p + 1),
+            },
+            Some(&c) => (c == name[n]).then_some(p + 1),
+            None => None,
+        };
+
+        match (step, star) {
+            (Some(next), _) => {
+                p = next;
+                n += 1;
+            }
+            (None, Some((star_p, star_n))) => {
+                // Let the last `*` swallow one more character
+                p = star_p;
+                n = star_n + 1;
+                star = Some((star_p, star_n + 1));
+            }
+            (None, None) => return false,
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Match `c` against the class starting at `pattern[start] == '['`. Returns
+/// whether it matched and the position after the class, or None if unterminated.
+fn match_class(pattern: &[char], start: usize, c: char) -> Option<(bool, usize)> {
+    let mut i = start + 1;
+    let negate = matches!(pattern.get(i), Some('!') | Some('^'));
+    if negate {
+        i += 1;
+    }
+
+    let mut matched = false;
+    let first = i;
+    while let Some(&ch) = pattern.get(i) {
+        // A ']' right after the opening bracket is a literal
+        if ch == ']' && i > first {
+            return Some((matched != negate, i + 1));
+        }
+        match (pattern.get(i + 1), pattern.get(i + 2)) {
+            (Some('-'), Some(&end)) if end != ']' => {
+                matched |= (ch..=end).contains(&c);
+                i += 3;
+            }
+            _ => {
+                matched |= ch == c;
+                i += 1;
+            }
+        }
+    }
+    None
+}
+
+/// What a glob batch command does with its matches
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BatchAction {
+    Delete,
+    Download,
+    Mark,
+}
+
+/// Parse a batch command such as `rm *.log.1` into its action and patterns
+pub fn parse_batch_command(input: &str) -> Result<(BatchAction, Vec<String>), String> {
+    let mut words = input.split_whitespace();
+    let action = match words.next() {
+        Some("rm") | Some("delete") => BatchAction::Delete,
+        Some("get") | Some("download") => BatchAction::Download,
+        Some("mark") | Some("select") => BatchAction::Mark,
+        Some(other) => return Err(format!("Unknown command: {} (use rm, get or mark)", other)),
+        None => return Err("Empty command".to_string()),
+    };
+
+    let patterns: Vec<String> = words.map(str::to_string).collect();
+    if patterns.is_empty() {
+        return Err("No pattern given".to_string());
+    }
+    Ok((action, patterns))
 }
 
 impl App {
@@ -23,39 +924,1199 @@ impl App {
         Self {
             current_path: String::from("/"),
             files: Vec::new(),
+            all_files: Vec::new(),
+            show_hidden: true,
+            detailed_view: false,
+            filter: None,
+            history: DirHistory::default(),
+            selections: DirSelections::default(),
+            file_positions: FilePositions::default(),
+            visited: VisitedDirs::default(),
             selected_index: 0,
             should_quit: false,
-            status_message: String::new(),
+            notifications: Notifications::default(),
             connection_string,
-            has_background_shell: false,
+            connection_color: None,
+            session_name: None,
+            background_shells: 0,
+            socks_port: None,
+            reconnecting: false,
+            rename: None,
+            marked: HashSet::new(),
+            visual_anchor: None,
+            clipboard: None,
+            pending_delete: None,
+            confirm_quit: false,
+            pending_download: None,
+            pending_overwrite: None,
+            transferring: None,
+            command: None,
+            execute: None,
+            execute_history: PromptHistory::default(),
+            palette: None,
+            jump: None,
+            shell_switcher: None,
+            run_script: None,
+            script_args_history: PromptHistory::default(),
+            command_history: PromptHistory::default(),
+            chmod: None,
+            chown: None,
+            compress: None,
+            paste_text: None,
+            upload: None,
+            help: None,
+            layout: PanelLayout::default(),
+            tabs: Tabs::default(),
+            checksum: None,
+            icons: IconMode::Emoji,
+            columns: default_columns(),
+            hyperlink_base: None,
+            preview: None,
+            disk_usage: None,
+            disk_usage_checked: None,
+            health: ConnectionHealth::Unknown,
+            health_checked: None,
+            pending_key: None,
+            loading: Vec::new(),
+            new_entries: HashSet::new(),
+            new_entries_at: None,
         }
     }
 
-    pub fn select_next(&mut self) {
-        if !self.files.is_empty() {
-            self.selected_index = (self.selected_index + 1) % self.files.len();
+    /// Replace the directory listing, applying the current view settings
+    pub fn set_files(&mut self, files: Vec<FileEntry>) {
+        self.all_files = files;
+        self.apply_view();
+    }
+
+    /// Add a batch of a directory that is still loading, keeping the cursor
+    /// on the same entry
+    pub fn append_files(&mut self, batch: Vec<FileEntry>) {
+        let selected_name = self.get_selected_file().map(|file| file.name.clone());
+        self.all_files.extend(batch);
+        sort_entries(&mut self.all_files);
+        self.apply_view();
+        if let Some(name) = selected_name {
+            self.select_by_name(&name);
         }
     }
 
-    pub fn select_previous(&mut self) {
-        if !self.files.is_empty() {
-            if self.selected_index == 0 {
-                self.selected_index = self.files.len() - 1;
-            } else {
-                self.selected_index -= 1;
-            }
+    /// Replace the listing of the current directory with a fresh one,
+    /// keeping the cursor on the same entry. Returns how many entries
+    /// appeared and disappeared.
+    pub fn refresh_files(&mut self, files: Vec<FileEntry>) -> (usize, usize) {
+        let selected_name = self.get_selected_file().map(|file| file.name.clone());
+        let previous: HashSet<&str> = self.all_files.iter().map(|f| f.path.as_str()).collect();
+        let current: HashSet<&str> = files.iter().map(|f| f.path.as_str()).collect();
+
+        let added: HashSet<String> = current
+            .difference(&previous)
+            .map(|path| path.to_string())
+            .collect();
+        let removed = previous.difference(&current).count();
+        let added_count = added.len();
+
+        // Marks on entries that are gone would act on nothing
+        self.marked.retain(|path| current.contains(path.as_str()));
+
+        if !added.is_empty() {
+            self.new_entries = added;
+            self.new_entries_at = Some(Instant::now());
+        }
+
+        self.set_files(files);
+        if let Some(name) = selected_name {
+            self.select_by_name(&name);
         }
+        (added_count, removed)
     }
 
-    pub fn get_selected_file(&self) -> Option<&FileEntry> {
-        self.files.get(self.selected_index)
+    /// Whether the entry at `index` appeared on a recent refresh
+    pub fn is_new_entry(&self, index: usize) -> bool {
+        self.new_entries_at
+            .is_some_and(|at| at.elapsed() < NEW_ENTRY_HIGHLIGHT)
+            && self
+                .files
+                .get(index)
+                .is_some_and(|file| self.new_entries.contains(&file.path))
     }
 
-    pub fn quit(&mut self) {
+    fn apply_view(&mut self) {
+        let query = self
+            .filter
+            .as_ref()
+            .map(FilterState::query)
+            .filter(|query| !query.is_empty());
+
+        self.files = visible_entries(&self.all_files, self.show_hidden, query)
+            .cloned()
+            .collect();
+
+        if self.selected_index >= self.files.len() {
+            self.selected_index = self.files.len().saturating_sub(1);
+        }
+    }
+
+    /// Show or hide dotfiles, keeping the cursor on the same entry if it stays visible
+    pub fn toggle_hidden(&mut self) {
+        let selected_name = self.get_selected_file().map(|file| file.name.clone());
+        self.show_hidden = !self.show_hidden;
+        self.apply_view();
+        if let Some(name) = selected_name {
+            self.select_by_name(&name);
+        }
+    }
+
+    /// Number of dotfiles hidden from the current listing
+    pub fn hidden_count(&self) -> usize {
+        if self.show_hidden {
+            return 0;
+        }
+        self.all_files.iter().filter(|file| is_hidden(&file.name)).count()
+    }
+
+    pub fn start_filter(&mut self) {
+        let previous_selection = self.get_selected_file().map(|file| file.name.clone());
+        self.filter = Some(FilterState {
+            prompt: Prompt::new("Filter", "").with_hint("Enter jumps to the match  ↑/↓ move  Esc clears"),
+            previous_selection,
+        });
+    }
+
+    /// Show the matches of the filter query as it is now, from the first
+    pub fn apply_filter(&mut self) {
+        self.selected_index = 0;
+        self.apply_view();
+    }
+
+    /// Close the filter, keeping the cursor on the matched entry
+    pub fn accept_filter(&mut self) {
+        let selected_name = self.get_selected_file().map(|file| file.name.clone());
+        self.filter = None;
+        self.apply_view();
+        if let Some(name) = selected_name {
+            self.select_by_name(&name);
+        }
+    }
+
+    /// Close the filter and return to the entry selected before filtering
+    pub fn cancel_filter(&mut self) {
+        let previous_selection = self.filter.take().and_then(|filter| filter.previous_selection);
+        self.apply_view();
+        if let Some(name) = previous_selection {
+            self.select_by_name(&name);
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.files.is_empty() {
+            self.selected_index = (self.selected_index + 1) % self.files.len();
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if !self.files.is_empty() {
+            if self.selected_index == 0 {
+                self.selected_index = self.files.len() - 1;
+            } else {
+                self.selected_index -= 1;
+            }
+        }
+    }
+
+    /// Move the selection by `delta` entries, stopping at either end
+    pub fn select_by(&mut self, delta: isize) {
+        let last = self.files.len().saturating_sub(1);
+        self.selected_index = self.selected_index.saturating_add_signed(delta).min(last);
+    }
+
+    pub fn select_first(&mut self) {
+        self.selected_index = 0;
+    }
+
+    pub fn select_last(&mut self) {
+        self.selected_index = self.files.len().saturating_sub(1);
+    }
+
+    pub fn get_selected_file(&self) -> Option<&FileEntry> {
+        self.files.get(self.selected_index)
+    }
+
+    /// Begin renaming the selected entry, pre-filled with its current name
+    pub fn start_rename(&mut self) -> bool {
+        match self.get_selected_file() {
+            Some(file) if file.name != ".." => {
+                let original_name = file.name.clone();
+                let prompt = Prompt::new(&format!("Rename {}", original_name), &original_name)
+                    .with_hint("Enter renames  Esc cancel")
+                    .with_validator(self.name_validator(&original_name));
+                self.rename = Some(RenameState {
+                    index: self.selected_index,
+                    original_name,
+                    prompt,
+                });
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Begin naming an archive of `entries`
+    pub fn start_compress(&mut self, entries: Vec<FileEntry>) {
+        self.compress = Some(CompressState::new(entries, self.name_validator("")));
+    }
+
+    /// Begin naming the file to write the clipboard text to, pre-filled
+    /// with the selected file
+    pub fn start_paste_text(&mut self) {
+        let name = self
+            .get_selected_file()
+            .filter(|f| !f.is_dir)
+            .map(|f| f.name.clone())
+            .unwrap_or_default();
+        let dirs: HashSet<String> = self.all_files.iter().filter(|f| f.is_dir).map(|f| f.name.clone()).collect();
+        self.paste_text = Some(PasteTextState::new(&name, move |value| {
+            let name = value.trim();
+            check_name(name, |_| false)?;
+            if dirs.contains(name) {
+                return Err(format!("{} is a directory", name));
+            }
+            Ok(())
+        }));
+    }
+
+    /// Begin asking which local file or directory to upload here
+    pub fn start_upload(&mut self) {
+        let prompt = Prompt::new(&format!("Upload to {}", self.current_path), "")
+            .with_hint("A directory uploads the files in it  Esc cancel");
+        self.upload = Some(prompt);
+    }
+
+    /// Checks for a new name in this directory, for a prompt. Names of
+    /// entries listed now other than `original` are taken.
+    pub fn name_validator(&self, original: &str) -> impl Fn(&str) -> Result<(), String> + 'static {
+        let taken: HashSet<String> = self
+            .all_files
+            .iter()
+            .map(|f| f.name.clone())
+            .filter(|name| name != original)
+            .collect();
+        move |value| check_name(value.trim(), |name| taken.contains(name))
+    }
+
+    /// Whether an inline prompt or confirmation is taking key presses
+    pub fn prompt_active(&self) -> bool {
+        self.rename.is_some()
+            || self.pending_delete.is_some()
+            || self.pending_download.is_some()
+            || self.pending_overwrite.is_some()
+            || self.confirm_quit
+            || self.command.is_some()
+            || self.execute.is_some()
+            || self.palette.is_some()
+            || self.jump.is_some()
+            || self.shell_switcher.is_some()
+            || self.run_script.is_some()
+            || self.filter.is_some()
+            || self.chmod.is_some()
+            || self.chown.is_some()
+            || self.compress.is_some()
+            || self.paste_text.is_some()
+            || self.upload.is_some()
+            || self.help.is_some()
+    }
+
+    /// Whether disk usage should be queried for the current directory
+    pub fn disk_usage_stale(&self) -> bool {
+        match self.disk_usage_checked {
+            Some((ref path, at)) => *path != self.current_path || at.elapsed() >= DISK_USAGE_REFRESH,
+            None => true,
+        }
+    }
+
+    /// Note the disk usage found for `path`, showing it unless the current
+    /// directory has changed since
+    pub fn set_disk_usage(&mut self, path: String, usage: Option<DiskUsage>) {
+        if path == self.current_path {
+            self.disk_usage = usage;
+        }
+        self.disk_usage_checked = Some((path, Instant::now()));
+    }
+
+    pub fn health_check_due(&self) -> bool {
+        self.health_checked.is_none_or(|at| at.elapsed() >= HEALTH_CHECK_INTERVAL)
+    }
+
+    pub fn set_health(&mut self, health: ConnectionHealth) {
+        self.health = health;
+        self.health_checked = Some(Instant::now());
+    }
+
+    /// Path of the selected entry if the preview pane is out of date
+    pub fn stale_preview_path(&self) -> Option<String> {
+        if !self.layout.preview {
+            return None;
+        }
+        let file = self.get_selected_file()?;
+        match self.preview {
+            Some(ref preview) if preview.path == file.path => None,
+            _ => Some(file.path.clone()),
+        }
+    }
+
+    /// SHA-256 of `file`, if it has been computed
+    pub fn checksum_of(&self, file: Option<&FileEntry>) -> Option<&str> {
+        match (file, &self.checksum) {
+            (Some(file), Some((path, checksum))) if *path == file.path => Some(checksum),
+            _ => None,
+        }
+    }
+
+    /// The current directory and selection, for the history
+    pub fn history_entry(&self) -> HistoryEntry {
+        HistoryEntry {
+            path: self.current_path.clone(),
+            selected: self.get_selected_file().map(|file| file.name.clone()),
+        }
+    }
+
+    /// Path of the `index`th breadcrumb above the current directory, with
+    /// the name of the child to select there
+    pub fn ancestor(&self, index: usize) -> Option<(String, String)> {
+        let crumbs = breadcrumbs(&self.current_path);
+        let (_, path) = crumbs.get(index)?;
+        let (child, _) = crumbs.get(index + 1)?;
+        Some((path.clone(), child.clone()))
+    }
+
+    /// Entries of the current directory matching any of the glob patterns.
+    /// Like a shell, dotfiles only match patterns starting with '.'.
+    pub fn glob_entries(&self, patterns: &[String]) -> Vec<FileEntry> {
+        self.all_files
+            .iter()
+            .filter(|file| file.name != "..")
+            .filter(|file| {
+                patterns.iter().any(|pattern| {
+                    (!is_hidden(&file.name) || pattern.starts_with('.')) && glob_match(pattern, &file.name)
+                })
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Select the entry with the given name, if present
+    pub fn select_by_name(&mut self, name: &str) {
+        if let Some(idx) = self.files.iter().position(|f| f.name == name) {
+            self.selected_index = idx;
+        }
+    }
+
+    /// Whether the entry at `index` is part of the current selection
+    pub fn is_marked(&self, index: usize) -> bool {
+        let Some(file) = self.files.get(index) else {
+            return false;
+        };
+        if file.name == ".." {
+            return false;
+        }
+        let in_visual_range = self.visual_anchor.is_some_and(|anchor| {
+            let (start, end) = if anchor <= self.selected_index {
+                (anchor, self.selected_index)
+            } else {
+                (self.selected_index, anchor)
+            };
+            (start..=end).contains(&index)
+        });
+        in_visual_range || self.marked.contains(&file.path)
+    }
+
+    /// Toggle the mark on the selected entry and move to the next one
+    pub fn toggle_mark(&mut self) {
+        if let Some(file) = self.get_selected_file()
+            && file.name != ".."
+        {
+            let path = file.path.clone();
+            if !self.marked.remove(&path) {
+                self.marked.insert(path);
+            }
+        }
+        if self.selected_index + 1 < self.files.len() {
+            self.selected_index += 1;
+        }
+    }
+
+    /// Start a visual range selection, or commit the current range
+    pub fn toggle_visual(&mut self) {
+        if self.visual_anchor.is_some() {
+            let paths: Vec<String> = (0..self.files.len())
+                .filter(|&i| self.is_marked(i))
+                .map(|i| self.files[i].path.clone())
+                .collect();
+            self.marked.extend(paths);
+            self.visual_anchor = None;
+        } else {
+            self.visual_anchor = Some(self.selected_index);
+        }
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.marked.clear();
+        self.visual_anchor = None;
+    }
+
+    pub fn has_selection(&self) -> bool {
+        self.visual_anchor.is_some() || !self.marked.is_empty()
+    }
+
+    /// Entries an operation should act on: the marked entries, or the
+    /// entry under the cursor when nothing is marked
+    pub fn selected_entries(&self) -> Vec<FileEntry> {
+        let marked: Vec<FileEntry> = (0..self.files.len())
+            .filter(|&i| self.is_marked(i))
+            .map(|i| self.files[i].clone())
+            .collect();
+
+        if !marked.is_empty() {
+            return marked;
+        }
+
+        self.get_selected_file()
+            .filter(|file| file.name != "..")
+            .cloned()
+            .into_iter()
+            .collect()
+    }
+
+    /// The selected entries quoted for a shell command line: by name for a
+    /// command run in the current directory, otherwise by full path
+    pub fn quoted_selection(&self, by_name: bool) -> String {
+        self.selected_entries()
+            .iter()
+            .map(|file| shell_quote(if by_name { &file.name } else { &file.path }))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Paths of the selected entries, or the current directory when only
+    /// the parent entry is under the cursor
+    pub fn selected_paths(&self) -> Vec<String> {
+        let paths: Vec<String> = self.selected_entries().into_iter().map(|file| file.path).collect();
+        if paths.is_empty() {
+            vec![self.current_path.clone()]
+        } else {
+            paths
+        }
+    }
+
+    /// Number of marked entries and the total size of the marked files
+    pub fn selection_summary(&self) -> (usize, u64) {
+        (0..self.files.len())
+            .filter(|&i| self.is_marked(i))
+            .fold((0, 0), |(count, size), i| {
+                let file = &self.files[i];
+                (count + 1, size + if file.is_dir { 0 } else { file.size })
+            })
+    }
+
+    /// Directories and files in the listing shown, not counting `..`, and
+    /// the total size of the files
+    pub fn listing_summary(&self) -> (usize, usize, u64) {
+        self.files
+            .iter()
+            .filter(|file| file.name != "..")
+            .fold((0, 0, 0), |(dirs, files, size), file| {
+                if file.is_dir {
+                    (dirs + 1, files, size)
+                } else {
+                    (dirs, files + 1, size + file.size)
+                }
+            })
+    }
+
+    /// Put the selected entries on the clipboard. Returns how many were taken.
+    pub fn yank(&mut self, mode: ClipboardMode) -> usize {
+        let entries = self.selected_entries();
+        let count = entries.len();
+        if count > 0 {
+            self.clipboard = Some(FileClipboard { mode, entries });
+            self.clear_selection();
+        }
+        count
+    }
+
+    /// Where a clipboard entry would land in the current directory. Returns
+    /// Ok(None) when moving an entry onto itself, which is a no-op.
+    pub fn paste_destination(
+        &self,
+        entry: &FileEntry,
+        mode: ClipboardMode,
+    ) -> Result<Option<String>, String> {
+        let destination = crate::file_ops::join_path(&self.current_path, &entry.name);
+
+        if destination == entry.path {
+            return match mode {
+                ClipboardMode::Move => Ok(None),
+                ClipboardMode::Copy => Err(format!("'{}' is already in this directory", entry.name)),
+            };
+        }
+        if entry.is_dir && destination.starts_with(&format!("{}/", entry.path)) {
+            return Err(format!("Cannot paste '{}' into itself", entry.name));
+        }
+        if self.all_files.iter().any(|f| f.name == entry.name) {
+            return Err(format!("'{}' already exists", entry.name));
+        }
+        Ok(Some(destination))
+    }
+
+    pub fn quit(&mut self) {
         self.should_quit = true;
     }
 
+    /// Show a spinner with `label` until `stop_loading(job)`; None for an
+    /// operation that is not a worker job
+    pub fn start_loading(&mut self, job: Option<u64>, label: String) {
+        self.stop_loading(job);
+        self.loading.push((job, Loading::new(label)));
+    }
+
+    pub fn stop_loading(&mut self, job: Option<u64>) {
+        self.loading.retain(|(running, _)| *running != job);
+    }
+
+    /// Change the label of `job`'s spinner, keeping its place
+    pub fn relabel_loading(&mut self, job: Option<u64>, label: String) {
+        if let Some((_, loading)) = self.loading.iter_mut().find(|(running, _)| *running == job) {
+            loading.label = label;
+        }
+    }
+
+    /// The spinner the footer shows
+    pub fn loading(&self) -> Option<&Loading> {
+        self.loading.last().map(|(_, loading)| loading)
+    }
+
+    /// Labels of all spinners, oldest first
+    pub fn loading_labels(&self) -> impl Iterator<Item = &str> {
+        self.loading.iter().map(|(_, loading)| loading.label.as_str())
+    }
+
+    /// The job whose spinner the footer shows, which Esc cancels
+    pub fn loading_job(&self) -> Option<u64> {
+        self.loading.last().and_then(|(job, _)| *job)
+    }
+
+    /// Drop the spinners of jobs that are no longer running
+    pub fn retain_loading(&mut self, running: impl Fn(u64) -> bool) {
+        self.loading.retain(|(job, _)| job.is_none_or(&running));
+    }
+
+    /// Show an info message in the footer; an empty one clears the footer
     pub fn set_status(&mut self, message: String) {
-        self.status_message = message;
+        if message.is_empty() {
+            self.notifications.dismiss();
+        } else {
+            self.notifications.push(Level::Info, message);
+        }
+    }
+
+    pub fn set_warning(&mut self, message: String) {
+        self.notifications.push(Level::Warn, message);
+    }
+
+    pub fn set_error(&mut self, message: String) {
+        self.notifications.push(Level::Error, message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str) -> FileEntry {
+        FileEntry {
+            name: name.to_string(),
+            path: format!("/srv/{}", name),
+            is_dir: false,
+            size: 100,
+            modified: None,
+            accessed: None,
+            permissions: None,
+            owner: None,
+            group: None,
+            link_target: None,
+            broken_link: false,
+        }
+    }
+
+    fn app_with(names: &[&str]) -> App {
+        let mut app = App::new("user@host:22".to_string());
+        app.set_files(names.iter().map(|n| entry(n)).collect());
+        app
+    }
+
+    #[test]
+    fn test_text_input_editing() {
+        let mut input = TextInput::new("notes.txt");
+        assert_eq!(input.cursor, 9);
+
+        for _ in 0..3 {
+            input.move_left();
+        }
+        input.delete_char();
+        input.insert_char('-');
+        assert_eq!(input.value, "notes-txt");
+        assert_eq!(input.with_cursor(), "notes-█txt");
+
+        input.move_home();
+        input.delete_char_forward();
+        input.move_end();
+        input.insert_char('2');
+        assert_eq!(input.value, "otes-txt2");
+    }
+
+    #[test]
+    fn test_text_input_multibyte() {
+        let mut input = TextInput::new("café");
+        input.delete_char();
+        input.insert_char('e');
+        assert_eq!(input.value, "cafe");
+    }
+
+    #[test]
+    fn test_parse_owner_spec() {
+        assert_eq!(parse_owner_spec("www-data"), Ok((Some("www-data".to_string()), None)));
+        assert_eq!(
+            parse_owner_spec("deploy:staff"),
+            Ok((Some("deploy".to_string()), Some("staff".to_string())))
+        );
+        assert_eq!(parse_owner_spec(":staff"), Ok((None, Some("staff".to_string()))));
+        assert_eq!(parse_owner_spec("1000:"), Ok((Some("1000".to_string()), None)));
+        assert!(parse_owner_spec(":").is_err());
+    }
+
+    #[test]
+    fn test_compress_state_cycles_format() {
+        let mut compress = CompressState::new(vec![entry("site")], |_| Ok(()));
+        assert_eq!(compress.prompt.input.value, "site.tar.gz");
+
+        compress.cycle_format();
+        assert_eq!(compress.prompt.input.value, "site.tar.xz");
+        compress.prompt.input = TextInput::new("backup.zip");
+        compress.cycle_format();
+        assert_eq!(compress.kind(), Some(ArchiveKind::TarGz));
+        assert_eq!(compress.prompt.input.value, "backup.tar.gz");
+
+        let compress = CompressState::new(vec![entry("a"), entry("b")], |_| Ok(()));
+        assert_eq!(compress.prompt.input.value, "archive.tar.gz");
+    }
+
+    #[test]
+    fn test_start_rename_skips_parent_entry() {
+        let mut app = app_with(&["..", "a.txt"]);
+        assert!(!app.start_rename());
+
+        app.selected_index = 1;
+        assert!(app.start_rename());
+        assert_eq!(app.rename.as_ref().unwrap().prompt.input.value, "a.txt");
+    }
+
+    #[test]
+    fn test_name_validator() {
+        let app = app_with(&["a.txt", "b.txt"]);
+        let valid = app.name_validator("a.txt");
+
+        assert!(valid("c.txt").is_ok());
+        assert!(valid("a.txt").is_ok());
+        assert_eq!(valid(" b.txt"), Err(String::from("'b.txt' already exists")));
+        assert!(valid("").is_err());
+        assert!(valid("x/y").is_err());
+        assert!(valid("..").is_err());
+    }
+
+    #[test]
+    fn test_hidden_entries_still_clash() {
+        let mut app = app_with(&["..", ".env", "a.txt"]);
+        app.toggle_hidden();
+        assert!(!app.show_hidden);
+        app.current_path = "/srv".to_string();
+
+        assert!(app.name_validator("a.txt")(".env").is_err());
+        let mut dotfile = entry(".env");
+        dotfile.path = "/tmp/.env".to_string();
+        assert!(app.paste_destination(&dotfile, ClipboardMode::Copy).is_err());
+    }
+
+    #[test]
+    fn test_rename_onto_existing_name_stays_in_the_prompt() {
+        use crate::tui::prompt::PromptResult;
+
+        let mut app = app_with(&["a.txt", "b.txt"]);
+        assert!(app.start_rename());
+        let prompt = &mut app.rename.as_mut().unwrap().prompt;
+        prompt.input = TextInput::new("b.txt");
+
+        assert_eq!(prompt.handle_key(KeyEvent::from(KeyCode::Enter)), PromptResult::Editing);
+        assert!(app.rename.is_some());
+    }
+
+    #[test]
+    fn test_select_by_name() {
+        let mut app = app_with(&["a", "b", "c"]);
+        app.select_by_name("c");
+        assert_eq!(app.selected_index, 2);
+
+        app.select_by_name("missing");
+        assert_eq!(app.selected_index, 2);
+    }
+
+    #[test]
+    fn test_toggle_mark_skips_parent_and_advances() {
+        let mut app = app_with(&["..", "a", "b"]);
+
+        app.toggle_mark();
+        assert!(app.marked.is_empty());
+        assert_eq!(app.selected_index, 1);
+
+        app.toggle_mark();
+        app.toggle_mark();
+        assert_eq!(app.selection_summary(), (2, 200));
+
+        app.selected_index = 1;
+        app.toggle_mark();
+        assert_eq!(app.selection_summary(), (1, 100));
+    }
+
+    #[test]
+    fn test_visual_range_selection() {
+        let mut app = app_with(&["a", "b", "c", "d"]);
+
+        app.selected_index = 2;
+        app.toggle_visual();
+        app.selected_index = 0;
+        assert!(app.is_marked(1));
+        assert!(!app.is_marked(3));
+
+        app.toggle_visual();
+        assert_eq!(app.visual_anchor, None);
+        assert_eq!(app.marked.len(), 3);
+    }
+
+    #[test]
+    fn test_selected_entries_falls_back_to_cursor() {
+        let mut app = app_with(&["..", "a", "b"]);
+        assert!(app.selected_entries().is_empty());
+
+        app.selected_index = 2;
+        let names: Vec<String> = app.selected_entries().into_iter().map(|f| f.name).collect();
+        assert_eq!(names, vec!["b"]);
+
+        app.marked.insert("/srv/a".to_string());
+        let names: Vec<String> = app.selected_entries().into_iter().map(|f| f.name).collect();
+        assert_eq!(names, vec!["a"]);
+    }
+
+    #[test]
+    fn test_selection_as_shell_arguments() {
+        let mut app = app_with(&["..", "a b", "c"]);
+        app.marked.insert("/srv/a b".to_string());
+        app.marked.insert("/srv/c".to_string());
+        assert_eq!(app.quoted_selection(false), "'/srv/a b' '/srv/c'");
+
+        let mut input = TextInput::new("tar czf backup.tgz");
+        input.insert_word(&app.quoted_selection(true));
+        assert_eq!(input.value, "tar czf backup.tgz 'a b' 'c'");
+    }
+
+    #[test]
+    fn test_yank_takes_selection() {
+        let mut app = app_with(&["a", "b"]);
+        app.marked.insert("/srv/b".to_string());
+
+        assert_eq!(app.yank(ClipboardMode::Move), 1);
+        let clipboard = app.clipboard.as_ref().unwrap();
+        assert_eq!(clipboard.mode, ClipboardMode::Move);
+        assert_eq!(clipboard.entries[0].name, "b");
+        assert!(!app.has_selection());
+    }
+
+    #[test]
+    fn test_paste_destination() {
+        let mut app = app_with(&["a"]);
+        app.current_path = "/srv".to_string();
+
+        let same = entry("a");
+        assert_eq!(app.paste_destination(&same, ClipboardMode::Move), Ok(None));
+        assert!(app.paste_destination(&same, ClipboardMode::Copy).is_err());
+
+        let mut other = entry("b");
+        other.path = "/tmp/b".to_string();
+        assert_eq!(
+            app.paste_destination(&other, ClipboardMode::Copy),
+            Ok(Some("/srv/b".to_string()))
+        );
+
+        let mut clash = entry("a");
+        clash.path = "/tmp/a".to_string();
+        assert!(app.paste_destination(&clash, ClipboardMode::Move).is_err());
+
+        let mut parent = entry("srv");
+        parent.path = "/srv".to_string();
+        parent.is_dir = true;
+        app.current_path = "/srv/nested".to_string();
+        assert!(app.paste_destination(&parent, ClipboardMode::Move).is_err());
+    }
+
+    #[test]
+    fn test_toggle_hidden_keeps_parent_and_selection() {
+        let mut app = App::new("user@host:22".to_string());
+        app.set_files(["..", ".bashrc", ".config", "notes"].iter().map(|n| entry(n)).collect());
+        app.select_by_name("notes");
+
+        app.toggle_hidden();
+        let names: Vec<&str> = app.files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["..", "notes"]);
+        assert_eq!(app.hidden_count(), 2);
+        assert_eq!(app.get_selected_file().unwrap().name, "notes");
+
+        app.toggle_hidden();
+        assert_eq!(app.files.len(), 4);
+        assert_eq!(app.get_selected_file().unwrap().name, "notes");
+    }
+
+    #[test]
+    fn test_set_files_clamps_selection_when_hiding() {
+        let mut app = App::new("user@host:22".to_string());
+        app.show_hidden = false;
+        app.selected_index = 3;
+        app.set_files(["a", ".b", ".c", ".d"].iter().map(|n| entry(n)).collect());
+
+        assert_eq!(app.files.len(), 1);
+        assert_eq!(app.selected_index, 0);
+    }
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert!(fuzzy_match("nginx.conf", "conf"));
+        assert!(fuzzy_match("nginx.conf", "NGX"));
+        assert!(fuzzy_match("anything", ""));
+        assert!(!fuzzy_match("nginx.conf", "fnc"));
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.log", "error.log"));
+        assert!(glob_match("*.log.1", "access.log.1"));
+        assert!(!glob_match("*.log", "error.log.1"));
+        assert!(glob_match("a?c", "abc"));
+        assert!(glob_match("data[0-9].csv", "data7.csv"));
+        assert!(!glob_match("data[!0-9].csv", "data7.csv"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("[]x]", "]"));
+        assert!(glob_match("a[b", "a[b"));
+        assert!(glob_match("*a*b*", "xxaxxbxx"));
+    }
+
+    #[test]
+    fn test_parse_batch_command() {
+        assert_eq!(
+            parse_batch_command("rm *.log.1 *.tmp"),
+            Ok((BatchAction::Delete, vec!["*.log.1".to_string(), "*.tmp".to_string()]))
+        );
+        assert_eq!(parse_batch_command("get *.conf").map(|(a, _)| a), Ok(BatchAction::Download));
+        assert!(parse_batch_command("rm").is_err());
+        assert!(parse_batch_command("chmod *").is_err());
+    }
+
+    #[test]
+    fn test_glob_entries_skips_dotfiles_unless_asked() {
+        let mut app = app_with(&["..", ".env", "a.conf", "b.conf", "notes.txt"]);
+        app.all_files = app.files.clone();
+
+        let names = |entries: Vec<FileEntry>| entries.into_iter().map(|f| f.name).collect::<Vec<_>>();
+        assert_eq!(names(app.glob_entries(&["*".to_string()])), ["a.conf", "b.conf", "notes.txt"]);
+        assert_eq!(names(app.glob_entries(&[".*".to_string()])), [".env"]);
+    }
+
+    #[test]
+    fn test_filter_narrows_and_accepts_match() {
+        let mut app = app_with(&["..", "access.log", "error.log", "nginx.conf"]);
+        app.all_files = app.files.clone();
+        app.selected_index = 0;
+
+        app.start_filter();
+        app.filter.as_mut().unwrap().prompt.input.insert_word("log");
+        app.apply_filter();
+        let names: Vec<&str> = app.files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["access.log", "error.log"]);
+
+        app.select_next();
+        app.accept_filter();
+        assert_eq!(app.files.len(), 4);
+        assert_eq!(app.get_selected_file().unwrap().name, "error.log");
+    }
+
+    #[test]
+    fn test_cancel_filter_restores_selection() {
+        let mut app = app_with(&["a", "b", "c"]);
+        app.all_files = app.files.clone();
+        app.selected_index = 2;
+
+        app.start_filter();
+        app.filter.as_mut().unwrap().prompt.input.insert_char('a');
+        app.apply_filter();
+        assert_eq!(app.files.len(), 1);
+
+        app.cancel_filter();
+        assert_eq!(app.files.len(), 3);
+        assert_eq!(app.get_selected_file().unwrap().name, "c");
+    }
+
+    fn history_entry(path: &str) -> HistoryEntry {
+        HistoryEntry {
+            path: path.to_string(),
+            selected: None,
+        }
+    }
+
+    #[test]
+    fn test_breadcrumbs_and_ancestors() {
+        let crumbs = breadcrumbs("/srv/www/");
+        let labels: Vec<&str> = crumbs.iter().map(|(label, _)| label.as_str()).collect();
+        assert_eq!(labels, ["/", "srv", "www"]);
+        assert_eq!(crumbs[2].1, "/srv/www");
+
+        let mut app = app_with(&[".."]);
+        app.current_path = String::from("/srv/www");
+        assert_eq!(app.ancestor(0), Some(("/".to_string(), "srv".to_string())));
+        assert_eq!(app.ancestor(1), Some(("/srv".to_string(), "www".to_string())));
+        assert_eq!(app.ancestor(2), None);
+    }
+
+    #[test]
+    fn test_history_back_and_forward() {
+        let mut history = DirHistory::default();
+        history.visit(history_entry("/var/log"));
+        history.visit(history_entry("/etc/nginx"));
+
+        // Currently in /srv
+        assert_eq!(history.back_target().unwrap().path, "/etc/nginx");
+        history.commit_back(history_entry("/srv"));
+        assert_eq!(history.back_target().unwrap().path, "/var/log");
+        assert_eq!(history.forward_target().unwrap().path, "/srv");
+
+        history.commit_forward(history_entry("/etc/nginx"));
+        assert_eq!(history.back_target().unwrap().path, "/etc/nginx");
+        assert!(history.forward_target().is_none());
+    }
+
+    #[test]
+    fn test_history_visit_clears_forward() {
+        let mut history = DirHistory::default();
+        history.visit(history_entry("/a"));
+        history.commit_back(history_entry("/b"));
+        assert!(history.forward_target().is_some());
+
+        history.visit(history_entry("/a"));
+        assert!(history.forward_target().is_none());
+    }
+
+    #[test]
+    fn test_selection_is_remembered_per_directory() {
+        let place = |path: &str, selected: Option<&str>| HistoryEntry {
+            path: path.to_string(),
+            selected: selected.map(str::to_string),
+        };
+        let mut selections = DirSelections::default();
+        selections.leave(place("/var/log", Some("nginx")));
+        selections.leave(place("/srv", Some("app")));
+        selections.leave(place("/var/log", Some("syslog")));
+        // An empty directory has nothing to remember
+        selections.leave(place("/srv", None));
+
+        assert_eq!(selections.get("/var/log"), Some("syslog"));
+        assert_eq!(selections.get("/srv"), Some("app"));
+        assert_eq!(selections.get("/etc"), None);
+        // Least recently left first, as saved in the session
+        assert_eq!(selections.entries()[1].path, "/var/log");
+    }
+
+    #[test]
+    fn test_chmod_grid_editing() {
+        let mut file = entry("run.sh");
+        file.permissions = Some(0o104644);
+        let mut chmod = ChmodState::new(vec![file.clone()]);
+        assert_eq!(chmod.mode, 0o644);
+
+        // Owner execute
+        chmod.move_cursor(0, 2);
+        chmod.toggle();
+        assert_eq!(chmod.mode, 0o744);
+        assert!(chmod.is_set(0, 2));
+
+        // Typing digits sets rows from the cursor downwards
+        chmod.move_cursor(1, 0);
+        chmod.set_digit(5);
+        chmod.set_digit(5);
+        assert_eq!(chmod.mode, 0o755);
+        assert_eq!(chmod.row, 0);
+
+        assert_eq!(chmod.mode_for(&file), 0o4755);
+    }
+
+    #[test]
+    fn test_chmod_cursor_wraps() {
+        let mut chmod = ChmodState::new(vec![entry("a")]);
+        chmod.move_cursor(-1, -1);
+        assert_eq!((chmod.row, chmod.col), (2, 2));
+    }
+
+    #[test]
+    fn test_preview_content_detects_binary() {
+        assert_eq!(
+            PreviewContent::from_bytes(b"server {\n  listen 80;\n}"),
+            PreviewContent::Text("server {\n  listen 80;\n}".to_string())
+        );
+        assert_eq!(PreviewContent::from_bytes(b"\x1f\x8b\x08\x00"), PreviewContent::Binary);
+    }
+
+    #[test]
+    fn test_stale_preview_path() {
+        let mut app = app_with(&["a", "b"]);
+        assert_eq!(app.stale_preview_path(), None);
+
+        app.layout.preview = true;
+        assert_eq!(app.stale_preview_path(), Some("/srv/a".to_string()));
+
+        app.preview = Some(Preview {
+            path: "/srv/a".to_string(),
+            content: PreviewContent::Binary,
+            truncated: false,
+        });
+        assert_eq!(app.stale_preview_path(), None);
+
+        app.select_next();
+        assert_eq!(app.stale_preview_path(), Some("/srv/b".to_string()));
+    }
+
+    #[test]
+    fn test_refresh_files_keeps_selection_and_flags_new_entries() {
+        let mut app = App::new("user@host:22".to_string());
+        app.set_files(vec![entry("a"), entry("b"), entry("c")]);
+        app.select_by_name("c");
+        app.marked.insert("/srv/b".to_string());
+
+        let (added, removed) = app.refresh_files(vec![entry("a"), entry("aa"), entry("c")]);
+        assert_eq!((added, removed), (1, 1));
+        assert_eq!(app.get_selected_file().unwrap().name, "c");
+        assert!(app.marked.is_empty());
+        assert!(app.is_new_entry(1));
+        assert!(!app.is_new_entry(0));
+    }
+
+    #[test]
+    fn test_page_selection_stops_at_ends() {
+        let mut app = app_with(&["a", "b", "c", "d", "e"]);
+        app.select_by(3);
+        assert_eq!(app.selected_index, 3);
+        app.select_by(3);
+        assert_eq!(app.selected_index, 4);
+        app.select_by(-10);
+        assert_eq!(app.selected_index, 0);
+        app.select_last();
+        assert_eq!(app.selected_index, 4);
+        app.select_first();
+        assert_eq!(app.selected_index, 0);
+    }
+
+    #[test]
+    fn test_append_files_keeps_listing_sorted() {
+        let mut app = App::new("user@host:22".to_string());
+        app.set_files(vec![FileEntry::parent(), entry("m")]);
+        app.select_by_name("m");
+
+        app.append_files(vec![entry("z"), entry("a")]);
+        let names: Vec<&str> = app.files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["..", "a", "m", "z"]);
+        assert_eq!(app.get_selected_file().unwrap().name, "m");
+    }
+
+    #[test]
+    fn test_unique_name_appends_counter() {
+        let taken = ["report.pdf", "report (1).pdf", ".bashrc"];
+        let is_taken = |name: &str| taken.contains(&name);
+        assert_eq!(unique_name("notes.txt", is_taken), "notes.txt");
+        assert_eq!(unique_name("report.pdf", is_taken), "report (2).pdf");
+        assert_eq!(unique_name(".bashrc", is_taken), ".bashrc (1)");
+    }
+
+    #[test]
+    fn test_transfer_batch_summary() {
+        let mut batch = TransferBatch::new(Direction::Download, vec![entry("a"), entry("b"), entry("c")]);
+        batch.record(&entry("a"), "a", Ok(0));
+        batch.skipped = 1;
+        batch.record(&entry("c"), "c", Err("permission denied".to_string()));
+        assert_eq!(batch.summary(), "Downloaded 1 of 3 files, 1 skipped; failed: c");
+
+        let mut single = TransferBatch::new(Direction::Download, vec![entry("a")]);
+        single.transferred.push(("a (1)".to_string(), 0));
+        assert_eq!(single.summary(), "Downloaded: a (1)");
+
+        let upload = Direction::Upload { remote_dir: String::from("/srv") };
+        let mut resumed = TransferBatch::new(upload, vec![entry("a")]);
+        resumed.record(&entry("a"), "/srv/a", Ok(4096));
+        assert_eq!(resumed.summary(), "Uploaded: /srv/a (resumed at 4096 bytes)");
+    }
+
+    #[test]
+    fn test_health_check_schedule_and_label() {
+        let mut app = App::new("user@host:22".to_string());
+        assert!(app.health_check_due());
+        assert_eq!(app.health.label(), None);
+
+        app.set_health(ConnectionHealth::Responding(Duration::from_millis(42)));
+        assert!(!app.health_check_due());
+        assert_eq!(app.health.label().as_deref(), Some(" [42 ms]"));
+        assert_eq!(ConnectionHealth::NoReply.label().as_deref(), Some(" [no reply]"));
+    }
+
+    #[test]
+    fn test_spinner_advances_with_time() {
+        let loading = Loading::new(String::from("Loading /var"));
+        let start = loading.started;
+        assert_eq!(loading.spinner(start), '⠋');
+        assert_eq!(loading.spinner(start + SPINNER_FRAME_TIME * 2), '⠹');
+        assert_eq!(loading.spinner(start + SPINNER_FRAME_TIME * 10), '⠋');
+    }
+
+    #[test]
+    fn test_disk_usage_of_a_directory_left_is_not_shown() {
+        let mut app = App::new(String::from("user@host"));
+        app.current_path = String::from("/srv");
+        let usage = DiskUsage { total: 100, used: 40, available: 60 };
+
+        app.set_disk_usage(String::from("/home"), Some(usage));
+        assert_eq!(app.disk_usage, None);
+        assert!(app.disk_usage_stale());
+
+        app.set_disk_usage(String::from("/srv"), Some(usage));
+        assert_eq!(app.disk_usage, Some(usage));
+        assert!(!app.disk_usage_stale());
+    }
+
+    #[test]
+    fn test_newest_spinner_is_shown_until_its_job_ends() {
+        let mut app = App::new(String::from("user@host"));
+        app.start_loading(Some(1), String::from("Downloading a.iso"));
+        app.start_loading(Some(2), String::from("Loading notes.txt"));
+        assert_eq!(app.loading().map(|l| l.label.as_str()), Some("Loading notes.txt"));
+        assert_eq!(app.loading_job(), Some(2));
+        assert_eq!(app.loading_labels().collect::<Vec<_>>(), ["Downloading a.iso", "Loading notes.txt"]);
+
+        app.retain_loading(|job| job != 2);
+        assert_eq!(app.loading().map(|l| l.label.as_str()), Some("Downloading a.iso"));
+        assert_eq!(app.loading_job(), Some(1));
+
+        app.stop_loading(Some(1));
+        assert!(app.loading().is_none());
     }
 }
@@ -0,0 +1,49 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Replace the contents of `path` so that a crash or full disk leaves
+/// either the old file or the new one, never a cut-off mix: the data goes
+/// to a temporary file next to it, reaches the disk, and is renamed over it
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let temporary = temporary_path(path);
+    let written = File::create(&temporary).and_then(|mut file| {
+        file.write_all(contents.as_ref())?;
+        file.sync_all()
+    });
+    match written.and_then(|()| fs::rename(&temporary, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&temporary);
+            Err(e)
+        }
+    }
+}
+
+/// `.name.tmp-PID` in the same directory, so the rename stays on one
+/// filesystem and two running copies of bssh do not share it
+fn temporary_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!(".{}.tmp-{}", name, std::process::id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_write_replaces_the_file_and_leaves_nothing_behind() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("connections.json");
+        fs::write(&path, "old").unwrap();
+
+        write_atomic(&path, "new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        // Nowhere to write to: the error comes back and no file is left
+        assert!(write_atomic(&dir.path().join("missing").join("state.json"), "x").is_err());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
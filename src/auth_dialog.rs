@@ -0,0 +1,271 @@
+use anyhow::{bail, Result};
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Alignment, Constraint, Direction, Layout},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, Paragraph, Wrap},
+    Frame, Terminal,
+};
+use std::io::{self, IsTerminal};
+
+use crate::theme::Theme;
+
+/// A single server-supplied prompt from a keyboard-interactive request
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthPrompt {
+    pub prompt: String,
+    pub echo: bool,
+}
+
+/// Form state for answering one round of keyboard-interactive prompts
+pub struct AuthForm {
+    pub name: String,
+    pub instructions: String,
+    pub prompts: Vec<AuthPrompt>,
+    pub responses: Vec<String>,
+    pub current_field: usize,
+}
+
+impl AuthForm {
+    pub fn new(name: String, instructions: String, prompts: Vec<AuthPrompt>) -> Self {
+        let responses = vec![String::new(); prompts.len()];
+        Self {
+            name,
+            instructions,
+            prompts,
+            responses,
+            current_field: 0,
+        }
+    }
+
+    pub fn next_field(&mut self) {
+        if !self.prompts.is_empty() {
+            self.current_field = (self.current_field + 1) % self.prompts.len();
+        }
+    }
+
+    pub fn prev_field(&mut self) {
+        if !self.prompts.is_empty() {
+            self.current_field = if self.current_field == 0 {
+                self.prompts.len() - 1
+            } else {
+                self.current_field - 1
+            };
+        }
+    }
+
+    pub fn is_last_field(&self) -> bool {
+        self.current_field + 1 >= self.prompts.len()
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        if let Some(value) = self.responses.get_mut(self.current_field) {
+            value.push(c);
+        }
+    }
+
+    pub fn delete_char(&mut self) {
+        if let Some(value) = self.responses.get_mut(self.current_field) {
+            value.pop();
+        }
+    }
+
+    /// Value to show for a field, masking input the server asked not to echo
+    pub fn display_value(&self, index: usize) -> String {
+        let value = &self.responses[index];
+        if self.prompts[index].echo {
+            value.clone()
+        } else {
+            "*".repeat(value.chars().count())
+        }
+    }
+}
+
+/// Show the prompts in a TUI dialog and collect the user's answers.
+/// Returns Ok(None) if the user cancelled. Fails when stdout is not a
+/// terminal, as for `bssh ls --json > file`, rather than drawing into it.
+pub fn prompt_keyboard_interactive(
+    name: &str,
+    instructions: &str,
+    prompts: Vec<AuthPrompt>,
+    theme: &Theme,
+) -> Result<Option<Vec<String>>> {
+    if !io::stdout().is_terminal() {
+        bail!("The server asks for a password or code, which needs a terminal; use a key instead");
+    }
+    let mut form = AuthForm::new(name.to_string(), instructions.to_string(), prompts);
+
+    let mut terminal = setup_terminal()?;
+    let result = run_dialog(&mut terminal, &mut form, theme);
+    restore_terminal(&mut terminal)?;
+
+    result
+}
+
+fn run_dialog(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    form: &mut AuthForm,
+    theme: &Theme,
+) -> Result<Option<Vec<String>>> {
+    loop {
+        terminal.draw(|f| render(f, form, theme))?;
+
+        if let Event::Key(key) = event::read()? {
+            match key.code {
+                KeyCode::Esc => return Ok(None),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(None);
+                }
+                KeyCode::Enter => {
+                    if form.is_last_field() {
+                        return Ok(Some(form.responses.clone()));
+                    }
+                    form.next_field();
+                }
+                KeyCode::Tab | KeyCode::Down => form.next_field(),
+                KeyCode::BackTab | KeyCode::Up => form.prev_field(),
+                KeyCode::Backspace => form.delete_char(),
+                KeyCode::Char(c) => form.insert_char(c),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn render(f: &mut Frame, form: &AuthForm, theme: &Theme) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Length(4),
+            Constraint::Min(0),
+            Constraint::Length(3),
+        ])
+        .split(f.area());
+
+    let title = if form.name.is_empty() {
+        "Authentication Required".to_string()
+    } else {
+        form.name.clone()
+    };
+
+    let header = Paragraph::new(vec![Line::from(vec![Span::styled(
+        title,
+        Style::default().fg(theme.accent).add_modifier(Modifier::BOLD),
+    )])])
+    .block(Block::default().borders(Borders::ALL).title("bssh"));
+
+    f.render_widget(header, chunks[0]);
+
+    let instructions = Paragraph::new(form.instructions.as_str())
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL).title("Instructions"));
+
+    f.render_widget(instructions, chunks[1]);
+
+    let items: Vec<ListItem> = form
+        .prompts
+        .iter()
+        .enumerate()
+        .map(|(i, prompt)| {
+            let is_selected = i == form.current_field;
+            let cursor = if is_selected { "█" } else { "" };
+
+            let line = Line::from(vec![
+                Span::styled(prompt.prompt.clone(), Style::default().fg(theme.key)),
+                Span::raw(" "),
+                Span::raw(format!("{}{}", form.display_value(i), cursor)),
+            ]);
+
+            let style = if is_selected {
+                Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+            } else {
+                Style::default()
+            };
+
+            ListItem::new(line).style(style)
+        })
+        .collect();
+
+    let list = List::new(items).block(Block::default().borders(Borders::ALL).title("Prompts"));
+
+    f.render_widget(list, chunks[2]);
+
+    let footer = Paragraph::new(vec![Line::from(vec![
+        Span::styled("Tab/↑↓", Style::default().fg(theme.key)),
+        Span::raw(": Navigate  "),
+        Span::styled("Enter", Style::default().fg(theme.key)),
+        Span::raw(": Submit  "),
+        Span::styled("Esc", Style::default().fg(theme.key)),
+        Span::raw(": Cancel"),
+    ])])
+    .block(Block::default().borders(Borders::ALL).title("Help"))
+    .alignment(Alignment::Left);
+
+    f.render_widget(footer, chunks[3]);
+}
+
+fn setup_terminal() -> Result<Terminal<CrosstermBackend<io::Stdout>>> {
+    crossterm::terminal::enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    crossterm::execute!(stdout, crossterm::terminal::EnterAlternateScreen)?;
+    let backend = CrosstermBackend::new(stdout);
+    let terminal = Terminal::new(backend)?;
+    Ok(terminal)
+}
+
+fn restore_terminal(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Result<()> {
+    crossterm::terminal::disable_raw_mode()?;
+    crossterm::execute!(
+        terminal.backend_mut(),
+        crossterm::terminal::LeaveAlternateScreen
+    )?;
+    terminal.show_cursor()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn otp_form() -> AuthForm {
+        AuthForm::new(
+            String::new(),
+            String::new(),
+            vec![
+                AuthPrompt { prompt: "Password:".to_string(), echo: false },
+                AuthPrompt { prompt: "Verification code:".to_string(), echo: true },
+            ],
+        )
+    }
+
+    #[test]
+    fn test_auth_form_masks_non_echo_prompts() {
+        let mut form = otp_form();
+
+        form.insert_char('s');
+        form.insert_char('3');
+        form.next_field();
+        form.insert_char('4');
+        form.insert_char('2');
+
+        assert_eq!(form.responses, vec!["s3", "42"]);
+        assert_eq!(form.display_value(0), "**");
+        assert_eq!(form.display_value(1), "42");
+    }
+
+    #[test]
+    fn test_auth_form_navigation_wraps() {
+        let mut form = otp_form();
+
+        assert!(!form.is_last_field());
+        form.next_field();
+        assert!(form.is_last_field());
+        form.next_field();
+        assert_eq!(form.current_field, 0);
+        form.prev_field();
+        assert_eq!(form.current_field, 1);
+    }
+}
@@ -0,0 +1,234 @@
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::Paragraph,
+    Frame,
+};
+use std::time::Duration;
+
+use crate::ssh::client::{shell_quote, CommandOutput};
+use crate::theme::Theme;
+
+/// Placeholder in a command for the selected entry's path
+pub const PLACEHOLDER: &str = "{}";
+
+/// The shell line for `command` typed in the browser: run from `directory`,
+/// with each `{}` replaced by the quoted path of the selected entry
+pub fn expand_command(command: &str, directory: &str, selected: Option<&str>) -> String {
+    let command = match selected {
+        Some(path) => command.replace(PLACEHOLDER, &shell_quote(path)),
+        None => command.to_string(),
+    };
+    format!("cd {} && {}", shell_quote(directory), command)
+}
+
+/// Scrollable output of a command run from the browser, either complete
+/// or still arriving
+pub struct CommandOutputView {
+    pub command: String,
+    /// Output lines, each marked with whether it came from stderr
+    lines: Vec<(bool, String)>,
+    /// Unfinished last line of stdout and of stderr while output streams in
+    partial: [String; 2],
+    exit_status: Option<u32>,
+    /// Output is still coming
+    pub running: bool,
+    /// First line in view
+    pub scroll: usize,
+    pub viewport_height: usize,
+    pub should_quit: bool,
+}
+
+impl CommandOutputView {
+    /// stdout first, then stderr: the channel does not say how they interleaved
+    pub fn new(command: String, output: CommandOutput) -> Self {
+        let mut view = Self::streaming(command);
+        view.push(false, &output.stdout);
+        view.push(true, &output.stderr);
+        view.finish(output.exit_status);
+        view
+    }
+
+    /// An empty view for output that is about to stream in
+    pub fn streaming(command: String) -> Self {
+        Self {
+            command,
+            lines: Vec::new(),
+            partial: [String::new(), String::new()],
+            exit_status: None,
+            running: true,
+            scroll: 0,
+            viewport_height: 20,
+            should_quit: false,
+        }
+    }
+
+    /// Add output as it arrives. While the end is in view, the view stays
+    /// on it, like `tail -f`.
+    pub fn push(&mut self, stderr: bool, text: &str) {
+        let following = self.scroll >= self.max_scroll();
+        let partial = &mut self.partial[stderr as usize];
+        partial.push_str(text);
+        if let Some(end) = partial.rfind('\n') {
+            let rest = partial.split_off(end + 1);
+            let complete = std::mem::replace(partial, rest);
+            self.lines.extend(complete.lines().map(|line| (stderr, line.to_string())));
+        }
+        if following {
+            self.scroll = self.max_scroll();
+        }
+    }
+
+    /// The command ended; keep what is left of unfinished lines
+    pub fn finish(&mut self, exit_status: Option<u32>) {
+        for (stderr, partial) in self.partial.iter_mut().enumerate() {
+            if !partial.is_empty() {
+                self.lines.push((stderr == 1, std::mem::take(partial)));
+            }
+        }
+        self.exit_status = exit_status;
+        self.running = false;
+    }
+
+    /// Complete lines, then any unfinished ones
+    fn all_lines(&self) -> impl Iterator<Item = (bool, &str)> {
+        let complete = self.lines.iter().map(|(stderr, line)| (*stderr, line.as_str()));
+        let partial = self
+            .partial
+            .iter()
+            .enumerate()
+            .filter(|(_, partial)| !partial.is_empty())
+            .map(|(stderr, partial)| (stderr == 1, partial.as_str()));
+        complete.chain(partial)
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.all_lines().count().saturating_sub(self.viewport_height)
+    }
+
+    pub fn scroll_by(&mut self, lines: isize) {
+        self.scroll = self.scroll.saturating_add_signed(lines).min(self.max_scroll());
+    }
+}
+
+pub fn render_command_output(f: &mut Frame, area: Rect, view: &CommandOutputView, theme: &Theme) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .split(area);
+
+    let status = match view.exit_status {
+        _ if view.running => Span::styled("running…", Style::default().fg(theme.accent)),
+        Some(0) => Span::styled("exit 0", Style::default().fg(theme.success)),
+        Some(code) => Span::styled(format!("exit {}", code), Style::default().fg(theme.error)),
+        None => Span::styled("no exit status", Style::default().fg(theme.warning)),
+    };
+    let header = Line::from(vec![
+        Span::styled("RUN", Style::default().fg(theme.special).add_modifier(Modifier::BOLD)),
+        Span::raw(" | "),
+        Span::raw(&view.command),
+        Span::raw(" | "),
+        status,
+    ]);
+    f.render_widget(Paragraph::new(header), chunks[0]);
+
+    let lines: Vec<Line> = if view.all_lines().next().is_none() && !view.running {
+        vec![Line::from(Span::styled("(no output)", Style::default().fg(theme.muted)))]
+    } else {
+        view.all_lines()
+            .skip(view.scroll)
+            .take(chunks[1].height as usize)
+            .map(|(stderr, line)| {
+                let style = if stderr { Style::default().fg(theme.error) } else { Style::default() };
+                Line::from(Span::styled(line, style))
+            })
+            .collect()
+    };
+    f.render_widget(Paragraph::new(lines), chunks[1]);
+
+    let footer = Line::from(vec![
+        Span::styled("↑/↓ PgUp/PgDn", Style::default().fg(theme.key)),
+        Span::raw(": Scroll  "),
+        Span::styled("g/G", Style::default().fg(theme.key)),
+        Span::raw(": Start/End  "),
+        Span::styled("q", Style::default().fg(theme.key)),
+        Span::raw(if view.running { ": Stop and close  " } else { ": Close  " }),
+        Span::styled("stderr", Style::default().fg(theme.error)),
+        Span::raw(" in red"),
+    ]);
+    f.render_widget(Paragraph::new(footer), chunks[2]);
+}
+
+pub fn handle_command_output_input(view: &mut CommandOutputView) -> Result<()> {
+    if event::poll(Duration::from_millis(100))?
+        && let Event::Key(key) = event::read()?
+    {
+        let page = view.viewport_height.max(1) as isize;
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                view.should_quit = true;
+            }
+            KeyCode::Char('q') | KeyCode::Esc | KeyCode::Enter => view.should_quit = true,
+            KeyCode::Up | KeyCode::Char('k') => view.scroll_by(-1),
+            KeyCode::Down | KeyCode::Char('j') => view.scroll_by(1),
+            KeyCode::PageUp => view.scroll_by(-page),
+            KeyCode::PageDown => view.scroll_by(page),
+            KeyCode::Home | KeyCode::Char('g') => view.scroll = 0,
+            KeyCode::End | KeyCode::Char('G') => view.scroll = view.max_scroll(),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_command() {
+        assert_eq!(
+            expand_command("chmod +x {}", "/srv/app", Some("/srv/app/run it.sh")),
+            "cd '/srv/app' && chmod +x '/srv/app/run it.sh'"
+        );
+        assert_eq!(expand_command("df -h", "/", None), "cd '/' && df -h");
+    }
+
+    #[test]
+    fn test_stderr_follows_stdout() {
+        let output = CommandOutput {
+            stdout: String::from("a\nb\n"),
+            stderr: String::from("warning: c\n"),
+            exit_status: Some(1),
+        };
+        let mut view = CommandOutputView::new(String::from("make"), output);
+        assert_eq!(view.lines[2], (true, String::from("warning: c")));
+
+        view.viewport_height = 2;
+        view.scroll_by(5);
+        assert_eq!(view.scroll, 1);
+    }
+
+    #[test]
+    fn test_streamed_output_is_split_into_lines() {
+        let mut view = CommandOutputView::streaming(String::from("./deploy.sh"));
+        view.viewport_height = 2;
+        view.push(false, "step 1\nstep");
+        view.push(false, " 2\nstep 3\nDone");
+        let shown: Vec<&str> = view.all_lines().map(|(_, line)| line).collect();
+        assert_eq!(shown, ["step 1", "step 2", "step 3", "Done"]);
+        // Following the end
+        assert_eq!(view.scroll, 2);
+
+        view.finish(Some(0));
+        assert_eq!(view.lines.len(), 4);
+        assert!(!view.running);
+    }
+}
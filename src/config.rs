@@ -0,0 +1,212 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::atomic_file::write_atomic;
+use crate::connections::{ConnectionSort, ConnectionsFormat, SavedConnection};
+use crate::editor::{BackupMode, EditorOptions};
+use crate::filetype::OpenAction;
+use crate::tui::columns::{default_columns, Column};
+use crate::tui::icons::IconMode;
+use crate::tui::layout::PanelLayout;
+use crate::ssh::client::DEFAULT_KEEPALIVE_SECS;
+use crate::tui::palette::PaletteCommand;
+
+/// User preferences from `~/.config/bssh/config.json`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    /// Ask before deleting files and directories
+    pub confirm_delete: bool,
+    /// How Enter opens files, by extension (e.g. `"log": "follow"`)
+    pub open_with: HashMap<String, OpenAction>,
+    /// Re-list the current directory every this many seconds; 0 disables it
+    pub auto_refresh_secs: u64,
+    /// Ranged SFTP requests kept in flight per download or upload
+    pub transfer_concurrency: usize,
+    /// Editor defaults for the `:set` options
+    #[serde(flatten)]
+    pub editor: EditorOptions,
+    /// Save modified editor buffers every this many seconds; 0 disables it
+    pub autosave_secs: u64,
+    /// Save modified editor buffers when the terminal loses focus
+    pub autosave_on_focus_lost: bool,
+    /// Keep a copy of each file before the editor first overwrites it
+    pub backup_on_save: BackupMode,
+    /// Built-in colour theme: `dark`, `light` or `solarized`
+    pub theme: String,
+    /// Colours overriding single roles of the theme (e.g. `"selection_bg": "#264f78"`)
+    pub theme_colors: HashMap<String, String>,
+    /// Browser panels, saved here when toggled or resized
+    pub layout: PanelLayout,
+    /// File list icons: `auto`, `emoji`, `nerd` or `ascii`
+    pub icons: IconMode,
+    /// File list columns in order, each with an optional width (e.g. `"name:60"`)
+    pub columns: Vec<Column>,
+    /// Make entry names clickable `sftp://` links on terminals known to support them
+    pub hyperlinks: bool,
+    /// Remote commands for the `Ctrl+p` palette, after the connection's own
+    pub commands: Vec<PaletteCommand>,
+    /// Seconds between keepalive messages; 0 sends none
+    pub keepalive_secs: u64,
+    /// Shell started in shell mode; empty for the remote user's login shell
+    pub shell: String,
+    /// Terminal type requested for shell mode
+    pub term: String,
+    /// List dotfiles on a connection opened for the first time
+    pub show_hidden: bool,
+    /// Order of the connection selector: `saved`, `recent` or `frequent`
+    pub connection_sort: ConnectionSort,
+    /// Store saved connections as `json` or `toml`; switching converts the file
+    pub connections_format: ConnectionsFormat,
+    /// Delete a saved session, shell history or list of visited directories
+    /// left unchanged for this many days; 0 keeps them
+    pub state_max_age_days: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            confirm_delete: true,
+            open_with: HashMap::new(),
+            auto_refresh_secs: 0,
+            transfer_concurrency: 4,
+            editor: EditorOptions::default(),
+            autosave_secs: 0,
+            autosave_on_focus_lost: false,
+            backup_on_save: BackupMode::Off,
+            theme: String::from("dark"),
+            theme_colors: HashMap::new(),
+            layout: PanelLayout::default(),
+            icons: IconMode::Auto,
+            columns: default_columns(),
+            hyperlinks: true,
+            commands: Vec::new(),
+            keepalive_secs: DEFAULT_KEEPALIVE_SECS,
+            shell: String::new(),
+            term: String::from("xterm-256color"),
+            show_hidden: true,
+            connection_sort: ConnectionSort::Saved,
+            connections_format: ConnectionsFormat::Json,
+            state_max_age_days: 180,
+        }
+    }
+}
+
+impl Config {
+    fn get_config_file_path() -> Result<PathBuf> {
+        let config_dir = dirs::config_dir()
+            .or_else(|| dirs::home_dir().map(|h| h.join(".config")))
+            .ok_or_else(|| anyhow::anyhow!("Could not find config directory"))?;
+
+        let bssh_dir = config_dir.join("bssh");
+        fs::create_dir_all(&bssh_dir)?;
+
+        Ok(bssh_dir.join("config.json"))
+    }
+
+    /// Load the config file, falling back to defaults if it is missing or invalid
+    pub fn load() -> Self {
+        Self::get_config_file_path()
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    /// Layer the settings `connection` overrides over these
+    pub fn apply_connection(&mut self, connection: &SavedConnection) {
+        if let Some(keepalive_secs) = connection.keepalive_secs {
+            self.keepalive_secs = keepalive_secs;
+        }
+        if let Some(ref shell) = connection.shell {
+            self.shell = shell.clone();
+        }
+        if let Some(ref term) = connection.term {
+            self.term = term.clone();
+        }
+        if let Some(show_hidden) = connection.show_hidden {
+            self.show_hidden = show_hidden;
+        }
+        if let Some(ref theme) = connection.theme {
+            self.theme = theme.clone();
+        }
+        self.commands.splice(0..0, connection.commands.iter().cloned());
+    }
+
+    /// Store `layout` in the config file, leaving the rest of it as it is
+    pub fn save_layout(layout: &PanelLayout) -> Result<()> {
+        let path = Self::get_config_file_path()?;
+        let json = match fs::read_to_string(&path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::from("{}"),
+            Err(e) => return Err(e.into()),
+        };
+        write_atomic(&path, with_layout(&json, layout)?)?;
+        Ok(())
+    }
+}
+
+/// The config file text `json` with its `layout` key replaced
+fn with_layout(json: &str, layout: &PanelLayout) -> Result<String> {
+    let mut config: serde_json::Value = serde_json::from_str(json).context("Config file is not valid JSON")?;
+    let object = config.as_object_mut().context("Config file is not a JSON object")?;
+    object.insert(String::from("layout"), serde_json::to_value(layout)?);
+    Ok(serde_json::to_string_pretty(&config)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_fields_use_defaults() {
+        let config: Config = serde_json::from_str("{}").unwrap();
+        assert_eq!(config, Config::default());
+        assert!(config.confirm_delete);
+    }
+
+    #[test]
+    fn test_editor_options_are_top_level_keys() {
+        let config: Config = serde_json::from_str(r#"{"tab_width": 8, "wrap_lines": true}"#).unwrap();
+        assert_eq!((config.editor.tab_width, config.editor.wrap), (8, true));
+        assert!(config.editor.expand_tab);
+    }
+
+    #[test]
+    fn test_saving_the_layout_keeps_other_keys() {
+        let layout = PanelLayout { preview: true, ..PanelLayout::default() };
+        let json = with_layout(r#"{"confirm_delete": false, "custom": 1, "layout": {"preview": false}}"#, &layout).unwrap();
+        let config: Config = serde_json::from_str(&json).unwrap();
+        assert!(!config.confirm_delete);
+        assert_eq!(config.layout, layout);
+        assert!(json.contains(r#""custom": 1"#));
+        assert!(with_layout("[]", &layout).is_err());
+    }
+
+    #[test]
+    fn test_connection_settings_override_the_config() {
+        let mut config: Config = serde_json::from_str(r#"{"keepalive_secs": 30, "term": "screen"}"#).unwrap();
+        let mut connection = SavedConnection::new("prod".into(), "h".into(), 22, "u".into(), None);
+        connection.theme = Some(String::from("light"));
+        connection.keepalive_secs = Some(0);
+        config.apply_connection(&connection);
+        assert_eq!((config.keepalive_secs, config.term.as_str()), (0, "screen"));
+        assert_eq!((config.theme.as_str(), config.show_hidden), ("light", true));
+    }
+
+    #[test]
+    fn test_confirm_delete_can_be_disabled() {
+        let config: Config = serde_json::from_str(r#"{"confirm_delete": false}"#).unwrap();
+        assert!(!config.confirm_delete);
+    }
+
+    #[test]
+    fn test_open_with_maps_extensions_to_actions() {
+        let config: Config = serde_json::from_str(r#"{"open_with": {"log": "follow", "dat": "hex"}}"#).unwrap();
+        assert_eq!(config.open_with.get("log"), Some(&OpenAction::Follow));
+        assert_eq!(config.open_with.get("dat"), Some(&OpenAction::Hex));
+    }
+}
@@ -0,0 +1,151 @@
+use anyhow::{bail, Context, Result};
+use clap::Subcommand;
+use std::path::PathBuf;
+
+use crate::connections::{load_connections, save_connections, SavedConnection};
+use crate::destination::parse_connection_string;
+
+/// `bssh conn ...`: manage saved connections without the TUI
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum ConnCommand {
+    /// List saved connections, one `name<TAB>user@host:port` per line
+    List,
+    /// Save a connection
+    Add {
+        name: String,
+        /// [user@]host[:port]
+        #[arg(value_name = "DESTINATION")]
+        destination: String,
+        /// Identity file (private key) for authentication
+        #[arg(short = 'i', long = "identity", value_name = "FILE")]
+        identity: Option<PathBuf>,
+        /// Port, if not given in the destination
+        #[arg(short = 'p', long = "port", value_name = "PORT")]
+        port: Option<u16>,
+        /// Replace a connection saved under the same name
+        #[arg(short = 'f', long = "force")]
+        force: bool,
+    },
+    /// Delete a saved connection
+    Remove { name: String },
+    /// Print a saved connection with all its settings, as JSON
+    Show { name: String },
+    /// Give a saved connection another name
+    Rename { name: String, new_name: String },
+}
+
+/// Carry out `command`, printing its result
+pub fn run(command: ConnCommand) -> Result<()> {
+    let mut connections = load_connections()?;
+    match command {
+        ConnCommand::List => {
+            for connection in &connections {
+                println!("{}\t{}", connection.name, connection.display_name());
+            }
+            return Ok(());
+        }
+        ConnCommand::Show { name } => {
+            let connection = find(&connections, &name)?;
+            println!("{}", serde_json::to_string_pretty(connection)?);
+            return Ok(());
+        }
+        ConnCommand::Add {
+            name,
+            destination,
+            identity,
+            port,
+            force,
+        } => {
+            let (username, host, destination_port, _) = parse_connection_string(&destination)?;
+            let port = port.unwrap_or(destination_port);
+            let connection = SavedConnection::new(name, host, port, username, identity);
+            let saved = format!("Saved {}: {}", connection.name, connection.display_name());
+            add(&mut connections, connection, force)?;
+            println!("{}", saved);
+        }
+        ConnCommand::Remove { name } => {
+            find(&connections, &name)?;
+            connections.retain(|c| c.name != name);
+            println!("Removed {}", name);
+        }
+        ConnCommand::Rename { name, new_name } => {
+            rename(&mut connections, &name, &new_name)?;
+            println!("Renamed {} to {}", name, new_name);
+        }
+    }
+    save_connections(&connections).context("Failed to save connections")
+}
+
+fn find<'a>(connections: &'a [SavedConnection], name: &str) -> Result<&'a SavedConnection> {
+    connections
+        .iter()
+        .find(|c| c.name == name)
+        .with_context(|| format!("No saved connection named '{}'", name))
+}
+
+fn add(connections: &mut Vec<SavedConnection>, connection: SavedConnection, force: bool) -> Result<()> {
+    match connections.iter_mut().find(|c| c.name == connection.name) {
+        Some(_) if !force => bail!("A connection named '{}' exists; --force replaces it", connection.name),
+        Some(existing) => *existing = connection,
+        None => connections.push(connection),
+    }
+    Ok(())
+}
+
+fn rename(connections: &mut [SavedConnection], name: &str, new_name: &str) -> Result<()> {
+    if connections.iter().any(|c| c.name == new_name) {
+        bail!("A connection named '{}' exists", new_name);
+    }
+    let connection = connections
+        .iter_mut()
+        .find(|c| c.name == name)
+        .with_context(|| format!("No saved connection named '{}'", name))?;
+    connection.name = new_name.to_string();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(subcommand)]
+        command: ConnCommand,
+    }
+
+    fn connection(name: &str) -> SavedConnection {
+        SavedConnection::new(name.to_string(), "h".to_string(), 22, "u".to_string(), None)
+    }
+
+    #[test]
+    fn test_add_and_rename_refuse_to_overwrite() {
+        let mut connections = vec![connection("web"), connection("db")];
+        assert!(add(&mut connections, connection("db"), false).is_err());
+        add(&mut connections, connection("db"), true).unwrap();
+        add(&mut connections, connection("cache"), false).unwrap();
+        assert_eq!(connections.len(), 3);
+
+        assert!(rename(&mut connections, "web", "db").is_err());
+        assert!(rename(&mut connections, "nope", "x").is_err());
+        rename(&mut connections, "web", "www").unwrap();
+        assert_eq!(connections[0].name, "www");
+    }
+
+    #[test]
+    fn test_subcommand_arguments() {
+        let cli = Cli::try_parse_from(["conn", "add", "prod", "deploy@web-1:2222", "-i", "/keys/prod"]).unwrap();
+        assert_eq!(
+            cli.command,
+            ConnCommand::Add {
+                name: String::from("prod"),
+                destination: String::from("deploy@web-1:2222"),
+                identity: Some(PathBuf::from("/keys/prod")),
+                port: None,
+                force: false,
+            }
+        );
+        assert!(Cli::try_parse_from(["conn", "rename", "prod"]).is_err());
+    }
+}
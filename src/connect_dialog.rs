@@ -0,0 +1,256 @@
+use crate::ssh::client::{is_retryable, ConnectOptions, SshClient, SshSession, RETRY_DELAY};
+use crate::ssh::host_key::HostKeyPrompt;
+use crate::theme::Theme;
+use crate::tui::confirm::Confirm;
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Alignment, Constraint, Direction, Layout},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph},
+    Frame, Terminal,
+};
+use std::io;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+const SPINNER: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// What the progress dialog is currently showing
+struct ConnectProgress {
+    target: String,
+    attempt: u32,
+    max_attempts: u32,
+    started: Instant,
+    last_error: Option<String>,
+    /// Unknown host key waiting for the user to trust or reject it
+    host_key: Option<HostKeyPrompt>,
+}
+
+impl ConnectProgress {
+    fn status_line(&self) -> String {
+        let elapsed = self.started.elapsed().as_secs();
+        if self.max_attempts > 1 {
+            format!(
+                "Attempt {}/{} ({}s elapsed)",
+                self.attempt, self.max_attempts, elapsed
+            )
+        } else {
+            format!("{}s elapsed", elapsed)
+        }
+    }
+}
+
+/// Open the SSH transport while showing a progress dialog that can be
+/// cancelled with Esc/Ctrl+C. Retries according to `options`.
+/// Returns Ok(None) if the user cancelled.
+pub async fn open_transport_with_progress(
+    host: &str,
+    port: u16,
+    username: &str,
+    options: &ConnectOptions,
+) -> Result<Option<SshSession>> {
+    let mut terminal = setup_terminal()?;
+    let result = run_dialog(&mut terminal, host, port, username, options).await;
+    restore_terminal(&mut terminal)?;
+
+    result
+}
+
+async fn run_dialog(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    host: &str,
+    port: u16,
+    username: &str,
+    options: &ConnectOptions,
+) -> Result<Option<SshSession>> {
+    let mut progress = ConnectProgress {
+        target: format!("{}@{}:{}", username, host, port),
+        attempt: 0,
+        max_attempts: options.retries + 1,
+        started: Instant::now(),
+        last_error: None,
+        host_key: None,
+    };
+
+    let (prompt_sender, mut prompt_receiver) = mpsc::unbounded_channel();
+
+    loop {
+        progress.attempt += 1;
+        // A question left over from the failed attempt has no one to answer
+        progress.host_key = None;
+
+        let connect = SshClient::open_transport(host, port, options, Some(prompt_sender.clone()));
+        tokio::pin!(connect);
+
+        let result = loop {
+            tokio::select! {
+                result = &mut connect => break result,
+                Some(request) = prompt_receiver.recv() => {
+                    progress.host_key = Some(request);
+                }
+                _ = tokio::time::sleep(Duration::from_millis(100)) => {
+                    terminal.draw(|f| render(f, &progress, &options.theme))?;
+                    if progress.host_key.is_some() {
+                        if let Some(request) = progress.host_key.take() {
+                            match host_key_answer(&request)? {
+                                Some(trusted) => {
+                                    let _ = request.reply.send(trusted);
+                                }
+                                None => progress.host_key = Some(request),
+                            }
+                        }
+                    } else if cancel_requested()? {
+                        return Ok(None);
+                    }
+                }
+            }
+        };
+
+        match result {
+            Ok(session) => return Ok(Some(session)),
+            Err(e) if progress.attempt >= progress.max_attempts || !is_retryable(&e) => {
+                return Err(e);
+            }
+            Err(e) => {
+                progress.last_error = Some(format!("{:#}", e));
+
+                // Keep the dialog responsive while waiting to retry
+                let retry_at = Instant::now() + RETRY_DELAY;
+                while Instant::now() < retry_at {
+                    terminal.draw(|f| render(f, &progress, &options.theme))?;
+                    if cancel_requested()? {
+                        return Ok(None);
+                    }
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            }
+        }
+    }
+}
+
+fn cancel_requested() -> Result<bool> {
+    while event::poll(Duration::ZERO)? {
+        if let Event::Key(key) = event::read()? {
+            match key.code {
+                KeyCode::Esc | KeyCode::Char('q') => return Ok(true),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(true);
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Read a y/n answer to the host key question, if one was typed
+fn host_key_answer(request: &HostKeyPrompt) -> Result<Option<bool>> {
+    let confirm = host_key_confirm(request);
+    while event::poll(Duration::ZERO)? {
+        if let Event::Key(key) = event::read()?
+            && let Some(trusted) = confirm.accepts(key, 'y')
+        {
+            return Ok(Some(trusted));
+        }
+    }
+    Ok(None)
+}
+
+fn host_key_confirm(request: &HostKeyPrompt) -> Confirm {
+    Confirm::new(
+        "Unknown host",
+        &format!(
+            "The authenticity of host '{}' (port {}) can't be established.",
+            request.host, request.port
+        ),
+    )
+    .with_details(vec![
+        Line::from(format!("{} key fingerprint is SHA256:{}", request.key_type, request.fingerprint)),
+        Line::from(""),
+        Line::from("Trust this host and add it to known_hosts?"),
+    ])
+    .with_choices(&[('y', "Trust")])
+}
+
+fn render(f: &mut Frame, progress: &ConnectProgress, theme: &Theme) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(3),
+        ])
+        .split(f.area());
+
+    let header = Paragraph::new(vec![Line::from(vec![Span::styled(
+        "Connecting",
+        Style::default().fg(theme.accent).add_modifier(Modifier::BOLD),
+    )])])
+    .block(Block::default().borders(Borders::ALL).title("bssh"));
+
+    f.render_widget(header, chunks[0]);
+
+    let frame = (progress.started.elapsed().as_millis() / 100) as usize % SPINNER.len();
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled(
+                format!("{} ", SPINNER[frame]),
+                Style::default().fg(theme.warning),
+            ),
+            Span::raw(format!("Connecting to {}...", progress.target)),
+        ]),
+        Line::from(Span::styled(
+            progress.status_line(),
+            Style::default().fg(theme.muted),
+        )),
+    ];
+
+    if let Some(ref error) = progress.last_error {
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            format!("Last attempt failed: {}", error),
+            Style::default().fg(theme.error),
+        )));
+    }
+
+    let body = Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Status"));
+
+    f.render_widget(body, chunks[1]);
+
+    let help = vec![
+        Span::styled("Esc", Style::default().fg(theme.key)),
+        Span::raw(": Cancel"),
+    ];
+
+    let footer = Paragraph::new(vec![Line::from(help)])
+    .block(Block::default().borders(Borders::ALL).title("Help"))
+    .alignment(Alignment::Left);
+
+    f.render_widget(footer, chunks[2]);
+
+    if let Some(ref request) = progress.host_key {
+        host_key_confirm(request).render(f, f.area(), theme);
+    }
+}
+
+fn setup_terminal() -> Result<Terminal<CrosstermBackend<io::Stdout>>> {
+    crossterm::terminal::enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    crossterm::execute!(stdout, crossterm::terminal::EnterAlternateScreen)?;
+    let backend = CrosstermBackend::new(stdout);
+    let terminal = Terminal::new(backend)?;
+    Ok(terminal)
+}
+
+fn restore_terminal(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Result<()> {
+    crossterm::terminal::disable_raw_mode()?;
+    crossterm::execute!(
+        terminal.backend_mut(),
+        crossterm::terminal::LeaveAlternateScreen
+    )?;
+    terminal.show_cursor()?;
+    Ok(())
+}
@@ -0,0 +1,256 @@
+use crate::connections::SavedConnection;
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+/// Most recent history lines searched for `ssh` commands
+const HISTORY_LINES: usize = 5000;
+
+/// ssh options that take a value, which is not the destination
+const SSH_VALUE_OPTIONS: &str = "BbcDEeFIiJLlmOoPpQRSWw";
+
+/// Where a suggested connection was found
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportSource {
+    History,
+    KnownHosts,
+}
+
+impl ImportSource {
+    pub fn label(self) -> &'static str {
+        match self {
+            ImportSource::History => "shell history",
+            ImportSource::KnownHosts => "known_hosts",
+        }
+    }
+}
+
+/// A connection that could be added, and whether it is ticked for adding
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportCandidate {
+    pub connection: SavedConnection,
+    pub source: ImportSource,
+    pub checked: bool,
+}
+
+/// Connections not saved yet, from recent `ssh user@host` commands in the
+/// shell history and then the hosts in `~/.ssh/known_hosts`
+pub fn suggest_connections(existing: &[SavedConnection]) -> Vec<ImportCandidate> {
+    let Some(home) = dirs::home_dir() else {
+        return Vec::new();
+    };
+    let mut history_files: Vec<PathBuf> = vec![home.join(".bash_history"), home.join(".zsh_history")];
+    if let Some(histfile) = std::env::var_os("HISTFILE") {
+        history_files.insert(0, PathBuf::from(histfile));
+    }
+    let history: Vec<String> = history_files
+        .iter()
+        .filter_map(|path| fs::read(path).ok())
+        .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+        .collect();
+    let known_hosts = fs::read_to_string(home.join(".ssh").join("known_hosts")).unwrap_or_default();
+    let username = std::env::var("USER").unwrap_or_default();
+
+    let mut found: Vec<(SavedConnection, ImportSource)> = Vec::new();
+    for text in &history {
+        found.extend(parse_history(text).into_iter().map(|c| (c, ImportSource::History)));
+    }
+    found.extend(
+        parse_known_hosts(&known_hosts)
+            .into_iter()
+            .map(|(host, port)| (new_connection(&username, &host, port), ImportSource::KnownHosts)),
+    );
+    candidates(found, existing)
+}
+
+/// Drop suggestions already saved or listed, naming the rest uniquely
+fn candidates(found: Vec<(SavedConnection, ImportSource)>, existing: &[SavedConnection]) -> Vec<ImportCandidate> {
+    let mut seen: HashSet<(String, String, u16)> = existing
+        .iter()
+        .map(|c| (c.username.clone(), c.host.clone(), c.port))
+        .collect();
+    let mut names: HashSet<String> = existing.iter().map(|c| c.name.clone()).collect();
+    let mut hosts_seen: HashSet<(String, u16)> = HashSet::new();
+
+    let mut result = Vec::new();
+    for (mut connection, source) in found {
+        let key = (connection.username.clone(), connection.host.clone(), connection.port);
+        // known_hosts does not know the user; a host from the history wins
+        let host_key = (connection.host.clone(), connection.port);
+        if connection.username.is_empty()
+            || seen.contains(&key)
+            || (source == ImportSource::KnownHosts && hosts_seen.contains(&host_key))
+        {
+            continue;
+        }
+        if names.contains(&connection.name) {
+            connection.name = connection.display_name();
+        }
+        seen.insert(key);
+        hosts_seen.insert(host_key);
+        names.insert(connection.name.clone());
+        result.push(ImportCandidate {
+            connection,
+            source,
+            checked: false,
+        });
+    }
+    result
+}
+
+fn new_connection(username: &str, host: &str, port: u16) -> SavedConnection {
+    SavedConnection::new(host.to_string(), host.to_string(), port, username.to_string(), None)
+}
+
+/// Hosts and ports in a known_hosts file. Hashed names cannot be read
+/// back, and patterns do not name one host, so both are skipped.
+fn parse_known_hosts(text: &str) -> Vec<(String, u16)> {
+    let mut hosts = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let mut names = fields.next().unwrap_or_default();
+        if names.starts_with('@') {
+            // @cert-authority and @revoked lines are not hosts to connect to
+            continue;
+        }
+        if names.starts_with("|1|") {
+            names = "";
+        }
+        for name in names.split(',').filter(|name| !name.is_empty()) {
+            if name.contains(['*', '?', '!']) {
+                continue;
+            }
+            let entry = match name.strip_prefix('[').and_then(|rest| rest.split_once("]:")) {
+                Some((host, port)) => port.parse().ok().map(|port| (host.to_string(), port)),
+                None => Some((name.to_string(), 22)),
+            };
+            if let Some(entry) = entry
+                && !hosts.contains(&entry)
+            {
+                hosts.push(entry);
+            }
+        }
+    }
+    hosts
+}
+
+/// Connections from `ssh user@host` commands in shell history, most recent
+/// first. Lines in zsh's extended history format are read too.
+fn parse_history(text: &str) -> Vec<SavedConnection> {
+    let lines: Vec<&str> = text.lines().collect();
+    let recent = &lines[lines.len().saturating_sub(HISTORY_LINES)..];
+    let mut connections: Vec<SavedConnection> = Vec::new();
+    for line in recent.iter().rev() {
+        // ": 1700000000:0;ssh deploy@web-1"
+        let line = match line.strip_prefix(": ").and_then(|rest| rest.split_once(';')) {
+            Some((_, command)) => command,
+            None => line,
+        };
+        if let Some(connection) = parse_ssh_command(line)
+            && !connections.iter().any(|c| c.display_name() == connection.display_name())
+        {
+            connections.push(connection);
+        }
+    }
+    connections
+}
+
+/// The destination of an `ssh` command line, if it names the user
+fn parse_ssh_command(line: &str) -> Option<SavedConnection> {
+    let mut words = line.split_whitespace();
+    if words.next()? != "ssh" {
+        return None;
+    }
+    let (mut user, mut port, mut identity) = (None, 22, None);
+    while let Some(word) = words.next() {
+        let Some(flags) = word.strip_prefix('-') else {
+            // The destination; what follows is the remote command
+            let (destination, url_port) = match word.strip_prefix("ssh://") {
+                Some(url) => match url.rsplit_once(':') {
+                    Some((destination, port)) => (destination, port.parse().ok()),
+                    None => (url, None),
+                },
+                None => (word, None),
+            };
+            let (user, host) = match destination.split_once('@') {
+                Some((user, host)) => (user.to_string(), host),
+                None => (user?, destination),
+            };
+            let mut connection = new_connection(&user, host, url_port.unwrap_or(port));
+            connection.identity_file = identity;
+            return Some(connection);
+        };
+        // Option value given with the flag (-p2222) or as the next word
+        let Some(position) = flags.find(|c| SSH_VALUE_OPTIONS.contains(c)) else {
+            continue;
+        };
+        let flag = flags[position..].chars().next()?;
+        let attached = &flags[position + 1..];
+        let value = if attached.is_empty() { words.next()? } else { attached };
+        match flag {
+            'p' => port = value.parse().ok()?,
+            'l' => user = Some(value.to_string()),
+            'i' => {
+                // The shell expanded ~ when the command ran
+                identity = Some(match (value.strip_prefix("~/"), dirs::home_dir()) {
+                    (Some(rest), Some(home)) => home.join(rest),
+                    _ => PathBuf::from(value),
+                })
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_hosts_entries() {
+        let text = "\
+# comment
+web-1,10.0.0.5 ssh-ed25519 AAAA
+[db.internal]:2222 ssh-rsa AAAA
+|1|abc=|def= ssh-ed25519 AAAA
+*.example.com ssh-rsa AAAA
+@cert-authority *.corp ssh-rsa AAAA
+";
+        assert_eq!(
+            parse_known_hosts(text),
+            [
+                (String::from("web-1"), 22),
+                (String::from("10.0.0.5"), 22),
+                (String::from("db.internal"), 2222)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ssh_commands_in_history() {
+        let text = "\
+ssh deploy@web-1
+ls
+: 1700000000:0;ssh -p 2222 -i /keys/ops admin@db.internal uptime
+ssh web-2
+ssh -l root -o ConnectTimeout=5 cache-1
+ssh deploy@web-1
+";
+        let found = parse_history(text);
+        let names: Vec<String> = found.iter().map(|c| c.display_name()).collect();
+        assert_eq!(names, ["deploy@web-1:22", "root@cache-1:22", "admin@db.internal:2222"]);
+        assert_eq!(found[2].identity_file, Some(PathBuf::from("/keys/ops")));
+
+        // Saved ones and hosts already found in the history are left out
+        let mut found: Vec<_> = found.into_iter().map(|c| (c, ImportSource::History)).collect();
+        found.push((new_connection("me", "web-1", 22), ImportSource::KnownHosts));
+        found.push((new_connection("me", "web-3", 22), ImportSource::KnownHosts));
+        let existing = [new_connection("root", "cache-1", 22)];
+        let names: Vec<String> = candidates(found, &existing).iter().map(|c| c.connection.name.clone()).collect();
+        assert_eq!(names, ["web-1", "db.internal", "web-3"]);
+    }
+}
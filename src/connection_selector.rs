@@ -1,44 +1,71 @@
-use crate::connections::SavedConnection;
+use crate::connection_import::{suggest_connections, ImportCandidate};
+use crate::connections::{save_connections, ConnectionSort, SavedConnection};
+use crate::state::SessionState;
+use crate::theme::Theme;
 use anyhow::Result;
 use arboard::Clipboard;
 use crossterm::event::{self, Event, KeyCode, KeyModifiers};
 use ratatui::{
     backend::CrosstermBackend,
     layout::{Alignment, Constraint, Direction, Layout},
-    style::{Color, Modifier, Style},
+    style::{Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, Paragraph},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
     Frame, Terminal,
 };
 use std::io;
 use std::time::{Duration, Instant};
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum SelectorResult {
-    Connect(SavedConnection),
-    Edit(SavedConnection),
-    Cancel,
+/// A saved session of the chosen connection
+struct SessionChoice {
+    /// None for the connection's default session
+    name: Option<String>,
+    /// Directory it was left in
+    path: String,
 }
 
 pub struct ConnectionSelector {
+    /// In saved order, as written back to the connections file
     connections: Vec<SavedConnection>,
+    sort: ConnectionSort,
+    /// Indices into `connections` in the order listed
+    order: Vec<usize>,
+    /// Position in the listed order
     selected_index: usize,
     status_message: Option<(String, Instant)>,
     edit_form: Option<EditForm>,
+    /// Suggested connections to tick and add, with the one under the cursor
+    import: Option<(Vec<ImportCandidate>, usize)>,
+    /// Session given on the command line, which skips the session list
+    session: Option<String>,
+    /// Sessions of the chosen connection, with the one under the cursor
+    sessions: Option<(Vec<SessionChoice>, usize)>,
+    theme: Theme,
 }
 
 impl ConnectionSelector {
-    pub fn new(connections: Vec<SavedConnection>) -> Self {
+    pub fn new(connections: Vec<SavedConnection>, sort: ConnectionSort, theme: Theme, session: Option<String>) -> Self {
         Self {
+            order: sort.order(&connections),
             connections,
+            sort,
             selected_index: 0,
             status_message: None,
             edit_form: None,
+            import: None,
+            session,
+            sessions: None,
+            theme,
         }
     }
 
-    pub fn run(mut self) -> Result<Option<SavedConnection>> {
+    /// The connection picked, with the name of the session to open in it
+    pub fn run(mut self) -> Result<Option<(SavedConnection, Option<String>)>> {
+        // With nothing saved yet, start with what can be imported
         if self.connections.is_empty() {
+            self.open_import();
+        }
+        if self.connections.is_empty() && self.import.is_none() {
             println!("No saved connections found.");
             println!("\nUsage: bssh [OPTIONS] <DESTINATION> [PATH]");
             println!("\nExample: bssh user@hostname");
@@ -55,7 +82,7 @@ impl ConnectionSelector {
     fn run_selector(
         &mut self,
         terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
-    ) -> Result<Option<SavedConnection>> {
+    ) -> Result<Option<(SavedConnection, Option<String>)>> {
         loop {
             terminal.draw(|f| self.render(f))?;
 
@@ -120,6 +147,25 @@ impl ConnectionSelector {
                     continue;
                 }
 
+                if self.import.is_some() {
+                    self.handle_import_key(key.code);
+                    if self.import.is_none() && self.connections.is_empty() {
+                        return Ok(None);
+                    }
+                    continue;
+                }
+
+                if self.sessions.is_some() {
+                    if let Some(session) = self.handle_sessions_key(key.code) {
+                        return Ok(Some((self.selected().clone(), session)));
+                    }
+                    continue;
+                }
+
+                if self.connections.is_empty() {
+                    continue;
+                }
+
                 // Normal mode input
                 match key.code {
                     KeyCode::Char('q') | KeyCode::Esc => {
@@ -128,18 +174,21 @@ impl ConnectionSelector {
                     KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                         return Ok(None);
                     }
-                    KeyCode::Up | KeyCode::Char('k') => {
-                        if self.selected_index > 0 {
-                            self.selected_index -= 1;
-                        }
+                    KeyCode::Up | KeyCode::Char('k') if self.selected_index > 0 => {
+                        self.selected_index -= 1;
                     }
-                    KeyCode::Down | KeyCode::Char('j') => {
-                        if self.selected_index < self.connections.len() - 1 {
-                            self.selected_index += 1;
-                        }
+                    KeyCode::Down | KeyCode::Char('j')
+                        if self.selected_index < self.connections.len() - 1 =>
+                    {
+                        self.selected_index += 1;
+                    }
+                    KeyCode::Char('s') => {
+                        self.sort = self.sort.next();
+                        self.resort();
+                        self.status_message = Some((format!("Sorted by {}", self.sort.label()), Instant::now()));
                     }
                     KeyCode::Char('c') => {
-                        let conn = &self.connections[self.selected_index];
+                        let conn = self.selected();
                         let ssh_cmd = conn.ssh_command();
                         match Clipboard::new().and_then(|mut cb| cb.set_text(&ssh_cmd)) {
                             Ok(_) => {
@@ -157,11 +206,20 @@ impl ConnectionSelector {
                         }
                     }
                     KeyCode::Char('e') => {
-                        let conn = &self.connections[self.selected_index];
+                        let conn = self.selected();
                         self.edit_form = Some(EditForm::from_connection(conn));
                     }
+                    KeyCode::Char('i') => {
+                        self.open_import();
+                        if self.import.is_none() {
+                            self.status_message = Some(("Nothing new to import".to_string(), Instant::now()));
+                        }
+                    }
                     KeyCode::Enter => {
-                        return Ok(Some(self.connections[self.selected_index].clone()));
+                        self.open_sessions();
+                        if self.sessions.is_none() {
+                            return Ok(Some((self.selected().clone(), self.session.clone())));
+                        }
                     }
                     _ => {}
                 }
@@ -169,6 +227,96 @@ impl ConnectionSelector {
         }
     }
 
+    fn selected(&self) -> &SavedConnection {
+        &self.connections[self.order[self.selected_index]]
+    }
+
+    /// List in the current sort order, starting from the top
+    fn resort(&mut self) {
+        self.order = self.sort.order(&self.connections);
+        self.selected_index = 0;
+    }
+
+    /// List connections from shell history and known_hosts that are not saved
+    fn open_import(&mut self) {
+        let candidates = suggest_connections(&self.connections);
+        self.import = (!candidates.is_empty()).then_some((candidates, 0));
+    }
+
+    /// List the chosen connection's sessions if it has named ones and none
+    /// was given on the command line
+    fn open_sessions(&mut self) {
+        if self.session.is_some() {
+            return;
+        }
+        let conn = self.selected();
+        let (host, port, username) = (conn.host.clone(), conn.port, conn.username.clone());
+        let names = SessionState::named_sessions(&host, port, &username);
+        if names.is_empty() {
+            return;
+        }
+        let sessions = std::iter::once(None)
+            .chain(names.into_iter().map(Some))
+            .map(|name| {
+                let path = SessionState::load(&host, port, &username, name.as_deref())
+                    .map(|state| state.current_path)
+                    .unwrap_or_default();
+                SessionChoice { name, path }
+            })
+            .collect();
+        self.sessions = Some((sessions, 0));
+    }
+
+    /// Move in the session list; Some with the session picked on Enter
+    fn handle_sessions_key(&mut self, code: KeyCode) -> Option<Option<String>> {
+        let (sessions, selected) = self.sessions.as_mut()?;
+        match code {
+            KeyCode::Esc | KeyCode::Char('q') => self.sessions = None,
+            KeyCode::Up | KeyCode::Char('k') => *selected = selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => *selected = (*selected + 1).min(sessions.len() - 1),
+            KeyCode::Enter => return Some(sessions[*selected].name.clone()),
+            _ => {}
+        }
+        None
+    }
+
+    fn handle_import_key(&mut self, code: KeyCode) {
+        let Some((candidates, selected)) = self.import.as_mut() else {
+            return;
+        };
+        match code {
+            KeyCode::Esc | KeyCode::Char('q') => self.import = None,
+            KeyCode::Up | KeyCode::Char('k') => *selected = selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => *selected = (*selected + 1).min(candidates.len() - 1),
+            KeyCode::Char(' ') => candidates[*selected].checked = !candidates[*selected].checked,
+            KeyCode::Char('a') => {
+                let check = !candidates.iter().all(|candidate| candidate.checked);
+                candidates.iter_mut().for_each(|candidate| candidate.checked = check);
+            }
+            KeyCode::Enter => {
+                let chosen: Vec<SavedConnection> = candidates
+                    .iter()
+                    .filter(|candidate| candidate.checked)
+                    .map(|candidate| candidate.connection.clone())
+                    .collect();
+                if chosen.is_empty() {
+                    self.status_message = Some(("Space ticks a connection to add".to_string(), Instant::now()));
+                    return;
+                }
+                let count = chosen.len();
+                self.connections.extend(chosen);
+                self.resort();
+                let message = match save_connections(&self.connections) {
+                    Ok(()) => format!("Added {} connection(s)", count),
+                    Err(e) => format!("Save failed: {}", e),
+                };
+                self.status_message = Some((message, Instant::now()));
+                self.import = None;
+            }
+            _ => {}
+        }
+    }
+
     fn render(&self, f: &mut Frame) {
         // If in edit mode, render the edit form
         if let Some(ref form) = self.edit_form {
@@ -176,6 +324,16 @@ impl ConnectionSelector {
             return;
         }
 
+        if let Some((ref candidates, selected)) = self.import {
+            self.render_import(f, candidates, selected);
+            return;
+        }
+
+        if let Some((ref sessions, selected)) = self.sessions {
+            self.render_sessions(f, sessions, selected);
+            return;
+        }
+
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
@@ -190,12 +348,13 @@ impl ConnectionSelector {
             Line::from(vec![
                 Span::styled(
                     "Select SSH Connection",
-                    Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+                    Style::default().fg(self.theme.accent).add_modifier(Modifier::BOLD),
                 ),
             ]),
             Line::from(vec![Span::raw(format!(
-                "{} saved connection(s)",
-                self.connections.len()
+                "{} saved connection(s), {}",
+                self.connections.len(),
+                self.sort.label()
             ))]),
         ])
         .block(Block::default().borders(Borders::ALL).title("bssh"));
@@ -203,22 +362,27 @@ impl ConnectionSelector {
         f.render_widget(header, chunks[0]);
 
         // Connection list
+        let now = chrono::Utc::now().timestamp();
         let items: Vec<ListItem> = self
-            .connections
+            .order
             .iter()
+            .map(|&index| &self.connections[index])
             .enumerate()
             .map(|(i, conn)| {
                 let line = Line::from(vec![
                     Span::styled(
-                        format!("{:<20}", conn.name),
-                        Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+                        format!("{:<20}", conn.label()),
+                        Style::default()
+                            .fg(conn.color().unwrap_or(self.theme.key))
+                            .add_modifier(Modifier::BOLD),
                     ),
                     Span::raw("  "),
-                    Span::raw(conn.display_name()),
+                    Span::raw(format!("{:<32}", conn.display_name())),
+                    Span::styled(usage(conn, now), Style::default().fg(self.theme.muted)),
                 ]);
 
                 let style = if i == self.selected_index {
-                    Style::default().bg(Color::DarkGray).fg(Color::White)
+                    Style::default().bg(self.theme.selection_bg).fg(self.theme.selection_fg)
                 } else {
                     Style::default()
                 };
@@ -235,13 +399,13 @@ impl ConnectionSelector {
         let footer_content = if let Some((ref msg, timestamp)) = self.status_message {
             if timestamp.elapsed() < Duration::from_secs(2) {
                 Line::from(vec![
-                    Span::styled(msg.clone(), Style::default().fg(Color::Green)),
+                    Span::styled(msg.clone(), Style::default().fg(self.theme.success)),
                 ])
             } else {
-                Self::help_line()
+                self.help_line()
             }
         } else {
-            Self::help_line()
+            self.help_line()
         };
 
         let footer = Paragraph::new(vec![footer_content])
@@ -266,7 +430,7 @@ impl ConnectionSelector {
             Line::from(vec![
                 Span::styled(
                     "Edit Connection",
-                    Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+                    Style::default().fg(self.theme.accent).add_modifier(Modifier::BOLD),
                 ),
             ]),
             Line::from(vec![Span::raw(format!("Editing: {}", form.original_name))]),
@@ -293,14 +457,14 @@ impl ConnectionSelector {
                 let line = Line::from(vec![
                     Span::styled(
                         format!("{:<14}", label),
-                        Style::default().fg(Color::Yellow),
+                        Style::default().fg(self.theme.key),
                     ),
                     Span::raw(": "),
                     Span::raw(format!("{}{}", value, cursor)),
                 ]);
 
                 let style = if is_selected {
-                    Style::default().bg(Color::DarkGray).fg(Color::White)
+                    Style::default().bg(self.theme.selection_bg).fg(self.theme.selection_fg)
                 } else {
                     Style::default()
                 };
@@ -316,11 +480,11 @@ impl ConnectionSelector {
         // Footer with edit mode help
         let footer = Paragraph::new(vec![
             Line::from(vec![
-                Span::styled("Tab/↑↓", Style::default().fg(Color::Yellow)),
+                Span::styled("Tab/↑↓", Style::default().fg(self.theme.key)),
                 Span::raw(": Navigate  "),
-                Span::styled("Enter", Style::default().fg(Color::Yellow)),
+                Span::styled("Enter", Style::default().fg(self.theme.key)),
                 Span::raw(": Save  "),
-                Span::styled("Esc", Style::default().fg(Color::Yellow)),
+                Span::styled("Esc", Style::default().fg(self.theme.key)),
                 Span::raw(": Cancel"),
             ]),
         ])
@@ -330,22 +494,192 @@ impl ConnectionSelector {
         f.render_widget(footer, chunks[2]);
     }
 
-    fn help_line() -> Line<'static> {
+    fn render_sessions(&self, f: &mut Frame, sessions: &[SessionChoice], selected: usize) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Min(0),
+                Constraint::Length(3),
+            ])
+            .split(f.area());
+
+        let header = Paragraph::new(vec![
+            Line::from(vec![
+                Span::styled(
+                    "Select Session",
+                    Style::default().fg(self.theme.accent).add_modifier(Modifier::BOLD),
+                ),
+            ]),
+            Line::from(vec![Span::raw(format!("{} on {}", self.selected().label(), self.selected().display_name()))]),
+        ])
+        .block(Block::default().borders(Borders::ALL).title("bssh"));
+
+        f.render_widget(header, chunks[0]);
+
+        let items: Vec<ListItem> = sessions
+            .iter()
+            .enumerate()
+            .map(|(i, session)| {
+                let line = Line::from(vec![
+                    Span::styled(
+                        format!("{:<20}", session.name.as_deref().unwrap_or("default")),
+                        Style::default().fg(self.theme.key).add_modifier(Modifier::BOLD),
+                    ),
+                    Span::raw("  "),
+                    Span::styled(session.path.clone(), Style::default().fg(self.theme.muted)),
+                ]);
+
+                let style = if i == selected {
+                    Style::default().bg(self.theme.selection_bg).fg(self.theme.selection_fg)
+                } else {
+                    Style::default()
+                };
+
+                ListItem::new(line).style(style)
+            })
+            .collect();
+
+        let mut state = ListState::default().with_selected(Some(selected));
+        let list = List::new(items).block(Block::default().borders(Borders::ALL).title("Sessions"));
+
+        f.render_stateful_widget(list, chunks[1], &mut state);
+
+        let footer = Paragraph::new(vec![Line::from(vec![
+            Span::styled("↑/↓", Style::default().fg(self.theme.key)),
+            Span::raw(": Navigate  "),
+            Span::styled("Enter", Style::default().fg(self.theme.key)),
+            Span::raw(": Open  "),
+            Span::styled("Esc", Style::default().fg(self.theme.key)),
+            Span::raw(": Back"),
+        ])])
+        .block(Block::default().borders(Borders::ALL).title("Help"));
+
+        f.render_widget(footer, chunks[2]);
+    }
+
+    fn render_import(&self, f: &mut Frame, candidates: &[ImportCandidate], selected: usize) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Min(0),
+                Constraint::Length(3),
+            ])
+            .split(f.area());
+
+        let checked = candidates.iter().filter(|candidate| candidate.checked).count();
+        let header = Paragraph::new(vec![
+            Line::from(vec![
+                Span::styled(
+                    "Import Connections",
+                    Style::default().fg(self.theme.accent).add_modifier(Modifier::BOLD),
+                ),
+            ]),
+            Line::from(vec![Span::raw(format!(
+                "{} found in shell history and known_hosts, {} ticked",
+                candidates.len(),
+                checked
+            ))]),
+        ])
+        .block(Block::default().borders(Borders::ALL).title("bssh"));
+
+        f.render_widget(header, chunks[0]);
+
+        let items: Vec<ListItem> = candidates
+            .iter()
+            .enumerate()
+            .map(|(i, candidate)| {
+                let line = Line::from(vec![
+                    Span::raw(if candidate.checked { "[x] " } else { "[ ] " }),
+                    Span::styled(
+                        format!("{:<20}", candidate.connection.name),
+                        Style::default().fg(self.theme.key).add_modifier(Modifier::BOLD),
+                    ),
+                    Span::raw("  "),
+                    Span::raw(candidate.connection.display_name()),
+                    Span::styled(
+                        format!("  from {}", candidate.source.label()),
+                        Style::default().fg(self.theme.muted),
+                    ),
+                ]);
+
+                let style = if i == selected {
+                    Style::default().bg(self.theme.selection_bg).fg(self.theme.selection_fg)
+                } else {
+                    Style::default()
+                };
+
+                ListItem::new(line).style(style)
+            })
+            .collect();
+
+        let mut state = ListState::default().with_selected(Some(selected));
+        let list = List::new(items).block(Block::default().borders(Borders::ALL).title("Suggestions"));
+
+        f.render_stateful_widget(list, chunks[1], &mut state);
+
+        let footer_content = match self.status_message {
+            Some((ref msg, timestamp)) if timestamp.elapsed() < Duration::from_secs(2) => {
+                Line::from(vec![Span::styled(msg.clone(), Style::default().fg(self.theme.success))])
+            }
+            _ => Line::from(vec![
+                Span::styled("Space", Style::default().fg(self.theme.key)),
+                Span::raw(": Tick  "),
+                Span::styled("a", Style::default().fg(self.theme.key)),
+                Span::raw(": Tick all  "),
+                Span::styled("Enter", Style::default().fg(self.theme.key)),
+                Span::raw(": Add ticked  "),
+                Span::styled("Esc", Style::default().fg(self.theme.key)),
+                Span::raw(": Cancel"),
+            ]),
+        };
+
+        let footer = Paragraph::new(vec![footer_content])
+            .block(Block::default().borders(Borders::ALL).title("Help"))
+            .alignment(Alignment::Left);
+
+        f.render_widget(footer, chunks[2]);
+    }
+
+    fn help_line(&self) -> Line<'static> {
         Line::from(vec![
-            Span::styled("↑/↓", Style::default().fg(Color::Yellow)),
+            Span::styled("↑/↓", Style::default().fg(self.theme.key)),
             Span::raw(": Navigate  "),
-            Span::styled("e", Style::default().fg(Color::Yellow)),
+            Span::styled("e", Style::default().fg(self.theme.key)),
             Span::raw(": Edit  "),
-            Span::styled("c", Style::default().fg(Color::Yellow)),
+            Span::styled("i", Style::default().fg(self.theme.key)),
+            Span::raw(": Import  "),
+            Span::styled("s", Style::default().fg(self.theme.key)),
+            Span::raw(": Sort  "),
+            Span::styled("c", Style::default().fg(self.theme.key)),
             Span::raw(": Copy  "),
-            Span::styled("Enter", Style::default().fg(Color::Yellow)),
+            Span::styled("Enter", Style::default().fg(self.theme.key)),
             Span::raw(": Connect  "),
-            Span::styled("q", Style::default().fg(Color::Yellow)),
+            Span::styled("q", Style::default().fg(self.theme.key)),
             Span::raw(": Quit"),
         ])
     }
 }
 
+/// When and how often `conn` was used, e.g. "3h ago, 12 times"
+fn usage(conn: &SavedConnection, now: i64) -> String {
+    let Some(last) = conn.last_connected else {
+        return String::from("never connected");
+    };
+    let secs = (now - last).max(0);
+    let ago = match secs {
+        0..60 => String::from("just now"),
+        60..3600 => format!("{}m ago", secs / 60),
+        3600..86400 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    };
+    match conn.connect_count {
+        1 => format!("{}, once", ago),
+        count => format!("{}, {} times", ago, count),
+    }
+}
+
 fn setup_terminal() -> Result<Terminal<CrosstermBackend<io::Stdout>>> {
     crossterm::terminal::enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -409,6 +743,8 @@ pub struct EditForm {
     pub identity_file: String,
     pub current_field: EditField,
     pub original_name: String,
+    /// The connection being edited, so settings without a form field survive
+    base: SavedConnection,
 }
 
 impl EditForm {
@@ -421,16 +757,7 @@ impl EditForm {
             identity_file: conn.identity_file.as_ref().map(|p| p.display().to_string()).unwrap_or_default(),
             current_field: EditField::Name,
             original_name: conn.name.clone(),
-        }
-    }
-
-    pub fn current_value(&self) -> &str {
-        match self.current_field {
-            EditField::Name => &self.name,
-            EditField::Host => &self.host,
-            EditField::Port => &self.port,
-            EditField::Username => &self.username,
-            EditField::IdentityFile => &self.identity_file,
+            base: conn.clone(),
         }
     }
 
@@ -467,13 +794,14 @@ impl EditForm {
         } else {
             Some(std::path::PathBuf::from(&self.identity_file))
         };
-        Ok(SavedConnection::new(
-            self.name.clone(),
-            self.host.clone(),
+        Ok(SavedConnection {
+            name: self.name.clone(),
+            host: self.host.clone(),
             port,
-            self.username.clone(),
+            username: self.username.clone(),
             identity_file,
-        ))
+            ..self.base.clone()
+        })
     }
 }
 
@@ -565,6 +893,32 @@ mod tests {
         assert_eq!(updated.identity_file, Some(PathBuf::from("/path/to/key")));
     }
 
+    #[test]
+    fn test_edit_form_preserves_connection_settings() {
+        let mut conn = SavedConnection::new("s".to_string(), "h".to_string(), 22, "u".to_string(), None);
+        conn.connect_timeout = Some(5);
+        conn.retries = Some(3);
+        let mut form = EditForm::from_connection(&conn);
+
+        form.host = "other".to_string();
+        let updated = form.to_connection().unwrap();
+
+        assert_eq!(updated.host, "other");
+        assert_eq!(updated.connect_timeout, Some(5));
+        assert_eq!(updated.retries, Some(3));
+    }
+
+    #[test]
+    fn test_usage_shows_recency_and_count() {
+        let mut conn = SavedConnection::new("s".to_string(), "h".to_string(), 22, "u".to_string(), None);
+        assert_eq!(usage(&conn, 1000), "never connected");
+        conn.last_connected = Some(1000);
+        conn.connect_count = 12;
+        assert_eq!(usage(&conn, 1000 + 3 * 3600 + 5), "3h ago, 12 times");
+        conn.connect_count = 1;
+        assert_eq!(usage(&conn, 1030), "just now, once");
+    }
+
     #[test]
     fn test_edit_form_invalid_port_returns_error() {
         let conn = SavedConnection::new("s".to_string(), "h".to_string(), 22, "u".to_string(), None);
@@ -1,7 +1,15 @@
-use anyhow::Result;
+use crate::atomic_file::write_atomic;
+use crate::config::Config;
+use crate::ssh::host_key::StrictHostKeyChecking;
+use crate::tui::palette::PaletteCommand;
+use anyhow::{Context, Result};
+use ratatui::style::Color;
+use serde::de::IntoDeserializer;
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use toml_edit::{ArrayOfTables, DocumentMut, Item, Table, Value};
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct SavedConnection {
@@ -10,6 +18,98 @@ pub struct SavedConnection {
     pub port: u16,
     pub username: String,
     pub identity_file: Option<PathBuf>,
+    /// Seconds to wait for the connection before giving up
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_timeout: Option<u64>,
+    /// Extra connection attempts after a failure
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+    /// Enable zlib compression of the SSH transport
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<bool>,
+    /// Host key checking mode for this connection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strict_host_key_checking: Option<StrictHostKeyChecking>,
+    /// Commands for the palette on this connection, listed before the config file's
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub commands: Vec<PaletteCommand>,
+    /// Seconds between keepalive messages, over the config file's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keepalive_secs: Option<u64>,
+    /// Shell started in shell mode instead of the login shell
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
+    /// Terminal type requested for shell mode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub term: Option<String>,
+    /// Whether dotfiles are listed when the connection is opened
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub show_hidden: Option<bool>,
+    /// Colour theme for this connection, e.g. `light` to tell production apart
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub theme: Option<String>,
+    /// Colour of the name in the list and of the header while connected,
+    /// in the theme's notation (`red`, `#rrggbb`, 0-255)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// Emoji or other short mark shown before the name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    /// Unix time of the last successful connection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_connected: Option<i64>,
+    /// Successful connections so far
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub connect_count: u32,
+}
+
+fn is_zero(count: &u32) -> bool {
+    *count == 0
+}
+
+/// Order of the connection selector's list
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionSort {
+    /// As saved in the connections file
+    #[default]
+    Saved,
+    /// Most recently connected first
+    Recent,
+    /// Most often connected first
+    Frequent,
+}
+
+impl ConnectionSort {
+    pub fn next(self) -> Self {
+        match self {
+            ConnectionSort::Saved => ConnectionSort::Recent,
+            ConnectionSort::Recent => ConnectionSort::Frequent,
+            ConnectionSort::Frequent => ConnectionSort::Saved,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ConnectionSort::Saved => "saved order",
+            ConnectionSort::Recent => "most recent",
+            ConnectionSort::Frequent => "most used",
+        }
+    }
+
+    /// Indices of `connections` in this order; ties keep their saved order
+    pub fn order(self, connections: &[SavedConnection]) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..connections.len()).collect();
+        match self {
+            ConnectionSort::Saved => {}
+            ConnectionSort::Recent => order.sort_by_key(|&i| std::cmp::Reverse(connections[i].last_connected)),
+            ConnectionSort::Frequent => order.sort_by_key(|&i| {
+                let c = &connections[i];
+                std::cmp::Reverse((c.connect_count, c.last_connected))
+            }),
+        }
+        order
+    }
 }
 
 impl SavedConnection {
@@ -26,6 +126,20 @@ impl SavedConnection {
             port,
             username,
             identity_file,
+            connect_timeout: None,
+            retries: None,
+            compression: None,
+            strict_host_key_checking: None,
+            commands: Vec::new(),
+            keepalive_secs: None,
+            shell: None,
+            term: None,
+            show_hidden: None,
+            theme: None,
+            color: None,
+            icon: None,
+            last_connected: None,
+            connect_count: 0,
         }
     }
 
@@ -33,6 +147,19 @@ impl SavedConnection {
         format!("{}@{}:{}", self.username, self.host, self.port)
     }
 
+    /// The `color` setting, if it names a colour
+    pub fn color(&self) -> Option<Color> {
+        self.color.as_deref().and_then(|color| Color::from_str(color).ok())
+    }
+
+    /// The name, after the icon if there is one
+    pub fn label(&self) -> String {
+        match self.icon.as_deref().filter(|icon| !icon.is_empty()) {
+            Some(icon) => format!("{} {}", icon, self.name),
+            None => self.name.clone(),
+        }
+    }
+
     pub fn ssh_command(&self) -> String {
         let mut cmd = format!("ssh -p {} {}@{}", self.port, self.username, self.host);
         if let Some(ref identity_file) = self.identity_file {
@@ -42,6 +169,42 @@ impl SavedConnection {
     }
 }
 
+/// How the connections file is stored
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionsFormat {
+    /// `connections.json`
+    #[default]
+    Json,
+    /// `connections.toml`, which can carry comments
+    Toml,
+}
+
+impl ConnectionsFormat {
+    fn file_name(self) -> &'static str {
+        match self {
+            ConnectionsFormat::Json => "connections.json",
+            ConnectionsFormat::Toml => "connections.toml",
+        }
+    }
+
+    fn other(self) -> Self {
+        match self {
+            ConnectionsFormat::Json => ConnectionsFormat::Toml,
+            ConnectionsFormat::Toml => ConnectionsFormat::Json,
+        }
+    }
+}
+
+/// The TOML file: one `[[connection]]` table per saved connection
+#[derive(Serialize, Deserialize, Default)]
+struct ConnectionsFile {
+    #[serde(default)]
+    connection: Vec<SavedConnection>,
+}
+
+/// The connections file in the format the config asks for. A file in the
+/// other format is converted on first use and kept with a `.bak` suffix.
 fn get_connections_file_path() -> Result<PathBuf> {
     let config_dir = dirs::config_dir()
         .or_else(|| dirs::home_dir().map(|h| h.join(".config")))
@@ -50,7 +213,132 @@ fn get_connections_file_path() -> Result<PathBuf> {
     let bssh_dir = config_dir.join("bssh");
     fs::create_dir_all(&bssh_dir)?;
 
-    Ok(bssh_dir.join("connections.json"))
+    let format = Config::load().connections_format;
+    let path = bssh_dir.join(format.file_name());
+    let other = bssh_dir.join(format.other().file_name());
+    if !path.exists() && other.exists() {
+        migrate_connections_file(&other, &path)?;
+    }
+    Ok(path)
+}
+
+fn migrate_connections_file(from: &Path, to: &Path) -> Result<()> {
+    let connections = read_connections_file(from)?;
+    write_connections_file(to, &connections)?;
+    fs::rename(from, backup_path(from))?;
+    Ok(())
+}
+
+/// `connections.json.bak`: the file as it was before the last write, or
+/// before it was converted to the other format
+fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    PathBuf::from(backup)
+}
+
+fn is_toml(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "toml")
+}
+
+fn read_connections_file(path: &Path) -> Result<Vec<SavedConnection>> {
+    let content = fs::read_to_string(path)?;
+    if is_toml(path) {
+        let file: ConnectionsFile =
+            toml_edit::de::from_str(&content).with_context(|| format!("Invalid {}", path.display()))?;
+        Ok(file.connection)
+    } else {
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+/// Write `connections` to `path`. A TOML file is updated in place, so the
+/// comments and layout of connections that are still there survive. The
+/// file being replaced is kept as the backup, unless it is damaged.
+fn write_connections_file(path: &Path, connections: &[SavedConnection]) -> Result<()> {
+    let content = if is_toml(path) {
+        let mut document = match fs::read_to_string(path) {
+            Ok(existing) => existing
+                .parse::<DocumentMut>()
+                .with_context(|| format!("Invalid {}", path.display()))?,
+            Err(_) => DocumentMut::new(),
+        };
+        merge_toml(&mut document, connections)?;
+        document.to_string()
+    } else {
+        serde_json::to_string_pretty(connections)?
+    };
+    if read_connections_file(path).is_ok() {
+        fs::copy(path, backup_path(path))?;
+    }
+    write_atomic(path, content)?;
+    Ok(())
+}
+
+/// Bring the `[[connection]]` tables of `document` in line with
+/// `connections`. A table is matched by name, or by position when it was
+/// renamed; only keys whose values changed are rewritten.
+fn merge_toml(document: &mut DocumentMut, connections: &[SavedConnection]) -> Result<()> {
+    let fresh = toml_edit::ser::to_document(&ConnectionsFile {
+        connection: connections.to_vec(),
+    })?;
+    // The serializer writes inline tables; the file gets a `[[connection]]` each
+    let fresh_tables: Vec<Table> = match fresh.get("connection").cloned().map(Item::into_array_of_tables) {
+        Some(Ok(tables)) => tables.into_iter().collect(),
+        _ => Vec::new(),
+    };
+    let mut old_tables: Vec<Option<Table>> = match document.remove("connection") {
+        Some(Item::ArrayOfTables(tables)) => tables.into_iter().map(Some).collect(),
+        _ => Vec::new(),
+    };
+
+    let name = |table: &Table| table.get("name").and_then(Item::as_str).map(str::to_string);
+    let old_names: Vec<Option<String>> = old_tables.iter().map(|table| table.as_ref().and_then(name)).collect();
+    let fresh_names: Vec<Option<String>> = fresh_tables.iter().map(name).collect();
+
+    let mut merged = ArrayOfTables::new();
+    for (i, fresh_table) in fresh_tables.into_iter().enumerate() {
+        let by_name = old_names.iter().position(|old| old.is_some() && *old == fresh_names[i]);
+        let renamed = (i < old_names.len() && !fresh_names.contains(&old_names[i])).then_some(i);
+        match by_name.or(renamed).and_then(|j| old_tables[j].take()) {
+            Some(mut table) => {
+                update_table(&mut table, &fresh_table);
+                merged.push(table);
+            }
+            None => merged.push(fresh_table),
+        }
+    }
+    if !merged.is_empty() {
+        document.insert("connection", Item::ArrayOfTables(merged));
+    }
+    Ok(())
+}
+
+fn update_table(table: &mut Table, fresh: &Table) {
+    let stale: Vec<String> = table
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .filter(|key| !fresh.contains_key(key))
+        .collect();
+    for key in stale {
+        table.remove(&key);
+    }
+    for (key, item) in fresh.iter() {
+        if !table.get(key).is_some_and(|old| same_item(old, item)) {
+            table[key] = item.clone();
+        }
+    }
+}
+
+/// Whether two items hold the same data, however they are written
+fn same_item(a: &Item, b: &Item) -> bool {
+    match (a.as_value(), b.as_value()) {
+        (Some(a), Some(b)) => {
+            let data = |value: &Value| serde_json::Value::deserialize(value.clone().into_deserializer()).ok();
+            data(a).is_some() && data(a) == data(b)
+        }
+        _ => a.to_string() == b.to_string(),
+    }
 }
 
 pub fn load_connections() -> Result<Vec<SavedConnection>> {
@@ -60,16 +348,19 @@ pub fn load_connections() -> Result<Vec<SavedConnection>> {
         return Ok(Vec::new());
     }
 
-    let content = fs::read_to_string(path)?;
-    let connections: Vec<SavedConnection> = serde_json::from_str(&content)?;
-    Ok(connections)
+    read_connections_file(&path).with_context(|| {
+        let backup = backup_path(&path);
+        if backup.exists() {
+            format!("{} is damaged; its previous version is in {}", path.display(), backup.display())
+        } else {
+            format!("{} is damaged", path.display())
+        }
+    })
 }
 
 pub fn save_connections(connections: &[SavedConnection]) -> Result<()> {
     let path = get_connections_file_path()?;
-    let json = serde_json::to_string_pretty(connections)?;
-    fs::write(path, json)?;
-    Ok(())
+    write_connections_file(&path, connections)
 }
 
 pub fn add_connection(connection: SavedConnection) -> Result<()> {
@@ -83,28 +374,39 @@ pub fn add_connection(connection: SavedConnection) -> Result<()> {
     Ok(())
 }
 
-pub fn remove_connection(name: &str) -> Result<()> {
-    let mut connections = load_connections()?;
-    connections.retain(|c| c.name != name);
-    save_connections(&connections)?;
-    Ok(())
-}
-
 pub fn update_connection(name: &str, updated: SavedConnection) -> Result<()> {
     let path = get_connections_file_path()?;
     update_connection_in_file(&path, name, updated)
 }
 
-fn update_connection_in_file(path: &PathBuf, name: &str, updated: SavedConnection) -> Result<()> {
-    let content = fs::read_to_string(path)?;
-    let mut connections: Vec<SavedConnection> = serde_json::from_str(&content)?;
+/// Note a successful connection to the saved connection `name`, if there
+/// is one, for sorting by recency and frequency
+pub fn record_connection(name: &str) -> Result<()> {
+    let path = get_connections_file_path()?;
+    if !path.exists() {
+        return Ok(());
+    }
+    record_connection_in_file(&path, name, chrono::Utc::now().timestamp())
+}
+
+fn record_connection_in_file(path: &Path, name: &str, now: i64) -> Result<()> {
+    let mut connections = read_connections_file(path)?;
+    if let Some(connection) = connections.iter_mut().find(|c| c.name == name) {
+        connection.last_connected = Some(now);
+        connection.connect_count += 1;
+        write_connections_file(path, &connections)?;
+    }
+    Ok(())
+}
+
+fn update_connection_in_file(path: &Path, name: &str, updated: SavedConnection) -> Result<()> {
+    let mut connections = read_connections_file(path)?;
 
     let pos = connections.iter().position(|c| c.name == name);
     match pos {
         Some(idx) => {
             connections[idx] = updated;
-            let json = serde_json::to_string_pretty(&connections)?;
-            fs::write(path, json)?;
+            write_connections_file(path, &connections)?;
             Ok(())
         }
         None => Err(anyhow::anyhow!("Connection '{}' not found", name)),
@@ -189,6 +491,108 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_connection_without_timeout_settings_deserializes() {
+        let json = r#"[{"name":"old","host":"h","port":22,"username":"u","identity_file":null}]"#;
+        let loaded: Vec<SavedConnection> = serde_json::from_str(json).unwrap();
+
+        assert_eq!(loaded[0].connect_timeout, None);
+        assert_eq!(loaded[0].retries, None);
+    }
+
+    #[test]
+    fn test_color_and_icon() {
+        let json = r##"[
+            {"name":"prod","host":"h","port":22,"username":"u","identity_file":null,"color":"#ff0000","icon":"🔥"},
+            {"name":"dev","host":"h","port":22,"username":"u","identity_file":null,"color":"reddish"}
+        ]"##;
+        let loaded: Vec<SavedConnection> = serde_json::from_str(json).unwrap();
+
+        assert_eq!(loaded[0].color(), Some(Color::Rgb(0xff, 0, 0)));
+        assert_eq!(loaded[0].label(), "🔥 prod");
+        assert_eq!(loaded[1].color(), None);
+        assert_eq!(loaded[1].label(), "dev");
+    }
+
+    #[test]
+    fn test_connections_are_recorded_and_sorted() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = setup_test_connections(&temp_dir);
+        let names = ["a", "b", "c"];
+        let connections: Vec<SavedConnection> = names
+            .iter()
+            .map(|name| SavedConnection::new(name.to_string(), "h".to_string(), 22, "u".to_string(), None))
+            .collect();
+        fs::write(&path, serde_json::to_string_pretty(&connections).unwrap()).unwrap();
+
+        record_connection_in_file(&path, "c", 100).unwrap();
+        record_connection_in_file(&path, "c", 200).unwrap();
+        record_connection_in_file(&path, "b", 300).unwrap();
+        record_connection_in_file(&path, "unsaved", 400).unwrap();
+
+        let loaded: Vec<SavedConnection> = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!((loaded[2].connect_count, loaded[2].last_connected), (2, Some(200)));
+
+        assert_eq!(ConnectionSort::Recent.order(&loaded), [1, 2, 0]);
+        assert_eq!(ConnectionSort::Frequent.order(&loaded), [2, 1, 0]);
+        assert_eq!(ConnectionSort::Saved.order(&loaded), [0, 1, 2]);
+    }
+
+    #[test]
+    fn test_previous_connections_are_kept_as_backup() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = setup_test_connections(&temp_dir);
+        let backup = path.with_extension("json.bak");
+        let web = SavedConnection::new("web".to_string(), "web-1".to_string(), 22, "deploy".to_string(), None);
+        let db = SavedConnection::new("db".to_string(), "db-1".to_string(), 22, "admin".to_string(), None);
+
+        write_connections_file(&path, std::slice::from_ref(&web)).unwrap();
+        assert!(!backup.exists());
+        write_connections_file(&path, &[web.clone(), db.clone()]).unwrap();
+        assert_eq!(read_connections_file(&backup).unwrap(), std::slice::from_ref(&web));
+
+        // A damaged file does not replace the good backup
+        fs::write(&path, "[{\"name\": \"we").unwrap();
+        write_connections_file(&path, &[db]).unwrap();
+        assert_eq!(read_connections_file(&backup).unwrap(), [web]);
+    }
+
+    #[test]
+    fn test_json_is_migrated_to_toml_keeping_comments() {
+        let temp_dir = TempDir::new().unwrap();
+        let json_path = setup_test_connections(&temp_dir);
+        let toml_path = json_path.with_extension("toml");
+        let connections = vec![
+            SavedConnection::new("web".to_string(), "web-1".to_string(), 22, "deploy".to_string(), None),
+            SavedConnection::new("db".to_string(), "db-1".to_string(), 5432, "admin".to_string(), None),
+        ];
+        fs::write(&json_path, serde_json::to_string_pretty(&connections).unwrap()).unwrap();
+
+        migrate_connections_file(&json_path, &toml_path).unwrap();
+        assert!(!json_path.exists());
+        assert!(json_path.with_extension("json.bak").exists());
+        assert_eq!(read_connections_file(&toml_path).unwrap(), connections);
+
+        // Comments written by hand stay through updates
+        let text = fs::read_to_string(&toml_path).unwrap();
+        let text = text.replacen("[[connection]]", "# Staging box, rebuilt weekly\n[[connection]]", 1);
+        let text = text.replace("port = 5432", "port = 5432 # pgbouncer");
+        fs::write(&toml_path, text).unwrap();
+
+        record_connection_in_file(&toml_path, "web", 100).unwrap();
+        let mut renamed = connections[1].clone();
+        renamed.name = "database".to_string();
+        update_connection_in_file(&toml_path, "db", renamed).unwrap();
+
+        let text = fs::read_to_string(&toml_path).unwrap();
+        assert!(text.starts_with("# Staging box, rebuilt weekly\n[[connection]]\nname = \"web\""));
+        assert!(text.contains("port = 5432 # pgbouncer"));
+        assert!(text.contains("connect_count = 1"));
+        let loaded = read_connections_file(&toml_path).unwrap();
+        assert_eq!(loaded[1].name, "database");
+        assert_eq!(loaded[0].last_connected, Some(100));
+    }
+
     #[test]
     fn test_update_connection_preserves_other_connections() {
         let temp_dir = TempDir::new().unwrap();
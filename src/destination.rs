@@ -0,0 +1,154 @@
+use anyhow::{bail, Context, Result};
+use std::env;
+
+/// Username, host, port and, for an `sftp://` or `ssh://` URL, the path in
+/// it, from `[user@]host[:port]` or such a URL
+pub fn parse_connection_string(conn_str: &str) -> Result<(String, String, u16, Option<String>)> {
+    let current_user = || env::var("USER").unwrap_or_else(|_| String::from("root"));
+    if let Some(url) = parse_remote_url(conn_str)? {
+        let username = url.username.unwrap_or_else(current_user);
+        return Ok((username, url.host, url.port.unwrap_or(22), url.path));
+    }
+
+    let (user_host, port) = if let Some(pos) = conn_str.rfind(':') {
+        let port_str = &conn_str[pos + 1..];
+        let port = port_str
+            .parse::<u16>()
+            .context("Invalid port number")?;
+        (&conn_str[..pos], port)
+    } else {
+        (conn_str, 22)
+    };
+
+    let (username, host) = if let Some(pos) = user_host.find('@') {
+        (user_host[..pos].to_string(), user_host[pos + 1..].to_string())
+    } else {
+        (current_user(), user_host.to_string())
+    };
+
+    Ok((username, host, port, None))
+}
+
+/// Parts of an `sftp://` or `ssh://` URL, as file managers and hosting
+/// panels hand them out
+#[derive(Debug, PartialEq)]
+pub struct RemoteUrl {
+    pub username: Option<String>,
+    pub host: String,
+    pub port: Option<u16>,
+    /// Path after the host, percent-decoded
+    pub path: Option<String>,
+}
+
+/// Read `sftp://[user@]host[:port][/path]`, or the same with `ssh://`, as
+/// `file_ops::sftp_url` writes them. None if `url` is not one of those.
+fn parse_remote_url(url: &str) -> Result<Option<RemoteUrl>> {
+    let Some(rest) = ["sftp://", "ssh://"].iter().find_map(|scheme| {
+        url.get(..scheme.len())
+            .filter(|prefix| prefix.eq_ignore_ascii_case(scheme))
+            .map(|_| &url[scheme.len()..])
+    }) else {
+        return Ok(None);
+    };
+
+    let (authority, path) = match rest.find('/') {
+        Some(slash) => (&rest[..slash], Some(percent_decode(&rest[slash..]))),
+        None => (rest, None),
+    };
+    // The user may be followed by a password or `;fingerprint=...`, which
+    // are not used
+    let (username, host_port) = match authority.rsplit_once('@') {
+        Some((user_info, host_port)) => {
+            let user = user_info.split([':', ';']).next().unwrap_or_default();
+            (Some(percent_decode(user)), host_port)
+        }
+        None => (None, authority),
+    };
+    let (host, port) = match host_port.strip_prefix('[').and_then(|bracketed| bracketed.split_once(']')) {
+        Some((host, port)) => (host, port.strip_prefix(':')),
+        None => match host_port.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (host_port, None),
+        },
+    };
+    if host.is_empty() {
+        bail!("No host in {}", url);
+    }
+    let port = port
+        .filter(|port| !port.is_empty())
+        .map(|port| port.parse::<u16>().context("Invalid port number"))
+        .transpose()?;
+
+    Ok(Some(RemoteUrl {
+        username: username.filter(|user| !user.is_empty()),
+        host: host.to_string(),
+        port,
+        path,
+    }))
+}
+
+/// Undo the `%XX` escapes of a URL part; a stray `%` is kept as it is
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| value.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_connection_string() {
+        assert_eq!(
+            parse_connection_string("deploy@web-1:2222").unwrap(),
+            (String::from("deploy"), String::from("web-1"), 2222, None)
+        );
+        assert_eq!(
+            parse_connection_string("sftp://u@h:2222/p").unwrap(),
+            (String::from("u"), String::from("h"), 2222, Some(String::from("/p")))
+        );
+        assert_eq!(
+            parse_connection_string("ssh://root@example.com").unwrap(),
+            (String::from("root"), String::from("example.com"), 22, None)
+        );
+        assert!(parse_connection_string("web-1:ssh").is_err());
+    }
+
+    #[test]
+    fn test_parse_remote_url() {
+        let url = parse_remote_url("sftp://deploy@example.com:2222/srv/my%20site").unwrap().unwrap();
+        assert_eq!(
+            url,
+            RemoteUrl {
+                username: Some(String::from("deploy")),
+                host: String::from("example.com"),
+                port: Some(2222),
+                path: Some(String::from("/srv/my site")),
+            }
+        );
+        let url = parse_remote_url("SSH://root;fingerprint=ssh-ed25519-abc@[::1]").unwrap().unwrap();
+        assert_eq!((url.username.as_deref(), url.host.as_str(), url.port, url.path), (Some("root"), "::1", None, None));
+
+        assert_eq!(parse_remote_url("deploy@example.com:22").unwrap(), None);
+        assert!(parse_remote_url("sftp://example.com:ssh/").is_err());
+        assert!(parse_remote_url("sftp:///var/www").is_err());
+    }
+}
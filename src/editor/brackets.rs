@@ -0,0 +1,97 @@
+use super::EditorState;
+
+const PAIRS: [(u8, u8); 3] = [(b'(', b')'), (b'[', b']'), (b'{', b'}')];
+
+impl EditorState {
+    /// `%`: jump to the bracket matching the one under the cursor, or the
+    /// first bracket after it on the line
+    pub fn jump_to_matching_bracket(&mut self) {
+        let line = self.get_current_line().as_bytes();
+        let Some(col) = (self.cursor_col..line.len()).find(|&i| PAIRS.iter().any(|&(o, c)| line[i] == o || line[i] == c))
+        else {
+            self.status_message = String::from("No bracket under or after the cursor");
+            return;
+        };
+
+        match self.find_match(self.cursor_row, col) {
+            Some((row, col)) => {
+                self.cursor_row = row;
+                self.cursor_col = col;
+            }
+            None => self.status_message = String::from("Unbalanced bracket: no match found"),
+        }
+    }
+
+    /// Position of the bracket matching the one at `(row, col)`, counting
+    /// nested pairs of the same kind
+    fn find_match(&self, row: usize, col: usize) -> Option<(usize, usize)> {
+        let bracket = self.buffer[row].as_bytes()[col];
+        let (open, close, forward) = PAIRS.iter().find_map(|&(open, close)| match bracket {
+            b if b == open => Some((open, close, true)),
+            b if b == close => Some((open, close, false)),
+            _ => None,
+        })?;
+
+        let mut depth = 0usize;
+        let mut visit = |row: usize, col: usize| -> bool {
+            let byte = self.buffer[row].as_bytes()[col];
+            if byte == open {
+                depth = if forward { depth + 1 } else { depth.saturating_sub(1) };
+                !forward && depth == 0
+            } else if byte == close {
+                depth = if forward { depth.saturating_sub(1) } else { depth + 1 };
+                forward && depth == 0
+            } else {
+                false
+            }
+        };
+
+        if forward {
+            for r in row..self.buffer.len() {
+                let start = if r == row { col } else { 0 };
+                if let Some(c) = (start..self.buffer[r].len()).find(|&c| visit(r, c)) {
+                    return Some((r, c));
+                }
+            }
+        } else {
+            for r in (0..=row).rev() {
+                let end = if r == row { col + 1 } else { self.buffer[r].len() };
+                if let Some(c) = (0..end).rev().find(|&c| visit(r, c)) {
+                    return Some((r, c));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::EditorState;
+
+    #[test]
+    fn test_matching_bracket_across_lines() {
+        let content = "http {\n    server { listen [::]:80; }\n}\nbroken (";
+        let mut editor = EditorState::new("nginx.conf".to_string(), "/tmp/nginx.conf".to_string(), content.to_string());
+
+        editor.jump_to_matching_bracket();
+        assert_eq!((editor.cursor_row, editor.cursor_col), (2, 0));
+        editor.jump_to_matching_bracket();
+        assert_eq!((editor.cursor_row, editor.cursor_col), (0, 5));
+
+        // Not on a bracket: use the next one on the line
+        editor.cursor_row = 1;
+        editor.cursor_col = 0;
+        editor.jump_to_matching_bracket();
+        assert_eq!((editor.cursor_row, editor.cursor_col), (1, 29));
+        editor.cursor_col = 22;
+        editor.jump_to_matching_bracket();
+        assert_eq!(editor.cursor_col, 20);
+
+        editor.cursor_row = 3;
+        editor.cursor_col = 0;
+        editor.jump_to_matching_bracket();
+        assert_eq!(editor.status_message, "Unbalanced bracket: no match found");
+        assert_eq!((editor.cursor_row, editor.cursor_col), (3, 0));
+    }
+}
@@ -0,0 +1,135 @@
+use super::EditorState;
+
+/// Buffer change the editor asks the caller to carry out, since opening a
+/// file or reading it again needs SFTP
+#[derive(Debug, Clone, PartialEq)]
+pub enum BufferRequest {
+    /// `:e`, with the path already resolved against the current file
+    Open(String),
+    /// `:bn`
+    Next,
+    /// `:bp`
+    Previous,
+    /// Save over a file changed on the server
+    Overwrite { quit_after: bool },
+    /// Replace the buffer with the file on the server
+    Reload,
+    /// Show how the buffer differs from the file on the server
+    Diff,
+}
+
+/// Files open in one editor session; only the current one is shown
+pub struct BufferList {
+    buffers: Vec<EditorState>,
+    current: usize,
+}
+
+impl BufferList {
+    pub fn new(first: EditorState) -> Self {
+        Self {
+            buffers: vec![first],
+            current: 0,
+        }
+    }
+
+    pub fn current(&self) -> &EditorState {
+        &self.buffers[self.current]
+    }
+
+    pub fn current_mut(&mut self) -> &mut EditorState {
+        &mut self.buffers[self.current]
+    }
+
+    /// Make the buffer for `remote_path` current; false if it is not open
+    pub fn switch_to(&mut self, remote_path: &str) -> bool {
+        match self.buffers.iter().position(|b| b.remote_path == remote_path) {
+            Some(index) => {
+                self.current = index;
+                self.announce();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Add a newly loaded file and show it
+    pub fn push(&mut self, editor: EditorState) {
+        self.buffers.push(editor);
+        self.current = self.buffers.len() - 1;
+        self.announce();
+    }
+
+    /// Show `editor`, in place of an open buffer for the same path if any
+    pub fn push_or_replace(&mut self, editor: EditorState) {
+        match self.buffers.iter().position(|b| b.remote_path == editor.remote_path) {
+            Some(index) => {
+                self.buffers[index] = editor;
+                self.current = index;
+                self.announce();
+            }
+            None => self.push(editor),
+        }
+    }
+
+    /// Show the next or previous buffer, wrapping around
+    pub fn cycle(&mut self, forward: bool) {
+        let len = self.buffers.len();
+        self.current = if forward { (self.current + 1) % len } else { (self.current + len - 1) % len };
+        self.announce();
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut EditorState> {
+        self.buffers.iter_mut()
+    }
+
+    /// A buffer other than the current one with unsaved changes
+    pub fn other_modified(&self) -> Option<&EditorState> {
+        self.buffers
+            .iter()
+            .enumerate()
+            .find(|&(i, b)| i != self.current && b.modified)
+            .map(|(_, b)| b)
+    }
+
+    /// Name the current buffer and its position in the status line
+    fn announce(&mut self) {
+        let (index, len) = (self.current + 1, self.buffers.len());
+        let editor = self.current_mut();
+        editor.status_message = format!("\"{}\" [{}/{}]", editor.filename, index, len);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(path: &str) -> EditorState {
+        let name = path.rsplit('/').next().unwrap().to_string();
+        EditorState::new(name, path.to_string(), String::from("line"))
+    }
+
+    #[test]
+    fn test_switching_buffers() {
+        let mut buffers = BufferList::new(buffer("/etc/nginx/nginx.conf"));
+        buffers.push(buffer("/etc/nginx/conf.d/site.conf"));
+        assert_eq!(buffers.current().status_message, "\"site.conf\" [2/2]");
+
+        buffers.cycle(true);
+        assert_eq!(buffers.current().filename, "nginx.conf");
+        buffers.cycle(false);
+        assert_eq!(buffers.current().filename, "site.conf");
+
+        assert!(buffers.switch_to("/etc/nginx/nginx.conf"));
+        assert!(!buffers.switch_to("/etc/hosts"));
+        assert_eq!(buffers.current().filename, "nginx.conf");
+
+        buffers.push_or_replace(buffer("/etc/nginx/nginx.conf"));
+        assert_eq!(buffers.current().status_message, "\"nginx.conf\" [1/2]");
+
+        assert!(buffers.other_modified().is_none());
+        buffers.cycle(true);
+        buffers.current_mut().modified = true;
+        buffers.cycle(true);
+        assert_eq!(buffers.other_modified().unwrap().filename, "site.conf");
+    }
+}
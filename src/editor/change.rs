@@ -0,0 +1,152 @@
+use super::{EditorMode, EditorState};
+
+impl EditorState {
+    /// `r{char}`: replace `count` characters under and after the cursor,
+    /// doing nothing when the line is too short, like vim
+    pub fn replace_chars(&mut self, replacement: char, count: usize) {
+        let cursor_col = self.cursor_col;
+        let line = self.get_current_line();
+        let Some(tail) = line.get(cursor_col..) else {
+            return;
+        };
+        let mut chars = tail.char_indices();
+        let Some(end) = chars.nth(count.max(1) - 1).map(|(i, c)| cursor_col + i + c.len_utf8()) else {
+            return;
+        };
+
+        self.save_undo_state();
+        let replaced = replacement.to_string().repeat(count.max(1));
+        self.get_current_line_mut().replace_range(cursor_col..end, &replaced);
+        self.cursor_col = cursor_col + replaced.len() - replacement.len_utf8();
+        self.modified = true;
+    }
+
+    /// `~`: switch the case of `count` characters and move past them
+    pub fn toggle_case(&mut self, count: usize) {
+        let cursor_col = self.cursor_col;
+        let line = self.get_current_line();
+        let Some(tail) = line.get(cursor_col..) else {
+            return;
+        };
+        if tail.is_empty() {
+            return;
+        }
+        let end = tail.char_indices().nth(count.max(1)).map_or(line.len(), |(i, _)| cursor_col + i);
+
+        let toggled: String = line[cursor_col..end]
+            .chars()
+            .flat_map(|c| {
+                let flipped: Vec<char> = if c.is_uppercase() {
+                    c.to_lowercase().collect()
+                } else {
+                    c.to_uppercase().collect()
+                };
+                flipped
+            })
+            .collect();
+        self.save_undo_state();
+        self.get_current_line_mut().replace_range(cursor_col..end, &toggled);
+        self.cursor_col = cursor_col + toggled.len();
+        self.modified = true;
+        self.clamp_cursor();
+    }
+
+    /// `cc`: replace `count` lines with one empty line and start inserting
+    pub fn change_lines(&mut self, count: usize) {
+        self.save_undo_state();
+        let end = (self.cursor_row + count.max(1)).min(self.buffer.len());
+        self.yank_register = self.buffer.splice(self.cursor_row..end, [String::new()]).collect();
+        self.yank_linewise = true;
+        self.modified = true;
+        self.start_change(0);
+    }
+
+    /// `cw`: change to the end of the `count`th word; on the last character
+    /// of a word only that character changes, like vim
+    pub fn change_word(&mut self, count: usize) {
+        let (row, col) = (self.cursor_row, self.cursor_col);
+        let chars: Vec<char> = self.get_current_line().chars().collect();
+        let at_word_end = chars.get(col).is_some_and(|&c| {
+            !c.is_whitespace()
+                && chars
+                    .get(col + 1)
+                    .is_none_or(|&next| next.is_whitespace() || Self::is_word_char(next) != Self::is_word_char(c))
+        });
+
+        let motions = if at_word_end { count.max(1) - 1 } else { count.max(1) };
+        for _ in 0..motions {
+            self.move_word_end();
+        }
+        let line_len = self.buffer[row].len();
+        let end = if self.cursor_row == row { (self.cursor_col + 1).min(line_len) } else { line_len };
+        (self.cursor_row, self.cursor_col) = (row, col);
+        self.change_range(end);
+    }
+
+    /// `c$`: change from the cursor to the end of the line
+    pub fn change_to_line_end(&mut self) {
+        let end = self.get_current_line().len();
+        self.change_range(end);
+    }
+
+    /// Delete from the cursor to `end` on the current line, keep it in the
+    /// register and start inserting
+    fn change_range(&mut self, end: usize) {
+        let start = self.cursor_col.min(end);
+        self.save_undo_state();
+        let removed: String = self.get_current_line_mut().drain(start..end).collect();
+        self.yank_register = vec![removed];
+        self.yank_linewise = false;
+        self.modified = true;
+        self.start_change(start);
+    }
+
+    fn start_change(&mut self, col: usize) {
+        self.mode = EditorMode::Insert;
+        self.cursor_col = col;
+        self.status_message = String::from("Insert mode");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::tests::press;
+    use crate::editor::{EditorMode, EditorState};
+
+    fn editor(content: &str) -> EditorState {
+        EditorState::new("test.conf".to_string(), "/tmp/test.conf".to_string(), content.to_string())
+    }
+
+    #[test]
+    fn test_replace_and_toggle_case() {
+        let mut editor = editor("listen 80;");
+        press(&mut editor, "w2r9");
+        assert_eq!(editor.buffer[0], "listen 99;");
+        assert_eq!(editor.cursor_col, 8);
+        // Too few characters left: nothing changes
+        press(&mut editor, "5rx");
+        assert_eq!(editor.buffer[0], "listen 99;");
+
+        press(&mut editor, "03~");
+        assert_eq!(editor.buffer[0], "LISten 99;");
+        assert_eq!(editor.cursor_col, 3);
+    }
+
+    #[test]
+    fn test_change_operator() {
+        let mut editor = editor("server_name old.example.com;\nroot /srv;\nindex index.html;");
+        press(&mut editor, "wcwnew\u{1b}");
+        assert_eq!(editor.buffer[0], "server_name new.example.com;");
+        assert_eq!(editor.yank_register, ["old"]);
+
+        press(&mut editor, "j0c$/var/www;\u{1b}");
+        assert_eq!(editor.buffer[1], "/var/www;");
+
+        press(&mut editor, "kccdone\u{1b}");
+        assert_eq!(editor.buffer, ["done", "/var/www;", "index index.html;"]);
+        assert_eq!(editor.mode, EditorMode::Normal);
+
+        assert_eq!(editor.yank_register, ["server_name new.example.com;"]);
+        assert!(editor.yank_linewise);
+    }
+}
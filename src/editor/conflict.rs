@@ -0,0 +1,126 @@
+use crossterm::event::KeyCode;
+use russh_sftp::client::SftpSession;
+
+use super::diff::unified_diff;
+use super::{BufferRequest, EditorState};
+
+/// Size and modification time of a remote file, compared before saving to
+/// notice changes made by someone else
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RemoteStamp {
+    pub size: Option<u64>,
+    pub mtime: Option<u32>,
+}
+
+/// Stamp of the file as it is on the server now; None if it cannot be read
+pub async fn remote_stamp(sftp: &SftpSession, remote_path: &str) -> Option<RemoteStamp> {
+    let meta = sftp.metadata(remote_path).await.ok()?;
+    Some(RemoteStamp {
+        size: meta.size,
+        mtime: meta.mtime,
+    })
+}
+
+impl EditorState {
+    /// Ask what to do about a save refused because the file changed on the
+    /// server since it was read
+    pub fn report_save_conflict(&mut self, quit_after: bool) {
+        self.save_conflict = Some(quit_after);
+        self.status_message =
+            String::from("File changed on server since it was read: (o)verwrite, (r)eload, (d)iff, Esc to cancel");
+    }
+
+    pub(super) fn answer_save_conflict(&mut self, key: KeyCode) {
+        let Some(quit_after) = self.save_conflict else {
+            return;
+        };
+        self.buffer_request = match key {
+            KeyCode::Char('o') => Some(BufferRequest::Overwrite { quit_after }),
+            KeyCode::Char('r') => Some(BufferRequest::Reload),
+            KeyCode::Char('d') => Some(BufferRequest::Diff),
+            KeyCode::Esc => None,
+            _ => return,
+        };
+        self.save_conflict = None;
+        if self.buffer_request.is_none() {
+            self.status_message = String::from("Save cancelled");
+        }
+    }
+
+    /// Replace the buffer with the file as it is on the server. The edits
+    /// are kept on the undo stack.
+    pub fn reload(&mut self, content: &str, stamp: Option<RemoteStamp>) {
+        self.save_undo_state();
+        self.buffer = content.lines().map(String::from).collect();
+        if self.buffer.is_empty() {
+            self.buffer.push(String::new());
+        }
+        self.remote_stamp = stamp;
+        self.modified = false;
+        self.clamp_cursor();
+        self.status_message = String::from("Reloaded from server (u to get your edits back)");
+    }
+
+    /// Read-only buffer holding a unified diff from the server's copy of the
+    /// file to this buffer, or None when they are the same
+    pub fn diff_buffer(&self, remote_content: &str) -> Option<EditorState> {
+        let remote: Vec<String> = remote_content.lines().map(String::from).collect();
+        let diff = unified_diff(
+            &remote,
+            &self.buffer,
+            &format!("{} (server)", self.remote_path),
+            &format!("{} (buffer)", self.remote_path),
+        );
+        if diff.is_empty() {
+            return None;
+        }
+        let mut editor = EditorState::new(
+            format!("{} (diff)", self.filename),
+            format!("{} (diff)", self.remote_path),
+            diff.join("\n"),
+        );
+        editor.read_only = true;
+        editor.diff_view = true;
+        editor.options = self.options;
+        Some(editor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::tests::press;
+
+    #[test]
+    fn test_conflict_prompt_answers() {
+        let mut editor = EditorState::new("app.conf".to_string(), "/etc/app.conf".to_string(), "a\nb".to_string());
+        editor.report_save_conflict(true);
+        // Other keys leave the prompt up and the buffer untouched
+        press(&mut editor, "x");
+        assert_eq!(editor.buffer, ["a", "b"]);
+        assert_eq!(editor.save_conflict, Some(true));
+
+        press(&mut editor, "o");
+        assert_eq!(editor.buffer_request, Some(BufferRequest::Overwrite { quit_after: true }));
+        assert!(editor.save_conflict.is_none());
+
+        editor.report_save_conflict(false);
+        press(&mut editor, "\u{1b}");
+        assert_eq!(editor.status_message, "Save cancelled");
+    }
+
+    #[test]
+    fn test_reload_and_diff() {
+        let mut editor = EditorState::new("app.conf".to_string(), "/etc/app.conf".to_string(), "a\nb".to_string());
+        press(&mut editor, "jccB\u{1b}");
+
+        let diff = editor.diff_buffer("a\nb\nc").unwrap();
+        assert!(diff.read_only && diff.diff_view);
+        assert_eq!(diff.buffer[2..], ["@@ -1,3 +1,2 @@", " a", "-b", "-c", "+B"]);
+        assert!(editor.diff_buffer("a\nB").is_none());
+
+        editor.reload("a\nb\nc", None);
+        assert_eq!(editor.buffer, ["a", "b", "c"]);
+        assert!(!editor.modified);
+    }
+}
@@ -0,0 +1,166 @@
+use crate::theme::Theme;
+use ratatui::style::{Modifier, Style};
+
+/// Unchanged lines shown around each change
+const CONTEXT: usize = 3;
+
+/// Largest changed region, in old lines times new lines, compared line by
+/// line; past this the whole region is shown as replaced
+const LCS_LIMIT: usize = 4_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DiffOp {
+    /// Index into the old and the new lines
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Line diff from `old` to `new` as a unified diff. Empty when they match.
+pub fn unified_diff(old: &[String], new: &[String], old_name: &str, new_name: &str) -> Vec<String> {
+    let ops = diff_ops(old, new);
+    let changes: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| !matches!(op, DiffOp::Equal(..)))
+        .map(|(i, _)| i)
+        .collect();
+    if changes.is_empty() {
+        return Vec::new();
+    }
+
+    // Ranges of ops to show, with context, merged when they touch
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &i in &changes {
+        let start = i.saturating_sub(CONTEXT);
+        let end = (i + CONTEXT + 1).min(ops.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    let mut lines = vec![format!("--- {}", old_name), format!("+++ {}", new_name)];
+    for (start, end) in hunks {
+        // Line numbers where the hunk starts, from the ops before it
+        let old_start = ops[..start].iter().filter(|op| !matches!(op, DiffOp::Insert(_))).count();
+        let new_start = ops[..start].iter().filter(|op| !matches!(op, DiffOp::Delete(_))).count();
+        let hunk = &ops[start..end];
+        let old_len = hunk.iter().filter(|op| !matches!(op, DiffOp::Insert(_))).count();
+        let new_len = hunk.iter().filter(|op| !matches!(op, DiffOp::Delete(_))).count();
+
+        lines.push(format!(
+            "@@ -{},{} +{},{} @@",
+            old_start + (old_len > 0) as usize,
+            old_len,
+            new_start + (new_len > 0) as usize,
+            new_len
+        ));
+        lines.extend(hunk.iter().map(|op| match *op {
+            DiffOp::Equal(i, _) => format!(" {}", old[i]),
+            DiffOp::Delete(i) => format!("-{}", old[i]),
+            DiffOp::Insert(j) => format!("+{}", new[j]),
+        }));
+    }
+    lines
+}
+
+/// Colour for a line of unified diff output
+pub fn line_style(line: &str, theme: &Theme) -> Style {
+    if line.starts_with("---") || line.starts_with("+++") {
+        return Style::default().add_modifier(Modifier::BOLD);
+    }
+    match line.chars().next() {
+        Some('+') => Style::default().fg(theme.success),
+        Some('-') => Style::default().fg(theme.error),
+        Some('@') => Style::default().fg(theme.accent),
+        _ => Style::default(),
+    }
+}
+
+fn diff_ops(old: &[String], new: &[String]) -> Vec<DiffOp> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (a, b) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+
+    let mut ops: Vec<DiffOp> = (0..prefix).map(|i| DiffOp::Equal(i, i)).collect();
+    if a.len().saturating_mul(b.len()) <= LCS_LIMIT {
+        ops.extend(lcs_ops(a, b, prefix));
+    } else {
+        ops.extend((0..a.len()).map(|i| DiffOp::Delete(prefix + i)));
+        ops.extend((0..b.len()).map(|j| DiffOp::Insert(prefix + j)));
+    }
+    let (old_tail, new_tail) = (old.len() - suffix, new.len() - suffix);
+    ops.extend((0..suffix).map(|k| DiffOp::Equal(old_tail + k, new_tail + k)));
+    ops
+}
+
+/// Ops for the changed middle from a longest-common-subsequence table;
+/// `offset` maps its indexes back to the whole files
+fn lcs_ops(a: &[String], b: &[String], offset: usize) -> Vec<DiffOp> {
+    let width = b.len() + 1;
+    // lengths[i * width + j]: LCS of a[i..] and b[j..]
+    let mut lengths = vec![0u32; (a.len() + 1) * width];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i * width + j] = if a[i] == b[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            ops.push(DiffOp::Equal(offset + i, offset + j));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lengths[(i + 1) * width + j] >= lengths[i * width + j + 1]) {
+            // Deletions before insertions, as diff(1) shows them
+            ops.push(DiffOp::Delete(offset + i));
+            i += 1;
+        } else {
+            ops.push(DiffOp::Insert(offset + j));
+            j += 1;
+        }
+    }
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_unified_diff_hunks() {
+        let old = lines("a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl");
+        let new = lines("a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm");
+        let diff = unified_diff(&old, &new, "remote", "buffer");
+        assert_eq!(
+            diff,
+            [
+                "--- remote", "+++ buffer", "@@ -1,5 +1,5 @@", " a", "-b", "+B", " c", " d", " e",
+                "@@ -10,3 +10,4 @@", " j", " k", " l", "+m",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_identical_and_empty() {
+        let text = lines("x\ny");
+        assert!(unified_diff(&text, &text, "a", "b").is_empty());
+        assert_eq!(unified_diff(&[], &text, "a", "b")[2], "@@ -0,0 +1,2 @@");
+    }
+}
@@ -0,0 +1,124 @@
+use crate::filetype::{looks_binary, SNIFF_BYTES};
+
+/// Text encoding a file was read in, and is written back in
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    /// Any byte sequence that is not UTF-8; every byte maps to one character
+    Latin1,
+    /// Detected from a byte order mark, which is written back
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Encoding {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Latin1 => "Latin-1",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+        }
+    }
+}
+
+/// File content decoded for editing
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decoded {
+    pub text: String,
+    pub encoding: Encoding,
+    /// Some bytes could not be decoded and were replaced with U+FFFD
+    pub lossy: bool,
+    /// The content looks like a binary format rather than text
+    pub binary: bool,
+}
+
+/// Decode file bytes: UTF-16 when there is a byte order mark, UTF-8 when
+/// valid, and Latin-1 otherwise, which keeps every byte so the file is
+/// written back unchanged
+pub fn decode(bytes: &[u8]) -> Decoded {
+    let utf16 = match bytes {
+        [0xff, 0xfe, rest @ ..] => Some((Encoding::Utf16Le, rest, u16::from_le_bytes as fn([u8; 2]) -> u16)),
+        [0xfe, 0xff, rest @ ..] => Some((Encoding::Utf16Be, rest, u16::from_be_bytes as fn([u8; 2]) -> u16)),
+        _ => None,
+    };
+    if let Some((encoding, rest, unit)) = utf16 {
+        let units = rest.chunks(2).map(|pair| unit([pair[0], *pair.get(1).unwrap_or(&0)]));
+        let mut lossy = rest.len() % 2 != 0;
+        let text = char::decode_utf16(units)
+            .map(|c| {
+                c.unwrap_or_else(|_| {
+                    lossy = true;
+                    char::REPLACEMENT_CHARACTER
+                })
+            })
+            .collect();
+        return Decoded {
+            text,
+            encoding,
+            lossy,
+            binary: false,
+        };
+    }
+
+    let binary = looks_binary(&bytes[..bytes.len().min(SNIFF_BYTES)]);
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Decoded {
+            text: text.to_string(),
+            encoding: Encoding::Utf8,
+            lossy: false,
+            binary,
+        },
+        Err(_) => Decoded {
+            text: bytes.iter().map(|&b| b as char).collect(),
+            encoding: Encoding::Latin1,
+            lossy: false,
+            binary,
+        },
+    }
+}
+
+/// Encode text for writing back; fails on characters the encoding lacks
+pub fn encode(text: &str, encoding: Encoding) -> Result<Vec<u8>, String> {
+    match encoding {
+        Encoding::Utf8 => Ok(text.as_bytes().to_vec()),
+        Encoding::Latin1 => text
+            .chars()
+            .map(|c| u8::try_from(c).map_err(|_| format!("'{}' cannot be saved as Latin-1", c)))
+            .collect(),
+        Encoding::Utf16Le => Ok([0xff, 0xfe].into_iter().chain(text.encode_utf16().flat_map(u16::to_le_bytes)).collect()),
+        Encoding::Utf16Be => Ok([0xfe, 0xff].into_iter().chain(text.encode_utf16().flat_map(u16::to_be_bytes)).collect()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latin1_round_trips() {
+        let bytes = b"caf\xe9 cr\xe8me\n";
+        let decoded = decode(bytes);
+        assert_eq!(decoded.encoding, Encoding::Latin1);
+        assert_eq!(decoded.text, "café crème\n");
+        assert_eq!(encode(&decoded.text, Encoding::Latin1).unwrap(), bytes);
+        assert!(encode("€", Encoding::Latin1).is_err());
+    }
+
+    #[test]
+    fn test_utf16_with_bom() {
+        let bytes = [0xff, 0xfe, b'h', 0, b'i', 0];
+        let decoded = decode(&bytes);
+        assert_eq!((decoded.text.as_str(), decoded.encoding, decoded.lossy), ("hi", Encoding::Utf16Le, false));
+        assert_eq!(encode("hi", Encoding::Utf16Le).unwrap(), bytes);
+
+        // An unpaired surrogate is replaced
+        let decoded = decode(&[0xfe, 0xff, 0xd8, 0x00, 0, b'x']);
+        assert_eq!(decoded.text, "\u{fffd}x");
+        assert!(decoded.lossy);
+
+        assert_eq!(decode("ok ✓".as_bytes()).encoding, Encoding::Utf8);
+        assert!(decode(b"\x7fELF\x02\x01\x01\x00").binary);
+    }
+}
@@ -0,0 +1,171 @@
+use crate::tui::help::HelpSection;
+
+/// Normal mode keys. Alternatives are separated by " / "; the tests press
+/// the first key of each and check the editor reacts, and check that every
+/// key the editor reacts to is listed.
+const NORMAL_KEYS: &[(&str, &str)] = &[
+    ("h / ←", "Move left"),
+    ("j / ↓", "Move down"),
+    ("k / ↑", "Move up"),
+    ("l / →", "Move right"),
+    ("w / b / e", "Next word / previous word / end of word"),
+    ("W / B / E", "The same for whitespace-separated words"),
+    ("0 / $", "Start / end of line"),
+    ("gg / G", "Start / end of file; with a count, go to that line"),
+    ("%", "Jump to the matching bracket"),
+    ("1-9", "Count for the next command, e.g. 3j or 5dd"),
+    ("Ctrl+d / Ctrl+u", "Half page down / up"),
+    ("Ctrl+f / Ctrl+b", "Full page down / up"),
+    ("Ctrl+g", "Show the position in the file"),
+    ("i / a", "Insert before / after the cursor"),
+    ("I / A", "Insert before the first non-blank / at the end of the line"),
+    ("o / O", "Open a line below / above"),
+    ("x", "Delete the character under the cursor"),
+    ("dd", "Delete the line"),
+    ("yy", "Yank the line"),
+    ("p / P", "Paste after / before"),
+    ("r{char}", "Replace the character under the cursor"),
+    ("~", "Switch case and move right"),
+    ("cw / cc / c$", "Change to the end of the word / the line / to the end of the line"),
+    ("v / V", "Character-wise / line-wise visual selection"),
+    ("u / Ctrl+r", "Undo / redo"),
+    ("q{a-z}", "Record a macro; q again stops"),
+    ("@{a-z} / @@", "Replay a macro / the last one"),
+    ("m{a-z}", "Set a mark"),
+    ("'{a-z} / `{a-z}", "Jump to a mark's line / exact position"),
+    (":", "Enter a command"),
+    ("/", "Open the search prompt"),
+    ("?", "Show this help"),
+    ("Ctrl+q", "Quit without saving"),
+];
+
+const VISUAL_KEYS: &[(&str, &str)] = &[
+    ("h / j / k / l", "Extend the selection"),
+    ("w / b / e / 0 / $", "Extend the selection by word or to the line ends"),
+    ("g / G / %", "Extend the selection to the start / end of file / matching bracket"),
+    ("Ctrl+d / Ctrl+u", "Extend the selection by half a page"),
+    ("y", "Yank the selection"),
+    ("d / x", "Delete the selection"),
+    ("> / <", "Indent / dedent the selected lines"),
+    ("v / V", "Switch between character-wise and line-wise"),
+    ("Esc / Ctrl+c", "Back to normal mode"),
+];
+
+const INSERT_KEYS: &[(&str, &str)] = &[
+    ("Esc", "Back to normal mode"),
+    ("Tab", "Indent to the next tab stop"),
+    ("Enter", "Split the line"),
+    ("Backspace", "Delete the character before the cursor"),
+    ("← / → / ↑ / ↓", "Move the cursor"),
+];
+
+const COMMANDS: &[(&str, &str)] = &[
+    (":w", "Save"),
+    (":q / :q!", "Quit / quit discarding changes"),
+    (":wq / :x", "Save and quit"),
+    (":N / :$", "Go to line N / the last line"),
+    (":e path", "Open another file in a new buffer"),
+    (":bn / :bp", "Next / previous buffer"),
+    (":diff", "Diff the buffer against the file on the server"),
+    (":[range]s/pat/rep/[gci]", "Substitute; % for the whole file, c to confirm each"),
+    (":set option", "tabstop=N, expandtab, list, number, wrap; no... to turn off"),
+    (":set", "Show every option"),
+];
+
+fn section(title: &'static str, entries: &[(&'static str, &'static str)]) -> HelpSection {
+    (title, entries.iter().map(|&(keys, description)| (keys.to_string(), description.to_string())).collect())
+}
+
+/// Everything the editor's help overlay lists
+pub fn sections() -> Vec<HelpSection> {
+    vec![
+        section("Normal mode", NORMAL_KEYS),
+        section("Visual mode", VISUAL_KEYS),
+        section("Insert mode", INSERT_KEYS),
+        section("Commands", COMMANDS),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::{handle_key, EditorMode, EditorState};
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    /// Editor with the cursor on a bracket mid-file and a yanked line
+    fn editor(mode: EditorMode) -> EditorState {
+        let content = "one (two) three\nfour [five] six\nseven eight nine".to_string();
+        let mut editor = EditorState::new("a.txt".to_string(), "/tmp/a.txt".to_string(), content);
+        (editor.cursor_row, editor.cursor_col) = (1, 5);
+        editor.yank_register = vec![String::from("pasted")];
+        if mode != EditorMode::Normal {
+            editor.start_visual(mode);
+        }
+        editor
+    }
+
+    /// Everything a key press can change
+    fn fingerprint(editor: &EditorState) -> String {
+        format!(
+            "{:?} {} {} {:?} {} {:?} {:?} {} {} {}",
+            editor.buffer,
+            editor.cursor_row,
+            editor.cursor_col,
+            editor.mode,
+            editor.status_message,
+            editor.pending_operator,
+            editor.pending_count,
+            editor.should_quit,
+            editor.help.is_some(),
+            editor.recording.is_some()
+        )
+    }
+
+    /// The key that starts a help label such as "Ctrl+d", "←" or "r{char}"
+    fn first_key(label: &str) -> KeyEvent {
+        if let Some(c) = label.strip_prefix("Ctrl+").and_then(|rest| rest.chars().next()) {
+            return KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+        }
+        KeyEvent::from(match label {
+            "←" => KeyCode::Left,
+            "→" => KeyCode::Right,
+            "↑" => KeyCode::Up,
+            "↓" => KeyCode::Down,
+            "Esc" => KeyCode::Esc,
+            _ => KeyCode::Char(label.chars().next().unwrap()),
+        })
+    }
+
+    fn check_table(mode: EditorMode, table: &[(&str, &str)]) {
+        for label in table.iter().flat_map(|(keys, _)| keys.split(" / ")) {
+            let mut editor = editor(mode);
+            let before = fingerprint(&editor);
+            handle_key(&mut editor, first_key(label), 20);
+            assert_ne!(fingerprint(&editor), before, "{:?} in {:?} does nothing", label, mode);
+        }
+
+        let listed: Vec<char> = table
+            .iter()
+            .flat_map(|(keys, _)| keys.split(" / "))
+            .filter(|label| !label.starts_with("Ctrl+"))
+            .filter_map(|label| label.chars().next())
+            .collect();
+        for c in ' '..='~' {
+            let mut editor = editor(mode);
+            let before = fingerprint(&editor);
+            handle_key(&mut editor, KeyEvent::from(KeyCode::Char(c)), 20);
+            let listed = listed.contains(&c) || (mode == EditorMode::Normal && c.is_ascii_digit());
+            assert!(listed || fingerprint(&editor) == before, "{:?} in {:?} is missing from help", c, mode);
+        }
+    }
+
+    #[test]
+    fn test_help_matches_normal_mode_keys() {
+        check_table(EditorMode::Normal, NORMAL_KEYS);
+    }
+
+    #[test]
+    fn test_help_matches_visual_mode_keys() {
+        check_table(EditorMode::Visual, VISUAL_KEYS);
+    }
+}
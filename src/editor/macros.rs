@@ -0,0 +1,95 @@
+use crossterm::event::KeyEvent;
+
+use super::{handle_key, EditorState};
+
+/// Deepest `@` inside `@` replay before giving up, so a macro that calls
+/// itself cannot loop forever
+const MAX_MACRO_DEPTH: usize = 20;
+
+impl EditorState {
+    /// `q{reg}`: record the following keys into a register
+    pub fn start_recording(&mut self, register: char) {
+        self.recording = Some((register, Vec::new()));
+        self.status_message = format!("recording @{}", register);
+    }
+
+    /// `q` while recording: save the keys, minus the `q` itself
+    pub fn stop_recording(&mut self) {
+        let Some((register, mut keys)) = self.recording.take() else {
+            return;
+        };
+        keys.pop();
+        self.macros.insert(register, keys);
+        self.status_message = format!("Recorded @{}", register);
+    }
+
+    /// Keep a key typed while recording
+    pub(super) fn record_key(&mut self, key: KeyEvent) {
+        if let Some((_, keys)) = self.recording.as_mut() {
+            keys.push(key);
+        }
+    }
+}
+
+/// `@{reg}`: replay a recorded macro `count` times; `@@` repeats the last one
+pub(super) fn replay_macro(editor: &mut EditorState, register: char, count: usize, viewport_height: usize) {
+    let register = if register == '@' {
+        match editor.last_macro {
+            Some(register) => register,
+            None => {
+                editor.status_message = String::from("No previous macro");
+                return;
+            }
+        }
+    } else {
+        register
+    };
+    let Some(keys) = editor.macros.get(&register).cloned() else {
+        editor.status_message = format!("Register {} is empty", register);
+        return;
+    };
+    if editor.macro_depth >= MAX_MACRO_DEPTH {
+        editor.status_message = String::from("Macro recursion too deep");
+        return;
+    }
+
+    editor.last_macro = Some(register);
+    editor.macro_depth += 1;
+    for _ in 0..count {
+        for &key in &keys {
+            handle_key(editor, key, viewport_height);
+        }
+    }
+    editor.macro_depth -= 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::tests::press;
+    use crate::editor::EditorState;
+
+    #[test]
+    fn test_record_and_replay_with_count() {
+        let mut editor = EditorState::new(
+            "hosts".to_string(),
+            "/etc/hosts".to_string(),
+            "a\nb\nc\nd\ne".to_string(),
+        );
+
+        // Append ";" to the line and move down
+        press(&mut editor, "qq$a;");
+        press(&mut editor, "\u{1b}jq");
+        assert_eq!(editor.buffer[0], "a;");
+        assert!(editor.recording.is_none());
+
+        press(&mut editor, "2@q");
+        assert_eq!(editor.buffer, ["a;", "b;", "c;", "d", "e"]);
+        press(&mut editor, "@@");
+        assert_eq!(editor.buffer[3], "d;");
+
+        // A macro that replays itself stops instead of looping forever
+        press(&mut editor, "qrx@rq");
+        press(&mut editor, "@r");
+        assert_eq!(editor.status_message, "Macro recursion too deep");
+    }
+}
@@ -2,21 +2,65 @@ use anyhow::Result;
 use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style},
+    style::{Modifier, Style},
     text::{Line, Span},
     widgets::{Block, Borders, Paragraph},
     Frame,
 };
+use chrono::{DateTime, Local};
 use russh_sftp::client::SftpSession;
+use russh_sftp::protocol::OpenFlags;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
-#[derive(Debug, Clone, PartialEq)]
+use crate::theme::Theme;
+use crate::file_ops::{copy_file_sftp, resolve_link_target, set_permissions};
+use crate::tui::help::HelpOverlay;
+
+mod brackets;
+mod buffers;
+mod change;
+mod conflict;
+mod diff;
+mod encoding;
+mod help;
+mod macros;
+mod options;
+mod positions;
+mod substitute;
+mod visual;
+
+pub use buffers::{BufferList, BufferRequest};
+pub use conflict::{remote_stamp, RemoteStamp};
+pub use encoding::{Decoded, Encoding};
+pub use options::EditorOptions;
+pub use positions::{FilePosition, FilePositions};
+use substitute::{parse_substitute, PendingSubstitute};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum EditorMode {
     Normal,
     Insert,
     Command,
     Search,
+    /// Character-wise selection (`v`)
+    Visual,
+    /// Line-wise selection (`V`)
+    VisualLine,
+}
+
+/// Copy kept of a file before the editor first overwrites it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupMode {
+    #[default]
+    Off,
+    /// `file~`
+    Tilde,
+    /// `file.bak-YYYYMMDD-HHMMSS`
+    Timestamp,
 }
 
 #[derive(Debug, Clone)]
@@ -32,14 +76,57 @@ pub struct EditorState {
     pub cursor_col: usize,
     pub mode: EditorMode,
     pub yank_register: Vec<String>,
+    /// The register holds whole lines rather than a character-wise selection
+    pub yank_linewise: bool,
+    /// Where the visual selection started
+    pub visual_anchor: (usize, usize),
     pub status_message: String,
     pub command_buffer: String,
-    pub search_pattern: String,
     pub scroll_offset: usize,
     pub filename: String,
     pub remote_path: String,
     pub modified: bool,
     pub should_quit: bool,
+    /// Quit without checking other buffers for unsaved changes (`:q!`)
+    pub force_quit: bool,
+    /// Encoding the file is saved in
+    pub encoding: Encoding,
+    /// Opened without write access; edits and saves are refused
+    pub read_only: bool,
+    /// The original file was already backed up this session
+    pub backup_written: bool,
+    /// The buffer holds a `:diff` and is coloured as one
+    pub diff_view: bool,
+    /// The file on the server when it was read or last saved
+    pub remote_stamp: Option<RemoteStamp>,
+    /// A save found the file changed on the server and is waiting for an
+    /// answer, with whether to quit after saving
+    pub save_conflict: Option<bool>,
+    /// The terminal lost focus since the caller last looked
+    pub focus_lost: bool,
+    /// `:e`, `:bn`, `:bp` or a save conflict answer waiting for the caller
+    pub buffer_request: Option<BufferRequest>,
+    /// Settings changed with `:set`
+    pub options: EditorOptions,
+    /// Count typed before a command, e.g. the 3 in `3j`
+    pending_count: Option<usize>,
+    /// First key of a two-key command such as `dd`, `yy`, `gg` or `ma`,
+    /// with the count typed before it
+    pending_operator: Option<(char, usize)>,
+    /// Register and keys of the macro being recorded with `q{reg}`
+    pub recording: Option<(char, Vec<KeyEvent>)>,
+    /// Recorded macros by register
+    macros: HashMap<char, Vec<KeyEvent>>,
+    /// Register replayed by the last `@`, for `@@`
+    last_macro: Option<char>,
+    /// `@` replays currently running, one inside another
+    macro_depth: usize,
+    /// Positions saved with `m{a-z}`
+    marks: HashMap<char, (usize, usize)>,
+    /// Keybinding overlay opened with `?`
+    pub help: Option<HelpOverlay>,
+    /// `:s///c` waiting for an answer about the highlighted match
+    pub pending_substitute: Option<PendingSubstitute>,
     undo_stack: Vec<BufferSnapshot>,
     redo_stack: Vec<BufferSnapshot>,
 }
@@ -58,14 +145,34 @@ impl EditorState {
             cursor_col: 0,
             mode: EditorMode::Normal,
             yank_register: Vec::new(),
+            yank_linewise: true,
+            visual_anchor: (0, 0),
             status_message: String::from("Normal mode"),
             command_buffer: String::new(),
-            search_pattern: String::new(),
             scroll_offset: 0,
             filename,
             remote_path,
             modified: false,
             should_quit: false,
+            force_quit: false,
+            encoding: Encoding::Utf8,
+            read_only: false,
+            backup_written: false,
+            diff_view: false,
+            remote_stamp: None,
+            save_conflict: None,
+            focus_lost: false,
+            buffer_request: None,
+            options: EditorOptions::default(),
+            pending_count: None,
+            pending_operator: None,
+            recording: None,
+            macros: HashMap::new(),
+            last_macro: None,
+            macro_depth: 0,
+            marks: HashMap::new(),
+            help: None,
+            pending_substitute: None,
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
         }
@@ -172,7 +279,7 @@ impl EditorState {
         let max_col = if self.mode == EditorMode::Insert {
             line_len
         } else {
-            line_len.saturating_sub(1).max(0)
+            line_len.saturating_sub(1)
         };
 
         if self.cursor_col > max_col {
@@ -180,17 +287,28 @@ impl EditorState {
         }
     }
 
+    /// Screen column of the cursor, counting tabs as their expanded width
+    pub fn cursor_display_col(&self) -> usize {
+        display_column(self.get_current_line(), self.cursor_col, self.options.tab_width)
+    }
+
+    /// Move to `row`, keeping the cursor in the same screen column
+    fn move_to_row(&mut self, row: usize) {
+        let column = self.cursor_display_col();
+        self.cursor_row = row;
+        self.cursor_col = column_to_index(self.get_current_line(), column, self.options.tab_width);
+        self.clamp_cursor();
+    }
+
     pub fn move_cursor_up(&mut self) {
         if self.cursor_row > 0 {
-            self.cursor_row -= 1;
-            self.clamp_cursor();
+            self.move_to_row(self.cursor_row - 1);
         }
     }
 
     pub fn move_cursor_down(&mut self) {
         if self.cursor_row < self.buffer.len() - 1 {
-            self.cursor_row += 1;
-            self.clamp_cursor();
+            self.move_to_row(self.cursor_row + 1);
         }
     }
 
@@ -232,7 +350,7 @@ impl EditorState {
         self.cursor_col = if self.mode == EditorMode::Insert {
             line_len
         } else {
-            line_len.saturating_sub(1).max(0)
+            line_len.saturating_sub(1)
         };
     }
 
@@ -246,7 +364,50 @@ impl EditorState {
         self.move_to_line_end();
     }
 
-    fn is_word_char(c: char) -> bool {
+    /// Jump to a 1-based line, clamped to the buffer, at its first non-blank
+    pub fn goto_line(&mut self, line: usize) {
+        self.cursor_row = line.saturating_sub(1).min(self.buffer.len().saturating_sub(1));
+        let current = self.get_current_line();
+        self.cursor_col = current.len() - current.trim_start().len();
+        self.clamp_cursor();
+    }
+
+    pub fn set_mark(&mut self, mark: char) {
+        self.marks.insert(mark, (self.cursor_row, self.cursor_col));
+        self.status_message = format!("Mark {} set", mark);
+    }
+
+    /// Jump to a mark: its exact position, or with `linewise` the first
+    /// non-blank of its line
+    pub fn jump_to_mark(&mut self, mark: char, linewise: bool) {
+        let Some(&(row, col)) = self.marks.get(&mark) else {
+            self.status_message = format!("Mark not set: {}", mark);
+            return;
+        };
+        if linewise {
+            self.goto_line(row + 1);
+        } else {
+            self.cursor_row = row;
+            self.cursor_col = col;
+            self.clamp_cursor();
+        }
+    }
+
+    /// `Ctrl+G` summary of the file and cursor position
+    pub fn show_position(&mut self) {
+        let line = self.cursor_row + 1;
+        let total = self.buffer.len();
+        self.status_message = format!(
+            "\"{}\"{} line {} of {} ({}%)",
+            self.filename,
+            if self.modified { " [+]" } else { "" },
+            line,
+            total,
+            line * 100 / total.max(1)
+        );
+    }
+
+    pub(super) fn is_word_char(c: char) -> bool {
         c.is_alphanumeric() || c == '_'
     }
 
@@ -460,28 +621,62 @@ impl EditorState {
         self.clamp_cursor();
     }
 
-    pub fn delete_line(&mut self) {
+    /// Delete `count` lines from the cursor down into the register
+    pub fn delete_lines(&mut self, count: usize) {
         self.save_undo_state();
-        if self.buffer.len() == 1 {
-            self.yank_register = vec![self.buffer[0].clone()];
-            self.buffer[0].clear();
-        } else {
-            self.yank_register = vec![self.buffer.remove(self.cursor_row)];
-            if self.cursor_row >= self.buffer.len() {
-                self.cursor_row = self.buffer.len() - 1;
-            }
+        let end = (self.cursor_row + count.max(1)).min(self.buffer.len());
+        self.yank_register = self.buffer.drain(self.cursor_row..end).collect();
+        self.yank_linewise = true;
+        if self.buffer.is_empty() {
+            self.buffer.push(String::new());
+        }
+        if self.cursor_row >= self.buffer.len() {
+            self.cursor_row = self.buffer.len() - 1;
         }
         self.clamp_cursor();
         self.modified = true;
-        self.status_message = String::from("Line deleted");
+        self.status_message = match self.yank_register.len() {
+            1 => String::from("Line deleted"),
+            n => format!("{} lines deleted", n),
+        };
     }
 
-    pub fn yank_line(&mut self) {
-        self.yank_register = vec![self.buffer[self.cursor_row].clone()];
-        self.status_message = String::from("Line yanked");
+    /// Copy `count` lines from the cursor down into the register
+    pub fn yank_lines(&mut self, count: usize) {
+        let end = (self.cursor_row + count.max(1)).min(self.buffer.len());
+        self.yank_register = self.buffer[self.cursor_row..end].to_vec();
+        self.yank_linewise = true;
+        self.status_message = match self.yank_register.len() {
+            1 => String::from("Line yanked"),
+            n => format!("{} lines yanked", n),
+        };
+    }
+
+    /// Delete up to `count` characters from the cursor as one undo step
+    pub fn delete_chars(&mut self, count: usize) {
+        let line = self.get_current_line();
+        if self.cursor_col >= line.len() {
+            return;
+        }
+        let end = line[self.cursor_col..]
+            .char_indices()
+            .nth(count.max(1))
+            .map_or(line.len(), |(i, _)| self.cursor_col + i);
+
+        self.save_undo_state();
+        let cursor_col = self.cursor_col;
+        self.get_current_line_mut().drain(cursor_col..end);
+        self.modified = true;
+        self.clamp_cursor();
     }
 
     pub fn paste_below(&mut self) {
+        if !self.yank_linewise {
+            if !self.yank_register.is_empty() {
+                self.paste_charwise(true);
+            }
+            return;
+        }
         if !self.yank_register.is_empty() {
             self.save_undo_state();
             for (i, line) in self.yank_register.iter().enumerate() {
@@ -493,6 +688,49 @@ impl EditorState {
         }
     }
 
+    /// `P`: paste lines above the current one, or text before the cursor
+    pub fn paste_above(&mut self) {
+        if self.yank_register.is_empty() {
+            return;
+        }
+        if !self.yank_linewise {
+            return self.paste_charwise(false);
+        }
+        self.save_undo_state();
+        for (i, line) in self.yank_register.iter().enumerate() {
+            self.buffer.insert(self.cursor_row + i, line.clone());
+        }
+        self.modified = true;
+        self.clamp_cursor();
+        self.status_message = String::from("Pasted");
+    }
+
+    /// Enter insert mode with the cursor at `col`
+    fn start_insert_at(&mut self, col: usize) {
+        self.mode = EditorMode::Insert;
+        self.cursor_col = col;
+        self.status_message = String::from("Insert mode");
+    }
+
+    /// `A`: insert at the end of the line
+    pub fn append_at_line_end(&mut self) {
+        self.start_insert_at(self.get_current_line().len());
+    }
+
+    /// `I`: insert before the first non-blank character
+    pub fn insert_at_first_non_blank(&mut self) {
+        let line = self.get_current_line();
+        self.start_insert_at(line.len() - line.trim_start().len());
+    }
+
+    /// `O`: open a new line above and insert there
+    pub fn open_line_above(&mut self) {
+        self.save_undo_state();
+        self.buffer.insert(self.cursor_row, String::new());
+        self.modified = true;
+        self.start_insert_at(0);
+    }
+
     pub fn insert_char(&mut self, c: char) {
         self.save_undo_state();
         let cursor_col = self.cursor_col;
@@ -506,6 +744,20 @@ impl EditorState {
         self.modified = true;
     }
 
+    /// Insert a tab, or spaces up to the next tab stop with expandtab
+    pub fn insert_tab(&mut self) {
+        if !self.options.expand_tab {
+            return self.insert_char('\t');
+        }
+        self.save_undo_state();
+        let width = self.options.tab_width.max(1);
+        let spaces = width - self.cursor_display_col() % width;
+        let cursor_col = self.cursor_col.min(self.get_current_line().len());
+        self.get_current_line_mut().insert_str(cursor_col, &" ".repeat(spaces));
+        self.cursor_col = cursor_col + spaces;
+        self.modified = true;
+    }
+
     pub fn delete_char(&mut self) {
         self.save_undo_state();
         let cursor_col = self.cursor_col;
@@ -523,24 +775,6 @@ impl EditorState {
         }
     }
 
-    pub fn delete_char_at_cursor(&mut self) {
-        self.save_undo_state();
-        let cursor_col = self.cursor_col;
-        let line_len = self.get_current_line().len();
-
-        if cursor_col < line_len {
-            let line = self.get_current_line_mut();
-            line.remove(cursor_col);
-            let new_len = line.len();
-            self.modified = true;
-
-            // Clamp cursor if we deleted the last character
-            if self.mode == EditorMode::Normal && cursor_col >= new_len && new_len > 0 {
-                self.cursor_col = new_len - 1;
-            }
-        }
-    }
-
     pub fn insert_newline(&mut self) {
         self.save_undo_state();
         let cursor_col = self.cursor_col;
@@ -552,7 +786,43 @@ impl EditorState {
         self.modified = true;
     }
 
+    /// Insert pasted text at the cursor in one step, so a long paste is a
+    /// single undo and a single redraw. Terminals send line breaks as `\r`.
+    pub fn insert_text(&mut self, text: &str) {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        self.save_undo_state();
+        let line = self.get_current_line();
+        let mut cursor_col = self.cursor_col.min(line.len());
+        while !line.is_char_boundary(cursor_col) {
+            cursor_col -= 1;
+        }
+        let tail = self.get_current_line_mut().split_off(cursor_col);
+
+        let mut pieces = text.split('\n');
+        self.get_current_line_mut().push_str(pieces.next().unwrap_or_default());
+        for piece in pieces {
+            self.cursor_row += 1;
+            self.buffer.insert(self.cursor_row, piece.to_string());
+        }
+        self.cursor_col = self.get_current_line().len();
+        self.get_current_line_mut().push_str(&tail);
+        self.modified = true;
+
+        let lines = text.matches('\n').count() + 1;
+        if lines > 1 {
+            self.status_message = format!("{} lines pasted", lines);
+        }
+    }
+
     pub fn execute_command(&mut self, command: &str) {
+        if self.read_only
+            && (matches!(command, "w" | "write" | "wq" | "x")
+                || parse_substitute(command, self.cursor_row, self.buffer.len()).is_some())
+        {
+            self.status_message = String::from("File is read-only (no write permission)");
+            return;
+        }
+
         match command {
             "w" | "write" => {
                 self.status_message = String::from("Saving...");
@@ -566,43 +836,229 @@ impl EditorState {
             }
             "q!" => {
                 self.should_quit = true;
+                self.force_quit = true;
             }
             "wq" | "x" => {
                 self.status_message = String::from("Saving and quitting...");
             }
-            _ => {
-                self.status_message = format!("Unknown command: {}", command);
+            "diff" if self.diff_view => self.status_message = String::from("Already showing a diff"),
+            "diff" => self.buffer_request = Some(BufferRequest::Diff),
+            "bn" | "bnext" => self.buffer_request = Some(BufferRequest::Next),
+            "bp" | "bprevious" => self.buffer_request = Some(BufferRequest::Previous),
+            "$" => self.goto_line(self.buffer.len()),
+            _ if command.parse::<usize>().is_ok() => self.goto_line(command.parse().unwrap_or(1)),
+            _ if matches!(command.split_whitespace().next(), Some("e" | "edit")) => {
+                match command.split_once(' ').map(|(_, path)| path.trim()) {
+                    Some(path) if !path.is_empty() => {
+                        let path = resolve_link_target(&self.remote_path, path);
+                        self.buffer_request = Some(BufferRequest::Open(path));
+                    }
+                    _ => self.status_message = String::from("No file name"),
+                }
+            }
+            _ if command == "set" || command.starts_with("set ") => self.set_option(command["set".len()..].trim()),
+            _ => match parse_substitute(command, self.cursor_row, self.buffer.len()) {
+                Some(Ok(substitute)) => self.substitute(substitute),
+                Some(Err(e)) => self.status_message = e,
+                None => self.status_message = format!("Unknown command: {}", command),
+            },
+        }
+    }
+
+    /// `:set option`; a bare `:set` shows every option
+    fn set_option(&mut self, option: &str) {
+        self.status_message = match option {
+            "" => self.options.summary(),
+            _ => self.options.set(option).unwrap_or_else(|e| e),
+        };
+    }
+
+    /// With `wrap` on, scroll further until the cursor's screen row fits,
+    /// since wrapped lines above it take several rows
+    pub fn fit_wrapped_lines(&mut self, viewport_height: usize, screen_width: usize) {
+        if !self.options.wrap {
+            return;
+        }
+        let width = self.text_width(screen_width);
+        let cursor_row_offset = self.cursor_display_col() / width;
+        while self.scroll_offset < self.cursor_row {
+            let rows_above: usize = (self.scroll_offset..self.cursor_row).map(|row| self.screen_rows(row, width)).sum();
+            if rows_above + cursor_row_offset < viewport_height {
+                break;
             }
+            self.scroll_offset += 1;
         }
     }
 
+    /// Columns taken by line numbers with `:set number`, including the space
+    /// after them
+    pub fn gutter_width(&self) -> usize {
+        if self.options.number { self.buffer.len().to_string().len().max(3) + 1 } else { 0 }
+    }
+
+    /// Columns left for text in a screen `screen_width` wide
+    fn text_width(&self, screen_width: usize) -> usize {
+        screen_width.saturating_sub(self.gutter_width()).max(1)
+    }
+
+    /// Screen rows buffer line `row` takes at `width` columns
+    fn screen_rows(&self, row: usize, width: usize) -> usize {
+        if !self.options.wrap {
+            return 1;
+        }
+        let line = &self.buffer[row];
+        display_column(line, line.len(), self.options.tab_width).div_ceil(width).max(1)
+    }
+
     pub fn update_scroll(&mut self, viewport_height: usize) {
-        let margin = 3;
+        // A terminal resized down to a few rows has no room for the full margin
+        let margin = 3.min(viewport_height.saturating_sub(1) / 2);
 
         if self.cursor_row < self.scroll_offset + margin {
             self.scroll_offset = self.cursor_row.saturating_sub(margin);
         }
 
         if self.cursor_row >= self.scroll_offset + viewport_height - margin {
-            self.scroll_offset = self.cursor_row + margin - viewport_height + 1;
+            self.scroll_offset = self.cursor_row + margin + 1 - viewport_height;
+        }
+    }
+}
+
+/// Screen column where byte index `col` of `line` starts, with tabs
+/// advancing to the next multiple of `tab_width`
+pub fn display_column(line: &str, col: usize, tab_width: usize) -> usize {
+    line.char_indices()
+        .take_while(|&(i, _)| i < col)
+        .fold(0, |column, (_, c)| advance_column(column, c, tab_width))
+}
+
+/// Byte index of the character shown at screen `column`, or the line length
+/// when the line is shorter
+fn column_to_index(line: &str, column: usize, tab_width: usize) -> usize {
+    let mut current = 0;
+    for (i, c) in line.char_indices() {
+        let next = advance_column(current, c, tab_width);
+        if next > column {
+            return i;
+        }
+        current = next;
+    }
+    line.len()
+}
+
+fn advance_column(column: usize, c: char, tab_width: usize) -> usize {
+    if c == '\t' {
+        let width = tab_width.max(1);
+        column + width - column % width
+    } else {
+        column + 1
+    }
+}
+
+/// Build a screen line from styled pieces of a buffer line, expanding tabs
+/// to spaces so the terminal shows them at the right width. With `list`,
+/// tabs, non-breaking spaces and trailing whitespace are drawn visibly.
+fn render_line(pieces: &[(&str, Style)], tab_width: usize, list: bool, theme: &Theme) -> Line<'static> {
+    let whitespace = Style::default().fg(theme.muted);
+    let line: String = pieces.iter().map(|&(text, _)| text).collect();
+    let trailing_start = line.trim_end_matches([' ', '\t', '\u{a0}']).len();
+
+    let mut spans: Vec<(String, Style)> = Vec::new();
+    let mut column = 0;
+    let mut offset = 0;
+    for &(text, style) in pieces {
+        for c in text.chars() {
+            let next = advance_column(column, c, tab_width);
+            let trailing = list && offset >= trailing_start;
+            let (shown, visible) = match c {
+                '\t' if list => (format!("→{}", " ".repeat(next - column - 1)), true),
+                '\t' => (" ".repeat(next - column), false),
+                ' ' if trailing => (String::from("·"), true),
+                '\u{a0}' if list => (String::from("⍽"), true),
+                c => (c.to_string(), false),
+            };
+
+            let mut char_style = if visible { style.patch(whitespace) } else { style };
+            if trailing {
+                char_style = char_style.bg(theme.error);
+            }
+            match spans.last_mut() {
+                Some((content, last_style)) if *last_style == char_style => content.push_str(&shown),
+                _ => spans.push((shown, char_style)),
+            }
+            column = next;
+            offset += c.len_utf8();
+        }
+    }
+    Line::from(spans.into_iter().map(|(content, style)| Span::styled(content, style)).collect::<Vec<_>>())
+}
+
+/// Break a rendered line into screen rows of `width` columns
+fn wrap_line(line: Line<'static>, width: usize) -> Vec<Line<'static>> {
+    let mut rows = Vec::new();
+    let mut current: Vec<Span<'static>> = Vec::new();
+    let mut used = 0;
+    for span in line.spans {
+        let mut chars = span.content.chars().peekable();
+        while chars.peek().is_some() {
+            if used == width {
+                rows.push(Line::from(std::mem::take(&mut current)));
+                used = 0;
+            }
+            let piece: String = chars.by_ref().take(width - used).collect();
+            used += piece.chars().count();
+            current.push(Span::styled(piece, span.style));
         }
     }
+    rows.push(Line::from(current));
+    rows
 }
 
-pub async fn load_file_content(sftp: &SftpSession, remote_path: &str) -> Result<String> {
+pub async fn load_file_content(sftp: &SftpSession, remote_path: &str) -> Result<Decoded> {
     let mut file = sftp.open(remote_path).await?;
-    let mut content = String::new();
-    file.read_to_string(&mut content).await?;
-    Ok(content)
+    let mut content = Vec::new();
+    file.read_to_end(&mut content).await?;
+    Ok(encoding::decode(&content))
 }
 
-pub async fn save_file_content(sftp: &SftpSession, remote_path: &str, content: &str) -> Result<()> {
+/// Whether the file can be opened for writing, without changing it
+pub async fn is_writable(sftp: &SftpSession, remote_path: &str) -> bool {
+    sftp.open_with_flags(remote_path, OpenFlags::WRITE).await.is_ok()
+}
+
+/// Write `content` back in `encoding`. Content the encoding cannot hold is
+/// refused before the file is truncated.
+pub async fn save_file_content(sftp: &SftpSession, remote_path: &str, content: &str, encoding: Encoding) -> Result<()> {
+    let bytes = encoding::encode(content, encoding).map_err(anyhow::Error::msg)?;
     let mut file = sftp.create(remote_path).await?;
-    file.write_all(content.as_bytes()).await?;
+    file.write_all(&bytes).await?;
+    Ok(())
+}
+
+/// Where `mode` keeps the backup of `remote_path`
+pub fn backup_path(remote_path: &str, mode: BackupMode, now: DateTime<Local>) -> Option<String> {
+    match mode {
+        BackupMode::Off => None,
+        BackupMode::Tilde => Some(format!("{}~", remote_path)),
+        BackupMode::Timestamp => Some(format!("{}.bak-{}", remote_path, now.format("%Y%m%d-%H%M%S"))),
+    }
+}
+
+/// Copy the file as it is on the server to `backup`, with the same permission
+/// bits so a backup of a private file is not readable by others. A file that
+/// does not exist yet needs no backup.
+pub async fn write_backup(sftp: &SftpSession, remote_path: &str, backup: &str) -> Result<()> {
+    let Ok(meta) = sftp.metadata(remote_path).await else {
+        return Ok(());
+    };
+    copy_file_sftp(sftp, remote_path, backup).await?;
+    if let Some(mode) = meta.permissions {
+        set_permissions(sftp, backup, mode & 0o7777).await?;
+    }
     Ok(())
 }
 
-pub fn render_editor(f: &mut Frame, area: Rect, editor: &EditorState) {
+pub fn render_editor(f: &mut Frame, area: Rect, editor: &EditorState, theme: &Theme) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
@@ -614,19 +1070,31 @@ pub fn render_editor(f: &mut Frame, area: Rect, editor: &EditorState) {
 
     // Header
     let mode_indicator = match editor.mode {
-        EditorMode::Normal => Span::styled("NORMAL", Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD)),
-        EditorMode::Insert => Span::styled("INSERT", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
-        EditorMode::Command => Span::styled("COMMAND", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
-        EditorMode::Search => Span::styled("SEARCH", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+        EditorMode::Normal => Span::styled("NORMAL", Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)),
+        EditorMode::Insert => Span::styled("INSERT", Style::default().fg(theme.success).add_modifier(Modifier::BOLD)),
+        EditorMode::Command => Span::styled("COMMAND", Style::default().fg(theme.key).add_modifier(Modifier::BOLD)),
+        EditorMode::Search => Span::styled("SEARCH", Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)),
+        EditorMode::Visual => Span::styled("VISUAL", Style::default().fg(theme.special).add_modifier(Modifier::BOLD)),
+        EditorMode::VisualLine => Span::styled("V-LINE", Style::default().fg(theme.special).add_modifier(Modifier::BOLD)),
     };
 
     let modified_indicator = if editor.modified { " [+]" } else { "" };
-    let header = Line::from(vec![
+    let mut header = vec![
         mode_indicator,
         Span::raw(" | "),
         Span::raw(&editor.filename),
         Span::raw(modified_indicator),
-    ]);
+    ];
+    if let Some((register, _)) = &editor.recording {
+        header.push(Span::styled(format!(" recording @{}", register), Style::default().fg(theme.special)));
+    }
+    if editor.encoding != Encoding::Utf8 {
+        header.push(Span::raw(format!(" [{}]", editor.encoding.name())));
+    }
+    if editor.read_only {
+        header.push(Span::styled(" [read-only]", Style::default().fg(theme.error).add_modifier(Modifier::BOLD)));
+    }
+    let header = Line::from(header);
     let header_widget = Paragraph::new(header);
     f.render_widget(header_widget, chunks[0]);
 
@@ -635,9 +1103,50 @@ pub fn render_editor(f: &mut Frame, area: Rect, editor: &EditorState) {
     let visible_start = editor.scroll_offset;
     let visible_end = (visible_start + viewport_height).min(editor.buffer.len());
 
+    let highlight = editor
+        .pending_substitute
+        .as_ref()
+        .and_then(|pending| Some((pending.row, pending.current?)));
+    let gutter = editor.gutter_width();
+    let text_width = editor.text_width(chunks[1].width as usize);
     let visible_lines: Vec<Line> = editor.buffer[visible_start..visible_end]
         .iter()
-        .map(|line| Line::from(line.as_str()))
+        .enumerate()
+        .flat_map(|(i, line)| {
+            let row = visible_start + i;
+            let base = if editor.diff_view { diff::line_style(line, theme) } else { Style::default() };
+            let marked = match highlight {
+                Some((highlight_row, span)) if highlight_row == row => {
+                    Some((span, Style::default().bg(theme.highlight_bg).fg(theme.highlight_fg)))
+                }
+                _ => editor
+                    .selected_span(row)
+                    .map(|span| (span, Style::default().add_modifier(Modifier::REVERSED))),
+            };
+            let rendered = match marked {
+                Some(((start, end), style)) => render_line(
+                    &[
+                        (&line[..start], base),
+                        (&line[start..end], style),
+                        (&line[end..], base),
+                    ],
+                    editor.options.tab_width,
+                    editor.options.list,
+                    theme,
+                ),
+                None => render_line(&[(line, base)], editor.options.tab_width, editor.options.list, theme),
+            };
+            let screen_rows = if editor.options.wrap { wrap_line(rendered, text_width) } else { vec![rendered] };
+            screen_rows.into_iter().enumerate().map(move |(k, mut screen_row)| {
+                if gutter > 0 {
+                    // Only the first screen row of a wrapped line is numbered
+                    let number = if k == 0 { format!("{:>w$} ", row + 1, w = gutter - 1) } else { " ".repeat(gutter) };
+                    let color = if row == editor.cursor_row { theme.key } else { theme.muted };
+                    screen_row.spans.insert(0, Span::styled(number, Style::default().fg(color)));
+                }
+                screen_row
+            })
+        })
         .collect();
 
     let editor_widget = Paragraph::new(visible_lines)
@@ -654,10 +1163,20 @@ pub fn render_editor(f: &mut Frame, area: Rect, editor: &EditorState) {
     f.render_widget(footer, chunks[2]);
 
     // Set cursor position
-    let cursor_screen_row = editor.cursor_row.saturating_sub(editor.scroll_offset);
-    let cursor_x = chunks[1].x + editor.cursor_col as u16;
-    let cursor_y = chunks[1].y + cursor_screen_row as u16;
-    f.set_cursor_position((cursor_x, cursor_y));
+    let column = editor.cursor_display_col();
+    let (cursor_x, cursor_y) = if editor.options.wrap {
+        let rows_above: usize = (editor.scroll_offset..editor.cursor_row)
+            .map(|row| editor.screen_rows(row, text_width))
+            .sum();
+        (column % text_width, rows_above + column / text_width)
+    } else {
+        (column, editor.cursor_row.saturating_sub(editor.scroll_offset))
+    };
+    f.set_cursor_position((chunks[1].x + (gutter + cursor_x) as u16, chunks[1].y + cursor_y as u16));
+
+    if let Some(help) = &editor.help {
+        help.render(f, area, theme);
+    }
 }
 
 pub fn handle_editor_input(editor: &mut EditorState, viewport_height: usize) -> Result<bool> {
@@ -665,22 +1184,128 @@ pub fn handle_editor_input(editor: &mut EditorState, viewport_height: usize) ->
         return Ok(false);
     }
 
-    if let Event::Key(key) = event::read()? {
-        match editor.mode {
-            EditorMode::Normal => handle_normal_mode(editor, key, viewport_height),
-            EditorMode::Insert => handle_insert_mode(editor, key),
-            EditorMode::Command | EditorMode::Search => handle_command_mode(editor, key),
-        }
+    let event = event::read()?;
+    if let Event::FocusLost = event {
+        editor.focus_lost = true;
+        return Ok(true);
+    }
+    if let Event::Key(key) = event {
+        handle_key(editor, key, viewport_height);
+        return Ok(true);
+    }
+    if let Event::Paste(text) = event {
+        handle_paste(editor, &text);
         return Ok(true);
     }
+    // Keep the cursor on screen in the new viewport; the next draw fits the rest
+    if let Event::Resize(_, rows) = event {
+        editor.update_scroll(rows.saturating_sub(2) as usize);
+    }
 
     Ok(false)
 }
 
+/// Act on a bracketed paste, which arrives as one event
+fn handle_paste(editor: &mut EditorState, text: &str) {
+    match editor.mode {
+        EditorMode::Insert => editor.insert_text(text),
+        // A command line holds one line; stop at the first line break
+        EditorMode::Command | EditorMode::Search => {
+            editor.command_buffer.push_str(text.lines().next().unwrap_or_default());
+        }
+        _ => editor.status_message = String::from("Press i to paste into the file"),
+    }
+}
+
+/// Act on one key press; also used to replay macros
+fn handle_key(editor: &mut EditorState, key: KeyEvent, viewport_height: usize) {
+    if let Some(help) = editor.help.as_mut() {
+        if !help.handle_key(key, viewport_height) {
+            editor.help = None;
+        }
+        return;
+    }
+    editor.record_key(key);
+    if editor.save_conflict.is_some() {
+        editor.answer_save_conflict(key.code);
+        return;
+    }
+    if editor.pending_substitute.is_some() {
+        match key.code {
+            KeyCode::Char(c @ ('y' | 'n' | 'a' | 'q' | 'l')) => editor.answer_substitution(c),
+            KeyCode::Esc => editor.answer_substitution('q'),
+            _ => {}
+        }
+        return;
+    }
+    match editor.mode {
+        EditorMode::Normal => handle_normal_mode(editor, key, viewport_height),
+        EditorMode::Insert => handle_insert_mode(editor, key),
+        EditorMode::Command | EditorMode::Search => handle_command_mode(editor, key),
+        EditorMode::Visual | EditorMode::VisualLine => handle_visual_mode(editor, key, viewport_height),
+    }
+}
+
 fn handle_normal_mode(editor: &mut EditorState, key: KeyEvent, viewport_height: usize) {
+    if let KeyCode::Char(digit @ '0'..='9') = key.code
+        && (digit != '0' || editor.pending_count.is_some())
+        && !matches!(editor.pending_operator, Some(('r', _)))
+    {
+        let count = editor.pending_count.unwrap_or(0);
+        editor.pending_count = Some(count.saturating_mul(10).saturating_add(digit as usize - '0' as usize));
+        return;
+    }
+
+    if editor.read_only
+        && !key.modifiers.contains(KeyModifiers::CONTROL)
+        && editor.pending_operator.is_none()
+        && matches!(
+            key.code,
+            KeyCode::Char('i' | 'a' | 'o' | 'x' | 'p' | 'd' | 'c' | 'r' | '~' | 'A' | 'I' | 'O' | 'P')
+        )
+    {
+        editor.pending_count = None;
+        editor.status_message = String::from("File is read-only (no write permission)");
+        return;
+    }
+
+    // Counts multiply motions, x, p, dd and yy; with G they pick a line
+    let typed_count = editor.pending_count.take();
+    let count = typed_count.unwrap_or(1);
+    let repeat = |editor: &mut EditorState, action: fn(&mut EditorState)| {
+        for _ in 0..count {
+            action(editor);
+        }
+    };
+
+    if let Some((operator, first_count)) = editor.pending_operator.take() {
+        // `2d3d` deletes six lines, like vim
+        let count = first_count.saturating_mul(count);
+        match (operator, key.code) {
+            ('d', KeyCode::Char('d')) => editor.delete_lines(count),
+            ('y', KeyCode::Char('y')) => editor.yank_lines(count),
+            ('g', KeyCode::Char('g')) => editor.goto_line(count),
+            ('c', KeyCode::Char('c')) => editor.change_lines(count),
+            ('c', KeyCode::Char('w')) => editor.change_word(count),
+            ('c', KeyCode::Char('$')) => editor.change_to_line_end(),
+            ('r', KeyCode::Char(replacement)) => editor.replace_chars(replacement, count),
+            ('q', KeyCode::Char(register @ 'a'..='z')) => editor.start_recording(register),
+            ('@', KeyCode::Char(register @ ('a'..='z' | '@'))) => {
+                macros::replay_macro(editor, register, count, viewport_height);
+            }
+            ('m', KeyCode::Char(mark @ 'a'..='z')) => editor.set_mark(mark),
+            ('\'', KeyCode::Char(mark @ 'a'..='z')) => editor.jump_to_mark(mark, true),
+            ('`', KeyCode::Char(mark @ 'a'..='z')) => editor.jump_to_mark(mark, false),
+            // Anything else cancels the pending command
+            _ => {}
+        }
+        return;
+    }
+
     match key.code {
         KeyCode::Char('q') if key.modifiers.contains(KeyModifiers::CONTROL) => {
             editor.should_quit = true;
+            editor.force_quit = true;
         }
         KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
             editor.page_down(viewport_height);
@@ -697,24 +1322,29 @@ fn handle_normal_mode(editor: &mut EditorState, key: KeyEvent, viewport_height:
         KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
             editor.redo();
         }
-        KeyCode::Char('h') | KeyCode::Left => editor.move_cursor_left(),
-        KeyCode::Char('j') | KeyCode::Down => editor.move_cursor_down(),
-        KeyCode::Char('k') | KeyCode::Up => editor.move_cursor_up(),
-        KeyCode::Char('l') | KeyCode::Right => editor.move_cursor_right(),
-        KeyCode::Char('w') => editor.move_word_forward(),
-        KeyCode::Char('b') => editor.move_word_backward(),
-        KeyCode::Char('e') => editor.move_word_end(),
-        KeyCode::Char('W') => editor.move_big_word_forward(),
-        KeyCode::Char('B') => editor.move_big_word_backward(),
-        KeyCode::Char('E') => editor.move_big_word_end(),
+        KeyCode::Char('g') if key.modifiers.contains(KeyModifiers::CONTROL) => editor.show_position(),
+        KeyCode::Char('h') | KeyCode::Left => repeat(editor, EditorState::move_cursor_left),
+        KeyCode::Char('j') | KeyCode::Down => repeat(editor, EditorState::move_cursor_down),
+        KeyCode::Char('k') | KeyCode::Up => repeat(editor, EditorState::move_cursor_up),
+        KeyCode::Char('l') | KeyCode::Right => repeat(editor, EditorState::move_cursor_right),
+        KeyCode::Char('w') => repeat(editor, EditorState::move_word_forward),
+        KeyCode::Char('b') => repeat(editor, EditorState::move_word_backward),
+        KeyCode::Char('e') => repeat(editor, EditorState::move_word_end),
+        KeyCode::Char('W') => repeat(editor, EditorState::move_big_word_forward),
+        KeyCode::Char('B') => repeat(editor, EditorState::move_big_word_backward),
+        KeyCode::Char('E') => repeat(editor, EditorState::move_big_word_end),
         KeyCode::Char('0') => editor.move_to_line_start(),
         KeyCode::Char('$') => editor.move_to_line_end(),
-        KeyCode::Char('g') => {
-            editor.move_to_buffer_start();
-        }
-        KeyCode::Char('G') => {
-            editor.move_to_buffer_end();
-        }
+        KeyCode::Char('q') if editor.recording.is_some() => editor.stop_recording(),
+        KeyCode::Char('~') => editor.toggle_case(count),
+        KeyCode::Char('%') => editor.jump_to_matching_bracket(),
+        KeyCode::Char(operator @ ('d' | 'y' | 'c' | 'r' | 'g' | 'm' | '\'' | '`' | 'q' | '@')) => {
+            editor.pending_operator = Some((operator, count));
+        }
+        KeyCode::Char('G') => match typed_count {
+            Some(line) => editor.goto_line(line),
+            None => editor.move_to_buffer_end(),
+        },
         KeyCode::Char('i') => {
             editor.mode = EditorMode::Insert;
             editor.status_message = String::from("Insert mode");
@@ -730,21 +1360,17 @@ fn handle_normal_mode(editor: &mut EditorState, key: KeyEvent, viewport_height:
             editor.insert_newline();
             editor.status_message = String::from("Insert mode");
         }
-        KeyCode::Char('d') => {
-            editor.delete_line();
-        }
-        KeyCode::Char('y') => {
-            editor.yank_line();
-        }
-        KeyCode::Char('p') => {
-            editor.paste_below();
-        }
-        KeyCode::Char('x') => {
-            editor.delete_char_at_cursor();
-        }
+        KeyCode::Char('A') => editor.append_at_line_end(),
+        KeyCode::Char('I') => editor.insert_at_first_non_blank(),
+        KeyCode::Char('O') => editor.open_line_above(),
+        KeyCode::Char('p') => repeat(editor, EditorState::paste_below),
+        KeyCode::Char('P') => repeat(editor, EditorState::paste_above),
+        KeyCode::Char('x') => editor.delete_chars(count),
         KeyCode::Char('u') => {
             editor.undo();
         }
+        KeyCode::Char('v') => editor.start_visual(EditorMode::Visual),
+        KeyCode::Char('V') => editor.start_visual(EditorMode::VisualLine),
         KeyCode::Char(':') => {
             editor.mode = EditorMode::Command;
             editor.command_buffer.clear();
@@ -753,6 +1379,44 @@ fn handle_normal_mode(editor: &mut EditorState, key: KeyEvent, viewport_height:
             editor.mode = EditorMode::Search;
             editor.command_buffer.clear();
         }
+        KeyCode::Char('?') => editor.help = Some(HelpOverlay::new(help::sections())),
+        _ => {}
+    }
+}
+
+fn handle_visual_mode(editor: &mut EditorState, key: KeyEvent, viewport_height: usize) {
+    match key.code {
+        KeyCode::Esc => editor.exit_visual(),
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => editor.exit_visual(),
+        KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            editor.page_down(viewport_height);
+        }
+        KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            editor.page_up(viewport_height);
+        }
+        KeyCode::Char('v') if editor.mode == EditorMode::Visual => editor.exit_visual(),
+        KeyCode::Char('V') if editor.mode == EditorMode::VisualLine => editor.exit_visual(),
+        KeyCode::Char('v') => editor.mode = EditorMode::Visual,
+        KeyCode::Char('V') => editor.mode = EditorMode::VisualLine,
+        KeyCode::Char('h') | KeyCode::Left => editor.move_cursor_left(),
+        KeyCode::Char('j') | KeyCode::Down => editor.move_cursor_down(),
+        KeyCode::Char('k') | KeyCode::Up => editor.move_cursor_up(),
+        KeyCode::Char('l') | KeyCode::Right => editor.move_cursor_right(),
+        KeyCode::Char('w') => editor.move_word_forward(),
+        KeyCode::Char('b') => editor.move_word_backward(),
+        KeyCode::Char('e') => editor.move_word_end(),
+        KeyCode::Char('0') => editor.move_to_line_start(),
+        KeyCode::Char('$') => editor.move_to_line_end(),
+        KeyCode::Char('g') => editor.move_to_buffer_start(),
+        KeyCode::Char('G') => editor.move_to_buffer_end(),
+        KeyCode::Char('%') => editor.jump_to_matching_bracket(),
+        KeyCode::Char('y') => editor.yank_selection(),
+        KeyCode::Char('d' | 'x' | '>' | '<') if editor.read_only => {
+            editor.status_message = String::from("File is read-only (no write permission)");
+        }
+        KeyCode::Char('d') | KeyCode::Char('x') => editor.delete_selection(),
+        KeyCode::Char('>') => editor.shift_selection(true),
+        KeyCode::Char('<') => editor.shift_selection(false),
         _ => {}
     }
 }
@@ -775,6 +1439,7 @@ fn handle_insert_mode(editor: &mut EditorState, key: KeyEvent) {
         KeyCode::Enter => {
             editor.insert_newline();
         }
+        KeyCode::Tab => editor.insert_tab(),
         KeyCode::Left => editor.move_cursor_left(),
         KeyCode::Right => editor.move_cursor_right(),
         KeyCode::Up => editor.move_cursor_up(),
@@ -809,6 +1474,7 @@ fn handle_command_mode(editor: &mut EditorState, key: KeyEvent) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ratatui::style::Color;
 
     fn create_test_editor() -> EditorState {
         let content = "line 1\nline 2\nline 3".to_string();
@@ -1051,7 +1717,7 @@ mod tests {
         let mut editor = create_test_editor();
         editor.cursor_col = 2; // At 'n' in "line 1"
 
-        editor.delete_char_at_cursor();
+        editor.delete_chars(1);
         assert_eq!(editor.buffer[0], "lie 1");
         assert_eq!(editor.cursor_col, 2); // Cursor stays at same position
         assert!(editor.modified);
@@ -1062,7 +1728,7 @@ mod tests {
         let mut editor = create_test_editor();
         editor.cursor_col = 5; // At '1' (last char in "line 1")
 
-        editor.delete_char_at_cursor();
+        editor.delete_chars(1);
         assert_eq!(editor.buffer[0], "line ");
         assert_eq!(editor.cursor_col, 4); // Cursor clamped to new end
     }
@@ -1073,7 +1739,7 @@ mod tests {
         editor.cursor_col = 10; // Beyond line length
 
         let original = editor.buffer[0].clone();
-        editor.delete_char_at_cursor();
+        editor.delete_chars(1);
         assert_eq!(editor.buffer[0], original); // No change
         assert!(!editor.modified); // Not modified
     }
@@ -1082,7 +1748,7 @@ mod tests {
     fn test_delete_char_at_cursor_empty_line() {
         let mut editor = create_empty_editor();
 
-        editor.delete_char_at_cursor();
+        editor.delete_chars(1);
         assert_eq!(editor.buffer[0], ""); // Still empty
         assert!(!editor.modified); // Not modified
     }
@@ -1093,17 +1759,17 @@ mod tests {
         editor.cursor_col = 0; // At 'l' in "line 1"
 
         // Delete 'l'
-        editor.delete_char_at_cursor();
+        editor.delete_chars(1);
         assert_eq!(editor.buffer[0], "ine 1");
         assert_eq!(editor.cursor_col, 0);
 
         // Delete 'i'
-        editor.delete_char_at_cursor();
+        editor.delete_chars(1);
         assert_eq!(editor.buffer[0], "ne 1");
         assert_eq!(editor.cursor_col, 0);
 
         // Delete 'n'
-        editor.delete_char_at_cursor();
+        editor.delete_chars(1);
         assert_eq!(editor.buffer[0], "e 1");
         assert_eq!(editor.cursor_col, 0);
     }
@@ -1115,7 +1781,7 @@ mod tests {
         let mut editor = create_test_editor();
         editor.cursor_row = 1;
 
-        editor.delete_line();
+        editor.delete_lines(1);
         assert_eq!(editor.buffer.len(), 2);
         assert_eq!(editor.buffer[0], "line 1");
         assert_eq!(editor.buffer[1], "line 3");
@@ -1128,7 +1794,7 @@ mod tests {
         let mut editor = create_test_editor();
         editor.cursor_row = 2;
 
-        editor.delete_line();
+        editor.delete_lines(1);
         assert_eq!(editor.buffer.len(), 2);
         assert_eq!(editor.cursor_row, 1); // Should move up
     }
@@ -1138,7 +1804,7 @@ mod tests {
         let mut editor = create_empty_editor();
         editor.buffer[0] = "test".to_string();
 
-        editor.delete_line();
+        editor.delete_lines(1);
         assert_eq!(editor.buffer.len(), 1);
         assert_eq!(editor.buffer[0], "");
         assert_eq!(editor.yank_register, vec!["test"]);
@@ -1149,7 +1815,7 @@ mod tests {
         let mut editor = create_test_editor();
         editor.cursor_row = 1;
 
-        editor.yank_line();
+        editor.yank_lines(1);
         assert_eq!(editor.yank_register, vec!["line 2"]);
         assert!(!editor.modified); // Yank doesn't modify
     }
@@ -1292,6 +1958,18 @@ mod tests {
         assert!(editor.scroll_offset <= editor.cursor_row);
     }
 
+    #[test]
+    fn test_scroll_keeps_cursor_visible_in_a_tiny_viewport() {
+        let mut editor = create_test_editor();
+        editor.cursor_row = 10;
+
+        for height in [1, 2, 3] {
+            editor.update_scroll(height);
+            assert!(editor.cursor_row >= editor.scroll_offset);
+            assert!(editor.cursor_row < editor.scroll_offset + height);
+        }
+    }
+
     // ===== Edge Cases =====
 
     #[test]
@@ -1371,7 +2049,7 @@ mod tests {
 
         // Delete line 2 (cursor at row 0, move down once)
         editor.move_cursor_down();
-        editor.delete_line();
+        editor.delete_lines(1);
 
         // Paste it at the end
         editor.move_to_buffer_end();
@@ -1423,4 +2101,266 @@ mod tests {
         assert_eq!(editor.buffer[2], "second");
         assert_eq!(editor.buffer[3], "third");
     }
+
+    /// Type `keys` in whatever mode the editor is in; `\u{1b}` is Esc
+    pub(super) fn press(editor: &mut EditorState, keys: &str) {
+        for c in keys.chars() {
+            let code = if c == '\u{1b}' { KeyCode::Esc } else { KeyCode::Char(c) };
+            handle_key(editor, KeyEvent::new(code, KeyModifiers::NONE), 20);
+        }
+    }
+
+    #[test]
+    fn test_counts_and_two_key_commands() {
+        let mut editor = EditorState::new(
+            "test.txt".to_string(),
+            "/tmp/test.txt".to_string(),
+            "one\ntwo\nthree\nfour\nfive".to_string(),
+        );
+
+        press(&mut editor, "2j");
+        assert_eq!(editor.cursor_row, 2);
+
+        // A single d waits for the second key; another key cancels it
+        press(&mut editor, "d");
+        assert_eq!(editor.buffer.len(), 5);
+        press(&mut editor, "k");
+        assert_eq!((editor.buffer.len(), editor.cursor_row), (5, 2));
+        press(&mut editor, "2dd");
+        assert_eq!(editor.buffer, ["one", "two", "five"]);
+        assert_eq!(editor.yank_register, ["three", "four"]);
+
+        press(&mut editor, "gg2x");
+        assert_eq!(editor.buffer[0], "e");
+        press(&mut editor, "j10yy");
+        assert_eq!(editor.yank_register, ["two", "five"]);
+    }
+
+    #[test]
+    fn test_tab_columns() {
+        assert_eq!(display_column("\tab", 1, 4), 4);
+        assert_eq!(display_column("ab\tc", 3, 4), 4);
+        assert_eq!(display_column("ab\tc", 3, 8), 8);
+        assert_eq!(column_to_index("\tx", 2, 4), 0);
+        assert_eq!(column_to_index("\tx", 4, 4), 1);
+        assert_eq!(column_to_index("\tx", 9, 4), 2);
+        assert_eq!(render_line(&[("a\tb", Style::default())], 4, false, &Theme::dark()).to_string(), "a   b");
+    }
+
+    #[test]
+    fn test_insert_tab_and_vertical_moves_keep_screen_column() {
+        let mut editor = EditorState::new(
+            "test.txt".to_string(),
+            "/tmp/test.txt".to_string(),
+            "ab\n\tx = 1\n        y".to_string(),
+        );
+        editor.mode = EditorMode::Insert;
+        editor.insert_tab();
+        assert_eq!(editor.buffer[0], "    ab");
+        editor.cursor_col = 5;
+        editor.insert_tab();
+        assert_eq!(editor.buffer[0], "    a   b");
+
+        editor.options.expand_tab = false;
+        editor.cursor_col = 0;
+        editor.insert_tab();
+        assert_eq!(editor.buffer[0], "\t    a   b");
+
+        // Row 1 starts with a tab, so column 4 on row 2 lands after it
+        editor.mode = EditorMode::Normal;
+        editor.cursor_row = 2;
+        editor.cursor_col = 4;
+        editor.move_cursor_up();
+        assert_eq!((editor.cursor_col, editor.cursor_display_col()), (1, 4));
+        editor.move_cursor_down();
+        assert_eq!(editor.cursor_col, 4);
+
+        editor.execute_command("set ts=8");
+        editor.execute_command("set noet");
+        assert_eq!((editor.options.tab_width, editor.options.expand_tab), (8, false));
+        assert_eq!(editor.status_message, "tabstop=8 noexpandtab");
+    }
+
+    #[test]
+    fn test_paste_is_one_insertion() {
+        let mut editor = EditorState::new("a.txt".to_string(), "/tmp/a.txt".to_string(), "start end".to_string());
+        press(&mut editor, "wi");
+        handle_paste(&mut editor, "one\r\ntwo\rthree ");
+        assert_eq!(editor.buffer, ["start one", "two", "three end"]);
+        assert_eq!((editor.cursor_row, editor.cursor_col), (2, 6));
+        assert_eq!(editor.status_message, "3 lines pasted");
+
+        // The whole paste undoes at once
+        press(&mut editor, "\u{1b}u");
+        assert_eq!(editor.buffer, ["start end"]);
+
+        press(&mut editor, ":");
+        handle_paste(&mut editor, "set nu\nignored");
+        assert_eq!(editor.command_buffer, "set nu");
+    }
+
+    #[test]
+    fn test_number_and_wrap_layout() {
+        let content = (1..=120).map(|i| format!("line {}", i)).collect::<Vec<_>>().join("\n");
+        let mut editor = EditorState::new("big.txt".to_string(), "/tmp/big.txt".to_string(), content);
+        assert_eq!(editor.gutter_width(), 0);
+        editor.execute_command("set number");
+        assert_eq!(editor.gutter_width(), 4);
+        editor.execute_command("set");
+        assert_eq!(editor.status_message, "tabstop=4 expandtab nolist number nowrap");
+
+        let rows = wrap_line(Line::from("abcdefghij"), 4);
+        assert_eq!(rows.iter().map(|row| row.to_string()).collect::<Vec<_>>(), ["abcd", "efgh", "ij"]);
+
+        // "line N" is 6-8 columns, so each line takes two rows at width 5
+        editor.execute_command("set wrap");
+        editor.cursor_row = 5;
+        editor.update_scroll(10);
+        editor.fit_wrapped_lines(10, 9);
+        assert_eq!(editor.scroll_offset, 1);
+        editor.execute_command("set nowrap");
+        editor.cursor_row = 3;
+        editor.update_scroll(10);
+        editor.fit_wrapped_lines(10, 9);
+        assert_eq!(editor.scroll_offset, 0);
+    }
+
+    #[test]
+    fn test_goto_line() {
+        let mut editor = EditorState::new(
+            "nginx.conf".to_string(),
+            "/etc/nginx/nginx.conf".to_string(),
+            "events {}\nhttp {\n    server {}\n}".to_string(),
+        );
+
+        editor.execute_command("3");
+        assert_eq!((editor.cursor_row, editor.cursor_col), (2, 4));
+        editor.execute_command("99");
+        assert_eq!(editor.cursor_row, 3);
+
+        press(&mut editor, "2G");
+        assert_eq!(editor.cursor_row, 1);
+        press(&mut editor, "G");
+        assert_eq!(editor.cursor_row, 3);
+        press(&mut editor, "3gg");
+        assert_eq!(editor.cursor_row, 2);
+
+        handle_normal_mode(&mut editor, KeyEvent::new(KeyCode::Char('g'), KeyModifiers::CONTROL), 20);
+        assert_eq!(editor.status_message, "\"nginx.conf\" line 3 of 4 (75%)");
+    }
+
+    #[test]
+    fn test_buffer_commands_request_the_caller() {
+        let mut editor = EditorState::new(
+            "nginx.conf".to_string(),
+            "/etc/nginx/nginx.conf".to_string(),
+            String::new(),
+        );
+
+        editor.execute_command("e conf.d/../sites/default");
+        assert_eq!(editor.buffer_request, Some(BufferRequest::Open("/etc/nginx/sites/default".to_string())));
+        editor.execute_command("e /etc/hosts");
+        assert_eq!(editor.buffer_request, Some(BufferRequest::Open("/etc/hosts".to_string())));
+        editor.execute_command("bp");
+        assert_eq!(editor.buffer_request, Some(BufferRequest::Previous));
+        editor.execute_command("diff");
+        assert_eq!(editor.buffer_request, Some(BufferRequest::Diff));
+
+        editor.buffer_request = None;
+        editor.execute_command("e");
+        assert_eq!(editor.status_message, "No file name");
+        assert!(editor.buffer_request.is_none());
+    }
+
+    #[test]
+    fn test_backup_path() {
+        let now = chrono::Local::now();
+        assert_eq!(backup_path("/etc/ssh/sshd_config", BackupMode::Off, now), None);
+        assert_eq!(
+            backup_path("/etc/ssh/sshd_config", BackupMode::Tilde, now).as_deref(),
+            Some("/etc/ssh/sshd_config~")
+        );
+        let stamped = backup_path("/etc/ssh/sshd_config", BackupMode::Timestamp, now).unwrap();
+        assert_eq!(stamped, format!("/etc/ssh/sshd_config.bak-{}", now.format("%Y%m%d-%H%M%S")));
+    }
+
+    #[test]
+    fn test_read_only_refuses_edits() {
+        let mut editor = create_test_editor();
+        editor.read_only = true;
+        let before = editor.buffer.clone();
+
+        press(&mut editor, "ddxp");
+        assert_eq!(editor.mode, EditorMode::Normal);
+        press(&mut editor, "i");
+        assert_eq!(editor.mode, EditorMode::Normal);
+        assert_eq!(editor.status_message, "File is read-only (no write permission)");
+
+        press(&mut editor, "jyy");
+        assert_eq!(editor.cursor_row, 1);
+        assert_eq!(editor.yank_register.len(), 1);
+
+        editor.execute_command("%s/a/b/g");
+        editor.execute_command("w");
+        assert_eq!(editor.buffer, before);
+        assert!(!editor.modified);
+        assert_eq!(editor.status_message, "File is read-only (no write permission)");
+    }
+
+    #[test]
+    fn test_list_shows_whitespace() {
+        let line = render_line(&[("\tkey: a\u{a0}b  ", Style::default())], 4, true, &Theme::dark());
+        assert_eq!(line.to_string(), "→   key: a⍽b··");
+        let trailing = line.spans.last().unwrap();
+        assert_eq!((trailing.content.as_ref(), trailing.style.bg), ("··", Some(Color::Red)));
+        assert_eq!(line.spans[0].style.fg, Some(Color::DarkGray));
+    }
+
+    #[test]
+    fn test_marks() {
+        let mut editor = EditorState::new(
+            "test.txt".to_string(),
+            "/tmp/test.txt".to_string(),
+            "one\n  two\nthree\nfour".to_string(),
+        );
+
+        press(&mut editor, "jllma");
+        press(&mut editor, "Gmb");
+        press(&mut editor, "'a");
+        assert_eq!((editor.cursor_row, editor.cursor_col), (1, 2));
+        press(&mut editor, "`b");
+        assert_eq!((editor.cursor_row, editor.cursor_col), (3, 3));
+        editor.cursor_row = 1;
+        editor.cursor_col = 4;
+        press(&mut editor, "gg`a");
+        assert_eq!((editor.cursor_row, editor.cursor_col), (1, 2));
+
+        press(&mut editor, "'z");
+        assert_eq!(editor.status_message, "Mark not set: z");
+    }
+
+    #[test]
+    fn test_insert_entry_and_paste_above() {
+        let mut editor = EditorState::new(
+            "test.txt".to_string(),
+            "/tmp/test.txt".to_string(),
+            "    listen 80\nroot /srv".to_string(),
+        );
+
+        press(&mut editor, "A;\u{1b}");
+        assert_eq!(editor.buffer[0], "    listen 80;");
+        press(&mut editor, "I# \u{1b}");
+        assert_eq!(editor.buffer[0], "    # listen 80;");
+        press(&mut editor, "jOindex\u{1b}");
+        assert_eq!(editor.buffer, ["    # listen 80;", "index", "root /srv"]);
+        assert_eq!(editor.cursor_row, 1);
+
+        press(&mut editor, "yyjP");
+        assert_eq!(editor.buffer, ["    # listen 80;", "index", "index", "root /srv"]);
+        assert_eq!(editor.cursor_row, 2);
+
+        // Character-wise P inserts before the cursor
+        press(&mut editor, "jvly$P");
+        assert_eq!(editor.buffer[3], "root /sr /v");
+    }
 }
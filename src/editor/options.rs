@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+
+/// Editor settings changed with `:set`. The defaults come from the config
+/// file, where they sit alongside the other keys.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct EditorOptions {
+    /// Columns between tab stops, also the `>`/`<` indent (`tabstop`)
+    pub tab_width: usize,
+    /// Tab inserts spaces up to the next tab stop (`expandtab`)
+    pub expand_tab: bool,
+    /// Show tabs, non-breaking spaces and trailing whitespace (`list`)
+    #[serde(rename = "show_whitespace")]
+    pub list: bool,
+    /// Line numbers in a gutter (`number`)
+    #[serde(rename = "line_numbers")]
+    pub number: bool,
+    /// Wrap long lines onto further screen rows instead of cutting them off (`wrap`)
+    #[serde(rename = "wrap_lines")]
+    pub wrap: bool,
+}
+
+impl Default for EditorOptions {
+    fn default() -> Self {
+        Self {
+            tab_width: 4,
+            expand_tab: true,
+            list: false,
+            number: false,
+            wrap: false,
+        }
+    }
+}
+
+impl EditorOptions {
+    /// Apply one `:set` argument: `tabstop=N`, or a flag name to turn it on
+    /// and `no` + name to turn it off. Returns the status line message.
+    pub fn set(&mut self, option: &str) -> Result<String, String> {
+        if let Some((name, value)) = option.split_once('=') {
+            if !matches!(name, "tabstop" | "ts") {
+                return Err(format!("Unknown option: {}", option));
+            }
+            match value.parse::<usize>() {
+                Ok(width) if width > 0 => self.tab_width = width,
+                _ => return Err(format!("Invalid tab width: {}", value)),
+            }
+            return Ok(self.tab_summary());
+        }
+
+        let (name, on) = match option.strip_prefix("no") {
+            Some(name) => (name, false),
+            None => (option, true),
+        };
+        let flag = match name {
+            "expandtab" | "et" => &mut self.expand_tab,
+            "list" => &mut self.list,
+            "number" | "nu" => &mut self.number,
+            "wrap" => &mut self.wrap,
+            _ => return Err(format!("Unknown option: {}", option)),
+        };
+        *flag = on;
+        Ok(match name {
+            "expandtab" | "et" => self.tab_summary(),
+            _ => option.to_string(),
+        })
+    }
+
+    fn tab_summary(&self) -> String {
+        format!("tabstop={} {}", self.tab_width, flag_name("expandtab", self.expand_tab))
+    }
+
+    /// Every option as `:set` would name it, for a bare `:set`
+    pub fn summary(&self) -> String {
+        format!(
+            "{} {} {} {}",
+            self.tab_summary(),
+            flag_name("list", self.list),
+            flag_name("number", self.number),
+            flag_name("wrap", self.wrap)
+        )
+    }
+}
+
+fn flag_name(name: &str, on: bool) -> String {
+    if on { name.to_string() } else { format!("no{}", name) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_flags_and_tabstop() {
+        let mut options = EditorOptions::default();
+        assert_eq!(options.set("nu"), Ok(String::from("nu")));
+        assert_eq!(options.set("wrap"), Ok(String::from("wrap")));
+        assert_eq!(options.set("nolist"), Ok(String::from("nolist")));
+        assert!(options.number && options.wrap && !options.list);
+
+        assert_eq!(options.set("ts=0"), Err(String::from("Invalid tab width: 0")));
+        assert_eq!(options.set("sw=2"), Err(String::from("Unknown option: sw=2")));
+        assert_eq!(options.set("nofoo"), Err(String::from("Unknown option: nofoo")));
+        assert_eq!(options.summary(), "tabstop=4 expandtab nolist number wrap");
+    }
+
+    #[test]
+    fn test_defaults_read_from_config_keys() {
+        let options: EditorOptions = serde_json::from_str(r#"{"line_numbers": true, "tab_width": 2}"#).unwrap();
+        assert_eq!((options.tab_width, options.number, options.wrap), (2, true, false));
+    }
+}
@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+
+use super::EditorState;
+
+/// Most files whose cursor position is remembered
+const POSITIONS_LIMIT: usize = 100;
+
+/// Where the cursor was in a file when the editor closed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FilePosition {
+    pub path: String,
+    /// 0-based, as in the editor
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Cursor positions of recently edited files, like vim's viminfo marks;
+/// least recently closed first
+#[derive(Debug, Default)]
+pub struct FilePositions {
+    positions: Vec<FilePosition>,
+}
+
+impl FilePositions {
+    pub fn restore(positions: Vec<FilePosition>) -> Self {
+        let excess = positions.len().saturating_sub(POSITIONS_LIMIT);
+        Self {
+            positions: positions.into_iter().skip(excess).collect(),
+        }
+    }
+
+    pub fn positions(&self) -> &[FilePosition] {
+        &self.positions
+    }
+
+    /// Keep where the cursor is in `editor`'s file
+    pub fn remember(&mut self, editor: &EditorState) {
+        if editor.diff_view {
+            return;
+        }
+        self.positions.retain(|position| position.path != editor.remote_path);
+        self.positions.push(FilePosition {
+            path: editor.remote_path.clone(),
+            line: editor.cursor_row,
+            column: editor.cursor_col,
+        });
+        let excess = self.positions.len().saturating_sub(POSITIONS_LIMIT);
+        self.positions.drain(..excess);
+    }
+
+    /// Put the cursor of a freshly opened buffer back where it was, within
+    /// what is left of the file
+    pub fn restore_cursor(&self, editor: &mut EditorState) {
+        if let Some(position) = self.positions.iter().find(|position| position.path == editor.remote_path) {
+            editor.cursor_row = position.line;
+            editor.cursor_col = position.column;
+            editor.clamp_cursor();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_is_restored_within_the_file() {
+        let mut positions = FilePositions::default();
+        let mut editor = EditorState::new(
+            String::from("nginx.conf"),
+            String::from("/etc/nginx/nginx.conf"),
+            String::from("user www;\nworker_processes 4;\nevents {}"),
+        );
+        editor.cursor_row = 1;
+        editor.cursor_col = 7;
+        positions.remember(&editor);
+
+        let mut reopened = EditorState::new(editor.filename.clone(), editor.remote_path.clone(), editor.buffer.join("\n"));
+        positions.restore_cursor(&mut reopened);
+        assert_eq!((reopened.cursor_row, reopened.cursor_col), (1, 7));
+
+        // The file got shorter meanwhile
+        let mut shortened = EditorState::new(editor.filename.clone(), editor.remote_path.clone(), String::from("user"));
+        positions.restore_cursor(&mut shortened);
+        assert_eq!((shortened.cursor_row, shortened.cursor_col), (0, 3));
+    }
+}
@@ -0,0 +1,337 @@
+use regex::{Regex, RegexBuilder};
+
+use super::EditorState;
+
+/// A parsed `:s` command
+#[derive(Debug, Clone)]
+pub struct Substitute {
+    /// First and last line of the range, 0-based and inclusive
+    pub start: usize,
+    pub end: usize,
+    pub regex: Regex,
+    /// Replacement in `regex` expansion syntax (`${1}`, `${0}`)
+    pub replacement: String,
+    /// Replace every match on a line, not just the first
+    pub global: bool,
+    /// Ask before each replacement
+    pub confirm: bool,
+}
+
+/// A confirm-each substitution in progress
+#[derive(Debug, Clone)]
+pub struct PendingSubstitute {
+    pub substitute: Substitute,
+    /// Where the next match search starts
+    pub row: usize,
+    pub col: usize,
+    /// Byte range of the match awaiting an answer, on `row`
+    pub current: Option<(usize, usize)>,
+    pub replaced: usize,
+}
+
+/// Parse `[range]s/pattern/replacement/[flags]`. Returns None if `command`
+/// is not a substitution at all. Ranges are `%`, `N`, `N,M`, `.` and `$`;
+/// flags are `g` (all matches), `c` (confirm each) and `i` (ignore case).
+pub fn parse_substitute(command: &str, cursor_row: usize, line_count: usize) -> Option<Result<Substitute, String>> {
+    let split = command.find('s')?;
+    let (range, rest) = command.split_at(split);
+    let delimiter = rest[1..].chars().next()?;
+    if delimiter.is_alphanumeric() || delimiter == '\\' || delimiter.is_whitespace() {
+        return None;
+    }
+    let range = parse_range(range, cursor_row, line_count)?;
+    Some(build(range, &rest[1 + delimiter.len_utf8()..], delimiter))
+}
+
+fn parse_range(range: &str, cursor_row: usize, line_count: usize) -> Option<(usize, usize)> {
+    let last = line_count.saturating_sub(1);
+    let line = |spec: &str| -> Option<usize> {
+        match spec {
+            "." => Some(cursor_row),
+            "$" => Some(last),
+            n => n.parse::<usize>().ok().map(|n| n.saturating_sub(1).min(last)),
+        }
+    };
+
+    let (start, end) = match range {
+        "" => (cursor_row, cursor_row),
+        "%" => (0, last),
+        _ => match range.split_once(',') {
+            Some((start, end)) => (line(start)?, line(end)?),
+            None => (line(range)?, line(range)?),
+        },
+    };
+    Some((start.min(end), start.max(end)))
+}
+
+fn build((start, end): (usize, usize), body: &str, delimiter: char) -> Result<Substitute, String> {
+    let parts = split_unescaped(body, delimiter);
+    let pattern = parts.first().cloned().unwrap_or_default();
+    if pattern.is_empty() {
+        return Err("Empty search pattern".to_string());
+    }
+    let replacement = parts.get(1).cloned().unwrap_or_default();
+    let flags = parts.get(2).cloned().unwrap_or_default();
+    if let Some(flag) = flags.chars().find(|c| !"gci".contains(*c)) {
+        return Err(format!("Unknown flag: {}", flag));
+    }
+
+    let regex = RegexBuilder::new(&pattern)
+        .case_insensitive(flags.contains('i'))
+        .build()
+        .map_err(|e| format!("Invalid pattern: {}", e))?;
+
+    Ok(Substitute {
+        start,
+        end,
+        regex,
+        replacement: expansion(&replacement),
+        global: flags.contains('g'),
+        confirm: flags.contains('c'),
+    })
+}
+
+/// Split on `delimiter`, turning `\<delimiter>` into a literal delimiter
+fn split_unescaped(body: &str, delimiter: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(next) if next == delimiter => parts.last_mut().unwrap().push(next),
+                Some(next) => {
+                    let part = parts.last_mut().unwrap();
+                    part.push('\\');
+                    part.push(next);
+                }
+                None => parts.last_mut().unwrap().push('\\'),
+            },
+            c if c == delimiter => parts.push(String::new()),
+            c => parts.last_mut().unwrap().push(c),
+        }
+    }
+    parts
+}
+
+/// Convert a vim replacement (`&`, `\1`, `\&`) to `regex` expansion syntax
+fn expansion(replacement: &str) -> String {
+    let mut expanded = String::new();
+    let mut chars = replacement.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '&' => expanded.push_str("${0}"),
+            '$' => expanded.push_str("$$"),
+            '\\' => match chars.next() {
+                Some(digit @ '0'..='9') => expanded.push_str(&format!("${{{}}}", digit)),
+                Some('t') => expanded.push('\t'),
+                Some('$') => expanded.push_str("$$"),
+                Some(other) => expanded.push(other),
+                None => expanded.push('\\'),
+            },
+            c => expanded.push(c),
+        }
+    }
+    expanded
+}
+
+impl EditorState {
+    /// Run a parsed `:s` command, either at once or by starting a confirm-each prompt
+    pub fn substitute(&mut self, substitute: Substitute) {
+        let mut pending = PendingSubstitute {
+            row: substitute.start,
+            col: 0,
+            substitute,
+            current: None,
+            replaced: 0,
+        };
+
+        if pending.substitute.confirm {
+            if self.find_next_substitution(&mut pending) {
+                self.pending_substitute = Some(pending);
+                self.status_message = String::from("Replace? (y/n/a/q/l)");
+            } else {
+                self.status_message = format!("Pattern not found: {}", pending.substitute.regex);
+            }
+            return;
+        }
+
+        self.substitute_remaining(&mut pending);
+        self.finish_substitution(&pending);
+    }
+
+    /// Answer the confirm prompt for the highlighted match
+    pub fn answer_substitution(&mut self, answer: char) {
+        let Some(mut pending) = self.pending_substitute.take() else {
+            return;
+        };
+
+        match answer {
+            'y' | 'l' => {
+                self.replace_current(&mut pending);
+                if answer == 'l' {
+                    return self.finish_substitution(&pending);
+                }
+            }
+            'n' => {
+                if let Some((start, end)) = pending.current.take() {
+                    pending.col = self.after_match(pending.row, start, end);
+                    if !pending.substitute.global {
+                        pending.row += 1;
+                        pending.col = 0;
+                    }
+                }
+            }
+            'a' => {
+                self.replace_current(&mut pending);
+                self.substitute_remaining(&mut pending);
+                return self.finish_substitution(&pending);
+            }
+            _ => return self.finish_substitution(&pending),
+        }
+
+        if self.find_next_substitution(&mut pending) {
+            self.pending_substitute = Some(pending);
+        } else {
+            self.finish_substitution(&pending);
+        }
+    }
+
+    /// Move to the next match at or after the search position and highlight it
+    fn find_next_substitution(&mut self, pending: &mut PendingSubstitute) -> bool {
+        let end = pending.substitute.end.min(self.buffer.len().saturating_sub(1));
+        while pending.row <= end {
+            let line = &self.buffer[pending.row];
+            if pending.col <= line.len()
+                && let Some(found) = pending.substitute.regex.find_at(line, pending.col)
+            {
+                pending.current = Some((found.start(), found.end()));
+                self.cursor_row = pending.row;
+                self.cursor_col = found.start();
+                self.clamp_cursor();
+                return true;
+            }
+            pending.row += 1;
+            pending.col = 0;
+        }
+        pending.current = None;
+        false
+    }
+
+    /// Replace the highlighted match and continue after it
+    fn replace_current(&mut self, pending: &mut PendingSubstitute) {
+        let Some((start, _)) = pending.current.take() else {
+            return;
+        };
+        if pending.replaced == 0 {
+            self.save_undo_state();
+        }
+
+        let line = &self.buffer[pending.row];
+        let Some(captures) = pending.substitute.regex.captures_at(line, start) else {
+            return;
+        };
+        let found = captures.get(0).unwrap();
+        let mut replacement = String::new();
+        captures.expand(&pending.substitute.replacement, &mut replacement);
+        let (found_start, found_end) = (found.start(), found.end());
+
+        self.buffer[pending.row].replace_range(found_start..found_end, &replacement);
+        self.modified = true;
+        pending.replaced += 1;
+
+        if pending.substitute.global {
+            pending.col = self.after_match(pending.row, found_start, found_start + replacement.len());
+        } else {
+            pending.row += 1;
+            pending.col = 0;
+        }
+    }
+
+    /// Search position after a match; empty matches advance one character
+    fn after_match(&self, row: usize, start: usize, end: usize) -> usize {
+        if end > start {
+            return end;
+        }
+        let line = &self.buffer[row];
+        line[end..].chars().next().map_or(line.len() + 1, |c| end + c.len_utf8())
+    }
+
+    fn substitute_remaining(&mut self, pending: &mut PendingSubstitute) {
+        while self.find_next_substitution(pending) {
+            self.replace_current(pending);
+        }
+    }
+
+    fn finish_substitution(&mut self, pending: &PendingSubstitute) {
+        self.status_message = match pending.replaced {
+            0 if !pending.substitute.confirm => format!("Pattern not found: {}", pending.substitute.regex),
+            1 => String::from("1 substitution"),
+            n => format!("{} substitutions", n),
+        };
+        self.clamp_cursor();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor(content: &str) -> EditorState {
+        EditorState::new("test.conf".to_string(), "/tmp/test.conf".to_string(), content.to_string())
+    }
+
+    #[test]
+    fn test_parse_substitute_ranges_and_flags() {
+        let sub = parse_substitute("%s/foo/bar/g", 4, 10).unwrap().unwrap();
+        assert_eq!((sub.start, sub.end, sub.global, sub.confirm), (0, 9, true, false));
+
+        let sub = parse_substitute("10,20s#a/b#c#c", 0, 15).unwrap().unwrap();
+        assert_eq!((sub.start, sub.end, sub.confirm), (9, 14, true));
+        assert_eq!(sub.regex.as_str(), "a/b");
+
+        let sub = parse_substitute("s/x/y/", 3, 10).unwrap().unwrap();
+        assert_eq!((sub.start, sub.end), (3, 3));
+
+        assert!(parse_substitute("set", 0, 1).is_none());
+        assert!(parse_substitute("wq", 0, 1).is_none());
+        assert!(parse_substitute("s/(/x/", 0, 1).unwrap().is_err());
+    }
+
+    #[test]
+    fn test_substitute_whole_buffer() {
+        let mut editor = editor("listen 80;\nlisten 80 80;\nroot /srv;");
+        let sub = parse_substitute("%s/80/8080/g", 0, 3).unwrap().unwrap();
+        editor.substitute(sub);
+        assert_eq!(editor.buffer, ["listen 8080;", "listen 8080 8080;", "root /srv;"]);
+        assert_eq!(editor.status_message, "3 substitutions");
+
+        editor.undo();
+        assert_eq!(editor.buffer[1], "listen 80 80;");
+    }
+
+    #[test]
+    fn test_substitute_first_match_with_groups() {
+        let mut editor = editor("a=1 b=2\nc=3");
+        let sub = parse_substitute(r"%s/(\w)=(\d)/\2=\1 [&]/", 0, 2).unwrap().unwrap();
+        editor.substitute(sub);
+        assert_eq!(editor.buffer, ["1=a [a=1] b=2", "3=c [c=3]"]);
+    }
+
+    #[test]
+    fn test_substitute_confirm_each() {
+        let mut editor = editor("foo foo\nfoo");
+        let sub = parse_substitute("%s/foo/bar/gc", 0, 2).unwrap().unwrap();
+        editor.substitute(sub);
+        assert_eq!(editor.pending_substitute.as_ref().unwrap().current, Some((0, 3)));
+
+        editor.answer_substitution('n');
+        editor.answer_substitution('y');
+        assert_eq!(editor.buffer[0], "foo bar");
+        assert_eq!((editor.cursor_row, editor.cursor_col), (1, 0));
+
+        editor.answer_substitution('q');
+        assert!(editor.pending_substitute.is_none());
+        assert_eq!(editor.buffer[1], "foo");
+        assert_eq!(editor.status_message, "1 substitution");
+    }
+}
@@ -0,0 +1,215 @@
+use super::{EditorMode, EditorState};
+
+/// Largest char boundary at or below `index`
+fn char_floor(line: &str, index: usize) -> usize {
+    let mut index = index.min(line.len());
+    while !line.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+/// Byte index just past the character starting at `index`
+fn char_end(line: &str, index: usize) -> usize {
+    let start = char_floor(line, index);
+    line[start..].chars().next().map_or(line.len(), |c| start + c.len_utf8())
+}
+
+impl EditorState {
+    pub fn start_visual(&mut self, mode: EditorMode) {
+        self.visual_anchor = (self.cursor_row, self.cursor_col);
+        self.status_message = match mode {
+            EditorMode::VisualLine => String::from("-- VISUAL LINE --"),
+            _ => String::from("-- VISUAL --"),
+        };
+        self.mode = mode;
+    }
+
+    pub fn exit_visual(&mut self) {
+        self.mode = EditorMode::Normal;
+        self.status_message = String::from("Normal mode");
+        self.clamp_cursor();
+    }
+
+    /// Ordered start and end of the selection; the end column is inclusive
+    pub fn selection(&self) -> ((usize, usize), (usize, usize)) {
+        let cursor = (self.cursor_row, self.cursor_col);
+        if self.visual_anchor <= cursor {
+            (self.visual_anchor, cursor)
+        } else {
+            (cursor, self.visual_anchor)
+        }
+    }
+
+    /// Byte range of `row` covered by the selection, if any
+    pub fn selected_span(&self, row: usize) -> Option<(usize, usize)> {
+        let ((start_row, start_col), (end_row, end_col)) = self.selection();
+        if !matches!(self.mode, EditorMode::Visual | EditorMode::VisualLine) || row < start_row || row > end_row {
+            return None;
+        }
+        let line = self.buffer.get(row)?;
+        if self.mode == EditorMode::VisualLine {
+            return Some((0, line.len()));
+        }
+        let start = if row == start_row { char_floor(line, start_col) } else { 0 };
+        let end = if row == end_row { char_end(line, end_col) } else { line.len() };
+        Some((start, end.max(start)))
+    }
+
+    /// Text under the selection, one entry per line
+    fn selected_text(&self) -> Vec<String> {
+        let ((start_row, _), (end_row, _)) = self.selection();
+        (start_row..=end_row)
+            .map(|row| {
+                let (start, end) = self.selected_span(row).unwrap_or_default();
+                self.buffer[row][start..end].to_string()
+            })
+            .collect()
+    }
+
+    pub fn yank_selection(&mut self) {
+        self.yank_register = self.selected_text();
+        self.yank_linewise = self.mode == EditorMode::VisualLine;
+        let lines = self.yank_register.len();
+        let (start, _) = self.selection();
+        self.exit_visual();
+        (self.cursor_row, self.cursor_col) = start;
+        self.clamp_cursor();
+        self.status_message = format!("{} line{} yanked", lines, if lines == 1 { "" } else { "s" });
+    }
+
+    pub fn delete_selection(&mut self) {
+        self.save_undo_state();
+        self.yank_register = self.selected_text();
+        self.yank_linewise = self.mode == EditorMode::VisualLine;
+
+        let ((start_row, _), (end_row, _)) = self.selection();
+        if self.yank_linewise {
+            self.buffer.drain(start_row..=end_row);
+            if self.buffer.is_empty() {
+                self.buffer.push(String::new());
+            }
+            self.cursor_row = start_row;
+            self.cursor_col = 0;
+        } else {
+            let (start, _) = self.selected_span(start_row).unwrap_or_default();
+            let (_, end) = self.selected_span(end_row).unwrap_or_default();
+            let joined = format!("{}{}", &self.buffer[start_row][..start], &self.buffer[end_row][end..]);
+            self.buffer.drain(start_row + 1..=end_row);
+            self.buffer[start_row] = joined;
+            self.cursor_row = start_row;
+            self.cursor_col = start;
+        }
+
+        self.modified = true;
+        self.exit_visual();
+        self.status_message = String::from("Selection deleted");
+    }
+
+    /// Indent (`>`) or dedent (`<`) every line touched by the selection by
+    /// one tab stop, using a tab character unless expandtab is set
+    pub fn shift_selection(&mut self, indent: bool) {
+        self.save_undo_state();
+        let ((start_row, _), (end_row, _)) = self.selection();
+        let unit = if self.options.expand_tab { " ".repeat(self.options.tab_width) } else { String::from("\t") };
+        for line in &mut self.buffer[start_row..=end_row] {
+            if indent {
+                if !line.is_empty() {
+                    line.insert_str(0, &unit);
+                }
+            } else {
+                let spaces = line.len() - line.trim_start_matches(' ').len();
+                let remove = if line.starts_with('\t') { 1 } else { spaces.min(self.options.tab_width) };
+                line.drain(..remove);
+            }
+        }
+        self.modified = true;
+        self.exit_visual();
+        self.cursor_row = start_row;
+        self.cursor_col = 0;
+        let lines = end_row - start_row + 1;
+        self.status_message = format!("{} line{} shifted", lines, if lines == 1 { "" } else { "s" });
+    }
+
+    /// Paste a character-wise register after (`p`) or before (`P`) the cursor
+    pub(super) fn paste_charwise(&mut self, after: bool) {
+        self.save_undo_state();
+        let line = &self.buffer[self.cursor_row];
+        let at = if line.is_empty() {
+            0
+        } else if after {
+            char_end(line, self.cursor_col)
+        } else {
+            char_floor(line, self.cursor_col)
+        };
+        let tail = self.buffer[self.cursor_row].split_off(at);
+
+        let mut register = self.yank_register.clone();
+        if let Some(last) = register.last_mut() {
+            last.push_str(&tail);
+        }
+        let mut pieces = register.into_iter();
+        if let Some(first) = pieces.next() {
+            self.buffer[self.cursor_row].push_str(&first);
+        }
+        for (i, piece) in pieces.enumerate() {
+            self.buffer.insert(self.cursor_row + 1 + i, piece);
+        }
+
+        self.cursor_col = at;
+        self.modified = true;
+        self.clamp_cursor();
+        self.status_message = String::from("Pasted");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor() -> EditorState {
+        let content = "alpha\nbravo\ncharlie".to_string();
+        EditorState::new("test.txt".to_string(), "/tmp/test.txt".to_string(), content)
+    }
+
+    #[test]
+    fn test_charwise_delete_joins_lines() {
+        let mut editor = editor();
+        editor.cursor_col = 2;
+        editor.start_visual(EditorMode::Visual);
+        editor.cursor_row = 1;
+        editor.cursor_col = 1;
+
+        assert_eq!(editor.selected_span(0), Some((2, 5)));
+        assert_eq!(editor.selected_span(1), Some((0, 2)));
+        editor.delete_selection();
+
+        assert_eq!(editor.buffer, ["alavo", "charlie"]);
+        assert_eq!(editor.yank_register, ["pha", "br"]);
+        assert_eq!(editor.mode, EditorMode::Normal);
+
+        editor.cursor_col = 2;
+        editor.paste_below();
+        assert_eq!(editor.buffer, ["alapha", "brvo", "charlie"]);
+    }
+
+    #[test]
+    fn test_linewise_yank_and_shift() {
+        let mut editor = editor();
+        editor.cursor_row = 2;
+        editor.start_visual(EditorMode::VisualLine);
+        editor.cursor_row = 1;
+
+        editor.yank_selection();
+        assert_eq!(editor.yank_register, ["bravo", "charlie"]);
+        assert!(editor.yank_linewise);
+        assert_eq!(editor.cursor_row, 1);
+
+        editor.start_visual(EditorMode::VisualLine);
+        editor.shift_selection(true);
+        assert_eq!(editor.buffer[1], "    bravo");
+        editor.start_visual(EditorMode::VisualLine);
+        editor.shift_selection(false);
+        assert_eq!(editor.buffer[1], "bravo");
+    }
+}
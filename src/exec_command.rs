@@ -0,0 +1,108 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use russh::ChannelMsg;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::ssh::SshClient;
+use crate::theme::Theme;
+use crate::{connect_options, find_connection};
+
+/// Exit status when bssh could not run the command at all, or the remote
+/// side ended without one, as with `ssh`
+pub const EXIT_FAILURE: i32 = 255;
+
+/// `bssh exec DESTINATION -- COMMAND...`: run a command without the TUI
+#[derive(Args, Debug, PartialEq)]
+pub struct ExecArgs {
+    /// Saved connection name or [user@]host[:port]
+    #[arg(value_name = "DESTINATION")]
+    pub destination: String,
+    /// Command to run, joined with spaces and run by the remote shell
+    #[arg(value_name = "COMMAND", last = true, required = true)]
+    pub command: Vec<String>,
+    /// Identity file (private key) for authentication
+    #[arg(short = 'i', long = "identity", value_name = "FILE")]
+    pub identity: Option<PathBuf>,
+    /// Port to connect to on the remote host
+    #[arg(short = 'p', long = "port", value_name = "PORT")]
+    pub port: Option<u16>,
+}
+
+/// Connect to the saved connection, `[user@]host[:port]` or `sftp://` URL
+/// `destination` without prompting for unknown host keys, for use from
+/// scripts. Also returns the path in the URL, if there is one.
+pub async fn connect(
+    destination: &str,
+    port: Option<u16>,
+    identity: Option<PathBuf>,
+) -> Result<(SshClient, Option<String>)> {
+    let (connection, url_path) = find_connection(destination.to_string(), port, identity)?;
+    let mut config = Config::load();
+    config.apply_connection(&connection);
+    let (theme, _) = Theme::from_config(&config.theme, &config.theme_colors);
+    let options = connect_options(&connection, &config, theme);
+
+    let client = SshClient::connect(
+        &connection.host,
+        connection.port,
+        &connection.username,
+        connection.identity_file.as_deref(),
+        &options,
+    )
+    .await
+    .with_context(|| format!("Failed to connect to {}", connection.display_name()))?;
+    Ok((client, url_path))
+}
+
+/// Run the command, passing its output through as it comes, and return
+/// its exit status
+pub async fn run(args: ExecArgs) -> Result<i32> {
+    let (mut client, _) = connect(&args.destination, args.port, args.identity).await?;
+    let mut channel = client.start_command(&args.command.join(" ")).await?;
+
+    let mut exit_status = None;
+    while let Some(msg) = channel.wait().await {
+        match msg {
+            ChannelMsg::Data { ref data } => {
+                let mut stdout = io::stdout().lock();
+                stdout.write_all(data)?;
+                stdout.flush()?;
+            }
+            ChannelMsg::ExtendedData { ref data, .. } => io::stderr().write_all(data)?,
+            ChannelMsg::ExitStatus { exit_status: status } => exit_status = Some(status),
+            _ => {}
+        }
+    }
+    Ok(exit_code(exit_status))
+}
+
+/// The process exit code for the remote command's exit status
+fn exit_code(exit_status: Option<u32>) -> i32 {
+    exit_status.and_then(|status| i32::try_from(status).ok()).unwrap_or(EXIT_FAILURE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        exec: ExecArgs,
+    }
+
+    #[test]
+    fn test_command_follows_the_separator() {
+        let cli = Cli::try_parse_from(["exec", "-p", "2222", "web", "--", "ls", "-la", "/tmp"]).unwrap();
+        assert_eq!(cli.exec.destination, "web");
+        assert_eq!(cli.exec.port, Some(2222));
+        assert_eq!(cli.exec.command, ["ls", "-la", "/tmp"]);
+        assert!(Cli::try_parse_from(["exec", "web"]).is_err());
+
+        assert_eq!(exit_code(Some(3)), 3);
+        assert_eq!(exit_code(None), EXIT_FAILURE);
+    }
+}
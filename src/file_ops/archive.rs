@@ -0,0 +1,242 @@
+use anyhow::{Context, Result};
+use russh_sftp::client::SftpSession;
+use tokio::io::AsyncWriteExt;
+
+use super::{download_file, exec_unavailable, join_path, set_permissions};
+use crate::ssh::client::shell_quote;
+use crate::ssh::SshClient;
+
+/// Archive formats that can be created and extracted on the server
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArchiveKind {
+    Tar,
+    TarGz,
+    TarXz,
+    TarBz2,
+    Zip,
+}
+
+impl ArchiveKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::split_name(name).map(|(_, kind)| kind)
+    }
+
+    /// Split a file name into its stem and archive kind
+    pub fn split_name(name: &str) -> Option<(&str, Self)> {
+        let kinds = [
+            (".tar.gz", ArchiveKind::TarGz),
+            (".tgz", ArchiveKind::TarGz),
+            (".tar.xz", ArchiveKind::TarXz),
+            (".txz", ArchiveKind::TarXz),
+            (".tar.bz2", ArchiveKind::TarBz2),
+            (".tbz2", ArchiveKind::TarBz2),
+            (".tar", ArchiveKind::Tar),
+            (".zip", ArchiveKind::Zip),
+        ];
+        kinds.iter().find_map(|&(ext, kind)| {
+            let split = name.len().checked_sub(ext.len())?;
+            name.get(split..)
+                .filter(|suffix| suffix.eq_ignore_ascii_case(ext))
+                .map(|_| (&name[..split], kind))
+        })
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArchiveKind::Tar => ".tar",
+            ArchiveKind::TarGz => ".tar.gz",
+            ArchiveKind::TarXz => ".tar.xz",
+            ArchiveKind::TarBz2 => ".tar.bz2",
+            ArchiveKind::Zip => ".zip",
+        }
+    }
+
+    /// Format offered after this one when cycling in the compress prompt
+    pub fn next(&self) -> Self {
+        match self {
+            ArchiveKind::TarGz => ArchiveKind::TarXz,
+            ArchiveKind::TarXz => ArchiveKind::TarBz2,
+            ArchiveKind::TarBz2 => ArchiveKind::Tar,
+            ArchiveKind::Tar => ArchiveKind::Zip,
+            ArchiveKind::Zip => ArchiveKind::TarGz,
+        }
+    }
+
+    /// Shell command extracting `archive` into `destination`
+    pub fn extract_command(&self, archive: &str, destination: &str) -> String {
+        let archive = shell_quote(archive);
+        let destination = shell_quote(destination);
+        match self {
+            ArchiveKind::Tar => format!("tar -xf {} -C {}", archive, destination),
+            ArchiveKind::TarGz => format!("tar -xzf {} -C {}", archive, destination),
+            ArchiveKind::TarXz => format!("tar -xJf {} -C {}", archive, destination),
+            ArchiveKind::TarBz2 => format!("tar -xjf {} -C {}", archive, destination),
+            ArchiveKind::Zip => format!("unzip -o -q {} -d {}", archive, destination),
+        }
+    }
+
+    /// Shell command packing `names` (entries of `directory`) into `archive`
+    pub fn create_command(&self, directory: &str, archive: &str, names: &[String]) -> String {
+        // A ./ prefix keeps names starting with '-' from being read as options
+        let names: Vec<String> = names
+            .iter()
+            .map(|name| shell_quote(&format!("./{}", name)))
+            .collect();
+        let names = names.join(" ");
+        let archive = shell_quote(archive);
+
+        let create = match self {
+            ArchiveKind::Tar => format!("tar -cf {} {}", archive, names),
+            ArchiveKind::TarGz => format!("tar -czf {} {}", archive, names),
+            ArchiveKind::TarXz => format!("tar -cJf {} {}", archive, names),
+            ArchiveKind::TarBz2 => format!("tar -cjf {} {}", archive, names),
+            ArchiveKind::Zip => format!("zip -r -q {} {}", archive, names),
+        };
+        format!("cd {} && {}", shell_quote(directory), create)
+    }
+}
+
+/// Pack entries of `directory` into a new archive there by running tar/zip
+/// on the server
+pub async fn create_archive(
+    ssh_client: &mut SshClient,
+    directory: &str,
+    archive: &str,
+    names: &[String],
+    kind: ArchiveKind,
+) -> Result<()> {
+    ssh_client
+        .execute_command(&kind.create_command(directory, archive, names))
+        .await
+        .context("Failed to create archive")?;
+    Ok(())
+}
+
+/// Extract a remote archive into `destination` by running tar/unzip on the
+/// server. Zip archives fall back to unpacking through SFTP when exec or
+/// `unzip` is unavailable.
+pub async fn extract_archive(
+    ssh_client: &mut SshClient,
+    sftp: &SftpSession,
+    archive: &str,
+    destination: &str,
+    kind: ArchiveKind,
+) -> Result<()> {
+    let command = kind.extract_command(archive, destination);
+    let result = ssh_client.run_command(&command).await;
+    if kind == ArchiveKind::Zip && exec_unavailable(&result) {
+        return extract_zip_sftp(sftp, archive, destination).await;
+    }
+
+    let output = result.context("Failed to extract archive")?;
+    let program = if kind == ArchiveKind::Zip { "unzip" } else { "tar" };
+    let stderr = output.stderr.trim();
+    match output.exit_status {
+        Some(0) => Ok(()),
+        _ if !stderr.is_empty() => anyhow::bail!("{} failed: {}", program, stderr),
+        Some(code) => anyhow::bail!("{} exited with code {}", program, code),
+        None => anyhow::bail!("{} ended without an exit status", program),
+    }
+}
+
+/// Download a zip archive to a local temporary directory and write its
+/// entries back over SFTP one at a time, each going through a local file so
+/// neither the archive nor an entry has to fit in memory
+async fn extract_zip_sftp(sftp: &SftpSession, archive: &str, destination: &str) -> Result<()> {
+    let scratch = tempfile::tempdir().context("Failed to create a temporary directory")?;
+    let local_archive = scratch.path().join("archive.zip");
+    download_file(sftp, archive, &local_archive, 1)
+        .await
+        .context("Failed to read archive")?;
+    let local_entry = scratch.path().join("entry");
+
+    let file = std::fs::File::open(&local_archive).context("Failed to read archive")?;
+    let mut zip = zip::ZipArchive::new(file).context("Invalid zip archive")?;
+
+    for i in 0..zip.len() {
+        // Unpack the entry before awaiting; zip entries cannot be held across
+        let (relative, is_file, mode) = {
+            let mut entry = zip.by_index(i)?;
+            // Skip names that would escape the destination (zip slip)
+            let Some(relative) = entry.enclosed_name() else {
+                continue;
+            };
+            let is_file = !entry.is_dir();
+            if is_file {
+                let mut out = std::fs::File::create(&local_entry).context("Failed to write a temporary file")?;
+                std::io::copy(&mut entry, &mut out)
+                    .with_context(|| format!("Failed to decompress {}", entry.name()))?;
+            }
+            (relative, is_file, entry.unix_mode())
+        };
+
+        let mut path = destination.to_string();
+        for component in relative.components() {
+            create_dir_if_missing(sftp, &path).await?;
+            path = join_path(&path, &component.as_os_str().to_string_lossy());
+        }
+
+        if !is_file {
+            create_dir_if_missing(sftp, &path).await?;
+            continue;
+        }
+
+        let mut content = tokio::fs::File::open(&local_entry)
+            .await
+            .context("Failed to read a temporary file")?;
+        let mut file = sftp
+            .create(&path)
+            .await
+            .with_context(|| format!("Failed to create {}", path))?;
+        tokio::io::copy(&mut content, &mut file)
+            .await
+            .with_context(|| format!("Failed to write {}", path))?;
+        file.shutdown().await?;
+
+        if let Some(mode) = mode {
+            set_permissions(sftp, &path, mode & 0o7777).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn create_dir_if_missing(sftp: &SftpSession, path: &str) -> Result<()> {
+    if sftp.try_exists(path).await.unwrap_or(false) {
+        return Ok(());
+    }
+    sftp.create_dir(path)
+        .await
+        .with_context(|| format!("Failed to create directory {}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_kind_from_name() {
+        assert_eq!(ArchiveKind::from_name("site.tar.gz"), Some(ArchiveKind::TarGz));
+        assert_eq!(ArchiveKind::from_name("SITE.TGZ"), Some(ArchiveKind::TarGz));
+        assert_eq!(ArchiveKind::from_name("logs.tar.xz"), Some(ArchiveKind::TarXz));
+        assert_eq!(ArchiveKind::from_name("build.zip"), Some(ArchiveKind::Zip));
+        assert_eq!(ArchiveKind::from_name("notes.txt"), None);
+        assert_eq!(ArchiveKind::split_name("db.TAR.XZ"), Some(("db", ArchiveKind::TarXz)));
+    }
+
+    #[test]
+    fn test_extract_command_quotes_paths() {
+        let command = ArchiveKind::TarGz.extract_command("/srv/my site.tar.gz", "/srv");
+        assert_eq!(command, "tar -xzf '/srv/my site.tar.gz' -C '/srv'");
+
+        let command = ArchiveKind::Zip.extract_command("/srv/a.zip", "/srv");
+        assert_eq!(command, "unzip -o -q '/srv/a.zip' -d '/srv'");
+    }
+
+    #[test]
+    fn test_create_command_prefixes_names() {
+        let names = vec!["-rf".to_string(), "it's".to_string()];
+        let command = ArchiveKind::Zip.create_command("/srv", "out.zip", &names);
+        assert_eq!(command, "cd '/srv' && zip -r -q 'out.zip' './-rf' './it'\\''s'");
+    }
+}
@@ -1,11 +1,128 @@
 use anyhow::{Context, Result};
 use futures::future::join_all;
-use russh_sftp::client::SftpSession;
-use std::path::Path;
-use tokio::fs::File;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use futures::stream::{self, StreamExt};
+use russh_sftp::client::error::Error as SftpError;
+use russh_sftp::client::fs::File as SftpFile;
+use russh_sftp::client::{RawSftpSession, SftpSession};
+use russh_sftp::protocol::{FileAttributes, OpenFlags, StatusCode};
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::Mutex;
+
+pub mod archive;
+
+use crate::app::{sort_entries, ConnectionHealth, FileEntry};
+use crate::ssh::client::{shell_quote, CommandOutput};
+use crate::ssh::SshClient;
+
+/// Join a remote directory path and an entry name
+pub fn join_path(dir: &str, name: &str) -> String {
+    if dir.ends_with('/') {
+        format!("{}{}", dir, name)
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
+
+/// Split a remote path into its directory and entry name, the inverse of
+/// `join_path`
+pub fn split_path(path: &str) -> (&str, &str) {
+    match path.rsplit_once('/') {
+        Some(("", name)) => ("/", name),
+        Some((dir, name)) => (dir, name),
+        None => (".", path),
+    }
+}
+
+/// Normalize `.` and `..` components of an absolute remote path
+pub fn normalize_path(path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    format!("/{}", parts.join("/"))
+}
+
+/// `sftp://` URL for a remote path, percent-encoding reserved characters
+pub fn sftp_url(username: &str, host: &str, port: u16, path: &str) -> String {
+    let encode = |value: &str, keep: &[u8]| {
+        let mut encoded = String::new();
+        for &byte in value.as_bytes() {
+            if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) || keep.contains(&byte) {
+                encoded.push(byte as char);
+            } else {
+                encoded.push_str(&format!("%{:02X}", byte));
+            }
+        }
+        encoded
+    };
+
+    let host = if host.contains(':') {
+        format!("[{}]", host)
+    } else {
+        host.to_string()
+    };
+    format!("sftp://{}@{}:{}{}", encode(username, b""), host, port, encode(path, b"/"))
+}
+
+/// Absolute path a symlink at `link_path` points to
+pub fn resolve_link_target(link_path: &str, target: &str) -> String {
+    if target.starts_with('/') {
+        return normalize_path(target);
+    }
+    let parent = link_path.rsplit_once('/').map_or("", |(parent, _)| parent);
+    normalize_path(&format!("{}/{}", parent, target))
+}
+
+/// Metadata of a directory entry, without following symlinks
+struct EntryStat {
+    meta: FileAttributes,
+    link_target: Option<String>,
+    /// Metadata of the symlink target; None for broken links
+    target_meta: Option<FileAttributes>,
+}
+
+impl EntryStat {
+    fn broken_link(&self) -> bool {
+        self.link_target.is_some() && self.target_meta.is_none()
+    }
+}
+
+/// Complete the attributes READDIR returned for an entry. Only symlinks,
+/// and entries the server sent without a file type, cost extra round-trips.
+async fn stat_entry(sftp: &SftpSession, path: &str, listed: FileAttributes) -> Option<EntryStat> {
+    let meta = match listed.permissions {
+        Some(_) => listed,
+        None => sftp.symlink_metadata(path).await.ok()?,
+    };
+
+    if !meta.is_symlink() {
+        return Some(EntryStat {
+            meta,
+            link_target: None,
+            target_meta: None,
+        });
+    }
 
-use crate::app::FileEntry;
+    let link_target = sftp.read_link(path).await.unwrap_or_default();
+    let target_meta = sftp.metadata(path).await.ok();
+
+    Some(EntryStat {
+        meta,
+        link_target: Some(link_target),
+        target_meta,
+    })
+}
 
 pub async fn list_directory(sftp: &SftpSession, path: &str) -> Result<Vec<FileEntry>> {
     let entries = sftp
@@ -17,152 +134,455 @@ pub async fn list_directory(sftp: &SftpSession, path: &str) -> Result<Vec<FileEn
 
     // Add parent directory entry if not root
     if path != "/" {
-        files.push(FileEntry {
-            name: String::from(".."),
-            path: String::from(".."),
-            is_dir: true,
-            size: 0,
-            modified: None,
-            permissions: None,
-        });
+        files.push(FileEntry::parent());
     }
 
-    // Collect file info first
-    let mut file_info: Vec<(String, String)> = Vec::new();
+    let listed = entries.map(|entry| (entry.file_name(), entry.metadata())).collect();
+    files.extend(describe_entries(sftp, path, listed).await);
 
-    for entry in entries {
-        let filename = entry.file_name();
+    sort_entries(&mut files);
 
+    Ok(files)
+}
+
+/// The remote file or directory at `path`, if there is one
+pub async fn stat(sftp: &SftpSession, path: &str) -> Option<FileEntry> {
+    let meta = sftp.symlink_metadata(path).await.ok()?;
+    let (dir, name) = split_path(path);
+    describe_entries(sftp, dir, vec![(name.to_string(), meta)]).await.pop()
+}
+
+/// Turn names and attributes from READDIR into entries of `dir`
+async fn describe_entries(
+    sftp: &SftpSession,
+    dir: &str,
+    listed: Vec<(String, FileAttributes)>,
+) -> Vec<FileEntry> {
+    // Collect names with the attributes READDIR already returned
+    let mut file_info: Vec<(String, String, FileAttributes)> = Vec::new();
+
+    for (filename, attrs) in listed {
         // Skip . and .. entries since we handle .. explicitly
         if filename == "." || filename == ".." {
             continue;
         }
 
-        let full_path = if path.ends_with('/') {
-            format!("{}{}", path, filename)
-        } else {
-            format!("{}/{}", path, filename)
-        };
+        let full_path = join_path(dir, &filename);
 
-        file_info.push((filename.to_string(), full_path));
+        file_info.push((filename, full_path, attrs));
     }
 
-    // Create futures for all metadata fetches with owned strings
+    // Plain entries resolve immediately; symlinks are followed concurrently
     let metadata_futures: Vec<_> = file_info
         .iter()
-        .map(|(_, path)| sftp.metadata(path))
+        .map(|(_, path, listed)| stat_entry(sftp, path, listed.clone()))
         .collect();
 
-    // Fetch all metadata concurrently (this is the speedup!)
     let metadata_results = join_all(metadata_futures).await;
 
-    // Process results
-    for ((filename, full_path), metadata_result) in file_info.into_iter().zip(metadata_results) {
-        let metadata = metadata_result.ok();
+    let mut files = Vec::with_capacity(file_info.len());
 
-        let (is_dir, size, modified) = if let Some(meta) = metadata {
-            let modified_time = meta.modified().ok().and_then(|t| {
+    // Process results
+    for ((filename, full_path, _), stat) in file_info.into_iter().zip(metadata_results) {
+        let entry = if let Some(stat) = stat {
+            let modified_time = stat.meta.modified().ok().and_then(|t| {
                 t.duration_since(std::time::UNIX_EPOCH)
                     .ok()
                     .map(|d| d.as_secs() as i64)
             });
-
-            (
-                meta.is_dir(),
-                meta.len(),
-                modified_time,
-            )
+            let accessed_time = stat.meta.atime.map(i64::from);
+
+            // Links take their type and size from what they point at
+            let target_meta = stat.target_meta.as_ref().unwrap_or(&stat.meta);
+
+            FileEntry {
+                name: filename,
+                path: full_path,
+                is_dir: target_meta.is_dir(),
+                size: if stat.broken_link() { 0 } else { target_meta.len() },
+                modified: modified_time,
+                accessed: accessed_time,
+                permissions: stat.meta.permissions,
+                // SFTP v3 servers usually only send numeric ids
+                owner: stat.meta.user.clone().or_else(|| stat.meta.uid.map(|uid| uid.to_string())),
+                group: stat.meta.group.clone().or_else(|| stat.meta.gid.map(|gid| gid.to_string())),
+                broken_link: stat.broken_link(),
+                link_target: stat.link_target,
+            }
         } else {
             // Fallback if stat fails - assume it's a file
-            (false, 0, None)
+            FileEntry::new(filename, full_path, false)
         };
 
-        files.push(FileEntry {
-            name: filename,
-            path: full_path,
-            is_dir,
-            size,
-            modified,
-            permissions: None,
-        });
+        files.push(entry);
+    }
+
+    files
+}
+
+/// Whether `error` came from the SFTP channel or session rather than from
+/// the server refusing the request, e.g. with permission denied
+pub fn is_session_error(error: &anyhow::Error) -> bool {
+    !matches!(error.downcast_ref::<SftpError>(), Some(SftpError::Status(_)))
+}
+
+/// Directory listing read one READDIR batch at a time, so large directories
+/// can be shown while they load
+pub struct DirectoryStream {
+    lister: Arc<RawSftpSession>,
+    path: String,
+    handle: Option<String>,
+}
+
+impl DirectoryStream {
+    pub async fn open(lister: Arc<RawSftpSession>, path: &str) -> Result<Self> {
+        let handle = lister
+            .opendir(path)
+            .await
+            .context("Failed to read directory")?
+            .handle;
+        Ok(Self {
+            lister,
+            path: path.to_string(),
+            handle: Some(handle),
+        })
     }
 
-    files.sort_by(|a, b| {
-        match (a.is_dir, b.is_dir) {
-            (true, false) => std::cmp::Ordering::Less,
-            (false, true) => std::cmp::Ordering::Greater,
-            _ => a.name.cmp(&b.name),
+    /// Entries of the next batch, or None once the listing is complete
+    pub async fn next_batch(&mut self, sftp: &SftpSession) -> Result<Option<Vec<FileEntry>>> {
+        let Some(ref handle) = self.handle else {
+            return Ok(None);
+        };
+        match self.lister.readdir(handle.as_str()).await {
+            Ok(name) => {
+                let listed = name.files.into_iter().map(|file| (file.filename, file.attrs)).collect();
+                Ok(Some(describe_entries(sftp, &self.path, listed).await))
+            }
+            Err(SftpError::Status(status)) if status.status_code == StatusCode::Eof => {
+                self.close().await;
+                Ok(None)
+            }
+            Err(e) => {
+                self.close().await;
+                Err(e).context("Failed to read directory")
+            }
         }
-    });
+    }
 
-    Ok(files)
+    pub async fn close(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            let _ = self.lister.close(handle).await;
+        }
+    }
 }
 
-pub async fn download_file(
-    sftp: &SftpSession,
-    remote_path: &str,
-    local_path: &Path,
-) -> Result<()> {
-    let mut remote_file = sftp
-        .open(remote_path)
+impl Drop for DirectoryStream {
+    /// A listing given up halfway, e.g. when cancelled, still closes its handle
+    fn drop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            let lister = self.lister.clone();
+            tokio::spawn(async move {
+                let _ = lister.close(handle).await;
+            });
+        }
+    }
+}
+
+/// Read up to `limit` bytes from the start of a remote file
+pub async fn read_head(sftp: &SftpSession, path: &str, limit: usize) -> Result<Vec<u8>> {
+    let file = sftp
+        .open(path)
         .await
         .context("Failed to open remote file")?;
 
-    let mut local_file = File::create(local_path)
+    let mut content = Vec::new();
+    file.take(limit as u64)
+        .read_to_end(&mut content)
         .await
-        .context("Failed to create local file")?;
+        .context("Failed to read from remote file")?;
 
-    let mut buffer = vec![0u8; 32768];
-    loop {
-        let n = remote_file
-            .read(&mut buffer)
+    Ok(content)
+}
+
+/// Suffix for transfers in progress; a leftover partial file is resumed
+pub const PARTIAL_SUFFIX: &str = ".part";
+
+fn partial_local_path(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(PARTIAL_SUFFIX);
+    PathBuf::from(partial)
+}
+
+fn partial_remote_path(path: &str) -> String {
+    format!("{}{}", path, PARTIAL_SUFFIX)
+}
+
+/// Offset to resume from given the size of an existing partial file. A
+/// partial larger than the source cannot belong to it, so start over.
+fn resume_offset(partial_len: Option<u64>, total_len: u64) -> u64 {
+    match partial_len {
+        Some(len) if len <= total_len => len,
+        _ => 0,
+    }
+}
+
+/// Bytes per ranged request when a transfer runs several requests at once
+const TRANSFER_CHUNK: u64 = 256 * 1024;
+
+/// Byte ranges `start..end` splitting `offset..total` into transfer chunks
+fn transfer_chunks(offset: u64, total: u64) -> Vec<(u64, u64)> {
+    (offset..total)
+        .step_by(TRANSFER_CHUNK as usize)
+        .map(|start| (start, (start + TRANSFER_CHUNK).min(total)))
+        .collect()
+}
+
+/// Read `start..end` of a remote file; shorter if the file shrank
+async fn read_range(handle: &Mutex<SftpFile>, start: u64, end: u64) -> Result<Vec<u8>> {
+    let mut file = handle.lock().await;
+    file.seek(SeekFrom::Start(start))
+        .await
+        .context("Failed to seek remote file")?;
+
+    let mut data = vec![0u8; (end - start) as usize];
+    let mut filled = 0;
+    while filled < data.len() {
+        let n = file
+            .read(&mut data[filled..])
             .await
             .context("Failed to read from remote file")?;
-
         if n == 0 {
             break;
         }
+        filled += n;
+    }
+    data.truncate(filled);
+    Ok(data)
+}
 
+/// Download a remote file, resuming from `<local_path>.part` if an earlier
+/// attempt was interrupted. Up to `concurrency` ranged reads are in flight
+/// at once, each on its own handle. Returns the offset the transfer resumed
+/// from.
+pub async fn download_file(
+    sftp: &SftpSession,
+    remote_path: &str,
+    local_path: &Path,
+    concurrency: usize,
+) -> Result<u64> {
+    let total_len = sftp
+        .metadata(remote_path)
+        .await
+        .context("Failed to stat remote file")?
+        .len();
+
+    let partial_path = partial_local_path(local_path);
+    let partial_len = tokio::fs::metadata(&partial_path).await.ok().map(|m| m.len());
+    let offset = resume_offset(partial_len, total_len);
+    let chunks = transfer_chunks(offset, total_len);
+
+    let mut handles = Vec::new();
+    for _ in 0..concurrency.clamp(1, chunks.len().max(1)) {
+        let file = sftp
+            .open(remote_path)
+            .await
+            .context("Failed to open remote file")?;
+        handles.push(Mutex::new(file));
+    }
+
+    let mut local_file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(&partial_path)
+        .await
+        .context("Failed to create local file")?;
+
+    local_file.set_len(offset).await.context("Failed to truncate partial file")?;
+    if offset > 0 {
         local_file
-            .write_all(&buffer[..n])
+            .seek(SeekFrom::Start(offset))
+            .await
+            .context("Failed to seek partial file")?;
+    }
+
+    // Consecutive chunks use different handles; results are written in order
+    // so the partial file stays a prefix that can be resumed
+    let mut reads = stream::iter(chunks.into_iter().enumerate())
+        .map(|(i, (start, end))| read_range(&handles[i % handles.len()], start, end))
+        .buffered(handles.len());
+    while let Some(data) = reads.next().await {
+        local_file
+            .write_all(&data?)
             .await
             .context("Failed to write to local file")?;
     }
+    drop(reads);
 
-    Ok(())
+    // Pick up anything appended since the file was stat'ed
+    let mut remote_file = handles.swap_remove(0).into_inner();
+    remote_file
+        .seek(SeekFrom::Start(total_len.max(offset)))
+        .await
+        .context("Failed to seek remote file")?;
+    tokio::io::copy(&mut remote_file, &mut local_file)
+        .await
+        .context("Failed to read from remote file")?;
+
+    local_file.flush().await.context("Failed to write to local file")?;
+    tokio::fs::rename(&partial_path, local_path)
+        .await
+        .context("Failed to move completed download into place")?;
+
+    Ok(offset)
 }
 
-pub async fn upload_file(
-    sftp: &SftpSession,
-    local_path: &Path,
-    remote_path: &str,
-) -> Result<()> {
+/// Copy `start..end` of a local file to the same range of a remote file
+async fn write_range(local_path: &Path, handle: &Mutex<SftpFile>, start: u64, end: u64) -> Result<()> {
     let mut local_file = File::open(local_path)
         .await
         .context("Failed to open local file")?;
+    local_file
+        .seek(SeekFrom::Start(start))
+        .await
+        .context("Failed to seek local file")?;
+    let mut data = vec![0u8; (end - start) as usize];
+    local_file
+        .read_exact(&mut data)
+        .await
+        .context("Failed to read from local file")?;
 
-    let mut remote_file = sftp
-        .create(remote_path)
+    let mut file = handle.lock().await;
+    file.seek(SeekFrom::Start(start))
         .await
-        .context("Failed to create remote file")?;
+        .context("Failed to seek partial file")?;
+    file.write_all(&data)
+        .await
+        .context("Failed to write to remote file")?;
+    // Wait for the server to acknowledge the range
+    file.flush().await.context("Failed to write to remote file")?;
+    Ok(())
+}
 
-    let mut buffer = vec![0u8; 32768];
-    loop {
-        let n = local_file
-            .read(&mut buffer)
+/// Upload a local file, resuming from `<remote_path>.part` if an earlier
+/// attempt was interrupted. Up to `concurrency` ranged writes are in flight
+/// at once, each on its own handle. Returns the offset the transfer resumed
+/// from.
+pub async fn upload_file(
+    sftp: &SftpSession,
+    local_path: &Path,
+    remote_path: &str,
+    concurrency: usize,
+) -> Result<u64> {
+    let total_len = tokio::fs::metadata(local_path)
+        .await
+        .context("Failed to stat local file")?
+        .len();
+
+    let partial_path = partial_remote_path(remote_path);
+    let partial_len = sftp.metadata(partial_path.as_str()).await.ok().map(|m| m.len());
+    let offset = resume_offset(partial_len, total_len);
+    let chunks = transfer_chunks(offset, total_len);
+
+    let mut handles = Vec::new();
+    for i in 0..concurrency.clamp(1, chunks.len().max(1)) {
+        // Only the first handle may truncate, or it would undo the others
+        let mut flags = OpenFlags::CREATE | OpenFlags::WRITE;
+        if offset == 0 && i == 0 {
+            flags |= OpenFlags::TRUNCATE;
+        }
+        let file = sftp
+            .open_with_flags(partial_path.as_str(), flags)
             .await
-            .context("Failed to read from local file")?;
+            .context("Failed to create remote file")?;
+        handles.push(Mutex::new(file));
+    }
 
-        if n == 0 {
-            break;
+    let pool = &handles;
+    let mut writes = stream::iter(chunks.into_iter().enumerate())
+        .map(|(i, (start, end))| async move {
+            write_range(local_path, &pool[i % pool.len()], start, end).await?;
+            Ok::<_, anyhow::Error>(end)
+        })
+        .buffered(pool.len());
+    let mut written = offset;
+    let mut result = Ok(());
+    while let Some(end) = writes.next().await {
+        match end {
+            Ok(end) => written = end,
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
         }
+    }
+    drop(writes);
 
-        remote_file
-            .write_all(&buffer[..n])
-            .await
-            .context("Failed to write to remote file")?;
+    for handle in handles {
+        // Close every handle, waiting for every write to be acknowledged
+        let _ = handle.into_inner().shutdown().await;
+    }
+    if let Err(e) = result {
+        // Later ranges may have landed past a failed one; cut the partial
+        // file back so a resume does not skip the gap
+        let attrs = FileAttributes {
+            size: Some(written),
+            ..FileAttributes::empty()
+        };
+        let _ = sftp.set_metadata(partial_path.as_str(), attrs).await;
+        return Err(e);
+    }
+
+    // SFTP rename refuses to overwrite on most servers
+    if sftp.try_exists(remote_path).await.unwrap_or(false) {
+        delete_file(sftp, remote_path).await?;
+    }
+    rename(sftp, &partial_path, remote_path).await?;
+
+    Ok(offset)
+}
+
+/// Write text to a remote file, replacing its contents or appending to it.
+/// Appended text starts on a new line and ends with one.
+pub async fn write_text(sftp: &SftpSession, path: &str, text: &str, append: bool) -> Result<()> {
+    let size = if append {
+        sftp.metadata(path).await.map(|meta| meta.len()).unwrap_or(0)
+    } else {
+        0
+    };
+
+    let mut flags = OpenFlags::CREATE | OpenFlags::WRITE | OpenFlags::READ;
+    if !append {
+        flags |= OpenFlags::TRUNCATE;
     }
+    let mut file = sftp
+        .open_with_flags(path, flags)
+        .await
+        .context("Failed to open remote file")?;
 
+    let mut content = text.to_string();
+    if append {
+        if !content.ends_with('\n') {
+            content.push('\n');
+        }
+        if size > 0 {
+            let mut last = [0u8; 1];
+            file.seek(SeekFrom::Start(size - 1))
+                .await
+                .context("Failed to seek in remote file")?;
+            file.read_exact(&mut last)
+                .await
+                .context("Failed to read from remote file")?;
+            if last[0] != b'\n' {
+                content.insert(0, '\n');
+            }
+        }
+    }
+
+    file.write_all(content.as_bytes())
+        .await
+        .context("Failed to write to remote file")?;
+    file.shutdown().await.context("Failed to write to remote file")?;
     Ok(())
 }
 
@@ -180,6 +600,155 @@ pub async fn delete_directory(sftp: &SftpSession, path: &str) -> Result<()> {
     Ok(())
 }
 
+/// Change permission bits via SFTP setstat
+pub async fn set_permissions(sftp: &SftpSession, path: &str, mode: u32) -> Result<()> {
+    let attributes = FileAttributes {
+        permissions: Some(mode),
+        ..FileAttributes::empty()
+    };
+    sftp.set_metadata(path, attributes)
+        .await
+        .context("Failed to change permissions")?;
+    Ok(())
+}
+
+/// Change the owning user and/or group via SFTP setstat. SFTP sets both ids
+/// together, so a missing one keeps its current value.
+pub async fn set_owner(
+    sftp: &SftpSession,
+    path: &str,
+    uid: Option<u32>,
+    gid: Option<u32>,
+) -> Result<()> {
+    let current = sftp
+        .symlink_metadata(path)
+        .await
+        .context("Failed to stat file")?;
+
+    let attributes = FileAttributes {
+        uid: uid.or(current.uid),
+        gid: gid.or(current.gid),
+        ..FileAttributes::empty()
+    };
+    sftp.set_metadata(path, attributes)
+        .await
+        .context("Failed to change owner")?;
+    Ok(())
+}
+
+/// Resolve a user or group name to its numeric id with `getent` on the
+/// server. `database` is `passwd` or `group`; numeric input is used as-is.
+pub async fn lookup_id(ssh_client: &mut SshClient, database: &str, name: &str) -> Result<u32> {
+    if let Ok(id) = name.parse() {
+        return Ok(id);
+    }
+
+    let command = format!("getent {} {}", database, shell_quote(name));
+    let output = ssh_client
+        .execute_command(&command)
+        .await
+        .with_context(|| format!("Could not look up '{}'; try a numeric id", name))?;
+
+    parse_getent_id(&output).with_context(|| format!("Unknown {} entry '{}'", database, name))
+}
+
+/// The id field from a `getent passwd|group` line (`name:x:id:...`)
+fn parse_getent_id(output: &str) -> Option<u32> {
+    output.lines().next()?.split(':').nth(2)?.trim().parse().ok()
+}
+
+/// Longest wait for a health check reply before the server counts as unresponsive
+const PING_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Time a cheap SFTP round trip (resolving `.`) to tell a slow server from a
+/// dead connection. An error reply still counts as an answer.
+pub async fn check_health(ssh_client: &SshClient, sftp: &SftpSession) -> ConnectionHealth {
+    if !ssh_client.is_connected() {
+        return ConnectionHealth::Disconnected;
+    }
+    let started = Instant::now();
+    match tokio::time::timeout(PING_TIMEOUT, sftp.canonicalize(".")).await {
+        Err(_) => ConnectionHealth::NoReply,
+        Ok(_) if !ssh_client.is_connected() => ConnectionHealth::Disconnected,
+        Ok(_) => ConnectionHealth::Responding(started.elapsed()),
+    }
+}
+
+/// SHA-256 of a remote file, computed on the server by `sha256sum`
+pub async fn checksum(ssh_client: &mut SshClient, path: &str) -> Result<String> {
+    let output = ssh_client
+        .execute_command(&format!("sha256sum -- {}", shell_quote(path)))
+        .await
+        .context("Failed to run sha256sum")?;
+    parse_sha256sum_output(&output).context("sha256sum is not available on the server")
+}
+
+/// The digest from `sha256sum` output: 64 hex digits, then the file name
+fn parse_sha256sum_output(output: &str) -> Option<String> {
+    let digest = output.split_whitespace().next()?;
+    // GNU sha256sum escapes names with backslashes and prefixes the digest with one
+    let digest = digest.trim_start_matches('\\');
+    (digest.len() == 64 && digest.chars().all(|c| c.is_ascii_hexdigit())).then(|| digest.to_lowercase())
+}
+
+/// Space on the filesystem holding a directory, in bytes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiskUsage {
+    pub total: u64,
+    pub used: u64,
+    /// Free space available to unprivileged users
+    pub available: u64,
+}
+
+impl DiskUsage {
+    pub fn used_percent(&self) -> u64 {
+        // Like df, relative to the space usable by ordinary users
+        let usable = self.used + self.available;
+        if usable == 0 {
+            0
+        } else {
+            (self.used * 100).div_ceil(usable)
+        }
+    }
+}
+
+/// Longest wait for the disk usage of a directory
+const DISK_USAGE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Query disk usage with the SFTP statvfs extension, falling back to `df`
+pub async fn disk_usage(ssh_client: &mut SshClient, sftp: &SftpSession, path: &str) -> Result<DiskUsage> {
+    tokio::time::timeout(DISK_USAGE_TIMEOUT, query_disk_usage(ssh_client, sftp, path))
+        .await
+        .context("Disk usage query timed out")?
+}
+
+async fn query_disk_usage(ssh_client: &mut SshClient, sftp: &SftpSession, path: &str) -> Result<DiskUsage> {
+    if let Ok(Some(stat)) = sftp.fs_info(path).await {
+        return Ok(DiskUsage {
+            total: stat.blocks * stat.fragment_size,
+            used: stat.blocks.saturating_sub(stat.blocks_free) * stat.fragment_size,
+            available: stat.blocks_avail * stat.fragment_size,
+        });
+    }
+
+    let output = ssh_client
+        .execute_command(&format!("df -Pk -- {}", shell_quote(path)))
+        .await
+        .context("Failed to run df")?;
+    parse_df_output(&output).context("Unexpected df output")
+}
+
+/// Parse POSIX `df -Pk` output: total, used and available 1K blocks
+fn parse_df_output(output: &str) -> Option<DiskUsage> {
+    let fields: Vec<&str> = output.lines().nth(1)?.split_whitespace().collect();
+    let blocks = |i: usize| fields.get(i)?.parse::<u64>().ok().map(|kb| kb * 1024);
+    Some(DiskUsage {
+        total: blocks(1)?,
+        used: blocks(2)?,
+        available: blocks(3)?,
+    })
+}
+
 pub async fn create_directory(sftp: &SftpSession, path: &str) -> Result<()> {
     sftp.create_dir(path)
         .await
@@ -193,3 +762,222 @@ pub async fn rename(sftp: &SftpSession, old_path: &str, new_path: &str) -> Resul
         .context("Failed to rename file")?;
     Ok(())
 }
+
+/// Exit status of a shell that could not find the command
+const EXIT_COMMAND_NOT_FOUND: u32 = 127;
+
+/// Whether `cp` could not be run at all, because the server allows no exec
+/// channel or has no `cp`. Only then is copying over SFTP worth trying; a
+/// `cp` that ran and failed may have copied part of the entry already.
+fn exec_unavailable(result: &Result<CommandOutput>) -> bool {
+    match result {
+        Err(_) => true,
+        Ok(output) => output.exit_status == Some(EXIT_COMMAND_NOT_FOUND),
+    }
+}
+
+/// Copy a remote file or directory to a new remote path. Uses `cp -r` on the
+/// server when possible, falling back to streaming through SFTP when exec is
+/// unavailable (e.g. SFTP-only accounts).
+pub async fn copy_entry(
+    ssh_client: &mut SshClient,
+    sftp: &SftpSession,
+    source: &str,
+    destination: &str,
+    is_dir: bool,
+) -> Result<()> {
+    let command = format!("cp -r -- {} {}", shell_quote(source), shell_quote(destination));
+    let result = ssh_client.run_command(&command).await;
+    if !exec_unavailable(&result) {
+        let output = result?;
+        let stderr = output.stderr.trim();
+        return match output.exit_status {
+            Some(0) => Ok(()),
+            _ if !stderr.is_empty() => anyhow::bail!("cp failed: {}", stderr),
+            Some(code) => anyhow::bail!("cp exited with code {}", code),
+            None => anyhow::bail!("cp ended without an exit status"),
+        };
+    }
+
+    if is_dir {
+        copy_directory_sftp(sftp, source, destination).await
+    } else {
+        copy_file_sftp(sftp, source, destination).await
+    }
+}
+
+/// Stream a remote file to another remote path through SFTP
+pub async fn copy_file_sftp(sftp: &SftpSession, source: &str, destination: &str) -> Result<()> {
+    let mut source_file = sftp
+        .open(source)
+        .await
+        .context("Failed to open remote file")?;
+
+    let mut destination_file = sftp
+        .create(destination)
+        .await
+        .context("Failed to create remote file")?;
+
+    let mut buffer = vec![0u8; 32768];
+    loop {
+        let n = source_file
+            .read(&mut buffer)
+            .await
+            .context("Failed to read from remote file")?;
+
+        if n == 0 {
+            break;
+        }
+
+        destination_file
+            .write_all(&buffer[..n])
+            .await
+            .context("Failed to write to remote file")?;
+    }
+
+    Ok(())
+}
+
+async fn copy_directory_sftp(sftp: &SftpSession, source: &str, destination: &str) -> Result<()> {
+    create_directory(sftp, destination).await?;
+
+    let entries = sftp
+        .read_dir(source)
+        .await
+        .context("Failed to read directory")?;
+
+    for entry in entries {
+        let name = entry.file_name();
+        if name == "." || name == ".." {
+            continue;
+        }
+
+        let from = join_path(source, &name);
+        let to = join_path(destination, &name);
+
+        if entry.file_type().is_dir() {
+            Box::pin(copy_directory_sftp(sftp, &from, &to)).await?;
+        } else {
+            copy_file_sftp(sftp, &from, &to).await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join_path() {
+        assert_eq!(join_path("/", "etc"), "/etc");
+        assert_eq!(join_path("/home/user", "notes.txt"), "/home/user/notes.txt");
+        assert_eq!(join_path("/home/user/", "notes.txt"), "/home/user/notes.txt");
+    }
+
+    #[test]
+    fn test_split_path() {
+        assert_eq!(split_path("/etc"), ("/", "etc"));
+        assert_eq!(split_path("/home/user/notes.txt"), ("/home/user", "notes.txt"));
+    }
+
+    #[test]
+    fn test_refusals_are_not_session_errors() {
+        let denied = SftpError::Status(russh_sftp::protocol::Status {
+            id: 1,
+            status_code: StatusCode::PermissionDenied,
+            error_message: String::from("Permission denied"),
+            language_tag: String::from("en"),
+        });
+        assert!(!is_session_error(&anyhow::Error::from(denied).context("Failed to read directory")));
+        assert!(is_session_error(&anyhow::Error::from(SftpError::Timeout).context("Failed to read directory")));
+        assert!(is_session_error(&anyhow::anyhow!("Failed to open channel")));
+    }
+
+    #[test]
+    fn test_resume_offset() {
+        assert_eq!(resume_offset(None, 100), 0);
+        assert_eq!(resume_offset(Some(40), 100), 40);
+        assert_eq!(resume_offset(Some(100), 100), 100);
+        assert_eq!(resume_offset(Some(150), 100), 0);
+    }
+
+    #[test]
+    fn test_transfer_chunks_cover_the_rest_of_the_file() {
+        let chunk = TRANSFER_CHUNK;
+        assert_eq!(
+            transfer_chunks(10, 2 * chunk + 5),
+            [(10, chunk + 10), (chunk + 10, 2 * chunk + 5)]
+        );
+        assert!(transfer_chunks(100, 100).is_empty());
+    }
+
+    #[test]
+    fn test_partial_local_path() {
+        assert_eq!(partial_local_path(Path::new("dump.sql")), PathBuf::from("dump.sql.part"));
+    }
+
+    #[test]
+    fn test_partial_remote_path() {
+        assert_eq!(partial_remote_path("/srv/big.iso"), "/srv/big.iso.part");
+    }
+
+    #[test]
+    fn test_parse_getent_id() {
+        assert_eq!(parse_getent_id("www-data:x:33:33:www-data:/var/www:/usr/sbin/nologin\n"), Some(33));
+        assert_eq!(parse_getent_id("staff:x:50:alice,bob\n"), Some(50));
+        assert_eq!(parse_getent_id(""), None);
+    }
+
+    #[test]
+    fn test_sftp_url() {
+        assert_eq!(
+            sftp_url("deploy", "example.com", 22, "/srv/my site/ä.txt"),
+            "sftp://deploy@example.com:22/srv/my%20site/%C3%A4.txt"
+        );
+        assert_eq!(sftp_url("root", "::1", 2222, "/"), "sftp://root@[::1]:2222/");
+    }
+
+    #[test]
+    fn test_only_a_missing_cp_falls_back_to_sftp() {
+        let exited = |status| -> Result<CommandOutput> {
+            Ok(CommandOutput {
+                exit_status: status,
+                ..CommandOutput::default()
+            })
+        };
+        assert!(exec_unavailable(&Err(anyhow::anyhow!("Failed to open channel"))));
+        assert!(exec_unavailable(&exited(Some(127))));
+        assert!(!exec_unavailable(&exited(Some(1))));
+        assert!(!exec_unavailable(&exited(None)));
+    }
+
+    #[test]
+    fn test_parse_df_output() {
+        let output = "Filesystem     1024-blocks     Used Available Capacity Mounted on\n\
+                      /dev/sda1         10000000  7500000   2000000      79% /\n";
+        let usage = parse_df_output(output).unwrap();
+        assert_eq!(usage.total, 10_000_000 * 1024);
+        assert_eq!(usage.available, 2_000_000 * 1024);
+        assert_eq!(usage.used_percent(), 79);
+
+        assert!(parse_df_output("df: /nope: No such file or directory").is_none());
+    }
+
+    #[test]
+    fn test_parse_sha256sum_output() {
+        let digest = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+        assert_eq!(parse_sha256sum_output(&format!("{}  /srv/empty\n", digest)).as_deref(), Some(digest));
+        assert_eq!(parse_sha256sum_output(&format!("\\{}  /srv/a\\nb\n", digest)).as_deref(), Some(digest));
+        assert!(parse_sha256sum_output("sh: sha256sum: command not found").is_none());
+    }
+
+    #[test]
+    fn test_resolve_link_target() {
+        assert_eq!(resolve_link_target("/etc/nginx/sites-enabled/app", "../sites-available/app"), "/etc/nginx/sites-available/app");
+        assert_eq!(resolve_link_target("/usr/bin/python", "python3.12"), "/usr/bin/python3.12");
+        assert_eq!(resolve_link_target("/srv/current", "/srv/releases/./42/"), "/srv/releases/42");
+        assert_eq!(resolve_link_target("/root-link", ".."), "/");
+    }
+}
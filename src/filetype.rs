@@ -0,0 +1,186 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::file_ops::archive::ArchiveKind;
+use crate::tui::image::is_image_name;
+
+/// Bytes read from a file to sniff its type when the name is not enough
+pub const SNIFF_BYTES: usize = 512;
+
+/// Broad file category used for icons and choosing how to open a file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileKind {
+    Text,
+    Log,
+    Image,
+    Archive,
+    Binary,
+}
+
+/// What Enter does with a file
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OpenAction {
+    /// Built-in text editor
+    Editor,
+    /// Read-only hex dump
+    Hex,
+    /// Streaming `tail -f` view
+    Follow,
+    /// Show in the preview pane
+    Preview,
+}
+
+const TEXT_EXTENSIONS: &[&str] = &[
+    "txt", "md", "rst", "csv", "tsv", "json", "yaml", "yml", "toml", "ini", "cfg", "conf", "env",
+    "xml", "html", "htm", "css", "js", "ts", "jsx", "tsx", "rs", "py", "rb", "go", "java", "kt",
+    "c", "h", "cpp", "hpp", "cs", "php", "pl", "lua", "sh", "bash", "zsh", "fish", "sql", "svg",
+    "service", "timer", "lock", "gitignore", "dockerfile",
+];
+
+const BINARY_EXTENSIONS: &[&str] = &[
+    "bin", "exe", "dll", "so", "o", "a", "class", "jar", "pyc", "pdf", "iso", "img", "deb", "rpm",
+    "db", "sqlite", "gif", "webp", "bmp", "ico", "mp3", "mp4", "mkv", "wav", "flac", "ttf", "woff",
+    "woff2",
+];
+
+/// Compressed formats beyond the ones that can be extracted
+const ARCHIVE_EXTENSIONS: &[&str] = &["gz", "xz", "bz2", "zst", "7z", "rar"];
+
+impl FileKind {
+    /// Category from the file name alone; None when the name is inconclusive
+    pub fn from_name(name: &str) -> Option<Self> {
+        let lower = name.to_lowercase();
+        if ArchiveKind::from_name(&lower).is_some() {
+            return Some(FileKind::Archive);
+        }
+        if is_image_name(&lower) {
+            return Some(FileKind::Image);
+        }
+        let extension = lower.rsplit_once('.').map(|(_, ext)| ext)?;
+        // Rotated logs keep a numeric suffix, e.g. syslog.log.1
+        let rotated_log = lower.contains(".log.") && extension.chars().all(|c| c.is_ascii_digit());
+        if extension == "log" || rotated_log {
+            Some(FileKind::Log)
+        } else if TEXT_EXTENSIONS.contains(&extension) {
+            Some(FileKind::Text)
+        } else if ARCHIVE_EXTENSIONS.contains(&extension) {
+            Some(FileKind::Archive)
+        } else if BINARY_EXTENSIONS.contains(&extension) {
+            Some(FileKind::Binary)
+        } else {
+            None
+        }
+    }
+
+    /// Category from a known magic number at the start of a file
+    fn from_signature(bytes: &[u8]) -> Option<Self> {
+        const SIGNATURES: &[(&[u8], FileKind)] = &[
+            (b"\x89PNG\r\n\x1a\n", FileKind::Image),
+            (b"\xff\xd8\xff", FileKind::Image),
+            (b"PK\x03\x04", FileKind::Archive),
+            (b"\x1f\x8b", FileKind::Archive),
+            (b"\xfd7zXZ\x00", FileKind::Archive),
+            (b"BZh", FileKind::Archive),
+            (b"\x7fELF", FileKind::Binary),
+            (b"%PDF", FileKind::Binary),
+        ];
+
+        SIGNATURES
+            .iter()
+            .find(|(magic, _)| bytes.starts_with(magic))
+            .map(|&(_, kind)| kind)
+    }
+
+    /// Category from the first bytes of a file
+    pub fn sniff(bytes: &[u8]) -> Self {
+        if let Some(kind) = Self::from_signature(bytes) {
+            return kind;
+        }
+        if bytes.contains(&0) || std::str::from_utf8(trim_partial_char(bytes)).is_err() {
+            FileKind::Binary
+        } else {
+            FileKind::Text
+        }
+    }
+
+    pub fn default_action(&self) -> OpenAction {
+        match self {
+            FileKind::Text | FileKind::Log => OpenAction::Editor,
+            FileKind::Image => OpenAction::Preview,
+            FileKind::Archive | FileKind::Binary => OpenAction::Hex,
+        }
+    }
+}
+
+/// Content that would be damaged by a text editor: a known binary format or
+/// NUL bytes. Unlike `sniff`, text in a legacy encoding does not count.
+pub fn looks_binary(head: &[u8]) -> bool {
+    FileKind::from_signature(head).is_some() || head.contains(&0)
+}
+
+/// A sniffed prefix may end in the middle of a multi-byte character
+fn trim_partial_char(bytes: &[u8]) -> &[u8] {
+    match std::str::from_utf8(bytes) {
+        Err(e) if e.error_len().is_none() => &bytes[..e.valid_up_to()],
+        _ => bytes,
+    }
+}
+
+/// Action configured for the file's extension. Keys are extensions without
+/// the dot; the longest matching one wins, so `tar.gz` beats `gz`.
+pub fn configured_action(open_with: &HashMap<String, OpenAction>, name: &str) -> Option<OpenAction> {
+    let lower = name.to_lowercase();
+    open_with
+        .iter()
+        .filter(|(extension, _)| lower.ends_with(&format!(".{}", extension.to_lowercase())))
+        .max_by_key(|(extension, _)| extension.len())
+        .map(|(_, &action)| action)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kind_from_name() {
+        assert_eq!(FileKind::from_name("site.tar.gz"), Some(FileKind::Archive));
+        assert_eq!(FileKind::from_name("photo.JPG"), Some(FileKind::Image));
+        assert_eq!(FileKind::from_name("nginx.log.1"), Some(FileKind::Log));
+        assert_eq!(FileKind::from_name("nginx.log.2.gz"), Some(FileKind::Archive));
+        assert_eq!(FileKind::from_name("main.rs"), Some(FileKind::Text));
+        assert_eq!(FileKind::from_name("libfoo.so"), Some(FileKind::Binary));
+        assert_eq!(FileKind::from_name("Makefile"), None);
+    }
+
+    #[test]
+    fn test_sniff_magic_bytes() {
+        assert_eq!(FileKind::sniff(b"\x7fELF\x02\x01"), FileKind::Binary);
+        assert_eq!(FileKind::sniff(b"\x1f\x8b\x08\x00"), FileKind::Archive);
+        assert_eq!(FileKind::sniff(b"#!/bin/sh\necho hi\n"), FileKind::Text);
+        // Cut in the middle of "é"
+        assert_eq!(FileKind::sniff(b"caf\xc3"), FileKind::Text);
+        assert_eq!(FileKind::sniff(b"ab\x00cd"), FileKind::Binary);
+    }
+
+    #[test]
+    fn test_looks_binary() {
+        assert!(looks_binary(b"ustar\x00\x00"));
+        assert!(looks_binary(b"\x1f\x8b\x08\x00"));
+        assert!(!looks_binary(b"caf\xe9 = 1\n"));
+        assert!(!looks_binary(b"server {}\n"));
+    }
+
+    #[test]
+    fn test_configured_action_prefers_longest_extension() {
+        let open_with = HashMap::from([
+            ("log".to_string(), OpenAction::Follow),
+            ("gz".to_string(), OpenAction::Hex),
+            ("log.gz".to_string(), OpenAction::Editor),
+        ]);
+
+        assert_eq!(configured_action(&open_with, "app.LOG"), Some(OpenAction::Follow));
+        assert_eq!(configured_action(&open_with, "app.log.gz"), Some(OpenAction::Editor));
+        assert_eq!(configured_action(&open_with, "notes.txt"), None);
+    }
+}
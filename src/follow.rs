@@ -0,0 +1,450 @@
+use anyhow::{Context, Result};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::Paragraph,
+    Frame,
+};
+use russh_sftp::client::SftpSession;
+use std::collections::VecDeque;
+use std::io::SeekFrom;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::theme::Theme;
+
+/// Lines kept for scrolling back
+pub const FOLLOW_SCROLLBACK: usize = 10_000;
+
+/// How much of the end of the file to show when the view opens
+pub const FOLLOW_INITIAL_BYTES: u64 = 64 * 1024;
+
+/// How often to check the file for appended data
+pub const FOLLOW_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Most bytes read per poll, so a burst of output cannot stall the view
+const FOLLOW_READ_LIMIT: u64 = 1024 * 1024;
+
+/// Streaming view of a growing remote file, like `tail -f`
+pub struct FollowState {
+    pub remote_path: String,
+    lines: VecDeque<String>,
+    /// Bytes after the last newline, waiting for the rest of the line
+    partial: Vec<u8>,
+    /// Opened mid-file; the first line read is incomplete
+    skip_first_line: bool,
+    /// Next byte of the file to read
+    pub offset: u64,
+    /// Frozen view; new lines are buffered below it
+    pub paused: bool,
+    /// Lines between the bottom of the view and the end of the buffer
+    pub scroll: usize,
+    /// Lines appended since the view was paused
+    pub new_lines: usize,
+    /// Search prompt being typed
+    pub search_input: Option<String>,
+    pub query: Option<String>,
+    pub status_message: String,
+    pub viewport_height: usize,
+    pub should_quit: bool,
+}
+
+impl FollowState {
+    pub fn new(remote_path: String, offset: u64) -> Self {
+        Self {
+            remote_path,
+            lines: VecDeque::new(),
+            partial: Vec::new(),
+            skip_first_line: offset > 0,
+            offset,
+            paused: false,
+            scroll: 0,
+            new_lines: 0,
+            search_input: None,
+            query: None,
+            status_message: String::new(),
+            viewport_height: 20,
+            should_quit: false,
+        }
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Add bytes read from the end of the file
+    pub fn append(&mut self, bytes: &[u8]) {
+        self.partial.extend_from_slice(bytes);
+
+        let Some(last_newline) = self.partial.iter().rposition(|&b| b == b'\n') else {
+            return;
+        };
+        let rest = self.partial.split_off(last_newline + 1);
+        let complete = std::mem::replace(&mut self.partial, rest);
+
+        let mut added = 0;
+        for line in complete[..complete.len() - 1].split(|&b| b == b'\n') {
+            if self.skip_first_line {
+                self.skip_first_line = false;
+                continue;
+            }
+            let line = String::from_utf8_lossy(line);
+            self.lines.push_back(line.trim_end_matches('\r').to_string());
+            added += 1;
+        }
+
+        if self.paused {
+            self.scroll += added;
+            self.new_lines += added;
+        }
+
+        while self.lines.len() > FOLLOW_SCROLLBACK {
+            self.lines.pop_front();
+        }
+        self.clamp_scroll();
+    }
+
+    /// The file shrank (truncated or rotated); start again from the top
+    pub fn reset(&mut self) {
+        self.lines.push_back(String::from("--- file truncated ---"));
+        self.partial.clear();
+        self.skip_first_line = false;
+        self.offset = 0;
+        if self.paused {
+            self.scroll += 1;
+        }
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.lines.len().saturating_sub(self.viewport_height)
+    }
+
+    fn clamp_scroll(&mut self) {
+        self.scroll = self.scroll.min(self.max_scroll());
+    }
+
+    /// Range of buffer lines shown in the view
+    pub fn visible_range(&self) -> (usize, usize) {
+        let end = self.lines.len() - self.scroll.min(self.lines.len());
+        (end.saturating_sub(self.viewport_height), end)
+    }
+
+    pub fn toggle_pause(&mut self) {
+        if self.paused {
+            self.resume();
+        } else {
+            self.paused = true;
+            self.new_lines = 0;
+        }
+    }
+
+    /// Jump back to the end and keep following
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.scroll = 0;
+        self.new_lines = 0;
+    }
+
+    pub fn scroll_up(&mut self, amount: usize) {
+        if !self.paused {
+            self.toggle_pause();
+        }
+        self.scroll += amount;
+        self.clamp_scroll();
+    }
+
+    pub fn scroll_down(&mut self, amount: usize) {
+        self.scroll = self.scroll.saturating_sub(amount);
+    }
+
+    pub fn scroll_to_top(&mut self) {
+        self.scroll_up(self.lines.len());
+    }
+
+    /// Index of the bottom line in view
+    fn anchor(&self) -> usize {
+        self.visible_range().1.saturating_sub(1)
+    }
+
+    /// Move the view to a match of the query. `older` searches towards the
+    /// start of the buffer. With `include_current` the bottom line counts.
+    pub fn find(&mut self, older: bool, include_current: bool) -> bool {
+        let Some(query) = self.query.as_ref().map(|q| q.to_ascii_lowercase()) else {
+            return false;
+        };
+        let anchor = self.anchor();
+        let matches = |line: &String| line.to_ascii_lowercase().contains(&query);
+
+        let found = if older {
+            let end = if include_current { anchor + 1 } else { anchor };
+            (0..end.min(self.lines.len())).rev().find(|&i| matches(&self.lines[i]))
+        } else {
+            (anchor + 1..self.lines.len()).find(|&i| matches(&self.lines[i]))
+        };
+
+        match found {
+            Some(index) => {
+                if !self.paused {
+                    self.toggle_pause();
+                }
+                self.scroll = self.lines.len() - 1 - index;
+                self.clamp_scroll();
+                self.status_message.clear();
+                true
+            }
+            None => {
+                self.status_message = format!("Pattern not found: {}", self.query.as_deref().unwrap_or_default());
+                false
+            }
+        }
+    }
+}
+
+/// Read whatever was appended to the file since the last poll
+pub async fn poll_file(sftp: &SftpSession, follow: &mut FollowState) -> Result<()> {
+    let size = sftp
+        .metadata(&follow.remote_path)
+        .await
+        .context("Failed to stat file")?
+        .size
+        .unwrap_or(0);
+
+    if size < follow.offset {
+        follow.reset();
+    }
+    if size == follow.offset {
+        return Ok(());
+    }
+
+    let mut file = sftp
+        .open(&follow.remote_path)
+        .await
+        .context("Failed to open remote file")?;
+    file.seek(SeekFrom::Start(follow.offset))
+        .await
+        .context("Failed to seek in remote file")?;
+
+    let mut content = Vec::new();
+    file.take((size - follow.offset).min(FOLLOW_READ_LIMIT))
+        .read_to_end(&mut content)
+        .await
+        .context("Failed to read from remote file")?;
+
+    follow.offset += content.len() as u64;
+    follow.append(&content);
+    Ok(())
+}
+
+/// Line with every case-insensitive occurrence of `query` highlighted
+fn highlight<'a>(line: &'a str, query: Option<&str>, theme: &Theme) -> Line<'a> {
+    let Some(query) = query.filter(|q| !q.is_empty()) else {
+        return Line::from(line);
+    };
+
+    // ASCII lowercasing keeps byte offsets identical to the original
+    let lower = line.to_ascii_lowercase();
+    let query = query.to_ascii_lowercase();
+    let mut spans = Vec::new();
+    let mut start = 0;
+    while let Some(found) = lower[start..].find(&query) {
+        let begin = start + found;
+        let end = begin + query.len();
+        spans.push(Span::raw(&line[start..begin]));
+        spans.push(Span::styled(
+            &line[begin..end],
+            Style::default().fg(theme.highlight_fg).bg(theme.highlight_bg),
+        ));
+        start = end;
+    }
+    spans.push(Span::raw(&line[start..]));
+    Line::from(spans)
+}
+
+pub fn render_follow(f: &mut Frame, area: Rect, follow: &FollowState, theme: &Theme) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .split(area);
+
+    let mode_indicator = if follow.paused {
+        Span::styled("PAUSED", Style::default().fg(theme.warning).add_modifier(Modifier::BOLD))
+    } else {
+        Span::styled("FOLLOW", Style::default().fg(theme.success).add_modifier(Modifier::BOLD))
+    };
+    let mut header = vec![
+        mode_indicator,
+        Span::raw(" | "),
+        Span::raw(&follow.remote_path),
+        Span::raw(format!(" | {} lines", follow.line_count())),
+    ];
+    if follow.new_lines > 0 {
+        header.push(Span::styled(
+            format!(" (+{} new)", follow.new_lines),
+            Style::default().fg(theme.accent),
+        ));
+    }
+    f.render_widget(Paragraph::new(Line::from(header)), chunks[0]);
+
+    let (start, end) = follow.visible_range();
+    let lines: Vec<Line> = follow
+        .lines
+        .range(start..end)
+        .map(|line| highlight(line, follow.query.as_deref(), theme))
+        .collect();
+    f.render_widget(Paragraph::new(lines), chunks[1]);
+
+    let footer = if let Some(ref input) = follow.search_input {
+        Line::from(format!("/{}", input))
+    } else if !follow.status_message.is_empty() {
+        Line::from(follow.status_message.as_str())
+    } else {
+        Line::from(vec![
+            Span::styled("Space", Style::default().fg(theme.key)),
+            Span::raw(": Pause  "),
+            Span::styled("↑/↓ PgUp/PgDn", Style::default().fg(theme.key)),
+            Span::raw(": Scroll  "),
+            Span::styled("/", Style::default().fg(theme.key)),
+            Span::raw(": Search  "),
+            Span::styled("n/N", Style::default().fg(theme.key)),
+            Span::raw(": Older/Newer match  "),
+            Span::styled("G", Style::default().fg(theme.key)),
+            Span::raw(": Follow  "),
+            Span::styled("q", Style::default().fg(theme.key)),
+            Span::raw(": Close"),
+        ])
+    };
+    f.render_widget(Paragraph::new(footer), chunks[2]);
+}
+
+pub fn handle_follow_input(follow: &mut FollowState) -> Result<()> {
+    if event::poll(Duration::from_millis(100))?
+        && let Event::Key(key) = event::read()?
+    {
+        if follow.search_input.is_some() {
+            handle_search_key(follow, key);
+        } else {
+            handle_view_key(follow, key);
+        }
+    }
+    Ok(())
+}
+
+fn handle_view_key(follow: &mut FollowState, key: KeyEvent) {
+    let page = follow.viewport_height.max(1);
+    match key.code {
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            follow.should_quit = true;
+        }
+        KeyCode::Char('q') | KeyCode::Esc => follow.should_quit = true,
+        KeyCode::Char(' ') | KeyCode::Char('p') => follow.toggle_pause(),
+        KeyCode::Up | KeyCode::Char('k') => follow.scroll_up(1),
+        KeyCode::Down | KeyCode::Char('j') => follow.scroll_down(1),
+        KeyCode::PageUp => follow.scroll_up(page),
+        KeyCode::PageDown => follow.scroll_down(page),
+        KeyCode::Home | KeyCode::Char('g') => follow.scroll_to_top(),
+        KeyCode::End | KeyCode::Char('G') => follow.resume(),
+        KeyCode::Char('/') => {
+            follow.search_input = Some(String::new());
+            follow.status_message.clear();
+        }
+        KeyCode::Char('n') => {
+            follow.find(true, false);
+        }
+        KeyCode::Char('N') => {
+            follow.find(false, false);
+        }
+        _ => {}
+    }
+}
+
+fn handle_search_key(follow: &mut FollowState, key: KeyEvent) {
+    let Some(input) = follow.search_input.as_mut() else {
+        return;
+    };
+
+    match key.code {
+        KeyCode::Esc => follow.search_input = None,
+        KeyCode::Backspace => {
+            input.pop();
+        }
+        KeyCode::Char(c) => input.push(c),
+        KeyCode::Enter => {
+            let query = follow.search_input.take().unwrap_or_default();
+            if query.is_empty() {
+                follow.query = None;
+            } else {
+                follow.query = Some(query);
+                follow.find(true, true);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn follow_with(lines: &[&str]) -> FollowState {
+        let mut follow = FollowState::new("/var/log/app.log".to_string(), 0);
+        follow.viewport_height = 2;
+        for line in lines {
+            follow.append(format!("{}\n", line).as_bytes());
+        }
+        follow
+    }
+
+    #[test]
+    fn test_append_waits_for_complete_lines() {
+        let mut follow = follow_with(&[]);
+        follow.append(b"first\r\nsec");
+        assert_eq!(follow.lines, ["first"]);
+
+        follow.append(b"ond\n");
+        assert_eq!(follow.lines, ["first", "second"]);
+    }
+
+    #[test]
+    fn test_append_skips_partial_first_line_mid_file() {
+        let mut follow = FollowState::new("/a".to_string(), 100);
+        follow.append(b"tail of a line\nwhole line\n");
+        assert_eq!(follow.lines, ["whole line"]);
+    }
+
+    #[test]
+    fn test_paused_view_stays_put() {
+        let mut follow = follow_with(&["a", "b", "c"]);
+        follow.toggle_pause();
+        follow.append(b"d\ne\n");
+
+        assert_eq!(follow.visible_range(), (1, 3));
+        assert_eq!(follow.new_lines, 2);
+
+        follow.resume();
+        assert_eq!(follow.visible_range(), (3, 5));
+    }
+
+    #[test]
+    fn test_find_moves_between_matches() {
+        let mut follow = follow_with(&["ERROR one", "ok", "error two", "ok", "ok"]);
+        follow.query = Some("error".to_string());
+
+        assert!(follow.find(true, true));
+        assert!(follow.paused);
+        assert_eq!(follow.anchor(), 2);
+
+        assert!(follow.find(true, false));
+        // The first line cannot be the bottom of a two-line view
+        assert_eq!(follow.visible_range(), (0, 2));
+
+        assert!(follow.find(false, false));
+        assert_eq!(follow.anchor(), 2);
+        assert!(!follow.find(false, false));
+    }
+}
@@ -0,0 +1,148 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::app::fuzzy_match;
+use crate::atomic_file::write_atomic;
+
+/// Most directories remembered per connection; the least frecent go first
+const VISITED_LIMIT: usize = 500;
+
+const HOUR: i64 = 60 * 60;
+const DAY: i64 = 24 * HOUR;
+const WEEK: i64 = 7 * DAY;
+
+/// A directory entered on a connection
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VisitedDir {
+    pub path: String,
+    pub count: u32,
+    /// Unix time of the last visit
+    pub last_visit: i64,
+}
+
+impl VisitedDir {
+    /// Visits weighted by how recent the last one was, as zoxide ranks them
+    fn score(&self, now: i64) -> f64 {
+        let weight = match now - self.last_visit {
+            age if age < HOUR => 4.0,
+            age if age < DAY => 2.0,
+            age if age < WEEK => 0.5,
+            _ => 0.25,
+        };
+        self.count as f64 * weight
+    }
+}
+
+/// Directories entered on one connection, kept under `~/.config/bssh/` so
+/// the `z` prompt can jump to the likeliest match
+#[derive(Debug, Default)]
+pub struct VisitedDirs {
+    path: Option<PathBuf>,
+    dirs: Vec<VisitedDir>,
+}
+
+impl VisitedDirs {
+    /// The connection's directories; empty if there are none yet or the
+    /// file cannot be read
+    pub fn load(host: &str, port: u16, username: &str) -> Self {
+        let path = visited_file_path(host, port, username).ok();
+        let dirs = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        Self { path, dirs }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(ref path) = self.path {
+            write_atomic(path, serde_json::to_string(&self.dirs)?)?;
+        }
+        Ok(())
+    }
+
+    pub fn visit(&mut self, path: &str, now: i64) {
+        match self.dirs.iter_mut().find(|dir| dir.path == path) {
+            Some(dir) => {
+                dir.count = dir.count.saturating_add(1);
+                dir.last_visit = now;
+            }
+            None => self.dirs.push(VisitedDir {
+                path: path.to_string(),
+                count: 1,
+                last_visit: now,
+            }),
+        }
+        if self.dirs.len() > VISITED_LIMIT {
+            let (least, _) = self
+                .dirs
+                .iter()
+                .enumerate()
+                .filter(|(_, dir)| dir.path != path)
+                .min_by(|(_, a), (_, b)| a.score(now).total_cmp(&b.score(now)))
+                .expect("more than one directory");
+            self.dirs.remove(least);
+        }
+    }
+
+    /// Every directory but `current`, most frecent first
+    pub fn ranked(&self, current: &str, now: i64) -> Vec<String> {
+        let mut dirs: Vec<&VisitedDir> = self.dirs.iter().filter(|dir| dir.path != current).collect();
+        dirs.sort_by(|a, b| b.score(now).total_cmp(&a.score(now)));
+        dirs.into_iter().map(|dir| dir.path.clone()).collect()
+    }
+}
+
+/// `z`-style match: the words of `query` fuzzily match the path in order,
+/// and the last one matches its last component, so `ngx` finds
+/// `/etc/nginx` but not `/etc/nginx/conf.d`
+pub fn jump_match(path: &str, query: &str) -> bool {
+    let words: Vec<&str> = query.split_whitespace().collect();
+    let Some(last) = words.last() else {
+        return true;
+    };
+    let name = path.rsplit('/').find(|segment| !segment.is_empty()).unwrap_or("/");
+    fuzzy_match(path, &words.concat()) && fuzzy_match(name, last)
+}
+
+fn visited_file_path(host: &str, port: u16, username: &str) -> Result<PathBuf> {
+    let config_dir = dirs::config_dir()
+        .or_else(|| dirs::home_dir().map(|h| h.join(".config")))
+        .ok_or_else(|| anyhow::anyhow!("Could not find config directory"))?;
+
+    let bssh_dir = config_dir.join("bssh");
+    fs::create_dir_all(&bssh_dir)?;
+
+    Ok(bssh_dir.join(format!("dirs_{}@{}_{}.json", username, host, port)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jump_match() {
+        assert!(jump_match("/etc/nginx", "ngx"));
+        assert!(jump_match("/etc/nginx", "etc ngx"));
+        assert!(!jump_match("/etc/nginx/conf.d", "ngx"));
+        assert!(!jump_match("/etc/nginx", "ngx etc"));
+        assert!(jump_match("/var/log", ""));
+    }
+
+    #[test]
+    fn test_frequent_and_recent_dirs_rank_first() {
+        let now = 1_700_000_000;
+        let mut visited = VisitedDirs::default();
+        for _ in 0..3 {
+            visited.visit("/var/log", now - 2 * WEEK);
+        }
+        visited.visit("/etc/nginx", now - 10);
+        visited.visit("/srv/app", now - 2 * DAY);
+        visited.visit("/srv/app", now - DAY - 10);
+
+        assert_eq!(visited.ranked("/", now), ["/etc/nginx", "/srv/app", "/var/log"]);
+        assert_eq!(visited.ranked("/etc/nginx", now), ["/srv/app", "/var/log"]);
+    }
+}
@@ -0,0 +1,164 @@
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::Paragraph,
+    Frame,
+};
+use std::fmt::Write;
+use std::time::Duration;
+
+use crate::theme::Theme;
+
+/// Most bytes loaded into the hex viewer
+pub const HEX_VIEW_BYTES: usize = 1024 * 1024;
+
+const BYTES_PER_LINE: usize = 16;
+
+/// Read-only hex dump of the start of a file
+pub struct HexViewState {
+    pub remote_path: String,
+    bytes: Vec<u8>,
+    /// The file is larger than what was loaded
+    pub truncated: bool,
+    /// First line in view
+    pub scroll: usize,
+    pub viewport_height: usize,
+    pub should_quit: bool,
+}
+
+impl HexViewState {
+    pub fn new(remote_path: String, bytes: Vec<u8>, truncated: bool) -> Self {
+        Self {
+            remote_path,
+            bytes,
+            truncated,
+            scroll: 0,
+            viewport_height: 20,
+            should_quit: false,
+        }
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.bytes.len().div_ceil(BYTES_PER_LINE)
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.line_count().saturating_sub(self.viewport_height)
+    }
+
+    pub fn scroll_by(&mut self, lines: isize) {
+        self.scroll = self.scroll.saturating_add_signed(lines).min(self.max_scroll());
+    }
+}
+
+/// One `xxd`-style line: offset, hex bytes and printable ASCII
+pub fn format_hex_line(offset: usize, chunk: &[u8]) -> String {
+    let mut line = format!("{:08x}  ", offset);
+    for i in 0..BYTES_PER_LINE {
+        match chunk.get(i) {
+            Some(byte) => {
+                let _ = write!(line, "{:02x} ", byte);
+            }
+            None => line.push_str("   "),
+        }
+        if i == BYTES_PER_LINE / 2 - 1 {
+            line.push(' ');
+        }
+    }
+    line.push(' ');
+    line.extend(chunk.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }));
+    line
+}
+
+pub fn render_hex_view(f: &mut Frame, area: Rect, view: &HexViewState, theme: &Theme) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .split(area);
+
+    let mut header = vec![
+        Span::styled("HEX", Style::default().fg(theme.special).add_modifier(Modifier::BOLD)),
+        Span::raw(" | "),
+        Span::raw(&view.remote_path),
+        Span::raw(format!(" | {} bytes", view.bytes.len())),
+    ];
+    if view.truncated {
+        header.push(Span::styled(" (truncated)", Style::default().fg(theme.muted)));
+    }
+    f.render_widget(Paragraph::new(Line::from(header)), chunks[0]);
+
+    let lines: Vec<Line> = view
+        .bytes
+        .chunks(BYTES_PER_LINE)
+        .enumerate()
+        .skip(view.scroll)
+        .take(chunks[1].height as usize)
+        .map(|(i, chunk)| Line::from(format_hex_line(i * BYTES_PER_LINE, chunk)))
+        .collect();
+    f.render_widget(Paragraph::new(lines), chunks[1]);
+
+    let footer = Line::from(vec![
+        Span::styled("↑/↓ PgUp/PgDn", Style::default().fg(theme.key)),
+        Span::raw(": Scroll  "),
+        Span::styled("g/G", Style::default().fg(theme.key)),
+        Span::raw(": Start/End  "),
+        Span::styled("q", Style::default().fg(theme.key)),
+        Span::raw(": Close"),
+    ]);
+    f.render_widget(Paragraph::new(footer), chunks[2]);
+}
+
+pub fn handle_hex_view_input(view: &mut HexViewState) -> Result<()> {
+    if event::poll(Duration::from_millis(100))?
+        && let Event::Key(key) = event::read()?
+    {
+        let page = view.viewport_height.max(1) as isize;
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                view.should_quit = true;
+            }
+            KeyCode::Char('q') | KeyCode::Esc => view.should_quit = true,
+            KeyCode::Up | KeyCode::Char('k') => view.scroll_by(-1),
+            KeyCode::Down | KeyCode::Char('j') => view.scroll_by(1),
+            KeyCode::PageUp => view.scroll_by(-page),
+            KeyCode::PageDown => view.scroll_by(page),
+            KeyCode::Home | KeyCode::Char('g') => view.scroll = 0,
+            KeyCode::End | KeyCode::Char('G') => view.scroll = view.max_scroll(),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_hex_line() {
+        let line = format_hex_line(0x20, b"Hello\x00world!");
+        assert_eq!(
+            line,
+            "00000020  48 65 6c 6c 6f 00 77 6f  72 6c 64 21              Hello.world!"
+        );
+    }
+
+    #[test]
+    fn test_scroll_is_clamped() {
+        let mut view = HexViewState::new("/bin/ls".to_string(), vec![0; 100], false);
+        view.viewport_height = 4;
+
+        view.scroll_by(-3);
+        assert_eq!(view.scroll, 0);
+        view.scroll_by(10);
+        // 7 lines of 16 bytes, 4 visible
+        assert_eq!(view.scroll, 3);
+    }
+}
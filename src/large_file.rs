@@ -0,0 +1,271 @@
+use anyhow::{Context, Result};
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::Paragraph,
+    Frame,
+};
+use russh_sftp::client::SftpSession;
+use std::io::SeekFrom;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::theme::Theme;
+use crate::tui::format_size;
+
+/// Files larger than this open in the read-only windowed viewer instead of
+/// the editor, which holds the whole file in memory
+pub const LARGE_FILE_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Files larger than this open in the editor with a warning
+pub const LARGE_FILE_WARN_BYTES: u64 = 2 * 1024 * 1024;
+
+/// Bytes read per window
+const WINDOW_BYTES: u64 = 256 * 1024;
+
+/// Read-only view of a file too large to load, holding one window of lines
+pub struct LargeFileView {
+    pub remote_path: String,
+    pub size: u64,
+    lines: Vec<String>,
+    /// File offset where each loaded line starts
+    offsets: Vec<u64>,
+    /// File offset just past the last loaded line
+    window_end: u64,
+    /// First loaded line in view
+    pub scroll: usize,
+    /// Offset whose line should be at the top once the next window loads
+    wanted: Option<u64>,
+    /// The top line before the pending load, and how far to scroll from it
+    anchor: (u64, isize),
+    pub status_message: String,
+    pub viewport_height: usize,
+    pub should_quit: bool,
+}
+
+impl LargeFileView {
+    pub fn new(remote_path: String, size: u64) -> Self {
+        Self {
+            remote_path,
+            size,
+            lines: Vec::new(),
+            offsets: Vec::new(),
+            window_end: 0,
+            scroll: 0,
+            wanted: Some(0),
+            anchor: (0, 0),
+            status_message: format!("{} file opened read-only", format_size(size)),
+            viewport_height: 20,
+            should_quit: false,
+        }
+    }
+
+    /// Offset to read the next window from, if one is needed. Reading starts
+    /// one byte early so a window that starts on a line boundary is
+    /// recognised as such.
+    pub fn take_read(&mut self) -> Option<u64> {
+        self.wanted.take().map(|offset| offset.saturating_sub(1))
+    }
+
+    /// Replace the window with `bytes` read from `read_from`. Lines start
+    /// after the first newline unless the read began at the start of the file.
+    pub fn fill(&mut self, read_from: u64, bytes: &[u8]) {
+        let skip = match read_from {
+            0 => 0,
+            _ => bytes.iter().position(|&b| b == b'\n').map_or(0, |i| i + 1),
+        };
+        let at_eof = read_from + bytes.len() as u64 >= self.size;
+
+        self.lines.clear();
+        self.offsets.clear();
+        let mut start = skip;
+        while start < bytes.len() {
+            let end = match bytes[start..].iter().position(|&b| b == b'\n') {
+                Some(i) => start + i,
+                // Keep a trailing partial line only at the end of the file
+                None if at_eof || self.lines.is_empty() => bytes.len(),
+                None => break,
+            };
+            self.lines.push(String::from_utf8_lossy(&bytes[start..end]).trim_end_matches('\r').to_string());
+            self.offsets.push(read_from + start as u64);
+            start = end + 1;
+        }
+        self.window_end = read_from + start.min(bytes.len()) as u64;
+
+        let (top, delta) = std::mem::take(&mut self.anchor);
+        let index = self.offsets.partition_point(|&offset| offset < top);
+        self.scroll = index.saturating_add_signed(delta).min(self.max_scroll());
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.lines.len().saturating_sub(self.viewport_height)
+    }
+
+    fn top_offset(&self) -> u64 {
+        self.offsets.get(self.scroll).copied().unwrap_or(0)
+    }
+
+    pub fn scroll_by(&mut self, lines: isize) {
+        let target = self.scroll as isize + lines;
+        let before_window = target < 0 && self.offsets.first().is_some_and(|&first| first > 0);
+        let past_window = target.max(0) as usize + self.viewport_height > self.lines.len() && self.window_end < self.size;
+
+        if before_window || past_window {
+            let top = self.top_offset();
+            self.anchor = (top, lines);
+            self.wanted = Some(if before_window { top.saturating_sub(WINDOW_BYTES / 2) } else { top });
+        } else {
+            self.scroll = (target.max(0) as usize).min(self.max_scroll());
+        }
+    }
+
+    pub fn jump_to_start(&mut self) {
+        self.anchor = (0, 0);
+        self.wanted = Some(0);
+    }
+
+    pub fn jump_to_end(&mut self) {
+        self.anchor = (self.size, 0);
+        self.wanted = Some(self.size.saturating_sub(WINDOW_BYTES / 2));
+    }
+
+    /// Share of the file above the top line
+    pub fn percent(&self) -> u64 {
+        (self.top_offset() * 100).checked_div(self.size).unwrap_or(100)
+    }
+}
+
+/// Read the window the view asked for, if any
+pub async fn load_window(sftp: &SftpSession, view: &mut LargeFileView) -> Result<()> {
+    let Some(read_from) = view.take_read() else {
+        return Ok(());
+    };
+
+    let mut file = sftp
+        .open(&view.remote_path)
+        .await
+        .context("Failed to open remote file")?;
+    file.seek(SeekFrom::Start(read_from))
+        .await
+        .context("Failed to seek in remote file")?;
+
+    let mut content = Vec::new();
+    file.take(WINDOW_BYTES)
+        .read_to_end(&mut content)
+        .await
+        .context("Failed to read from remote file")?;
+
+    view.fill(read_from, &content);
+    Ok(())
+}
+
+pub fn render_large_file(f: &mut Frame, area: Rect, view: &LargeFileView, theme: &Theme) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .split(area);
+
+    let header = Line::from(vec![
+        Span::styled("READ-ONLY", Style::default().fg(theme.error).add_modifier(Modifier::BOLD)),
+        Span::raw(" | "),
+        Span::raw(&view.remote_path),
+        Span::raw(format!(" | {} | {}%", format_size(view.size), view.percent())),
+    ]);
+    f.render_widget(Paragraph::new(header), chunks[0]);
+
+    let lines: Vec<Line> = view
+        .lines
+        .iter()
+        .skip(view.scroll)
+        .take(chunks[1].height as usize)
+        .map(|line| Line::from(line.as_str()))
+        .collect();
+    f.render_widget(Paragraph::new(lines), chunks[1]);
+
+    let footer = if view.status_message.is_empty() {
+        Line::from(vec![
+            Span::styled("↑/↓ PgUp/PgDn", Style::default().fg(theme.key)),
+            Span::raw(": Scroll  "),
+            Span::styled("g/G", Style::default().fg(theme.key)),
+            Span::raw(": Start/End  "),
+            Span::styled("q", Style::default().fg(theme.key)),
+            Span::raw(": Close"),
+        ])
+    } else {
+        Line::from(view.status_message.as_str())
+    };
+    f.render_widget(Paragraph::new(footer), chunks[2]);
+}
+
+pub fn handle_large_file_input(view: &mut LargeFileView) -> Result<()> {
+    if event::poll(Duration::from_millis(100))?
+        && let Event::Key(key) = event::read()?
+    {
+        view.status_message.clear();
+        let page = view.viewport_height.max(1) as isize;
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                view.should_quit = true;
+            }
+            KeyCode::Char('q') | KeyCode::Esc => view.should_quit = true,
+            KeyCode::Up | KeyCode::Char('k') => view.scroll_by(-1),
+            KeyCode::Down | KeyCode::Char('j') => view.scroll_by(1),
+            KeyCode::PageUp => view.scroll_by(-page),
+            KeyCode::PageDown | KeyCode::Char(' ') => view.scroll_by(page),
+            KeyCode::Home | KeyCode::Char('g') => view.jump_to_start(),
+            KeyCode::End | KeyCode::Char('G') => view.jump_to_end(),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// File of `count` lines "line N\n", 7-8 bytes each
+    fn file(count: usize) -> Vec<u8> {
+        (0..count).flat_map(|i| format!("line {}\n", i).into_bytes()).collect()
+    }
+
+    fn load(view: &mut LargeFileView, content: &[u8], window: usize) {
+        let read_from = view.take_read().unwrap();
+        let end = (read_from as usize).saturating_add(window).min(content.len());
+        view.fill(read_from, &content[read_from as usize..end]);
+    }
+
+    #[test]
+    fn test_windows_start_on_line_boundaries() {
+        let content = file(100);
+        let mut view = LargeFileView::new("/var/log/big.log".to_string(), content.len() as u64);
+        view.viewport_height = 5;
+
+        load(&mut view, &content, 80);
+        assert_eq!(view.lines[0], "line 0");
+        // The window ends inside "line 11"; the partial line is dropped
+        assert_eq!(view.lines.last().unwrap(), "line 10");
+
+        view.scroll_by(3);
+        assert_eq!(view.take_read(), None);
+        // Scrolling past the window reads the next one from the top line
+        view.scroll_by(5);
+        assert_eq!(view.take_read(), Some(20));
+        view.scroll_by(5);
+        load(&mut view, &content, 80);
+        assert_eq!(view.lines[0], "line 3");
+        assert_eq!(view.lines[view.scroll], "line 8");
+
+        view.jump_to_end();
+        load(&mut view, &content, usize::MAX);
+        assert_eq!(view.lines.last().unwrap(), "line 99");
+        assert_eq!(view.lines.len() - view.scroll, 5);
+        assert_eq!(view.percent(), 94);
+    }
+}
@@ -0,0 +1,158 @@
+use anyhow::{Context, Result};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use uzers::{Groups, Users, UsersCache};
+
+use crate::app::{sort_entries, FileEntry};
+
+/// Name of user `uid`, or the number if it has no passwd entry
+fn user_name(users: &UsersCache, uid: u32) -> String {
+    users
+        .get_user_by_uid(uid)
+        .map(|user| user.name().to_string_lossy().to_string())
+        .unwrap_or_else(|| uid.to_string())
+}
+
+/// Name of group `gid`, or the number if it has no group entry
+fn group_name(users: &UsersCache, gid: u32) -> String {
+    users
+        .get_group_by_gid(gid)
+        .map(|group| group.name().to_string_lossy().to_string())
+        .unwrap_or_else(|| gid.to_string())
+}
+
+// `&mut` only because a shared `UsersCache` cannot be held across an await
+async fn read_entry(users: &mut UsersCache, path: &Path, name: String) -> FileEntry {
+    let full_path = path.join(&name).to_string_lossy().to_string();
+
+    let Ok(meta) = fs::symlink_metadata(path.join(&name)).await else {
+        return FileEntry::new(name, full_path, false);
+    };
+
+    let link_target = if meta.file_type().is_symlink() {
+        fs::read_link(path.join(&name))
+            .await
+            .ok()
+            .map(|target| target.to_string_lossy().to_string())
+    } else {
+        None
+    };
+    let target_meta = match link_target {
+        Some(_) => fs::metadata(path.join(&name)).await.ok(),
+        None => Some(meta.clone()),
+    };
+    let broken_link = target_meta.is_none();
+    // Links take their type and size from what they point at
+    let shown = target_meta.as_ref().unwrap_or(&meta);
+
+    FileEntry {
+        name,
+        path: full_path,
+        is_dir: shown.is_dir(),
+        size: if broken_link { 0 } else { shown.len() },
+        modified: Some(meta.mtime()),
+        accessed: Some(meta.atime()),
+        permissions: Some(meta.permissions().mode()),
+        owner: Some(user_name(users, meta.uid())),
+        group: Some(group_name(users, meta.gid())),
+        link_target,
+        broken_link,
+    }
+}
+
+/// `path` as typed, with a leading `~` standing for the home directory
+pub fn expand_home(path: &str) -> PathBuf {
+    let rest = match path.strip_prefix('~') {
+        Some("") => "",
+        Some(rest) if rest.starts_with('/') => &rest[1..],
+        _ => return PathBuf::from(path),
+    };
+    match dirs::home_dir() {
+        Some(home) => home.join(rest),
+        None => PathBuf::from(path),
+    }
+}
+
+/// The local file or directory at `path`, if there is one
+pub async fn stat(path: &Path) -> Option<FileEntry> {
+    fs::symlink_metadata(path).await.ok()?;
+    let name = path.file_name()?.to_string_lossy().to_string();
+    let parent = path.parent().filter(|parent| !parent.as_os_str().is_empty());
+    Some(read_entry(&mut UsersCache::new(), parent.unwrap_or(Path::new(".")), name).await)
+}
+
+/// List a local directory, with a `..` entry unless it is the root. Produces
+/// the same entries as the remote listing so local pickers can share its
+/// sorting and filtering.
+pub async fn list_directory(path: &Path) -> Result<Vec<FileEntry>> {
+    let mut reader = fs::read_dir(path)
+        .await
+        .with_context(|| format!("Failed to read directory {}", path.display()))?;
+
+    let mut files = Vec::new();
+    if path.parent().is_some() {
+        files.push(FileEntry::parent());
+    }
+
+    // Owners repeat, so look each one up once
+    let mut users = UsersCache::new();
+    while let Some(entry) = reader.next_entry().await.context("Failed to read directory entry")? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        files.push(read_entry(&mut users, path, name).await);
+    }
+
+    sort_entries(&mut files);
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_list_directory_sorts_and_describes_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("b.txt"), "hello").unwrap();
+        std::fs::create_dir(dir.path().join("a_dir")).unwrap();
+        std::os::unix::fs::symlink("missing", dir.path().join("dangling")).unwrap();
+
+        let files = list_directory(dir.path()).await.unwrap();
+        let names: Vec<&str> = files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["..", "a_dir", "b.txt", "dangling"]);
+
+        let file = &files[2];
+        assert_eq!(file.size, 5);
+        assert!(file.path.ends_with("/b.txt"));
+
+        let link = &files[3];
+        assert!(link.broken_link);
+        assert_eq!(link.link_target.as_deref(), Some("missing"));
+    }
+
+    #[tokio::test]
+    async fn test_stat_names_the_owner() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        assert!(stat(&path).await.is_none());
+
+        std::fs::write(&path, "hi").unwrap();
+        let file = stat(&path).await.unwrap();
+        assert_eq!((file.name.as_str(), file.size), ("a.txt", 2));
+        // Whoever runs the tests owns the file, named if they have an entry
+        let uid = uzers::get_current_uid();
+        let expected = uzers::get_user_by_uid(uid)
+            .map(|user| user.name().to_string_lossy().to_string())
+            .unwrap_or_else(|| uid.to_string());
+        assert_eq!(file.owner, Some(expected));
+    }
+
+    #[test]
+    fn test_expand_home() {
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(expand_home("~"), home);
+        assert_eq!(expand_home("~/notes.txt"), home.join("notes.txt"));
+        assert_eq!(expand_home("~bob/notes.txt"), PathBuf::from("~bob/notes.txt"));
+        assert_eq!(expand_home("./notes.txt"), PathBuf::from("./notes.txt"));
+    }
+}
@@ -0,0 +1,147 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use serde::Serialize;
+use std::path::PathBuf;
+
+use crate::app::FileEntry;
+use crate::connections::load_connections;
+use crate::exec_command::connect;
+use crate::file_ops::list_directory;
+
+/// `bssh ls DESTINATION[:PATH]`: a directory listing for other tools
+#[derive(Args, Debug, PartialEq)]
+pub struct LsArgs {
+    /// Saved connection name or [user@]host[:port], then `:PATH`; the home
+    /// directory without one
+    #[arg(value_name = "DESTINATION[:PATH]")]
+    pub target: String,
+    /// Print a JSON array instead of one tab-separated line per entry
+    #[arg(long = "json")]
+    pub json: bool,
+    /// Identity file (private key) for authentication
+    #[arg(short = 'i', long = "identity", value_name = "FILE")]
+    pub identity: Option<PathBuf>,
+    /// Port to connect to on the remote host
+    #[arg(short = 'p', long = "port", value_name = "PORT")]
+    pub port: Option<u16>,
+}
+
+/// One entry of the listing as printed
+#[derive(Serialize, Debug, PartialEq)]
+struct ListedEntry<'a> {
+    name: &'a str,
+    size: u64,
+    /// Permission bits in octal, e.g. `755`
+    mode: Option<String>,
+    /// Unix time of the last modification
+    mtime: Option<i64>,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<&'a str>,
+}
+
+impl<'a> From<&'a FileEntry> for ListedEntry<'a> {
+    fn from(entry: &'a FileEntry) -> Self {
+        let kind = match (&entry.link_target, entry.is_dir) {
+            (Some(_), _) => "symlink",
+            (None, true) => "dir",
+            (None, false) => "file",
+        };
+        Self {
+            name: &entry.name,
+            size: entry.size,
+            mode: entry.permissions.map(|mode| format!("{:o}", mode & 0o7777)),
+            mtime: entry.modified,
+            kind,
+            target: entry.link_target.as_deref(),
+        }
+    }
+}
+
+impl ListedEntry<'_> {
+    /// `type mode size mtime name`, with `-` for what the server did not send
+    fn tsv(&self) -> String {
+        let mode = self.mode.as_deref().unwrap_or("-");
+        let mtime = self.mtime.map_or(String::from("-"), |mtime| mtime.to_string());
+        format!("{}\t{}\t{}\t{}\t{}", self.kind, mode, self.size, mtime, self.name)
+    }
+}
+
+/// Split `DESTINATION[:PATH]` at the last colon. Digits after it are the
+/// port of `host:port`, unless the destination already has a port or is
+/// one of the `saved` connection names. A URL carries its path itself.
+fn split_target<'a>(target: &'a str, saved: &[String]) -> (&'a str, Option<&'a str>) {
+    if target.contains("://") {
+        return (target, None);
+    }
+    match target.rsplit_once(':') {
+        Some((destination, "")) => (destination, None),
+        Some((destination, path))
+            if !path.chars().all(|c| c.is_ascii_digit())
+                || destination.contains(':')
+                || saved.iter().any(|name| name == destination) =>
+        {
+            (destination, Some(path))
+        }
+        _ => (target, None),
+    }
+}
+
+/// List the directory and print it
+pub async fn run(args: LsArgs) -> Result<()> {
+    let saved: Vec<String> = load_connections().unwrap_or_default().into_iter().map(|c| c.name).collect();
+    let (destination, path) = split_target(&args.target, &saved);
+    let (mut client, url_path) = connect(destination, args.port, args.identity).await?;
+    let path = path.or(url_path.as_deref()).unwrap_or(".");
+    let sftp = client.open_sftp().await.context("Failed to open SFTP session")?;
+
+    let files = list_directory(&sftp, path)
+        .await
+        .with_context(|| format!("Failed to list {}", path))?;
+    let entries: Vec<ListedEntry> = files.iter().filter(|entry| entry.name != "..").map(ListedEntry::from).collect();
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+    } else {
+        for entry in &entries {
+            println!("{}", entry.tsv());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_target() {
+        let saved = [String::from("prod")];
+        assert_eq!(split_target("prod:/var/www", &saved), ("prod", Some("/var/www")));
+        assert_eq!(split_target("deploy@web-1:2222:logs", &saved), ("deploy@web-1:2222", Some("logs")));
+        assert_eq!(split_target("deploy@web-1:2222", &saved), ("deploy@web-1:2222", None));
+        assert_eq!(split_target("prod:", &saved), ("prod", None));
+        assert_eq!(split_target("prod", &saved), ("prod", None));
+        assert_eq!(split_target("sftp://web-1:2222/srv", &saved), ("sftp://web-1:2222/srv", None));
+
+        // A directory named by digits, after a saved name or a port
+        assert_eq!(split_target("prod:2024", &saved), ("prod", Some("2024")));
+        assert_eq!(split_target("web-1:2222:2024", &saved), ("web-1:2222", Some("2024")));
+    }
+
+    #[test]
+    fn test_entries_are_printed_with_their_type_and_mode() {
+        let mut entry = FileEntry::new(String::from("current"), String::from("/srv/current"), true);
+        entry.permissions = Some(0o120777);
+        entry.link_target = Some(String::from("/srv/releases/42"));
+        entry.size = 4096;
+        let listed = ListedEntry::from(&entry);
+        assert_eq!(listed.tsv(), "symlink\t777\t4096\t-\tcurrent");
+
+        let json = serde_json::to_value(&listed).unwrap();
+        assert_eq!(json["type"], "symlink");
+        assert_eq!(json["mode"], "777");
+        assert_eq!(json["target"], "/srv/releases/42");
+    }
+}
@@ -1,33 +1,101 @@
 mod app;
+mod atomic_file;
+mod command_output;
+mod auth_dialog;
+mod config;
+mod conn_command;
+mod connection_import;
+mod connect_dialog;
+mod destination;
 mod connection_selector;
 mod connections;
 mod editor;
+mod exec_command;
 mod file_ops;
+mod filetype;
+mod follow;
+mod frecency;
+mod hex_view;
+mod large_file;
+mod local_fs;
+mod ls_command;
+mod notifications;
 mod ssh;
 mod state;
+mod state_command;
+mod tabs;
+mod theme;
 mod shell;
+mod shell_history;
 mod tui;
+mod worker;
 
 use anyhow::{Context, Result};
-use app::App;
-use clap::Parser;
+use app::{
+    parse_batch_command, parse_owner_spec, App, BatchAction, ChmodState, ChownState,
+    ClipboardMode, ConnectionHealth, DirSelections, Direction, FileEntry, HistoryEntry, Preview,
+    PreviewContent, TransferBatch, SPINNER_FRAME_TIME,
+};
+use arboard::Clipboard;
+use clap::{Parser, Subcommand};
+use command_output::{handle_command_output_input, render_command_output, CommandOutputView};
+use config::Config;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use connect_dialog::open_transport_with_progress;
 use connection_selector::ConnectionSelector;
-use connections::{add_connection, load_connections, SavedConnection};
-use editor::{load_file_content, save_file_content, EditorState, handle_editor_input, render_editor};
+use connections::{add_connection, load_connections, record_connection, SavedConnection};
+use destination::parse_connection_string;
+use editor::{
+    backup_path, is_writable, load_file_content, remote_stamp, save_file_content, write_backup, BufferList, BufferRequest,
+    EditorState, Encoding, FilePositions, handle_editor_input, render_editor,
+};
+use file_ops::archive::ArchiveKind;
+use filetype::OpenAction;
+use frecency::VisitedDirs;
+use follow::{handle_follow_input, poll_file, render_follow, FollowState};
+use hex_view::{handle_hex_view_input, render_hex_view, HexViewState, HEX_VIEW_BYTES};
+use large_file::{handle_large_file_input, load_window, render_large_file, LargeFileView, LARGE_FILE_BYTES};
+use futures::FutureExt;
+use russh::{ChannelMsg, Sig};
 use russh_sftp::client::SftpSession;
-use shell::ShellSession;
+use shell::{ShellOptions, ShellSession, ShellTabs};
+use shell_history::ShellHistory;
+use ssh::client::{ConnectOptions, ConnectionInfo};
+use ssh::host_key::StrictHostKeyChecking;
+use ssh::socks::SocksProxy;
 use ssh::SshClient;
 use state::SessionState;
-use std::env;
+use tabs::{Tab, Tabs, MAX_TABS};
+use theme::Theme;
+use std::future::Future;
 use std::path::PathBuf;
-use tui::{handle_input, InputAction, Tui};
+use std::time::{Duration, Instant};
+use std::sync::Arc;
+use tui::confirm::Answer;
+use tui::jump::{JumpPrompt, JumpResult};
+use tui::palette::{Palette, PaletteResult};
+use tui::prompt::{Prompt, PromptResult};
+use tui::shell_switcher::{ShellEntry, ShellSwitcher, SwitcherResult};
+use tui::{entries_confirm, handle_input, help::HelpOverlay, hyperlink, keymap, overwrite_confirm, quit_confirm, InputAction, Tui};
+use worker::{Change, Job, JobId, JobResult, OnConflict, Worker};
+
+/// Largest file whose contents can be copied to the clipboard
+const CLIPBOARD_MAX_BYTES: u64 = 256 * 1024;
+
+/// How long to wait before retrying after a failed reconnect
+const RECONNECT_BACKOFF: Duration = Duration::from_secs(5);
+
 
 #[derive(Parser)]
 #[command(name = "bssh")]
 #[command(about = "Better SSH - A modern SSH file browser with TUI", long_about = None)]
 #[command(version)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Cli {
-    /// SSH connection string [user@]host[:port] or saved connection name
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// SSH connection string [user@]host[:port], sftp:// or ssh:// URL, or saved connection name
     #[arg(value_name = "DESTINATION")]
     destination: Option<String>,
 
@@ -46,133 +114,301 @@ struct Cli {
     /// Save this connection for future use
     #[arg(long = "save", value_name = "NAME")]
     save_as: Option<String>,
+
+    /// Seconds to wait for the connection before giving up
+    #[arg(long = "connect-timeout", value_name = "SECONDS")]
+    connect_timeout: Option<u64>,
+
+    /// Enable compression of the SSH transport
+    #[arg(short = 'C', long = "compress")]
+    compress: bool,
+
+    /// How to treat unknown or changed host keys
+    #[arg(long = "strict-host-key-checking", value_name = "MODE")]
+    strict_host_key_checking: Option<StrictHostKeyChecking>,
+
+    /// Start a local SOCKS5 proxy on this port, tunnelled over the connection
+    #[arg(short = 'D', long = "dynamic-forward", value_name = "PORT")]
+    dynamic_forward: Option<u16>,
+
+    /// Open a named session of the connection, with its own directory, tabs and layout
+    #[arg(long = "session", value_name = "NAME")]
+    session: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Manage saved connections
+    Conn {
+        #[command(subcommand)]
+        action: conn_command::ConnCommand,
+    },
+    /// Run a command on a host, passing its output through and exiting with its exit status
+    Exec(exec_command::ExecArgs),
+    /// List a remote directory as tab-separated lines or JSON
+    Ls(ls_command::LsArgs),
+    /// List or clear the sessions, shell histories and visited directories kept per connection
+    State {
+        #[command(subcommand)]
+        action: state_command::StateCommand,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
+    match cli.command {
+        Some(Command::Conn { action }) => return conn_command::run(action),
+        Some(Command::State { action }) => return state_command::run(action),
+        Some(Command::Ls(args)) => return ls_command::run(args).await,
+        Some(Command::Exec(args)) => {
+            let code = exec_command::run(args).await.unwrap_or_else(|e| {
+                eprintln!("Error: {:#}", e);
+                exec_command::EXIT_FAILURE
+            });
+            std::process::exit(code);
+        }
+        None => {}
+    }
+    if let Some(ref name) = cli.session {
+        SessionState::check_name(name)?;
+    }
+    let mut config = Config::load();
+    if config.state_max_age_days > 0
+        && let Err(e) = state::prune_state(Duration::from_secs(config.state_max_age_days * 24 * 60 * 60))
+    {
+        eprintln!("Warning: Failed to remove old session state: {}", e);
+    }
+    let (theme, theme_warnings) = Theme::from_config(&config.theme, &config.theme_colors);
+    for warning in &theme_warnings {
+        eprintln!("Warning: {}", warning);
+    }
 
     // If no destination provided, show connection selector
-    let (username, host, port, identity_file) = if let Some(dest) = cli.destination {
-        // Try to find saved connection by name first
-        let saved_connections = load_connections().unwrap_or_default();
-        if let Some(conn) = saved_connections.iter().find(|c| c.name == dest) {
-            // Use saved connection
-            (
-                conn.username.clone(),
-                conn.host.clone(),
-                conn.port,
-                conn.identity_file.clone(),
-            )
-        } else {
-            // Parse as connection string
-            let (username, host, default_port) = parse_connection_string(&dest)?;
-            let port = cli.port.unwrap_or(default_port);
-            (username, host, port, cli.identity.clone())
-        }
+    let (connection, session_name, url_path) = if let Some(dest) = cli.destination {
+        let (connection, url_path) = find_connection(dest, cli.port, cli.identity.clone())?;
+        (connection, cli.session.clone(), url_path)
     } else {
         // No destination - show connection selector
-        let connections = load_connections().unwrap_or_default();
-        let selector = ConnectionSelector::new(connections);
+        let connections = load_connections().unwrap_or_else(|e| {
+            eprintln!("Warning: {:#}", e);
+            Vec::new()
+        });
+        let selector = ConnectionSelector::new(connections, config.connection_sort, theme, cli.session.clone());
 
         match selector.run()? {
-            Some(conn) => (
-                conn.username.clone(),
-                conn.host.clone(),
-                conn.port,
-                conn.identity_file.clone(),
-            ),
+            Some((connection, session_name)) => (connection, session_name, None),
             None => {
                 return Ok(());
             }
         }
     };
 
+    let SavedConnection {
+        ref username,
+        ref host,
+        port,
+        ref identity_file,
+        ..
+    } = connection;
     let key_path = identity_file.as_deref();
 
+    config.apply_connection(&connection);
+    let theme = match connection.theme {
+        Some(_) => {
+            let (theme, theme_warnings) = Theme::from_config(&config.theme, &config.theme_colors);
+            for warning in &theme_warnings {
+                eprintln!("Warning: {}", warning);
+            }
+            theme
+        }
+        None => theme,
+    };
+    if let Some(color) = &connection.color
+        && connection.color().is_none()
+    {
+        eprintln!("Warning: Invalid colour '{}' for connection {}", color, connection.name);
+    }
+    let connection_options = connect_options(&connection, &config, theme);
+
+    let connect_options = ConnectOptions {
+        connect_timeout: cli
+            .connect_timeout
+            .map(Duration::from_secs)
+            .unwrap_or(connection_options.connect_timeout),
+        compression: cli.compress || connection_options.compression,
+        strict_host_key_checking: cli
+            .strict_host_key_checking
+            .unwrap_or(connection_options.strict_host_key_checking),
+        ..connection_options
+    };
+
     println!("Connecting to {}@{}:{}...", username, host, port);
     if let Some(key) = key_path {
         println!("Using identity file: {}", key.display());
     }
 
-    let mut ssh_client = SshClient::connect(&host, port, &username, key_path)
-        .await
-        .context("Failed to establish SSH connection")?;
+    let Some(session) =
+        open_transport_with_progress(host, port, username, &connect_options).await?
+    else {
+        println!("Connection cancelled");
+        return Ok(());
+    };
+
+    let mut ssh_client =
+        SshClient::authenticate(session, host, port, username, key_path, &connect_options)
+            .await
+            .context("Failed to establish SSH connection")?;
 
     let sftp = ssh_client
         .open_sftp()
         .await
         .context("Failed to open SFTP session")?;
 
+    let socks_proxy = match cli.dynamic_forward {
+        Some(socks_port) => {
+            let proxy = SocksProxy::start(ssh_client.session.clone(), socks_port).await?;
+            println!("SOCKS5 proxy listening on 127.0.0.1:{}", socks_port);
+            Some(proxy)
+        }
+        None => None,
+    };
+
     println!("Connected! Starting TUI...");
 
     // Save connection if --save flag was provided
-    if let Some(save_name) = cli.save_as {
-        let connection = SavedConnection::new(
-            save_name.clone(),
-            host.clone(),
-            port,
-            username.clone(),
-            identity_file.clone(),
-        );
-        if let Err(e) = add_connection(connection) {
+    if let Some(ref save_name) = cli.save_as {
+        let mut saved = connection.clone();
+        saved.name = save_name.clone();
+        saved.connect_timeout = cli.connect_timeout.or(saved.connect_timeout);
+        if cli.compress {
+            saved.compression = Some(true);
+        }
+        if let Err(e) = add_connection(saved) {
             eprintln!("Warning: Failed to save connection: {}", e);
         } else {
             println!("Connection saved as: {}", save_name);
         }
     }
 
+    let saved_name = cli.save_as.as_deref().unwrap_or(&connection.name);
+    if let Err(e) = record_connection(saved_name) {
+        eprintln!("Warning: Failed to record the connection: {}", e);
+    }
+
     // Try to load saved state for this connection
-    let (initial_path, initial_index) = if let Some(path_arg) = cli.path.as_deref() {
-        // If path was explicitly provided, use it
-        (path_arg.to_string(), 0)
-    } else if let Some(state) = SessionState::load(&host, port, &username) {
-        // Load from saved state
-        println!("Restoring previous session: {}", state.current_path);
-        (state.current_path, state.selected_index)
-    } else {
+    let saved_state = SessionState::load(host, port, username, session_name.as_deref());
+
+    let session = match (cli.path.as_deref().or(url_path.as_deref()), saved_state) {
+        // If path was explicitly provided, use it in the active tab
+        (Some(path_arg), saved) => {
+            let mut state = saved.unwrap_or_else(|| {
+                SessionState::new(host.clone(), port, username.clone(), String::new(), 0, config.show_hidden)
+            });
+            state.current_path = path_arg.to_string();
+            state.selected_index = 0;
+            state
+        }
+        (None, Some(state)) => {
+            match state.name {
+                Some(ref name) => println!("Restoring session {}: {}", name, state.current_path),
+                None => println!("Restoring previous session: {}", state.current_path),
+            }
+            state
+        }
         // Default to root
-        ("/".to_string(), 0)
+        (None, None) => SessionState::new(host.clone(), port, username.clone(), "/".to_string(), 0, config.show_hidden),
+    };
+    let session = SessionState {
+        name: session_name,
+        ..session
     };
 
-    run_app(
-        ssh_client,
-        sftp,
-        host.clone(),
-        port,
-        username.clone(),
-        initial_path,
-        initial_index
-    ).await?;
+    run_app(ssh_client, sftp, session, socks_proxy, config, &connection).await?;
+
+    Ok(())
+}
+
+/// The switcher listing `shells`, on the active one
+fn shell_switcher(shells: &ShellTabs) -> ShellSwitcher {
+    let entries = shells
+        .shells()
+        .iter()
+        .map(|shell| ShellEntry {
+            title: shell.title.clone(),
+            has_output: shell.has_backlog(),
+        })
+        .collect();
+    ShellSwitcher::new(entries, shells.active())
+}
 
+/// Enter the active shell, or a new one if `new_shell`, with `typed` already
+/// on its input line, and come back to the browser with the listing
+/// refreshed once the user toggles back
+async fn open_shell(
+    app: &mut App,
+    ssh_client: &mut SshClient,
+    shells: &mut ShellTabs,
+    new_shell: bool,
+    typed: &str,
+    tui: &mut Tui,
+    worker: &mut Worker,
+) -> Result<()> {
+    let count = shells.len();
+    let result = enter_shell_mode(ssh_client, shells, new_shell, &app.current_path, typed, tui).await;
+    tui.resume()?;
+    app.background_shells = shells.len();
+    match result {
+        Ok(()) => {
+            if shells.len() < count + new_shell as usize {
+                app.set_status("Shell exited".to_string());
+            }
+            // Pick up whatever was changed from the shell
+            if !worker.busy() {
+                let path = app.current_path.clone();
+                start_job(app, worker, Job::Refresh { path, quiet: true }, None);
+            }
+        }
+        Err(e) => app.set_error(format!("Shell error: {}", e)),
+    }
     Ok(())
 }
 
 async fn enter_shell_mode(
     ssh_client: &mut SshClient,
-    shell_session: &mut Option<ShellSession>,
+    shells: &mut ShellTabs,
+    new_shell: bool,
     current_path: &str,
+    typed: &str,
     tui: &mut Tui,
-) -> Result<bool> {
+) -> Result<()> {
     // Leave TUI alternate screen for shell
+    tui.clear_image()?;
     tui.restore()?;
 
-    // Clear screen for fresh shell view
-    print!("\x1B[2J\x1B[H");
-    std::io::Write::flush(&mut std::io::stdout())?;
-
     // Enable raw mode for shell I/O
     crossterm::terminal::enable_raw_mode()?;
 
-    // Create new shell if none exists
-    if shell_session.is_none() {
-        *shell_session = Some(ShellSession::new(&ssh_client.session, current_path).await?);
+    if new_shell || shells.is_empty() {
+        let info = &ssh_client.connection_info;
+        let history = ShellHistory::load(&info.host, info.port, &info.username);
+        let shell = ShellSession::new(&ssh_client.session, current_path, history, &shells.options).await?;
+        shells.add(shell);
+    }
+
+    // The main screen still shows the output of the shell last entered, so
+    // that one carries on from it; any other starts on a clear screen
+    if shells.show_active() {
+        print!("\x1B[2J\x1B[H");
+        std::io::Write::flush(&mut std::io::stdout())?;
     }
 
-    let session = shell_session.as_mut().unwrap();
+    let index = shells.active();
+    let session = shells.active_mut().context("No shell open")?;
 
     // Update terminal size in case it changed
     session.update_size().await?;
+    session.send_input(typed.as_bytes()).await?;
 
     // Run shell until toggle or exit
     let toggled_back = session.run().await?;
@@ -186,286 +422,2118 @@ async fn enter_shell_mode(
     }
 
     if !toggled_back {
-        // Shell exited, clear session
-        *shell_session = None;
+        shells.remove(index);
     }
-
-    Ok(toggled_back || shell_session.is_some())
+    Ok(())
 }
 
 async fn open_in_editor(
     sftp: &SftpSession,
     remote_path: &str,
     filename: &str,
+    config: &Config,
+    positions: &mut FilePositions,
     tui: &mut Tui,
 ) -> Result<bool> {
-    // Load file content
-    let content = load_file_content(sftp, remote_path).await?;
-    let mut editor = EditorState::new(filename.to_string(), remote_path.to_string(), content);
+    tui.clear_image()?;
+
+    let mut buffers = BufferList::new(load_editor_buffer(sftp, remote_path, filename, config, positions).await?);
+
+    tui.set_bracketed_paste(true)?;
+    let result = edit_buffers(sftp, &mut buffers, config, positions, tui).await;
+    tui.set_bracketed_paste(false)?;
+    for editor in buffers.iter_mut() {
+        positions.remember(editor);
+    }
+    result
+}
 
+/// Run the editor over `buffers` until it quits; returns whether anything was saved
+async fn edit_buffers(
+    sftp: &SftpSession,
+    buffers: &mut BufferList,
+    config: &Config,
+    positions: &FilePositions,
+    tui: &mut Tui,
+) -> Result<bool> {
     let mut saved = false;
     let mut viewport_height = 20; // Default
+    let mut last_autosave = Instant::now();
 
     loop {
         tui.terminal.draw(|f| {
             let area = f.area();
             viewport_height = area.height.saturating_sub(2) as usize;
+            let editor = buffers.current_mut();
             editor.update_scroll(viewport_height);
-            render_editor(f, area, &editor);
+            editor.fit_wrapped_lines(viewport_height, area.width as usize);
+            render_editor(f, area, buffers.current(), &tui.theme);
         })?;
 
-        if handle_editor_input(&mut editor, viewport_height)? {
+        let editor = buffers.current_mut();
+        if handle_editor_input(editor, viewport_height)? {
             // Check if we need to save
-            if editor.status_message == "Saving..." {
-                let content = editor.buffer.join("\n");
-                save_file_content(sftp, &editor.remote_path, &content).await?;
-                editor.modified = false;
-                editor.status_message = String::from("Saved");
-                saved = true;
-            } else if editor.status_message == "Saving and quitting..." {
-                let content = editor.buffer.join("\n");
-                save_file_content(sftp, &editor.remote_path, &content).await?;
-                editor.modified = false;
-                saved = true;
-                break;
+            let quit_after = editor.status_message == "Saving and quitting...";
+            if quit_after || editor.status_message == "Saving..." {
+                if changed_on_server(sftp, editor).await {
+                    editor.report_save_conflict(quit_after);
+                } else {
+                    saved |= save_from_command(sftp, editor, config, quit_after).await;
+                }
+            }
+        }
+
+        let focus_lost = std::mem::take(&mut buffers.current_mut().focus_lost);
+        let interval_due = config.autosave_secs > 0
+            && last_autosave.elapsed() >= Duration::from_secs(config.autosave_secs);
+        if interval_due || (focus_lost && config.autosave_on_focus_lost) {
+            last_autosave = Instant::now();
+            saved |= autosave(sftp, buffers, config).await;
+        }
+
+        if let Some(request) = buffers.current_mut().buffer_request.take() {
+            match request {
+                BufferRequest::Next => buffers.cycle(true),
+                BufferRequest::Previous => buffers.cycle(false),
+                BufferRequest::Overwrite { quit_after } => {
+                    saved |= save_from_command(sftp, buffers.current_mut(), config, quit_after).await;
+                }
+                BufferRequest::Reload => {
+                    let editor = buffers.current_mut();
+                    let stamp = remote_stamp(sftp, &editor.remote_path).await;
+                    match load_file_content(sftp, &editor.remote_path).await {
+                        Ok(decoded) => {
+                            editor.encoding = decoded.encoding;
+                            editor.reload(&decoded.text, stamp);
+                        }
+                        Err(e) => editor.status_message = format!("Reload failed: {:#}", e),
+                    }
+                }
+                BufferRequest::Diff => match load_file_content(sftp, &buffers.current().remote_path).await {
+                    Ok(decoded) => match buffers.current().diff_buffer(&decoded.text) {
+                        Some(diff) => buffers.push_or_replace(diff),
+                        None => {
+                            buffers.current_mut().status_message = String::from("No differences from the file on the server");
+                        }
+                    },
+                    Err(e) => buffers.current_mut().status_message = format!("Diff failed: {:#}", e),
+                },
+                BufferRequest::Open(path) => {
+                    if !buffers.switch_to(&path) {
+                        let name = path.rsplit('/').next().unwrap_or(&path).to_string();
+                        match load_editor_buffer(sftp, &path, &name, config, positions).await {
+                            Ok(editor) => buffers.push(editor),
+                            Err(e) => buffers.current_mut().status_message = format!("Failed to open {}: {}", path, e),
+                        }
+                    }
+                }
             }
         }
 
+        let editor = buffers.current();
         if editor.should_quit {
-            break;
+            if editor.force_quit {
+                break;
+            }
+            // Don't drop unsaved changes in a buffer that isn't on screen
+            match buffers.other_modified().map(|b| b.filename.clone()) {
+                Some(name) => {
+                    let editor = buffers.current_mut();
+                    editor.should_quit = false;
+                    editor.status_message = format!("No write since last change for \"{}\" (use :q! to override)", name);
+                }
+                None => break,
+            }
         }
     }
 
     Ok(saved)
 }
 
-async fn run_app(
-    mut ssh_client: SshClient,
-    sftp: SftpSession,
-    host: String,
-    port: u16,
-    username: String,
-    initial_path: String,
-    initial_index: usize,
-) -> Result<()> {
-    let connection_string = format!("{}@{}:{}", username, host, port);
-    let mut app = App::new(connection_string);
-    app.current_path = initial_path;
-    app.selected_index = initial_index;
+/// Write every modified buffer back. Failures are reported in the status
+/// line rather than closing the editor, so the edits survive a dropped
+/// connection. Returns whether anything was saved.
+async fn autosave(sftp: &SftpSession, buffers: &mut BufferList, config: &Config) -> bool {
+    let mut saved = Vec::new();
+    let mut failed = None;
+    for editor in buffers.iter_mut().filter(|editor| editor.modified) {
+        if changed_on_server(sftp, editor).await {
+            failed = Some(format!("Autosave of {} skipped: file changed on server; use :w to resolve", editor.filename));
+            continue;
+        }
+        match save_buffer(sftp, editor, config).await {
+            Ok(()) => saved.push(editor.filename.clone()),
+            Err(e) => failed = Some(format!("Autosave of {} failed: {}", editor.filename, e)),
+        }
+    }
 
-    let mut tui = Tui::new()?;
-    let mut shell_session: Option<ShellSession> = None;
+    let status = match failed {
+        Some(error) => error,
+        None if saved.is_empty() => return false,
+        None => format!("Autosaved {}", saved.join(", ")),
+    };
+    buffers.current_mut().status_message = status;
+    !saved.is_empty()
+}
 
-    app.files = file_ops::list_directory(&sftp, &app.current_path)
-        .await
-        .unwrap_or_default();
+/// Save for `:w`, `:wq` or an overwrite answer. A failed save keeps the
+/// editor open so the edits aren't lost. Returns whether it saved.
+async fn save_from_command(sftp: &SftpSession, editor: &mut EditorState, config: &Config, quit_after: bool) -> bool {
+    match save_buffer(sftp, editor, config).await {
+        Ok(()) => {
+            editor.status_message = String::from("Saved");
+            editor.should_quit = quit_after;
+            true
+        }
+        Err(e) => {
+            editor.status_message = format!("Save failed: {:#}", e);
+            false
+        }
+    }
+}
+
+/// Whether the file on the server is no longer the one the buffer was read from
+async fn changed_on_server(sftp: &SftpSession, editor: &EditorState) -> bool {
+    remote_stamp(sftp, &editor.remote_path).await != editor.remote_stamp
+}
 
-    // Clamp selected index to valid range
-    if app.selected_index >= app.files.len() && !app.files.is_empty() {
-        app.selected_index = app.files.len() - 1;
+/// Write a buffer back to its file, first backing up the original if
+/// configured and not yet done this session
+async fn save_buffer(sftp: &SftpSession, editor: &mut EditorState, config: &Config) -> Result<()> {
+    if !editor.backup_written
+        && let Some(backup) = backup_path(&editor.remote_path, config.backup_on_save, chrono::Local::now())
+    {
+        write_backup(sftp, &editor.remote_path, &backup)
+            .await
+            .with_context(|| format!("Backup to {} failed", backup))?;
+        editor.backup_written = true;
     }
 
-    loop {
-        tui.draw(&app)?;
+    let content = editor.buffer.join("\n");
+    save_file_content(sftp, &editor.remote_path, &content, editor.encoding).await?;
+    editor.remote_stamp = remote_stamp(sftp, &editor.remote_path).await;
+    editor.modified = false;
+    Ok(())
+}
 
-        match handle_input()? {
-            InputAction::MoveUp => {
-                app.select_previous();
-            }
-            InputAction::MoveDown => {
-                app.select_next();
-            }
-            InputAction::Enter => {
-                if let Some(file) = app.get_selected_file() {
-                    if file.is_dir {
-                        let going_back = file.name == "..";
-                        // Remember current dir name to highlight when going back
-                        let prev_dir_name = if going_back {
-                            app.current_path
-                                .trim_end_matches('/')
-                                .rsplit('/')
-                                .next()
-                                .map(|s| s.to_string())
-                        } else {
-                            None
-                        };
+/// Load a remote file into a new editor buffer with the configured tab
+/// settings, the cursor where it was left last time
+async fn load_editor_buffer(
+    sftp: &SftpSession,
+    remote_path: &str,
+    filename: &str,
+    config: &Config,
+    positions: &FilePositions,
+) -> Result<EditorState> {
+    // Taken before reading, so a change made during the read is noticed on save
+    let stamp = remote_stamp(sftp, remote_path).await;
+    let size = stamp.and_then(|stamp| stamp.size).unwrap_or(0);
+    if size > LARGE_FILE_BYTES {
+        anyhow::bail!("{} is {}; open it from the browser to view it read-only", filename, tui::format_size(size));
+    }
 
-                        let new_path = if going_back {
-                            get_parent_path(&app.current_path)
-                        } else {
-                            file.path.clone()
-                        };
+    let decoded = load_file_content(sftp, remote_path).await?;
+    let mut editor = EditorState::new(filename.to_string(), remote_path.to_string(), decoded.text);
+    editor.encoding = decoded.encoding;
+    editor.remote_stamp = stamp;
+    editor.options = config.editor;
+    editor.options.tab_width = editor.options.tab_width.max(1);
+    editor.read_only = !is_writable(sftp, remote_path).await;
+    positions.restore_cursor(&mut editor);
+    if decoded.binary {
+        // Saving would rewrite line endings and corrupt the file
+        editor.read_only = true;
+        editor.status_message = String::from("Binary file: opened read-only; download it or use the hex viewer instead");
+    } else if editor.read_only {
+        editor.status_message = String::from("Opened read-only: no write permission");
+    } else if decoded.lossy {
+        editor.status_message = format!(
+            "Warning: undecodable {} bytes were replaced; saving will change them",
+            decoded.encoding.name()
+        );
+    } else if decoded.encoding == Encoding::Latin1 {
+        editor.status_message = String::from("Warning: not valid UTF-8; opened as Latin-1 and will be saved as Latin-1");
+    } else if size > large_file::LARGE_FILE_WARN_BYTES {
+        editor.status_message = format!("Large file ({}); editing may be slow", tui::format_size(size));
+    }
+    Ok(editor)
+}
 
-                        app.current_path = new_path;
-                        app.selected_index = 0;
-
-                        match file_ops::list_directory(&sftp, &app.current_path).await {
-                            Ok(files) => {
-                                // If going back, find and select the previous directory
-                                if let Some(ref prev_name) = prev_dir_name {
-                                    if let Some(idx) = files.iter().position(|f| f.name == *prev_name) {
-                                        app.selected_index = idx;
-                                    }
-                                }
-                                app.files = files;
-                                app.set_status(String::new());
-                            }
-                            Err(e) => {
-                                app.set_status(format!("Error: {}", e));
-                            }
-                        }
-                    } else {
-                        // Save state before opening editor so we can restore position
-                        let state = SessionState::new(
-                            host.clone(),
-                            port,
-                            username.clone(),
-                            app.current_path.clone(),
-                            app.selected_index,
-                        );
-                        let _ = state.save();
-
-                        // Open file in built-in editor
-                        match open_in_editor(&sftp, &file.path, &file.name, &mut tui).await {
-                            Ok(saved) => {
-                                if saved {
-                                    app.set_status(format!("Saved: {}", file.name));
-                                } else {
-                                    app.set_status(format!("Closed: {}", file.name));
-                                }
-                            }
-                            Err(e) => {
-                                app.set_status(format!("Editor error: {}", e));
-                            }
-                        }
-                    }
-                }
-            }
-            InputAction::Download => {
-                if let Some(file) = app.get_selected_file() {
-                    if !file.is_dir {
-                        let local_path = PathBuf::from(&file.name);
-                        match file_ops::download_file(&sftp, &file.path, &local_path).await {
-                            Ok(_) => {
-                                app.set_status(format!("Downloaded: {}", file.name));
-                            }
-                            Err(e) => {
-                                app.set_status(format!("Download failed: {}", e));
-                            }
-                        }
-                    }
-                }
-            }
-            InputAction::Upload => {
-                app.set_status("Upload not yet implemented".to_string());
-            }
-            InputAction::NewDirectory => {
-                app.set_status("New directory not yet implemented".to_string());
+/// Show a remote file in the follow view until the user closes it
+async fn open_follow_view(sftp: &SftpSession, remote_path: &str, tui: &mut Tui) -> Result<()> {
+    tui.clear_image()?;
+
+    let size = sftp
+        .metadata(remote_path)
+        .await
+        .context("Failed to stat file")?
+        .size
+        .unwrap_or(0);
+    let start = size.saturating_sub(follow::FOLLOW_INITIAL_BYTES);
+    let mut follow = FollowState::new(remote_path.to_string(), start);
+    let mut last_poll: Option<Instant> = None;
+
+    while !follow.should_quit {
+        if last_poll.is_none_or(|at| at.elapsed() >= follow::FOLLOW_POLL_INTERVAL) {
+            if let Err(e) = poll_file(sftp, &mut follow).await {
+                follow.status_message = format!("Read failed: {:#}", e);
             }
-            InputAction::Rename => {
-                app.set_status("Rename not yet implemented".to_string());
+            last_poll = Some(Instant::now());
+        }
+
+        tui.terminal.draw(|f| {
+            let area = f.area();
+            follow.viewport_height = area.height.saturating_sub(2) as usize;
+            render_follow(f, area, &follow, &tui.theme);
+        })?;
+
+        handle_follow_input(&mut follow)?;
+    }
+
+    Ok(())
+}
+
+/// Have the worker read a small remote file for the clipboard
+fn start_copy_contents(app: &mut App, worker: &mut Worker, file: FileEntry) {
+    if file.size > CLIPBOARD_MAX_BYTES {
+        app.set_warning(format!(
+            "{} is larger than the {} KB clipboard limit",
+            file.name,
+            CLIPBOARD_MAX_BYTES / 1024
+        ));
+        return;
+    }
+    let label = format!("Reading {}…", file.name);
+    // Read one byte past the limit in case the listing was out of date
+    let limit = CLIPBOARD_MAX_BYTES as usize + 1;
+    start_job(app, worker, Job::ReadText { file, limit }, Some(label));
+}
+
+/// Put the text read from `file` on the local clipboard
+fn copy_file_contents(app: &mut App, file: &FileEntry, bytes: Result<Vec<u8>>) {
+    let bytes = match bytes {
+        Ok(bytes) if bytes.len() as u64 > CLIPBOARD_MAX_BYTES => {
+            app.set_warning(format!(
+                "{} is larger than the {} KB clipboard limit",
+                file.name,
+                CLIPBOARD_MAX_BYTES / 1024
+            ));
+            return;
+        }
+        Ok(bytes) => bytes,
+        Err(e) => {
+            app.set_error(format!("Copy failed: {:#}", e));
+            return;
+        }
+    };
+
+    let text = match String::from_utf8(bytes) {
+        Ok(text) if !text.contains('\0') => text,
+        _ => {
+            app.set_warning(format!("{} is not a text file", file.name));
+            return;
+        }
+    };
+
+    match Clipboard::new().and_then(|mut clipboard| clipboard.set_text(&text)) {
+        Ok(_) => app.set_status(format!("Copied contents of {} ({} bytes)", file.name, text.len())),
+        Err(e) => app.set_error(format!("Failed to copy to clipboard: {}", e)),
+    }
+}
+
+/// Apply a key press to the clipboard-to-file prompt, writing the file on Enter
+fn handle_paste_text_key(app: &mut App, worker: &mut Worker, key: KeyEvent) {
+    let Some(paste) = app.paste_text.as_mut() else {
+        return;
+    };
+    if key.code == KeyCode::Tab {
+        paste.toggle_append();
+        return;
+    }
+    let name = match paste.prompt.handle_key(key) {
+        PromptResult::Editing => return,
+        PromptResult::Cancelled => {
+            app.paste_text = None;
+            return;
+        }
+        PromptResult::Submitted(name) => name.trim().to_string(),
+    };
+
+    let text = match Clipboard::new().and_then(|mut clipboard| clipboard.get_text()) {
+        Ok(text) if !text.is_empty() => text,
+        Ok(_) => {
+            app.set_status("Clipboard is empty".to_string());
+            return;
+        }
+        Err(e) => {
+            app.set_error(format!("Failed to read clipboard: {}", e));
+            return;
+        }
+    };
+
+    let append = paste.append;
+    app.paste_text = None;
+
+    let label = format!("Writing {}…", name);
+    start_change(app, worker, Change::WriteText { name, text, append }, label);
+}
+
+/// Open `file` the way `action` says, in a view that takes over the terminal
+/// until it is closed
+async fn open_file(
+    app: &mut App,
+    tui: &mut Tui,
+    sftp: &SftpSession,
+    config: &Config,
+    info: &ConnectionInfo,
+    file: FileEntry,
+    action: OpenAction,
+) {
+    match action {
+        OpenAction::Editor if file.size > LARGE_FILE_BYTES => {
+            if let Err(e) = open_large_file_view(sftp, &file, tui).await {
+                app.set_error(format!("Viewer failed: {:#}", e));
             }
-            InputAction::Delete => {
-                if let Some(file) = app.get_selected_file() {
-                    let result = if file.is_dir {
-                        file_ops::delete_directory(&sftp, &file.path).await
-                    } else {
-                        file_ops::delete_file(&sftp, &file.path).await
-                    };
+        }
+        OpenAction::Editor => {
+            // Save state before opening editor so we can restore position
+            let _ = session_state(app, &info.host, info.port, &info.username).save();
 
-                    match result {
-                        Ok(_) => {
-                            app.set_status(format!("Deleted: {}", file.name));
-                            match file_ops::list_directory(&sftp, &app.current_path).await {
-                                Ok(files) => {
-                                    app.files = files;
-                                    if app.selected_index >= app.files.len() && app.selected_index > 0
-                                    {
-                                        app.selected_index = app.files.len() - 1;
-                                    }
-                                }
-                                Err(e) => {
-                                    app.set_status(format!("Error refreshing: {}", e));
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            app.set_status(format!("Delete failed: {}", e));
-                        }
+            // Open file in built-in editor
+            match open_in_editor(sftp, &file.path, &file.name, config, &mut app.file_positions, tui).await {
+                Ok(saved) => {
+                    if saved {
+                        app.set_status(format!("Saved: {}", file.name));
+                    } else {
+                        app.set_status(format!("Closed: {}", file.name));
                     }
                 }
-            }
-            InputAction::Execute => {
-                app.set_status("Execute not yet implemented".to_string());
-            }
-            InputAction::ToggleShell => {
-                match enter_shell_mode(
-                    &mut ssh_client,
-                    &mut shell_session,
-                    &app.current_path,
-                    &mut tui,
-                ).await {
-                    Ok(_) => {
-                        // Reinitialize TUI after shell mode
-                        tui = Tui::new()?;
-                        app.has_background_shell = shell_session.is_some();
-                        if shell_session.is_none() {
-                            app.set_status("Shell exited".to_string());
-                        }
-                    }
-                    Err(e) => {
-                        // Reinitialize TUI on error too
-                        tui = Tui::new()?;
-                        app.set_status(format!("Shell error: {}", e));
-                        shell_session = None;
-                        app.has_background_shell = false;
-                    }
+                Err(e) => {
+                    app.set_error(format!("Editor error: {}", e));
                 }
             }
-            InputAction::Quit => {
-                app.quit();
+            // The file may have changed in the editor
+            app.preview = None;
+        }
+        OpenAction::Hex => {
+            if let Err(e) = open_hex_view(sftp, &file, tui).await {
+                app.set_error(format!("Hex view failed: {:#}", e));
             }
-            InputAction::None => {}
         }
-
-        if app.should_quit {
-            break;
+        OpenAction::Follow => {
+            if let Err(e) = open_follow_view(sftp, &file.path, tui).await {
+                app.set_error(format!("Follow failed: {:#}", e));
+            }
+        }
+        OpenAction::Preview => {
+            app.layout.preview = true;
+            app.preview = None;
+            save_layout(app);
         }
     }
+}
 
-    // Save state before quitting
-    let state = SessionState::new(
-        host,
-        port,
-        username,
-        app.current_path,
-        app.selected_index,
-    );
-    let _ = state.save();
+/// Show the start of a file as a hex dump until the user closes it
+async fn open_hex_view(sftp: &SftpSession, file: &FileEntry, tui: &mut Tui) -> Result<()> {
+    tui.clear_image()?;
+
+    let bytes = file_ops::read_head(sftp, &file.path, HEX_VIEW_BYTES).await?;
+    let truncated = file.size > bytes.len() as u64;
+    let mut view = HexViewState::new(file.path.clone(), bytes, truncated);
+
+    while !view.should_quit {
+        tui.terminal.draw(|f| {
+            let area = f.area();
+            view.viewport_height = area.height.saturating_sub(2) as usize;
+            render_hex_view(f, area, &view, &tui.theme);
+        })?;
+
+        handle_hex_view_input(&mut view)?;
+    }
 
-    tui.restore()?;
     Ok(())
 }
 
-fn parse_connection_string(conn_str: &str) -> Result<(String, String, u16)> {
-    let (user_host, port) = if let Some(pos) = conn_str.rfind(':') {
-        let port_str = &conn_str[pos + 1..];
-        let port = port_str
-            .parse::<u16>()
-            .context("Invalid port number")?;
-        (&conn_str[..pos], port)
-    } else {
-        (conn_str, 22)
-    };
+/// Page through a file too large for the editor without loading all of it
+async fn open_large_file_view(sftp: &SftpSession, file: &FileEntry, tui: &mut Tui) -> Result<()> {
+    tui.clear_image()?;
 
-    let (username, host) = if let Some(pos) = user_host.find('@') {
-        (user_host[..pos].to_string(), user_host[pos + 1..].to_string())
-    } else {
-        let current_user = env::var("USER").unwrap_or_else(|_| String::from("root"));
-        (current_user, user_host.to_string())
-    };
+    let mut view = LargeFileView::new(file.path.clone(), file.size);
+    while !view.should_quit {
+        if let Err(e) = load_window(sftp, &mut view).await {
+            view.status_message = format!("Read failed: {:#}", e);
+        }
+
+        tui.terminal.draw(|f| {
+            let area = f.area();
+            view.viewport_height = area.height.saturating_sub(2) as usize;
+            render_large_file(f, area, &view, &tui.theme);
+        })?;
+
+        handle_large_file_input(&mut view)?;
+    }
+
+    Ok(())
+}
 
-    Ok((username, host, port))
+/// Await `task`, redrawing the screen while it runs so the spinner keeps
+/// turning. Nothing is drawn if it finishes within one spinner frame.
+async fn while_drawing<T>(app: &App, tui: &mut Tui, task: impl Future<Output = T>) -> T {
+    tokio::pin!(task);
+    let start = tokio::time::Instant::now() + SPINNER_FRAME_TIME;
+    let mut ticker = tokio::time::interval_at(start, SPINNER_FRAME_TIME);
+    loop {
+        tokio::select! {
+            result = &mut task => return result,
+            _ = ticker.tick() => {
+                let _ = tui.draw(app);
+            }
+        }
+    }
+}
+
+/// Await `task` with a spinner and `label` in the footer
+async fn with_spinner<T>(app: &mut App, tui: &mut Tui, label: String, task: impl Future<Output = T>) -> T {
+    app.start_loading(None, label);
+    let result = while_drawing(app, tui, task).await;
+    app.stop_loading(None);
+    result
+}
+
+/// Like `with_spinner`, but Esc stops waiting and gives None. Other keys
+/// pressed meanwhile are dropped.
+async fn with_cancellable_spinner<T>(
+    app: &mut App,
+    tui: &mut Tui,
+    label: String,
+    task: impl Future<Output = T>,
+) -> Option<T> {
+    with_spinner(app, tui, label, until_escape(task)).await
+}
+
+/// Await `task`, or give None once Esc is pressed. Other keys pressed
+/// meanwhile are dropped.
+async fn until_escape<T>(task: impl Future<Output = T>) -> Option<T> {
+    tokio::pin!(task);
+    let mut keys = tokio::time::interval(Duration::from_millis(50));
+    loop {
+        tokio::select! {
+            result = &mut task => return Some(result),
+            _ = keys.tick() => {
+                if escape_pressed() {
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+/// Whether Esc is among the keys waiting to be read
+fn escape_pressed() -> bool {
+    while crossterm::event::poll(Duration::ZERO).unwrap_or(false) {
+        if let Ok(Event::Key(key)) = crossterm::event::read()
+            && key.code == KeyCode::Esc
+        {
+            return true;
+        }
+    }
+    false
+}
+
+/// A directory the worker is opening, and what to do once it is open
+struct Navigation {
+    job: JobId,
+    /// Entry to select once it arrives
+    select: Option<String>,
+    after: AfterNavigation,
+    opened: bool,
+}
+
+/// What a navigation does once its directory is open
+enum AfterNavigation {
+    /// Remember the place left, to go back to it
+    Visit(HistoryEntry),
+    Back(HistoryEntry),
+    Forward(HistoryEntry),
+    /// Report the tab switched to
+    Tab,
+    /// Like `Visit`, then warn if the link target `name` is not there
+    LinkTarget {
+        left: HistoryEntry,
+        name: String,
+        resolved: String,
+    },
+}
+
+impl Navigation {
+    /// Select the entry asked for once it has arrived
+    fn select_arrived(&mut self, app: &mut App) {
+        if let Some(ref name) = self.select {
+            app.select_by_name(name);
+            if app.get_selected_file().is_some_and(|file| &file.name == name) {
+                self.select = None;
+            }
+        }
+    }
+}
+
+/// Have the worker list `path` to make it the current directory, selecting
+/// `select` if given. Entries are shown batch by batch as they arrive, and
+/// Esc stops the listing with what has arrived. A navigation still loading
+/// is given up.
+fn navigate_to(
+    app: &mut App,
+    worker: &mut Worker,
+    navigation: &mut Option<Navigation>,
+    path: String,
+    select: Option<&str>,
+    after: AfterNavigation,
+) {
+    if let Some(previous) = navigation.take() {
+        worker.cancel(previous.job);
+        app.stop_loading(Some(previous.job));
+    }
+    let label = format!("Loading {}…", path);
+    let job = start_job(app, worker, Job::List { path }, Some(label));
+    *navigation = Some(Navigation {
+        job,
+        select: select.map(str::to_string),
+        after,
+        opened: false,
+    });
+}
+
+/// Apply what came back for the directory being opened
+fn apply_listing(app: &mut App, navigation: &mut Option<Navigation>, job: JobId, result: JobResult) {
+    let Some(nav) = navigation.as_mut().filter(|nav| nav.job == job) else {
+        return;
+    };
+    match result {
+        JobResult::Opened { path, files } => {
+            nav.opened = true;
+            nav.select = nav.select.take().or(app.selections.get(&path).map(str::to_string));
+            enter_directory(app, path, files, None);
+            nav.select_arrived(app);
+            match nav.after {
+                AfterNavigation::Visit(ref left) | AfterNavigation::LinkTarget { ref left, .. } => {
+                    app.history.visit(left.clone())
+                }
+                AfterNavigation::Back(ref left) => app.history.commit_back(left.clone()),
+                AfterNavigation::Forward(ref left) => app.history.commit_forward(left.clone()),
+                AfterNavigation::Tab => app.set_status(format!("Tab {} of {}", app.tabs.active() + 1, app.tabs.len())),
+            }
+        }
+        JobResult::Batch { path, files } => {
+            app.append_files(files);
+            nav.select_arrived(app);
+            app.relabel_loading(Some(job), format!("Loading {}… {} entries", path, app.all_files.len()));
+        }
+        JobResult::Listed { result, .. } => {
+            let Some(nav) = navigation.take() else {
+                return;
+            };
+            match result {
+                Err(e) if nav.opened => app.set_status(format!("Listing incomplete: {}", e)),
+                Err(e) => app.set_error(format!("Error: {}", e)),
+                Ok(()) => {
+                    if let AfterNavigation::LinkTarget { name, resolved, .. } = nav.after
+                        && !name.is_empty()
+                        && !app.files.iter().any(|f| f.name == name)
+                    {
+                        app.set_warning(format!("Link target not found: {}", resolved));
+                    }
+                }
+            }
+        }
+        JobResult::Cancelled(Job::List { path }) => {
+            let opened = navigation.take().is_some_and(|nav| nav.opened);
+            if opened {
+                app.set_warning(format!("Listing stopped at {} entries", app.all_files.len()));
+            } else {
+                app.set_warning(format!("Stopped loading {}", path));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Show tab `index`, keeping the current directory in the tab being left
+fn show_tab(app: &mut App, worker: &mut Worker, navigation: &mut Option<Navigation>, index: usize) {
+    if index >= app.tabs.len() {
+        app.set_warning(format!("No tab {}", index + 1));
+        return;
+    }
+    if index == app.tabs.active() {
+        return;
+    }
+    let current = Tab {
+        place: app.history_entry(),
+        history: std::mem::take(&mut app.history),
+    };
+    if let Some(tab) = app.tabs.switch(index, current) {
+        enter_tab(app, worker, navigation, tab);
+    }
+}
+
+/// List the directory of a tab that was in the background and take over its
+/// history
+fn enter_tab(app: &mut App, worker: &mut Worker, navigation: &mut Option<Navigation>, tab: Tab) {
+    let Tab { place, history } = tab;
+    app.history = history;
+    navigate_to(app, worker, navigation, place.path, place.selected.as_deref(), AfterNavigation::Tab);
+}
+
+/// Make `path` the current directory with its listing, selecting `select`
+/// or else the entry selected when the directory was last left
+fn enter_directory(app: &mut App, path: String, files: Vec<FileEntry>, select: Option<&str>) {
+    app.selections.leave(app.history_entry());
+    app.visited.visit(&path, chrono::Utc::now().timestamp());
+    app.current_path = path;
+    app.selected_index = 0;
+    app.clear_selection();
+    app.set_files(files);
+    let select = select.or(app.selections.get(&app.current_path)).map(str::to_string);
+    if let Some(name) = select {
+        app.select_by_name(&name);
+    }
+    app.set_status(String::new());
+}
+
+/// Apply a key press to the file list filter prompt
+fn handle_filter_key(app: &mut App, key: KeyEvent) {
+    // ↑/↓ move through the matches rather than the prompt's history
+    match key.code {
+        KeyCode::Up => return app.select_previous(),
+        KeyCode::Down => return app.select_next(),
+        _ => {}
+    }
+    let Some(filter) = app.filter.as_mut() else {
+        return;
+    };
+    let query = filter.query().to_string();
+    match filter.prompt.handle_key(key) {
+        PromptResult::Editing if filter.query() != query => app.apply_filter(),
+        PromptResult::Editing => {}
+        PromptResult::Cancelled => app.cancel_filter(),
+        PromptResult::Submitted(_) => app.accept_filter(),
+    }
+}
+
+/// Apply a key press to the permissions dialog, applying the mode on Enter
+fn handle_chmod_key(app: &mut App, worker: &mut Worker, key: KeyEvent) {
+    let Some(chmod) = app.chmod.as_mut() else {
+        return;
+    };
+
+    match key.code {
+        KeyCode::Esc => {
+            app.chmod = None;
+            return;
+        }
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            app.chmod = None;
+            return;
+        }
+        KeyCode::Enter => {}
+        KeyCode::Up | KeyCode::Char('k') => chmod.move_cursor(-1, 0),
+        KeyCode::Down | KeyCode::Char('j') => chmod.move_cursor(1, 0),
+        KeyCode::Left | KeyCode::Char('h') => chmod.move_cursor(0, -1),
+        KeyCode::Right | KeyCode::Char('l') => chmod.move_cursor(0, 1),
+        KeyCode::Char(' ') => chmod.toggle(),
+        KeyCode::Char(c) if c.is_digit(8) => chmod.set_digit(c.to_digit(8).unwrap_or(0)),
+        _ => {}
+    }
+
+    if key.code != KeyCode::Enter {
+        return;
+    }
+
+    let Some(chmod) = app.chmod.take() else {
+        return;
+    };
+
+    let entries = chmod.entries.iter().map(|entry| (entry.clone(), chmod.mode_for(entry))).collect();
+    let change = Change::Chmod { entries, mode: chmod.mode };
+    let label = format!("Changing the mode of {} item(s)…", chmod.entries.len());
+    start_change(app, worker, change, label);
+    app.clear_selection();
+}
+
+/// Answer the delete confirmation dialog
+fn handle_delete_confirm_key(app: &mut App, worker: &mut Worker, key: KeyEvent) {
+    let Some(entries) = app.pending_delete.take() else {
+        return;
+    };
+    match entries_confirm("Delete", &entries).accepts(key, 'y') {
+        Some(true) => delete_entries(app, worker, entries),
+        Some(false) => {}
+        None => app.pending_delete = Some(entries),
+    }
+}
+
+/// Apply a key press to the batch command prompt, expanding its globs on Enter
+fn handle_command_key(app: &mut App, key: KeyEvent) {
+    let Some(command) = app.command.as_mut() else {
+        return;
+    };
+
+    let value = match command.handle_key(key) {
+        PromptResult::Editing => return,
+        PromptResult::Cancelled => {
+            app.command = None;
+            return;
+        }
+        PromptResult::Submitted(value) => value,
+    };
+    app.command = None;
+    app.command_history.push(&value);
+
+    // The prompt only accepts commands that parse
+    let Ok((action, patterns)) = parse_batch_command(&value) else {
+        return;
+    };
+
+    let entries = app.glob_entries(&patterns);
+    if entries.is_empty() {
+        app.set_warning(format!("No matches for {}", patterns.join(" ")));
+        return;
+    }
+
+    match action {
+        // Globs can match more than intended, so always confirm
+        BatchAction::Delete => app.pending_delete = Some(entries),
+        BatchAction::Download => {
+            let files: Vec<FileEntry> = entries.into_iter().filter(|f| !f.is_dir).collect();
+            if files.is_empty() {
+                app.set_status("Only directories matched; nothing to download".to_string());
+            } else {
+                app.pending_download = Some(files);
+            }
+        }
+        BatchAction::Mark => {
+            let count = entries.len();
+            app.marked.extend(entries.into_iter().map(|f| f.path));
+            app.set_status(format!("Marked {} item(s)", count));
+        }
+    }
+}
+
+/// Apply a key press to the run command prompt. On Enter, returns the
+/// command as typed and the shell line that runs it.
+fn handle_execute_key(app: &mut App, key: KeyEvent) -> Option<(String, String)> {
+    if is_insert_selection_key(key) {
+        let names = app.quoted_selection(true);
+        app.execute.as_mut()?.input.insert_word(&names);
+        return None;
+    }
+    let value = match app.execute.as_mut()?.handle_key(key) {
+        PromptResult::Editing => return None,
+        PromptResult::Cancelled => {
+            app.execute = None;
+            return None;
+        }
+        PromptResult::Submitted(value) => value,
+    };
+    app.execute = None;
+    app.execute_history.push(&value);
+
+    let selected = app.get_selected_file().filter(|file| file.name != "..").map(|file| file.path.as_str());
+    let line = command_output::expand_command(&value, &app.current_path, selected);
+    Some((value, line))
+}
+
+/// Ctrl+f in a command prompt types the selected entries' quoted names
+fn is_insert_selection_key(key: KeyEvent) -> bool {
+    key.code == KeyCode::Char('f') && key.modifiers.contains(KeyModifiers::CONTROL)
+}
+
+/// Apply a key press to the command palette. On Enter, returns the picked
+/// command's name and the shell line that runs it.
+fn handle_palette_key(app: &mut App, key: KeyEvent) -> Option<(String, String)> {
+    let picked = match app.palette.as_mut()?.handle_key(key) {
+        PaletteResult::Picking => return None,
+        PaletteResult::Cancelled => {
+            app.palette = None;
+            return None;
+        }
+        PaletteResult::Run(picked) => picked,
+    };
+    app.palette = None;
+
+    let selected = app.get_selected_file().filter(|file| file.name != "..").map(|file| file.path.as_str());
+    let line = command_output::expand_command(&picked.command, &app.current_path, selected);
+    Some((picked.name, line))
+}
+
+/// Apply a key press to the script arguments prompt. On Enter, returns a
+/// title for the output pane and the shell line that runs the script from
+/// its own directory.
+fn handle_run_script_key(app: &mut App, key: KeyEvent) -> Option<(String, String)> {
+    let names = app.quoted_selection(true);
+    let (file, prompt) = app.run_script.as_mut()?;
+    if is_insert_selection_key(key) {
+        prompt.input.insert_word(&names);
+        return None;
+    }
+    let args = match prompt.handle_key(key) {
+        PromptResult::Editing => return None,
+        PromptResult::Cancelled => {
+            app.run_script = None;
+            return None;
+        }
+        PromptResult::Submitted(args) => args,
+    };
+    let file = file.clone();
+    app.run_script = None;
+    app.script_args_history.push(&args);
+
+    let script = ssh::client::shell_quote(&format!("./{}", file.name));
+    // A script without the executable bit still runs through sh
+    let program = if file.permissions.is_some_and(|mode| mode & 0o111 != 0) { script } else { format!("sh {}", script) };
+    let line = command_output::expand_command(&format!("{} {}", program, args), &app.current_path, None);
+    let title = format!("{} {}", file.name, args).trim_end().to_string();
+    Some((title, line))
+}
+
+/// Run `line` on the server, showing its output as it arrives. Closing the
+/// pane first stops the command. Returns its exit status, or None if it was
+/// stopped or did not report one.
+async fn stream_in_output_view(ssh_client: &mut SshClient, tui: &mut Tui, title: String, line: &str) -> Result<Option<u32>> {
+    let mut channel = ssh_client.start_command(line).await?;
+    tui.clear_image()?;
+
+    let mut view = CommandOutputView::streaming(title);
+    let mut exit_status = None;
+    while !view.should_quit {
+        // Take in what has arrived since the last frame
+        while view.running
+            && let Some(msg) = channel.wait().now_or_never()
+        {
+            match msg {
+                Some(ChannelMsg::Data { data }) => view.push(false, &String::from_utf8_lossy(&data)),
+                Some(ChannelMsg::ExtendedData { data, .. }) => view.push(true, &String::from_utf8_lossy(&data)),
+                Some(ChannelMsg::ExitStatus { exit_status: status }) => exit_status = Some(status),
+                Some(_) => {}
+                None => view.finish(exit_status),
+            }
+        }
+
+        tui.terminal.draw(|f| {
+            let area = f.area();
+            view.viewport_height = area.height.saturating_sub(2) as usize;
+            render_command_output(f, area, &view, &tui.theme);
+        })?;
+
+        handle_command_output_input(&mut view)?;
+    }
+
+    if view.running {
+        // Servers that ignore the signal still hang up the command with the channel
+        let _ = channel.signal(Sig::TERM).await;
+        let _ = channel.close().await;
+        return Ok(None);
+    }
+    Ok(exit_status)
+}
+
+/// Run `line` on the server and page through what it printed
+async fn run_in_output_view(
+    app: &mut App,
+    ssh_client: &mut SshClient,
+    tui: &mut Tui,
+    command: String,
+    line: String,
+) -> Result<()> {
+    let label = format!("Running {}… (Esc stops waiting)", command);
+    let output = match with_cancellable_spinner(app, tui, label, ssh_client.run_command(&line)).await {
+        Some(Ok(output)) => output,
+        Some(Err(e)) => {
+            app.set_error(format!("Could not run {}: {:#}", command, e));
+            return Ok(());
+        }
+        None => {
+            app.set_warning(format!("Stopped waiting for {}", command));
+            return Ok(());
+        }
+    };
+
+    match output.exit_status {
+        Some(0) => app.set_status(format!("{} exited with 0", command)),
+        Some(code) => app.set_warning(format!("{} exited with {}", command, code)),
+        None => app.set_warning(format!("{} ended without an exit status", command)),
+    }
+
+    tui.clear_image()?;
+    let mut view = CommandOutputView::new(command, output);
+    while !view.should_quit {
+        tui.terminal.draw(|f| {
+            let area = f.area();
+            view.viewport_height = area.height.saturating_sub(2) as usize;
+            render_command_output(f, area, &view, &tui.theme);
+        })?;
+
+        handle_command_output_input(&mut view)?;
+    }
+    Ok(())
+}
+
+fn handle_download_confirm_key(app: &mut App, worker: &mut Worker, concurrency: usize, key: KeyEvent) {
+    let Some(entries) = app.pending_download.take() else {
+        return;
+    };
+    match entries_confirm("Download", &entries).accepts(key, 'y') {
+        Some(true) => download_entries(app, worker, &entries, concurrency),
+        Some(false) => {}
+        None => app.pending_download = Some(entries),
+    }
+}
+
+/// Download the files among `entries` into the working directory
+fn download_entries(app: &mut App, worker: &mut Worker, entries: &[FileEntry], concurrency: usize) {
+    let files: Vec<FileEntry> = entries.iter().filter(|f| !f.is_dir).cloned().collect();
+
+    if app.transferring.is_some() {
+        app.set_warning("A transfer is already running (Esc cancels it)".to_string());
+    } else if !files.is_empty() {
+        run_transfers(app, worker, TransferBatch::new(Direction::Download, files), concurrency);
+    } else if app.has_selection() {
+        app.set_status("No files selected (directories are skipped)".to_string());
+    }
+}
+
+/// Apply a key press to the upload prompt, looking up what to upload on Enter
+fn handle_upload_key(app: &mut App, worker: &mut Worker, key: KeyEvent) {
+    let Some(prompt) = app.upload.as_mut() else {
+        return;
+    };
+    let path = match prompt.handle_key(key) {
+        PromptResult::Editing => return,
+        PromptResult::Cancelled => {
+            app.upload = None;
+            return;
+        }
+        PromptResult::Submitted(path) => path.trim().to_string(),
+    };
+    app.upload = None;
+    if app.transferring.is_some() {
+        app.set_warning("A transfer is already running (Esc cancels it)".to_string());
+        return;
+    }
+    let label = format!("Looking up {}…", path);
+    start_job(app, worker, Job::LocalFiles { path }, Some(label));
+}
+
+/// Start the next queued transfer, or report the batch once it is done. The
+/// worker pauses it with a conflict when the target already exists.
+fn run_transfers(app: &mut App, worker: &mut Worker, mut batch: TransferBatch, concurrency: usize) {
+    if let Some(file) = batch.queue.pop_front() {
+        let on_conflict = if batch.overwrite_all {
+            OnConflict::Overwrite
+        } else {
+            OnConflict::Ask
+        };
+        start_transfer(app, worker, batch, file, concurrency, on_conflict);
+        return;
+    }
+
+    if batch.failed.is_empty() {
+        app.set_status(batch.summary());
+    } else {
+        app.set_error(batch.summary());
+    }
+    match batch.direction {
+        Direction::Download if batch.total > 1 => app.clear_selection(),
+        Direction::Download => {}
+        // Show what arrived
+        Direction::Upload { remote_dir } => {
+            start_job(app, worker, Job::Refresh { path: remote_dir, quiet: true }, None);
+        }
+    }
+}
+
+/// Hand one file of `batch` to the worker; its result continues the batch
+fn start_transfer(
+    app: &mut App,
+    worker: &mut Worker,
+    batch: TransferBatch,
+    file: FileEntry,
+    concurrency: usize,
+    on_conflict: OnConflict,
+) {
+    let done = batch.total - batch.queue.len();
+    let (verb, job) = match &batch.direction {
+        Direction::Download => {
+            let local_path = PathBuf::from(&file.name);
+            let job = Job::Download {
+                file: file.clone(),
+                local_path,
+                concurrency,
+                on_conflict,
+            };
+            ("Downloading", job)
+        }
+        Direction::Upload { remote_dir } => {
+            let job = Job::Upload {
+                file: file.clone(),
+                remote_path: file_ops::join_path(remote_dir, &file.name),
+                concurrency,
+                on_conflict,
+            };
+            ("Uploading", job)
+        }
+    };
+    let label = format!("{} {} ({}/{})…  Esc cancels", verb, file.name, done, batch.total);
+    start_job(app, worker, job, Some(label));
+    app.transferring = Some(batch);
+}
+
+/// Apply a key press to the overwrite prompt of a paused transfer
+fn handle_overwrite_key(app: &mut App, worker: &mut Worker, concurrency: usize, key: KeyEvent) {
+    let Some(confirm) = app.pending_overwrite.as_ref().and_then(overwrite_confirm) else {
+        return;
+    };
+    let choice = match confirm.answer(key) {
+        Some(Answer::Choice(choice)) => choice,
+        Some(Answer::Cancel) => {
+            if let Some(batch) = app.pending_overwrite.take() {
+                app.set_status(format!("{} (cancelled)", batch.summary()));
+            }
+            return;
+        }
+        None => return,
+    };
+
+    let Some(mut batch) = app.pending_overwrite.take() else {
+        return;
+    };
+    let Some(file) = batch.queue.pop_front() else {
+        return;
+    };
+    batch.existing = None;
+    match choice {
+        's' => {
+            batch.skipped += 1;
+            run_transfers(app, worker, batch, concurrency);
+        }
+        'r' => start_transfer(app, worker, batch, file, concurrency, OnConflict::Rename),
+        choice => {
+            batch.overwrite_all |= choice == 'a';
+            start_transfer(app, worker, batch, file, concurrency, OnConflict::Overwrite);
+        }
+    }
+}
+
+/// Hand `job` to the worker, with `label` next to the spinner until it is
+/// done
+fn start_job(app: &mut App, worker: &mut Worker, job: Job, label: Option<String>) -> JobId {
+    let id = worker.submit(job);
+    if let Some(label) = label {
+        app.start_loading(Some(id), label);
+    }
+    id
+}
+
+/// Have the worker change files in the current directory and list it again
+fn start_change(app: &mut App, worker: &mut Worker, change: Change, label: String) {
+    let path = app.current_path.clone();
+    start_job(app, worker, Job::Change { path, change }, Some(label));
+}
+
+/// Apply what a background job came back with
+fn apply_job_result(
+    app: &mut App,
+    worker: &mut Worker,
+    navigation: &mut Option<Navigation>,
+    (job, result): (JobId, JobResult),
+    concurrency: usize,
+) {
+    match result {
+        JobResult::Refreshed { path, quiet, files } => {
+            // Indices held by a prompt or visual range would no longer match
+            if path != app.current_path || app.prompt_active() || app.visual_anchor.is_some() {
+                return;
+            }
+            match files {
+                Ok(files) => {
+                    let (added, removed) = app.refresh_files(files);
+                    if !quiet {
+                        app.set_status(format!("Refreshed: {} new, {} removed", added, removed));
+                    }
+                }
+                Err(e) if !quiet => app.set_error(format!("Refresh failed: {}", e)),
+                Err(_) => {}
+            }
+        }
+        result @ (JobResult::Opened { .. }
+        | JobResult::Batch { .. }
+        | JobResult::Listed { .. }
+        | JobResult::Cancelled(Job::List { .. })) => apply_listing(app, navigation, job, result),
+        JobResult::Previewed(preview) => {
+            if app.get_selected_file().is_some_and(|file| file.path == preview.path) {
+                app.preview = Some(preview);
+            }
+        }
+        JobResult::Downloaded { file, local_path, result } => {
+            let Some(mut batch) = app.transferring.take() else {
+                return;
+            };
+            let local_path = local_path.to_string_lossy();
+            batch.record(&file, &local_path, result.map_err(|e| e.to_string()));
+            run_transfers(app, worker, batch, concurrency);
+        }
+        JobResult::Uploaded { file, remote_path, result } => {
+            let Some(mut batch) = app.transferring.take() else {
+                return;
+            };
+            batch.record(&file, &remote_path, result.map_err(|e| e.to_string()));
+            run_transfers(app, worker, batch, concurrency);
+        }
+        JobResult::LocalFiles { files } => match files {
+            Ok(files) => {
+                let direction = Direction::Upload {
+                    remote_dir: app.current_path.clone(),
+                };
+                run_transfers(app, worker, TransferBatch::new(direction, files), concurrency);
+            }
+            Err(e) => app.set_error(format!("Upload failed: {:#}", e)),
+        },
+        JobResult::Conflict { file, existing } => {
+            let Some(mut batch) = app.transferring.take() else {
+                return;
+            };
+            batch.queue.push_front(file);
+            batch.existing = Some(existing);
+            app.pending_overwrite = Some(batch);
+        }
+        JobResult::Changed {
+            path,
+            report,
+            select,
+            files,
+        } => {
+            match report {
+                Ok(message) => app.set_status(message),
+                Err(message) => app.set_error(message),
+            }
+            if path != app.current_path || app.prompt_active() || app.visual_anchor.is_some() {
+                return;
+            }
+            match files {
+                Ok(files) => {
+                    app.set_files(files);
+                    if let Some(name) = select {
+                        app.select_by_name(&name);
+                        // Its content may be what changed
+                        app.preview = None;
+                    }
+                }
+                Err(e) => app.set_error(format!("Error refreshing: {}", e)),
+            }
+        }
+        JobResult::Checksummed { file, result } => match result {
+            Ok(checksum) => {
+                app.set_status(format!("SHA-256 of {}: {}", file.name, checksum));
+                app.checksum = Some((file.path, checksum));
+            }
+            Err(e) => app.set_error(format!("Checksum failed: {:#}", e)),
+        },
+        JobResult::Health(health) => app.set_health(health),
+        JobResult::DiskUsage { path, usage } => app.set_disk_usage(path, usage.ok()),
+        JobResult::TextRead { file, result } => copy_file_contents(app, &file, result),
+        // Opened by the main loop
+        JobResult::Resolved { .. } => {}
+        JobResult::Cancelled(job) => match job {
+            Job::Refresh { quiet: false, .. } => app.set_warning("Refresh cancelled".to_string()),
+            Job::Refresh { .. } | Job::List { .. } => {}
+            Job::Preview { file } => {
+                app.preview = Some(Preview {
+                    path: file.path,
+                    content: PreviewContent::Error(String::from("Cancelled")),
+                    truncated: false,
+                });
+            }
+            Job::Download { .. } | Job::Upload { .. } => {
+                if let Some(batch) = app.transferring.take() {
+                    app.set_warning(format!("{} (cancelled)", batch.summary()));
+                }
+            }
+            Job::Change { path, .. } => {
+                // Part of the change may have been made; show what there is now
+                app.set_warning("Stopped; the change may be partly done".to_string());
+                start_job(app, worker, Job::Refresh { path, quiet: true }, None);
+            }
+            Job::Checksum { .. } => app.set_warning("Checksum cancelled".to_string()),
+            Job::ReadText { .. } => app.set_warning("Copy cancelled".to_string()),
+            Job::LocalFiles { .. } => app.set_warning("Upload cancelled".to_string()),
+            Job::Health | Job::DiskUsage { .. } | Job::Resolve { .. } => {}
+        },
+    }
+}
+
+/// Have the worker delete the given entries and list the directory again
+fn delete_entries(app: &mut App, worker: &mut Worker, entries: Vec<FileEntry>) {
+    let label = match entries.as_slice() {
+        [file] => format!("Deleting {}…", file.name),
+        _ => format!("Deleting {} items…", entries.len()),
+    };
+    start_change(app, worker, Change::Delete(entries), label);
+    app.clear_selection();
+}
+
+/// Apply a key press to the owner/group prompt, changing ownership on Enter
+fn handle_chown_key(app: &mut App, worker: &mut Worker, key: KeyEvent) {
+    let Some(chown) = app.chown.as_mut() else {
+        return;
+    };
+    let spec = match chown.prompt.handle_key(key) {
+        PromptResult::Editing => return,
+        PromptResult::Cancelled => {
+            app.chown = None;
+            return;
+        }
+        PromptResult::Submitted(spec) => spec.trim().to_string(),
+    };
+    let Some(chown) = app.chown.take() else {
+        return;
+    };
+    // The prompt only accepts specs that parse
+    let Ok((owner, group)) = parse_owner_spec(&spec) else {
+        return;
+    };
+
+    let label = format!("Changing the ownership of {} item(s)…", chown.entries.len());
+    let change = Change::Chown {
+        entries: chown.entries,
+        owner,
+        group,
+        spec,
+    };
+    start_change(app, worker, change, label);
+    app.clear_selection();
+}
+
+/// Apply a key press to the inline rename prompt, renaming on Enter
+fn handle_rename_key(app: &mut App, worker: &mut Worker, key: KeyEvent) {
+    let Some(rename) = app.rename.as_mut() else {
+        return;
+    };
+    // The prompt only accepts names that are free and valid
+    let new_name = match rename.prompt.handle_key(key) {
+        PromptResult::Editing => return,
+        PromptResult::Cancelled => {
+            app.rename = None;
+            return;
+        }
+        PromptResult::Submitted(name) => name.trim().to_string(),
+    };
+    let Some(rename) = app.rename.take() else {
+        return;
+    };
+    let original = rename.original_name;
+    if new_name == original {
+        return;
+    }
+
+    let label = format!("Renaming {}…", original);
+    start_change(app, worker, Change::Rename { from: original, to: new_name }, label);
+}
+
+/// Have the worker copy or move the clipboard entries into the current
+/// directory
+fn paste_clipboard(app: &mut App, worker: &mut Worker) {
+    let Some(clipboard) = app.clipboard.clone() else {
+        app.set_status("Clipboard is empty".to_string());
+        return;
+    };
+
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+    for entry in clipboard.entries {
+        match app.paste_destination(&entry, clipboard.mode) {
+            Ok(Some(destination)) => entries.push((entry, destination)),
+            Ok(None) => {}
+            Err(e) => errors.push(e),
+        }
+    }
+
+    // Moved entries no longer exist at their old paths
+    if clipboard.mode == ClipboardMode::Move {
+        app.clipboard = None;
+    }
+
+    let verb = match clipboard.mode {
+        ClipboardMode::Copy => "Copying",
+        ClipboardMode::Move => "Moving",
+    };
+    let label = format!("{} {} item(s)…", verb, entries.len());
+    let change = Change::Paste {
+        entries,
+        mode: clipboard.mode,
+        errors,
+    };
+    start_change(app, worker, change, label);
+}
+
+/// Apply a key press to the archive name prompt, creating the archive on Enter
+fn handle_compress_key(app: &mut App, worker: &mut Worker, key: KeyEvent) {
+    let Some(compress) = app.compress.as_mut() else {
+        return;
+    };
+    if key.code == KeyCode::Tab {
+        compress.cycle_format();
+        return;
+    }
+    // The prompt only accepts free names with an archive extension
+    let name = match compress.prompt.handle_key(key) {
+        PromptResult::Editing => return,
+        PromptResult::Cancelled => {
+            app.compress = None;
+            return;
+        }
+        PromptResult::Submitted(name) => name.trim().to_string(),
+    };
+    let Some(compress) = app.compress.take() else {
+        return;
+    };
+    let Some(kind) = compress.kind() else {
+        return;
+    };
+    let names: Vec<String> = compress.entries.iter().map(|entry| entry.name.clone()).collect();
+
+    let label = format!("Creating {}…", name);
+    start_change(app, worker, Change::Compress { name, names, kind }, label);
+    app.clear_selection();
+}
+
+/// Extract the selected archive into the current directory
+fn extract_selected(app: &mut App, worker: &mut Worker) {
+    let Some(file) = app.get_selected_file().filter(|f| !f.is_dir).cloned() else {
+        return;
+    };
+    let Some(kind) = ArchiveKind::from_name(&file.name) else {
+        app.set_warning(format!("Not a supported archive: {}", file.name));
+        return;
+    };
+
+    let label = format!("Extracting {}…", file.name);
+    start_change(app, worker, Change::Extract { file, kind }, label);
+}
+
+/// Rebuild the SSH and SFTP sessions after the connection dropped, keeping
+/// the browser on the same directory and selection
+async fn reconnect(
+    ssh_client: &mut SshClient,
+    sftp: &mut Arc<SftpSession>,
+    app: &mut App,
+    tui: &mut Tui,
+) -> Result<()> {
+    app.reconnecting = true;
+    tui.draw(app)?;
+
+    let result = async {
+        let mut client = ssh_client.reconnect().await?;
+        let new_sftp = client.open_sftp().await?;
+        Ok::<_, anyhow::Error>((client, new_sftp))
+    }
+    .await;
+
+    app.reconnecting = false;
+
+    // Re-authentication may have shown its own dialog over the TUI
+    tui.restore()?;
+    *tui = Tui::new(tui.theme)?;
+
+    let (client, new_sftp) = result?;
+    *ssh_client = client;
+    *sftp = Arc::new(new_sftp);
+
+    let files = file_ops::list_directory(sftp, &app.current_path).await?;
+    app.set_files(files);
+
+    Ok(())
+}
+
+async fn run_app(
+    mut ssh_client: SshClient,
+    sftp: SftpSession,
+    session: SessionState,
+    socks_proxy: Option<SocksProxy>,
+    config: Config,
+    connection: &SavedConnection,
+) -> Result<()> {
+    let host = ssh_client.connection_info.host.clone();
+    let port = ssh_client.connection_info.port;
+    let username = ssh_client.connection_info.username.clone();
+    let mut connection_string = format!("{}@{}:{}", username, host, port);
+    if let Some(icon) = connection.icon.as_deref().filter(|icon| !icon.is_empty()) {
+        connection_string = format!("{} {}", icon, connection_string);
+    }
+    let mut app = App::new(connection_string);
+    app.connection_color = connection.color();
+    app.layout = session.layout.unwrap_or(config.layout);
+    app.session_name = session.name.clone();
+    app.icons = config.icons.resolve();
+    app.columns = config.columns.clone();
+    app.hyperlink_base = (config.hyperlinks && hyperlink::supported()).then(|| hyperlink::sftp_base(&username, &host, port));
+    app.socks_port = socks_proxy.as_ref().map(|proxy| proxy.port);
+    app.current_path = session.current_path;
+    app.selected_index = session.selected_index;
+    // The connection's own setting is already layered over the config's
+    app.show_hidden = session.show_hidden.unwrap_or(config.show_hidden);
+    app.tabs = Tabs::restore(session.tabs, session.active_tab);
+    app.selections = DirSelections::restore(session.selections);
+    app.file_positions = FilePositions::restore(session.file_positions);
+    app.detailed_view = session.detailed_view;
+    app.visited = VisitedDirs::load(&host, port, &username);
+    app.visited.visit(&app.current_path, chrono::Utc::now().timestamp());
+
+    let theme = ssh_client.connection_info.options.theme;
+    let mut tui = Tui::new(theme)?;
+    let mut shells = ShellTabs::new(ShellOptions {
+        shell: config.shell.clone(),
+        term: config.term.clone(),
+    });
+    let mut sftp = Arc::new(sftp);
+    let mut worker = Worker::spawn(sftp.clone(), ssh_client.clone());
+    let mut navigation: Option<Navigation> = None;
+    // Path of the preview the worker was last asked for
+    let mut requested_preview: Option<String> = None;
+    // Background checks in flight, so they are not asked for twice
+    let mut health_check: Option<JobId> = None;
+    let mut disk_usage_check: Option<JobId> = None;
+
+    let files = file_ops::list_directory(&sftp, &app.current_path)
+        .await
+        .unwrap_or_default();
+    // Also clamps the restored selection to the listing
+    app.set_files(files);
+
+    let mut next_reconnect_attempt = Instant::now();
+    let mut last_refresh = Instant::now();
+
+    loop {
+        if !ssh_client.is_connected() && Instant::now() >= next_reconnect_attempt {
+            match reconnect(&mut ssh_client, &mut sftp, &mut app, &mut tui).await {
+                Ok(()) => {
+                    shells.clear();
+                    app.background_shells = 0;
+                    // Jobs on the old session are lost with it
+                    worker = Worker::spawn(sftp.clone(), ssh_client.clone());
+                    navigation = None;
+                    requested_preview = None;
+                    health_check = None;
+                    disk_usage_check = None;
+                    app.retain_loading(|_| false);
+                    if let Some(batch) = app.transferring.take() {
+                        app.set_warning(format!("{} (interrupted)", batch.summary()));
+                    }
+                    if let Some(ref proxy) = socks_proxy {
+                        proxy.update_session(ssh_client.session.clone());
+                    }
+                    app.set_health(ConnectionHealth::Unknown);
+                    app.set_status("Reconnected".to_string());
+                }
+                Err(e) => {
+                    next_reconnect_attempt = Instant::now() + RECONNECT_BACKOFF;
+                    app.set_error(format!("Connection lost: {} (retrying)", e));
+                }
+            }
+        }
+
+        while let Some((job, result)) = worker.try_result() {
+            if result.preview_path().is_some() && result.preview_path() == requested_preview.as_deref() {
+                requested_preview = None;
+            }
+            // Opening takes over the terminal, which only the loop has
+            if let JobResult::Resolved { file, action } = result {
+                if app.get_selected_file().is_some_and(|selected| selected.path == file.path) {
+                    let info = &ssh_client.connection_info;
+                    open_file(&mut app, &mut tui, &sftp, &config, info, file, action).await;
+                }
+                continue;
+            }
+            apply_job_result(&mut app, &mut worker, &mut navigation, (job, result), config.transfer_concurrency);
+        }
+        app.retain_loading(|job| worker.is_running(job));
+
+        if shells.buffer_output() > 0 {
+            app.background_shells = shells.len();
+            app.set_status("Background shell exited".to_string());
+            if let Some(switcher) = app.shell_switcher.as_mut() {
+                *switcher = shell_switcher(&shells);
+            }
+        }
+
+        if let Some(path) = app.stale_preview_path()
+            && requested_preview.as_ref() != Some(&path)
+            && let Some(file) = app.get_selected_file().cloned()
+        {
+            let label = format!("Loading {}…", file.name);
+            start_job(&mut app, &mut worker, Job::Preview { file }, Some(label));
+            requested_preview = Some(path);
+        }
+
+        if app.health_check_due() && !health_check.is_some_and(|job| worker.is_running(job)) {
+            health_check = Some(worker.submit(Job::Health));
+        }
+
+        if app.disk_usage_stale() && !disk_usage_check.is_some_and(|job| worker.is_running(job)) {
+            let path = app.current_path.clone();
+            disk_usage_check = Some(worker.submit(Job::DiskUsage { path }));
+        }
+
+        // Skipped while a prompt or visual range holds indices into the listing
+        if config.auto_refresh_secs > 0
+            && last_refresh.elapsed() >= Duration::from_secs(config.auto_refresh_secs)
+            && ssh_client.is_connected()
+            && !app.prompt_active()
+            && app.visual_anchor.is_none()
+        {
+            if !worker.busy() {
+                let path = app.current_path.clone();
+                start_job(&mut app, &mut worker, Job::Refresh { path, quiet: true }, None);
+            }
+            last_refresh = Instant::now();
+        }
+
+        tui.draw(&app)?;
+
+        let action = handle_input(&mut app)?;
+        match action {
+            InputAction::MoveUp => {
+                app.select_previous();
+            }
+            InputAction::MoveDown => {
+                app.select_next();
+            }
+            InputAction::PageUp => app.select_by(-(tui.list_page_size() as isize)),
+            InputAction::PageDown => app.select_by(tui.list_page_size() as isize),
+            InputAction::KeyPrefix(_) => {}
+            InputAction::Resize => tui.resize()?,
+            InputAction::HalfPageUp => app.select_by(-(tui.list_page_size() as isize / 2)),
+            InputAction::HalfPageDown => app.select_by(tui.list_page_size() as isize / 2),
+            InputAction::First => app.select_first(),
+            InputAction::Last => app.select_last(),
+            InputAction::Enter => {
+                if let Some(file) = app.get_selected_file() {
+                    if file.broken_link {
+                        app.set_warning(format!(
+                            "Broken symlink: {} -> {}",
+                            file.name,
+                            file.link_target.as_deref().unwrap_or("?")
+                        ));
+                    } else if file.is_dir {
+                        let going_back = file.name == "..";
+                        // Remember current dir name to highlight when going back
+                        let prev_dir_name = if going_back {
+                            app.current_path
+                                .trim_end_matches('/')
+                                .rsplit('/')
+                                .next()
+                                .map(|s| s.to_string())
+                        } else {
+                            None
+                        };
+
+                        let new_path = if going_back {
+                            get_parent_path(&app.current_path)
+                        } else {
+                            file.path.clone()
+                        };
+
+                        let current = app.history_entry();
+                        let after = AfterNavigation::Visit(current);
+                        navigate_to(&mut app, &mut worker, &mut navigation, new_path, prev_dir_name.as_deref(), after);
+                    } else {
+                        let file = file.clone();
+                        match filetype::configured_action(&config.open_with, &file.name) {
+                            Some(action) => {
+                                let info = &ssh_client.connection_info;
+                                open_file(&mut app, &mut tui, &sftp, &config, info, file, action).await;
+                            }
+                            None => {
+                                let label = format!("Opening {}…", file.name);
+                                start_job(&mut app, &mut worker, Job::Resolve { file }, Some(label));
+                            }
+                        }
+                    }
+                }
+            }
+            InputAction::Download => {
+                let entries = app.selected_entries();
+                download_entries(&mut app, &mut worker, &entries, config.transfer_concurrency);
+            }
+            InputAction::Upload => app.start_upload(),
+            InputAction::NewDirectory => {
+                app.set_warning("New directory not yet implemented".to_string());
+            }
+            InputAction::Rename => {
+                if !app.start_rename() {
+                    app.set_warning("Cannot rename this entry".to_string());
+                }
+            }
+            InputAction::PromptKey(key) => {
+                if let Some(help) = app.help.as_mut() {
+                    // The overlay covers the screen inside its border
+                    let rows = tui.terminal.size().map_or(20, |size| size.height.saturating_sub(2) as usize);
+                    if !help.handle_key(key, rows) {
+                        app.help = None;
+                    }
+                } else if app.pending_delete.is_some() {
+                    handle_delete_confirm_key(&mut app, &mut worker, key);
+                } else if app.pending_download.is_some() {
+                    handle_download_confirm_key(&mut app, &mut worker, config.transfer_concurrency, key);
+                } else if app.pending_overwrite.is_some() {
+                    handle_overwrite_key(&mut app, &mut worker, config.transfer_concurrency, key);
+                } else if app.confirm_quit {
+                    match quit_confirm(&app).accepts(key, 'y') {
+                        Some(true) => app.quit(),
+                        Some(false) => app.confirm_quit = false,
+                        None => {}
+                    }
+                } else if app.command.is_some() {
+                    handle_command_key(&mut app, key);
+                } else if let Some(switcher) = app.shell_switcher.as_mut() {
+                    match switcher.handle_key(key) {
+                        SwitcherResult::Picking => {}
+                        SwitcherResult::Cancelled => app.shell_switcher = None,
+                        SwitcherResult::Open(index) => {
+                            app.shell_switcher = None;
+                            shells.switch(index);
+                            open_shell(&mut app, &mut ssh_client, &mut shells, false, "", &mut tui, &mut worker).await?;
+                        }
+                        SwitcherResult::New => {
+                            app.shell_switcher = None;
+                            open_shell(&mut app, &mut ssh_client, &mut shells, true, "", &mut tui, &mut worker).await?;
+                        }
+                        SwitcherResult::Close(index) => {
+                            shells.close(index).await;
+                            switcher.remove(index);
+                            app.background_shells = shells.len();
+                        }
+                    }
+                } else if let Some(jump) = app.jump.as_mut() {
+                    match jump.handle_key(key) {
+                        JumpResult::Picking => {}
+                        JumpResult::Cancelled => app.jump = None,
+                        JumpResult::Go(path) => {
+                            app.jump = None;
+                            let after = AfterNavigation::Visit(app.history_entry());
+                            navigate_to(&mut app, &mut worker, &mut navigation, path, None, after);
+                        }
+                    }
+                } else if app.run_script.is_some() {
+                    if let Some((title, line)) = handle_run_script_key(&mut app, key) {
+                        match stream_in_output_view(&mut ssh_client, &mut tui, title.clone(), &line).await {
+                            Ok(Some(0)) => app.set_status(format!("{} exited with 0", title)),
+                            Ok(Some(code)) => app.set_warning(format!("{} exited with {}", title, code)),
+                            Ok(None) => app.set_warning(format!("{} was stopped", title)),
+                            Err(e) => app.set_error(format!("Could not run {}: {:#}", title, e)),
+                        }
+                        if !worker.busy() {
+                            let path = app.current_path.clone();
+                            start_job(&mut app, &mut worker, Job::Refresh { path, quiet: true }, None);
+                        }
+                    }
+                } else if app.execute.is_some() || app.palette.is_some() {
+                    let picked = if app.palette.is_some() {
+                        handle_palette_key(&mut app, key)
+                    } else {
+                        handle_execute_key(&mut app, key)
+                    };
+                    if let Some((command, line)) = picked {
+                        run_in_output_view(&mut app, &mut ssh_client, &mut tui, command, line).await?;
+                        if !worker.busy() {
+                            // The command may have changed the directory
+                            let path = app.current_path.clone();
+                            start_job(&mut app, &mut worker, Job::Refresh { path, quiet: true }, None);
+                        }
+                    }
+                } else if app.filter.is_some() {
+                    handle_filter_key(&mut app, key);
+                } else if app.chmod.is_some() {
+                    handle_chmod_key(&mut app, &mut worker, key);
+                } else if app.chown.is_some() {
+                    handle_chown_key(&mut app, &mut worker, key);
+                } else if app.paste_text.is_some() {
+                    handle_paste_text_key(&mut app, &mut worker, key);
+                } else if app.upload.is_some() {
+                    handle_upload_key(&mut app, &mut worker, key);
+                } else if app.compress.is_some() {
+                    handle_compress_key(&mut app, &mut worker, key);
+                } else {
+                    handle_rename_key(&mut app, &mut worker, key);
+                }
+            }
+            InputAction::Delete => {
+                let entries = app.selected_entries();
+                if !entries.is_empty() {
+                    if config.confirm_delete {
+                        app.pending_delete = Some(entries);
+                    } else {
+                        delete_entries(&mut app, &mut worker, entries);
+                    }
+                }
+            }
+            InputAction::Jump => {
+                let dirs = app.visited.ranked(&app.current_path, chrono::Utc::now().timestamp());
+                if dirs.is_empty() {
+                    app.set_warning("No other directories visited yet".to_string());
+                } else {
+                    app.jump = Some(JumpPrompt::new(dirs));
+                }
+            }
+            InputAction::Palette => {
+                if config.commands.is_empty() {
+                    app.set_warning("No commands yet; add them under \"commands\" in the config file".to_string());
+                } else {
+                    app.palette = Some(Palette::new(config.commands.clone()));
+                }
+            }
+            InputAction::RunScript => match app.get_selected_file().filter(|file| file.is_script()).cloned() {
+                Some(file) => {
+                    let prompt = Prompt::new(&format!("Run {} with arguments", file.name), "")
+                        .with_hint("Enter to run, empty for none  Ctrl+f selection  ↑/↓ history")
+                        .with_history(&app.script_args_history);
+                    app.run_script = Some((file, prompt));
+                }
+                None => app.set_warning("Select an executable or .sh file to run".to_string()),
+            },
+            InputAction::Execute => {
+                let hint = match app.get_selected_file().filter(|file| file.name != "..") {
+                    Some(file) => format!("{} is {}  Ctrl+f selection  ↑/↓ history", command_output::PLACEHOLDER, file.name),
+                    None => String::from("Runs in the current directory  Ctrl+f selection  ↑/↓ history"),
+                };
+                app.execute = Some(
+                    Prompt::new("Run command", "")
+                        .with_hint(&hint)
+                        .with_validator(|value| {
+                            if value.trim().is_empty() { Err(String::from("Command is empty")) } else { Ok(()) }
+                        })
+                        .with_history(&app.execute_history),
+                );
+            }
+            InputAction::ToggleShell => {
+                open_shell(&mut app, &mut ssh_client, &mut shells, false, "", &mut tui, &mut worker).await?;
+            }
+            InputAction::SelectionToShell => {
+                let typed = format!("{} ", app.quoted_selection(false));
+                if typed.trim().is_empty() {
+                    app.set_warning("Nothing selected to send to the shell".to_string());
+                } else {
+                    open_shell(&mut app, &mut ssh_client, &mut shells, false, &typed, &mut tui, &mut worker).await?;
+                }
+            }
+            InputAction::Shells => {
+                app.shell_switcher = Some(shell_switcher(&shells));
+            }
+            InputAction::ToggleMark => {
+                app.toggle_mark();
+            }
+            InputAction::VisualSelect => {
+                app.toggle_visual();
+            }
+            InputAction::ClearSelection => match app.loading_job() {
+                Some(job) => worker.cancel(job),
+                None => app.clear_selection(),
+            },
+            InputAction::Yank | InputAction::Cut => {
+                let (mode, verb) = match action {
+                    InputAction::Cut => (ClipboardMode::Move, "move"),
+                    _ => (ClipboardMode::Copy, "copy"),
+                };
+                match app.yank(mode) {
+                    0 => app.set_status("Nothing to yank".to_string()),
+                    count => app.set_status(format!(
+                        "{} item(s) ready to {}; press p in the target directory",
+                        count, verb
+                    )),
+                }
+            }
+            InputAction::Paste => {
+                paste_clipboard(&mut app, &mut worker);
+            }
+            InputAction::GotoLinkTarget => {
+                let link = app
+                    .get_selected_file()
+                    .and_then(|file| Some((file.path.clone(), file.link_target.clone()?)));
+
+                match link {
+                    Some((path, target)) => {
+                        let resolved = file_ops::resolve_link_target(&path, &target);
+                        let parent = get_parent_path(&resolved);
+                        let name = resolved.rsplit('/').next().unwrap_or_default().to_string();
+
+                        let after = AfterNavigation::LinkTarget {
+                            left: app.history_entry(),
+                            name: name.clone(),
+                            resolved,
+                        };
+                        navigate_to(&mut app, &mut worker, &mut navigation, parent, Some(&name), after);
+                    }
+                    None => app.set_warning("Not a symlink".to_string()),
+                }
+            }
+            InputAction::Extract => {
+                extract_selected(&mut app, &mut worker);
+            }
+            InputAction::Ancestor(index) => {
+                if let Some((path, child)) = app.ancestor(index) {
+                    let after = AfterNavigation::Visit(app.history_entry());
+                    navigate_to(&mut app, &mut worker, &mut navigation, path, Some(&child), after);
+                }
+            }
+            InputAction::NewTab => {
+                if app.tabs.len() >= MAX_TABS {
+                    app.set_warning(format!("At most {} tabs can be open", MAX_TABS));
+                } else {
+                    let current = Tab {
+                        place: app.history_entry(),
+                        history: std::mem::take(&mut app.history),
+                    };
+                    app.tabs.open(current);
+                    app.set_status(format!("Tab {} of {}", app.tabs.active() + 1, app.tabs.len()));
+                }
+            }
+            InputAction::SwitchTab(index) => {
+                show_tab(&mut app, &mut worker, &mut navigation, index);
+            }
+            InputAction::NextTab | InputAction::PreviousTab => {
+                let step = if action == InputAction::NextTab { 1 } else { -1 };
+                let index = app.tabs.neighbour(step);
+                show_tab(&mut app, &mut worker, &mut navigation, index);
+            }
+            InputAction::CloseTab => match app.tabs.close() {
+                Some(tab) => enter_tab(&mut app, &mut worker, &mut navigation, tab),
+                None => app.set_warning("Cannot close the last tab".to_string()),
+            },
+            InputAction::CopyPath | InputAction::CopyUrl => {
+                let paths = app.selected_paths();
+                let lines: Vec<String> = match action {
+                    InputAction::CopyUrl => paths
+                        .iter()
+                        .map(|path| file_ops::sftp_url(&username, &host, port, path))
+                        .collect(),
+                    _ => paths,
+                };
+                let text = lines.join("\n");
+                match Clipboard::new().and_then(|mut clipboard| clipboard.set_text(&text)) {
+                    Ok(_) if lines.len() == 1 => app.set_status(format!("Copied: {}", text)),
+                    Ok(_) => app.set_status(format!("Copied {} paths", lines.len())),
+                    Err(e) => app.set_error(format!("Failed to copy to clipboard: {}", e)),
+                }
+            }
+            InputAction::CopyContents => {
+                if let Some(file) = app.get_selected_file().filter(|f| !f.is_dir).cloned() {
+                    start_copy_contents(&mut app, &mut worker, file);
+                }
+            }
+            InputAction::PasteText => {
+                app.start_paste_text();
+            }
+            InputAction::Command => {
+                app.command = Some(
+                    Prompt::new("Batch command", "")
+                        .with_hint("rm/get/mark <glob>...  ↑/↓ history  Esc cancel")
+                        .with_validator(|value| parse_batch_command(value).map(|_| ()))
+                        .with_history(&app.command_history),
+                );
+            }
+            InputAction::Refresh => {
+                let path = app.current_path.clone();
+                let label = format!("Refreshing {}…", path);
+                start_job(&mut app, &mut worker, Job::Refresh { path, quiet: false }, Some(label));
+                last_refresh = Instant::now();
+            }
+            InputAction::Follow => {
+                if let Some(file) = app.get_selected_file().filter(|f| !f.is_dir).cloned()
+                    && let Err(e) = open_follow_view(&sftp, &file.path, &mut tui).await
+                {
+                    app.set_error(format!("Follow failed: {:#}", e));
+                }
+            }
+            InputAction::Compress => {
+                let entries = app.selected_entries();
+                if !entries.is_empty() {
+                    app.start_compress(entries);
+                }
+            }
+            InputAction::TogglePreview => {
+                app.layout.preview = !app.layout.preview;
+                app.preview = None;
+                save_layout(&mut app);
+            }
+            InputAction::ToggleDetailsPanel => {
+                app.layout.details = !app.layout.details;
+                save_layout(&mut app);
+            }
+            InputAction::Checksum => {
+                if let Some(file) = app.get_selected_file().filter(|f| !f.is_dir).cloned() {
+                    let label = format!("Computing the checksum of {}…", file.name);
+                    start_job(&mut app, &mut worker, Job::Checksum { file }, Some(label));
+                }
+            }
+            InputAction::WidenPreview | InputAction::NarrowPreview => {
+                let steps = if action == InputAction::WidenPreview { 1 } else { -1 };
+                let message = app.layout.resize_preview(steps);
+                app.layout.preview = true;
+                app.set_status(message);
+                save_layout(&mut app);
+            }
+            InputAction::ToggleHeaderHints => {
+                app.layout.header_hints = !app.layout.header_hints;
+                save_layout(&mut app);
+            }
+            InputAction::Back => {
+                match app.history.back_target().cloned() {
+                    Some(target) => {
+                        let after = AfterNavigation::Back(app.history_entry());
+                        navigate_to(&mut app, &mut worker, &mut navigation, target.path, target.selected.as_deref(), after);
+                    }
+                    None => app.set_status("No previous directory".to_string()),
+                }
+            }
+            InputAction::Forward => {
+                match app.history.forward_target().cloned() {
+                    Some(target) => {
+                        let after = AfterNavigation::Forward(app.history_entry());
+                        navigate_to(&mut app, &mut worker, &mut navigation, target.path, target.selected.as_deref(), after);
+                    }
+                    None => app.set_status("No next directory".to_string()),
+                }
+            }
+            InputAction::Chmod => {
+                let entries = app.selected_entries();
+                if !entries.is_empty() {
+                    app.chmod = Some(ChmodState::new(entries));
+                }
+            }
+            InputAction::Chown => {
+                let entries = app.selected_entries();
+                if !entries.is_empty() {
+                    app.chown = Some(ChownState::new(entries));
+                }
+            }
+            InputAction::ToggleDetails => {
+                app.detailed_view = !app.detailed_view;
+            }
+            InputAction::Filter => {
+                app.start_filter();
+            }
+            InputAction::ToggleHidden => {
+                app.toggle_hidden();
+                if app.show_hidden {
+                    app.set_status("Showing hidden files".to_string());
+                } else {
+                    app.set_status("Hiding hidden files".to_string());
+                }
+            }
+            InputAction::Help => {
+                app.help = Some(HelpOverlay::new(vec![("File browser", keymap::browser_help())]));
+            }
+            InputAction::NotificationLog => {
+                let entries: Vec<(String, String)> = app
+                    .notifications
+                    .log()
+                    .rev()
+                    .map(|n| (format!("{} {}", n.time.format("%H:%M:%S"), n.level.name()), n.message.clone()))
+                    .collect();
+                if entries.is_empty() {
+                    app.set_status("No messages yet".to_string());
+                } else {
+                    app.help = Some(HelpOverlay::new(vec![("Newest first", entries)]).with_title("Messages"));
+                }
+            }
+            InputAction::Quit => {
+                if worker.busy() {
+                    app.confirm_quit = true;
+                } else {
+                    app.quit();
+                }
+            }
+            InputAction::None => {}
+        }
+
+        if app.should_quit {
+            break;
+        }
+    }
+
+    // Save state before quitting
+    let _ = session_state(&app, &host, port, &username).save();
+    let _ = app.visited.save();
+
+    tui.restore()?;
+    Ok(())
+}
+
+/// The browser's directories, selection, tabs and panels, to resume from
+/// next time
+fn session_state(app: &App, host: &str, port: u16, username: &str) -> SessionState {
+    let tabs = app.tabs.places(&app.history_entry());
+    SessionState::new(
+        host.to_string(),
+        port,
+        username.to_string(),
+        app.current_path.clone(),
+        app.selected_index,
+        app.show_hidden,
+    )
+    .with_tabs(tabs, app.tabs.active())
+    .with_selections(app.selections.entries().to_vec())
+    .with_file_positions(app.file_positions.positions().to_vec())
+    .with_name(app.session_name.clone())
+    .with_view(app.layout, app.detailed_view)
+}
+
+/// The saved connection called `destination`, or else a new one to
+/// `[user@]host[:port]` or an `sftp://` URL; `port` overrides the one in the
+/// destination. Also returns the path in the URL, if there is one.
+fn find_connection(
+    destination: String,
+    port: Option<u16>,
+    identity: Option<PathBuf>,
+) -> Result<(SavedConnection, Option<String>)> {
+    let saved_connections = load_connections().unwrap_or_else(|e| {
+        eprintln!("Warning: {:#}", e);
+        Vec::new()
+    });
+    if let Some(connection) = saved_connections.into_iter().find(|c| c.name == destination) {
+        return Ok((connection, None));
+    }
+    let (username, host, default_port, path) = parse_connection_string(&destination)?;
+    let port = port.unwrap_or(default_port);
+    Ok((SavedConnection::new(destination, host, port, username, identity), path))
+}
+
+/// How to reach `connection`, from its own settings, `config` (with the
+/// connection's overrides applied) and the defaults
+fn connect_options(connection: &SavedConnection, config: &Config, theme: Theme) -> ConnectOptions {
+    let defaults = ConnectOptions::default();
+    ConnectOptions {
+        connect_timeout: connection
+            .connect_timeout
+            .map(Duration::from_secs)
+            .unwrap_or(defaults.connect_timeout),
+        retries: connection.retries.unwrap_or(defaults.retries),
+        compression: connection.compression.unwrap_or(defaults.compression),
+        strict_host_key_checking: connection
+            .strict_host_key_checking
+            .unwrap_or(defaults.strict_host_key_checking),
+        theme,
+        keepalive: Duration::from_secs(config.keepalive_secs),
+    }
+}
+
+/// Make the panel layout the one connections start with until their session
+/// has its own. A named session's layout stays with the session only.
+fn save_layout(app: &mut App) {
+    if app.session_name.is_some() {
+        return;
+    }
+    if let Err(e) = Config::save_layout(&app.layout) {
+        app.set_error(format!("Could not save layout: {:#}", e));
+    }
 }
 
 fn get_parent_path(path: &str) -> String {
@@ -0,0 +1,130 @@
+use chrono::{DateTime, Local};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Messages kept for the notification log
+const LOG_LIMIT: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    /// How long a message stays in the footer; problems stay longer
+    fn lifetime(self) -> Duration {
+        match self {
+            Level::Info => Duration::from_secs(5),
+            Level::Warn => Duration::from_secs(8),
+            Level::Error => Duration::from_secs(12),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Info => "info",
+            Level::Warn => "warn",
+            Level::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub level: Level,
+    pub message: String,
+    /// Wall-clock time, for the log
+    pub time: DateTime<Local>,
+    shown_at: Instant,
+}
+
+impl Notification {
+    fn expired(&self, now: Instant) -> bool {
+        now.duration_since(self.shown_at) >= self.level.lifetime()
+    }
+}
+
+/// Status messages that expire from the footer, plus a log of the recent ones
+#[derive(Debug, Default)]
+pub struct Notifications {
+    log: VecDeque<Notification>,
+    /// Messages from before this are off the screen
+    dismissed_at: Option<Instant>,
+}
+
+impl Notifications {
+    pub fn push(&mut self, level: Level, message: String) {
+        if self.log.len() == LOG_LIMIT {
+            self.log.pop_front();
+        }
+        self.log.push_back(Notification {
+            level,
+            message,
+            time: Local::now(),
+            shown_at: Instant::now(),
+        });
+    }
+
+    /// The message to show at `now` and how many others are still on
+    /// screen. The newest of the most severe wins, so an error is not
+    /// hidden by a later info message.
+    pub fn current(&self, now: Instant) -> Option<(&Notification, usize)> {
+        let active: Vec<&Notification> = self
+            .log
+            .iter()
+            .rev()
+            .filter(|n| !n.expired(now) && self.dismissed_at.is_none_or(|at| n.shown_at > at))
+            .collect();
+        let worst = active.iter().map(|n| n.level).max()?;
+        let shown = active.iter().find(|n| n.level == worst)?;
+        Some((shown, active.len() - 1))
+    }
+
+    /// Every logged message, oldest first
+    pub fn log(&self) -> impl DoubleEndedIterator<Item = &Notification> {
+        self.log.iter()
+    }
+
+    /// Take whatever is on screen off it; it stays in the log
+    pub fn dismiss(&mut self) {
+        self.dismissed_at = Some(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_errors_outrank_and_outlast_info() {
+        let mut notifications = Notifications::default();
+        notifications.push(Level::Error, String::from("Delete failed"));
+        notifications.push(Level::Info, String::from("Copied"));
+        let now = Instant::now();
+
+        let (shown, others) = notifications.current(now).unwrap();
+        assert_eq!((shown.message.as_str(), others), ("Delete failed", 1));
+
+        let later = now + Duration::from_secs(6);
+        let (shown, others) = notifications.current(later).unwrap();
+        assert_eq!((shown.level, others), (Level::Error, 0));
+        assert!(notifications.current(now + Duration::from_secs(13)).is_none());
+        assert_eq!(notifications.log().count(), 2);
+    }
+
+    #[test]
+    fn test_log_is_bounded_and_dismiss_keeps_it() {
+        let mut notifications = Notifications::default();
+        for i in 0..LOG_LIMIT + 5 {
+            notifications.push(Level::Info, i.to_string());
+        }
+        assert_eq!(notifications.log().count(), LOG_LIMIT);
+        assert_eq!(notifications.log().next().unwrap().message, "5");
+
+        notifications.dismiss();
+        assert!(notifications.current(Instant::now()).is_none());
+        assert_eq!(notifications.log().count(), LOG_LIMIT);
+    }
+}
@@ -1,13 +1,38 @@
 use anyhow::{Context, Result};
 use crossterm::terminal;
-use russh::Channel;
-use russh::ChannelStream;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use futures::FutureExt;
+use russh::{Channel, ChannelMsg};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read};
+use std::os::unix::fs::OpenOptionsExt;
+use tokio::io::unix::AsyncFd;
+use tokio::io::{AsyncWriteExt, Interest};
+use tokio::signal::unix::{signal, SignalKind};
+use crate::shell_history::ShellHistory;
 use crate::ssh::client::SshSession;
 
+/// How shells are started, from the config and the connection
+#[derive(Debug, Clone, Default)]
+pub struct ShellOptions {
+    /// Program to run; empty for the remote user's login shell
+    pub shell: String,
+    /// Terminal type for the PTY
+    pub term: String,
+}
+
+/// Most output kept from while the browser is shown; older output is dropped
+const BACKLOG_LIMIT: usize = 1024 * 1024;
+
 pub struct ShellSession {
+    /// Directory the shell was started in, to tell shells apart
+    pub title: String,
+    /// None once the shell has exited
     channel: Option<Channel<russh::client::Msg>>,
-    stream: Option<ChannelStream<russh::client::Msg>>,
+    /// Output that arrived while the browser was shown, printed on the next toggle
+    backlog: VecDeque<u8>,
+    /// Commands typed here, saved for the next shell on this connection
+    history: ShellHistory,
     pub is_active: bool,
 }
 
@@ -15,6 +40,8 @@ impl ShellSession {
     pub async fn new(
         session: &SshSession,
         initial_dir: &str,
+        history: ShellHistory,
+        options: &ShellOptions,
     ) -> Result<Self> {
         let channel = session
             .channel_open_session()
@@ -26,7 +53,7 @@ impl ShellSession {
         channel
             .request_pty(
                 true,
-                "xterm-256color",
+                &options.term,
                 cols as u32,
                 rows as u32,
                 0,
@@ -36,18 +63,27 @@ impl ShellSession {
             .await
             .context("Failed to request PTY")?;
 
-        // Start shell with cd to initial directory
-        let shell_cmd = format!("cd {} && exec $SHELL -l", shell_escape(initial_dir));
+        // Start shell with cd to initial directory, going through sh to
+        // pick the shell and pass on the history whatever the login shell
+        let mut script = String::new();
+        if !options.shell.is_empty() {
+            script.push_str(&format!("SHELL={}; export SHELL; ", shell_escape(&options.shell)));
+        }
+        if let Some(preload) = history.preload_script() {
+            script.push_str(&format!("{}; ", preload));
+        }
+        script.push_str("exec \"$SHELL\" -l");
+        let shell_cmd = format!("cd {} && exec sh -c {}", shell_escape(initial_dir), shell_escape(&script));
         channel
             .exec(true, shell_cmd.as_str())
             .await
             .context("Failed to start shell")?;
 
-        let stream = channel.into_stream();
-
         Ok(Self {
-            channel: None,
-            stream: Some(stream),
+            title: initial_dir.to_string(),
+            channel: Some(channel),
+            backlog: VecDeque::new(),
+            history,
             is_active: true,
         })
     }
@@ -55,34 +91,36 @@ impl ShellSession {
     /// Run the shell I/O loop. Returns when user presses Ctrl+s or shell exits.
     /// Returns Ok(true) if user toggled back, Ok(false) if shell exited.
     pub async fn run(&mut self) -> Result<bool> {
-        let stream = self.stream.take().context("Stream already consumed")?;
-        let (mut read_half, mut write_half) = tokio::io::split(stream);
-
         let mut stdout = tokio::io::stdout();
+        if !self.backlog.is_empty() {
+            stdout.write_all(self.backlog.make_contiguous()).await?;
+            stdout.flush().await?;
+            self.backlog.clear();
+        }
+
+        let channel = self.channel.as_mut().context("Shell channel closed")?;
         let mut stdin_buf = [0u8; 1024];
-        let mut stdout_buf = [0u8; 4096];
+        let stdin = TerminalInput::open()?;
 
-        // Use tokio stdin for async reading
-        let mut stdin = tokio::io::stdin();
+        // SIGWINCH arrives whenever the local terminal changes size
+        let mut resized = signal(SignalKind::window_change()).context("Failed to watch for terminal resize")?;
 
         let result = loop {
             tokio::select! {
                 // Read from remote shell, write to local stdout
-                result = read_half.read(&mut stdout_buf) => {
-                    match result {
-                        Ok(0) => {
-                            // Shell closed
-                            self.is_active = false;
-                            break Ok(false);
-                        }
-                        Ok(n) => {
-                            stdout.write_all(&stdout_buf[..n]).await?;
+                msg = channel.wait() => {
+                    match msg {
+                        Some(ChannelMsg::Data { data }) | Some(ChannelMsg::ExtendedData { data, .. }) => {
+                            self.history.output(&data);
+                            stdout.write_all(&data).await?;
                             stdout.flush().await?;
                         }
-                        Err(_) => {
+                        Some(ChannelMsg::Eof) | Some(ChannelMsg::Close) | None => {
+                            // Shell closed
                             self.is_active = false;
                             break Ok(false);
                         }
+                        Some(_) => {}
                     }
                 }
                 // Read from local stdin, check for Ctrl+s, write to remote
@@ -93,35 +131,230 @@ impl ShellSession {
                             continue;
                         }
                         Ok(n) => {
-                            // Check for Ctrl+s (ASCII 19)
-                            if stdin_buf[..n].contains(&19) {
-                                // User pressed Ctrl+s, toggle back to browser
+                            // Ctrl+s (ASCII 19) toggles back to the browser;
+                            // what was typed before it still goes to the shell
+                            let toggle = stdin_buf[..n].iter().position(|&byte| byte == 19);
+                            let input = &stdin_buf[..toggle.unwrap_or(n)];
+                            if !input.is_empty() {
+                                self.history.input(input);
+                                channel.data(input).await.context("Failed to send input to shell")?;
+                            }
+                            if toggle.is_some() {
                                 break Ok(true);
                             }
-                            write_half.write_all(&stdin_buf[..n]).await?;
-                            write_half.flush().await?;
                         }
                         Err(_) => continue,
                     }
                 }
+                // Pass the new size on so full-screen programs redraw to fit
+                _ = resized.recv() => {
+                    send_size(channel).await?;
+                }
             }
         };
 
-        // If we're toggling back (not exiting), reassemble the stream
-        if let Ok(true) = result {
-            self.stream = Some(read_half.unsplit(write_half));
+        if let Ok(false) = result {
+            self.channel = None;
         }
+        // Losing the history is not worth interrupting the shell for
+        let _ = self.history.save();
 
         result
     }
 
-    /// Update PTY size after terminal resize
-    /// Note: Currently not functional as channel is consumed for stream I/O
-    pub async fn update_size(&self) -> Result<()> {
-        // TODO: Implement window size updates - requires keeping channel reference
-        // For now, initial size is set during PTY request
+    /// Take in output that arrived while the browser is shown, so it is not
+    /// lost and does not queue up without bound. Returns false once the
+    /// shell has exited.
+    pub fn buffer_output(&mut self) -> bool {
+        let Some(channel) = self.channel.as_mut() else {
+            return false;
+        };
+        while let Some(msg) = channel.wait().now_or_never() {
+            match msg {
+                Some(ChannelMsg::Data { data }) | Some(ChannelMsg::ExtendedData { data, .. }) => {
+                    self.history.output(&data);
+                    self.backlog.extend(&data[..]);
+                }
+                Some(ChannelMsg::Eof) | Some(ChannelMsg::Close) | None => {
+                    self.channel = None;
+                    self.is_active = false;
+                    return false;
+                }
+                Some(_) => {}
+            }
+        }
+        let excess = self.backlog.len().saturating_sub(BACKLOG_LIMIT);
+        self.backlog.drain(..excess);
+        true
+    }
+
+    /// Type `input` into the shell as if it came from the keyboard
+    pub async fn send_input(&mut self, input: &[u8]) -> Result<()> {
+        if let Some(channel) = &self.channel
+            && !input.is_empty()
+        {
+            self.history.input(input);
+            channel.data(input).await.context("Failed to send input to shell")?;
+        }
         Ok(())
     }
+
+    /// Whether output arrived while the browser was shown
+    pub fn has_backlog(&self) -> bool {
+        !self.backlog.is_empty()
+    }
+
+    /// Tell the remote PTY the terminal's current size, which may have
+    /// changed while the browser was on screen
+    pub async fn update_size(&self) -> Result<()> {
+        match &self.channel {
+            Some(channel) => send_size(channel).await,
+            None => Ok(()),
+        }
+    }
+}
+
+/// The shells open on one connection, each on its own channel, so one can
+/// run `journalctl -f` while another is used. Ctrl+s enters the active one.
+#[derive(Default)]
+pub struct ShellTabs {
+    shells: Vec<ShellSession>,
+    active: usize,
+    /// Shell whose output the terminal's main screen last showed
+    on_screen: Option<usize>,
+    pub options: ShellOptions,
+}
+
+impl ShellTabs {
+    pub fn new(options: ShellOptions) -> Self {
+        Self {
+            options,
+            ..Self::default()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.shells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shells.is_empty()
+    }
+
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    pub fn shells(&self) -> &[ShellSession] {
+        &self.shells
+    }
+
+    pub fn active_mut(&mut self) -> Option<&mut ShellSession> {
+        self.shells.get_mut(self.active)
+    }
+
+    /// Add a shell and make it the active one
+    pub fn add(&mut self, shell: ShellSession) {
+        self.shells.push(shell);
+        self.active = self.shells.len() - 1;
+    }
+
+    pub fn switch(&mut self, index: usize) {
+        if index < self.shells.len() {
+            self.active = index;
+        }
+    }
+
+    /// Mark the active shell as the one on screen. Returns true if another
+    /// shell's output was there, so the screen needs clearing.
+    pub fn show_active(&mut self) -> bool {
+        self.on_screen.replace(self.active) != Some(self.active)
+    }
+
+    /// Hang up shell `index` and drop it
+    pub async fn close(&mut self, index: usize) {
+        if let Some(channel) = self.shells.get_mut(index).and_then(|shell| shell.channel.take()) {
+            let _ = channel.close().await;
+        }
+        self.remove(index);
+    }
+
+    /// Drop shell `index`, which has exited
+    pub fn remove(&mut self, index: usize) {
+        if index >= self.shells.len() {
+            return;
+        }
+        self.shells.remove(index);
+        if index < self.active || self.active == self.shells.len() {
+            self.active = self.active.saturating_sub(1);
+        }
+        self.on_screen = match self.on_screen {
+            Some(shown) if shown == index => None,
+            Some(shown) if shown > index => Some(shown - 1),
+            shown => shown,
+        };
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new(std::mem::take(&mut self.options));
+    }
+
+    /// Buffer the output of every shell, dropping the ones that exited.
+    /// Returns how many did.
+    pub fn buffer_output(&mut self) -> usize {
+        let mut exited = 0;
+        let mut index = 0;
+        while index < self.shells.len() {
+            if self.shells[index].buffer_output() {
+                index += 1;
+            } else {
+                self.remove(index);
+                exited += 1;
+            }
+        }
+        exited
+    }
+}
+
+/// Keystrokes from the controlling terminal. `tokio::io::stdin` reads on a
+/// blocking thread that keeps going after the shell is left, swallowing the
+/// first key pressed in the browser; this reads only when input is there.
+struct TerminalInput {
+    tty: AsyncFd<File>,
+}
+
+impl TerminalInput {
+    fn open() -> Result<Self> {
+        // A file description of its own, so stdout stays blocking
+        let tty = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open("/dev/tty")
+            .context("Failed to open the terminal")?;
+        // SAFETY: the file owns its descriptor and is only closed when the
+        // AsyncFd holding it is dropped
+        let tty = unsafe { AsyncFd::register_with_interest(tty, Interest::READABLE) }
+            .map_err(|e| e.into_parts().1)
+            .context("Failed to watch the terminal")?;
+        Ok(Self { tty })
+    }
+
+    async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let mut guard = self.tty.readable().await?;
+            if let Ok(result) = guard.try_io(|tty| tty.get_ref().read(buf)) {
+                return result;
+            }
+        }
+    }
+}
+
+async fn send_size(channel: &Channel<russh::client::Msg>) -> Result<()> {
+    let (cols, rows) = terminal::size().context("Failed to read terminal size")?;
+    channel
+        .window_change(cols as u32, rows as u32, 0, 0)
+        .await
+        .context("Failed to resize remote terminal")
 }
 
 fn shell_escape(s: &str) -> String {
@@ -0,0 +1,244 @@
+use anyhow::Result;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::atomic_file::write_atomic;
+use crate::ssh::client::shell_quote;
+
+/// Most commands kept per connection
+const HISTORY_LIMIT: usize = 1000;
+/// Most recent output searched for the echo of a typed line
+const ECHO_LIMIT: usize = 4096;
+
+/// Commands typed into the integrated shell of one connection, kept under
+/// `~/.config/bssh/` and loaded into each new shell, so up-arrow reaches
+/// them even when the remote shell never wrote its own history
+#[derive(Debug)]
+pub struct ShellHistory {
+    path: Option<PathBuf>,
+    lines: Vec<String>,
+    /// Commands entered in this shell, for merging into the file another
+    /// shell may have saved meanwhile
+    entered: Vec<String>,
+    /// Line being typed, or None once a key was used that this cannot
+    /// follow (arrows, Tab, Ctrl+r, ...)
+    typed: Option<String>,
+    /// Output since the line was started, to check the shell echoed it
+    echoed: String,
+}
+
+impl ShellHistory {
+    /// History for a connection; empty if there is none yet or it cannot be read
+    pub fn load(host: &str, port: u16, username: &str) -> Self {
+        let path = history_file_path(host, port, username).ok();
+        let lines = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|text| text.lines().map(str::to_string).collect())
+            .unwrap_or_default();
+        Self::new(path, lines)
+    }
+
+    fn new(path: Option<PathBuf>, lines: Vec<String>) -> Self {
+        Self {
+            path,
+            lines,
+            entered: Vec::new(),
+            typed: Some(String::new()),
+            echoed: String::new(),
+        }
+    }
+
+    pub fn save(&mut self) -> Result<()> {
+        let Some(path) = self.path.clone() else {
+            return Ok(());
+        };
+        if let Ok(text) = fs::read_to_string(&path) {
+            self.lines = text.lines().map(str::to_string).collect();
+            for line in self.entered.clone() {
+                self.push(line);
+            }
+        }
+        let mut text = self.lines.join("\n");
+        text.push('\n');
+        write_atomic(&path, text)?;
+        self.entered.clear();
+        Ok(())
+    }
+
+    /// Follow output from the shell
+    pub fn output(&mut self, data: &[u8]) {
+        self.echoed.push_str(&String::from_utf8_lossy(data));
+        if self.echoed.len() > ECHO_LIMIT {
+            let mut cut = self.echoed.len() - ECHO_LIMIT;
+            while !self.echoed.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.echoed.drain(..cut);
+        }
+    }
+
+    /// Follow keys sent to the shell, keeping each command entered. A line
+    /// the shell did not echo, such as a password, is not kept.
+    pub fn input(&mut self, data: &[u8]) {
+        for c in String::from_utf8_lossy(data).chars() {
+            match c {
+                '\r' | '\n' => {
+                    if let Some(line) = self.typed.take()
+                        && !line.trim().is_empty()
+                        && self.echoed.contains(line.as_str())
+                    {
+                        self.entered.push(line.clone());
+                        self.push(line);
+                    }
+                    self.typed = Some(String::new());
+                    self.echoed.clear();
+                }
+                // Backspace
+                '\u{7f}' | '\u{8}' => {
+                    if let Some(line) = self.typed.as_mut() {
+                        line.pop();
+                    }
+                }
+                // Ctrl+c and Ctrl+u start the line over
+                '\u{3}' | '\u{15}' => {
+                    self.typed = Some(String::new());
+                    self.echoed.clear();
+                }
+                c if c.is_control() => self.typed = None,
+                c => {
+                    if let Some(line) = self.typed.as_mut() {
+                        if line.is_empty() {
+                            self.echoed.clear();
+                        }
+                        line.push(c);
+                    }
+                }
+            }
+        }
+    }
+
+    fn push(&mut self, line: String) {
+        self.lines.retain(|existing| *existing != line);
+        self.lines.push(line);
+        let excess = self.lines.len().saturating_sub(HISTORY_LIMIT);
+        self.lines.drain(..excess);
+    }
+
+    /// `sh` code that starts bash with the history handed over and exits
+    /// when it does. Bash runs `PROMPT_COMMAND` before its first prompt,
+    /// after loading its own history file, so the commands from here come up
+    /// first. Other shells, or a failed `mktemp`, fall through to the code
+    /// after it.
+    pub fn preload_script(&self) -> Option<String> {
+        if self.lines.is_empty() {
+            return None;
+        }
+        let lines: Vec<String> = self.lines.iter().map(|line| shell_quote(line)).collect();
+        // sh stays as bash's parent so the file goes even if bash never
+        // reaches a prompt. The hook runs ahead of any PROMPT_COMMAND from
+        // the environment or the rc files and leaves those as they were.
+        Some(format!(
+            "case \"$SHELL\" in *bash) if f=$(mktemp \"${{TMPDIR:-/tmp}}/.bssh_history.XXXXXX\"); then \
+             trap 'rm -f \"$f\"' EXIT HUP TERM; printf '%s\\n' {} > \"$f\"; \
+             BSSH_HISTORY=$f BSSH_HOOK={}; export BSSH_HISTORY BSSH_HOOK; \
+             PROMPT_COMMAND=\"$BSSH_HOOK${{PROMPT_COMMAND:+\n$PROMPT_COMMAND}}\"; export PROMPT_COMMAND; \
+             \"$SHELL\" -l; exit; fi;; esac",
+            lines.join(" "),
+            shell_quote(PRELOAD_HOOK)
+        ))
+    }
+}
+
+/// Bash code that loads the handed-over history once and then replaces
+/// itself in `PROMPT_COMMAND` with a no-op, keeping whatever else is there
+const PRELOAD_HOOK: &str =
+    r#"history -r "$BSSH_HISTORY"; rm -f "$BSSH_HISTORY"; PROMPT_COMMAND=${PROMPT_COMMAND/"$BSSH_HOOK"/:}; unset BSSH_HISTORY BSSH_HOOK"#;
+
+fn history_file_path(host: &str, port: u16, username: &str) -> Result<PathBuf> {
+    let config_dir = dirs::config_dir()
+        .or_else(|| dirs::home_dir().map(|h| h.join(".config")))
+        .ok_or_else(|| anyhow::anyhow!("Could not find config directory"))?;
+
+    let bssh_dir = config_dir.join("bssh");
+    fs::create_dir_all(&bssh_dir)?;
+
+    Ok(bssh_dir.join(format!("shell_history_{}@{}_{}", username, host, port)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_echoed_lines_are_kept() {
+        let mut history = ShellHistory::new(None, Vec::new());
+
+        history.output(b"$ ");
+        history.input(b"ls -l");
+        history.output(b"ls -l");
+        history.input(b"a\x7f\r");
+        // A password prompt does not echo
+        history.output(b"\r\n[sudo] password: ");
+        history.input(b"hunter2\r");
+        // Recalled with up-arrow, so the line is not known
+        history.input(b"\x1b[A\r");
+        history.output(b"$ ");
+        history.input(b"cd /srv");
+        history.output(b"cd /srv");
+        history.input(b"\r");
+        assert_eq!(history.lines, ["ls -l", "cd /srv"]);
+
+        let preload = history.preload_script().unwrap();
+        assert!(preload.contains("printf '%s\\n' 'ls -l' 'cd /srv' >"));
+    }
+
+    /// Run `script` with `sh`, bash being `shell`
+    fn run_sh(script: &str, shell: &std::path::Path, tmp: &std::path::Path) -> String {
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(script)
+            .env("SHELL", shell)
+            .env("TMPDIR", tmp)
+            .output()
+            .unwrap();
+        String::from_utf8_lossy(&output.stdout).to_string()
+    }
+
+    #[test]
+    fn test_preload_file_goes_when_bash_never_prompts() {
+        let dir = tempfile::tempdir().unwrap();
+        let bash = dir.path().join("bash");
+        std::os::unix::fs::symlink("/bin/true", &bash).unwrap();
+        let history = ShellHistory::new(None, vec![String::from("ls")]);
+
+        let script = format!("{}; echo fell through", history.preload_script().unwrap());
+        assert_eq!(run_sh(&script, &bash, dir.path()), "");
+        let left: Vec<_> = fs::read_dir(dir.path()).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(left, ["bash"]);
+    }
+
+    #[test]
+    fn test_preload_hook_keeps_other_prompt_commands() {
+        if !std::path::Path::new("/bin/bash").exists() {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("history");
+        fs::write(&file, "cd /srv\n").unwrap();
+        // As if an rc file had put hooks on either side of this one
+        let script = format!(
+            "BSSH_HISTORY={} BSSH_HOOK={}; PROMPT_COMMAND=\"a=1;$BSSH_HOOK\n b=2\"; set -o history; \
+             eval \"$PROMPT_COMMAND\"; printf '%s|' \"$PROMPT_COMMAND\" \"$(history 1)\" \"${{BSSH_HOOK-unset}}\"",
+            shell_quote(&file.to_string_lossy()),
+            shell_quote(PRELOAD_HOOK)
+        );
+        let output = std::process::Command::new("/bin/bash").arg("-c").arg(script).output().unwrap();
+        let output = String::from_utf8_lossy(&output.stdout);
+        let parts: Vec<&str> = output.split('|').collect();
+        assert_eq!(parts[0], "a=1;:\n b=2");
+        assert!(parts[1].ends_with("cd /srv"), "{}", parts[1]);
+        assert_eq!(parts[2], "unset");
+        assert!(!file.exists());
+    }
+}
@@ -1,57 +1,349 @@
 use anyhow::{Context, Result};
-use russh::client::{self, Handle};
+use russh::client::{self, Handle, KeyboardInteractiveAuthResponse};
 use russh::*;
 use russh_keys::key::PublicKey;
-use russh_sftp::client::SftpSession;
+use russh_sftp::client::{RawSftpSession, SftpSession};
+use crate::auth_dialog::{prompt_keyboard_interactive, AuthPrompt};
+use crate::theme::Theme;
+use super::host_key::{verify_host_key, HostKeyPromptSender, StrictHostKeyChecking};
+use std::borrow::Cow;
+use std::future::Future;
 use std::path::Path;
 use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::time::Duration;
+use tokio::sync::{watch, Mutex};
+use tokio::time::Instant;
 
+#[derive(Clone)]
 pub struct ConnectionInfo {
     pub host: String,
     pub port: u16,
     pub username: String,
     pub key_path: Option<std::path::PathBuf>,
+    pub options: ConnectOptions,
 }
 
-pub struct Client;
+/// Errors raised by the client handler during the session
+#[derive(Debug)]
+pub enum ClientError {
+    Ssh(russh::Error),
+    HostKeyRejected(String),
+}
+
+impl From<russh::Error> for ClientError {
+    fn from(e: russh::Error) -> Self {
+        ClientError::Ssh(e)
+    }
+}
+
+impl std::fmt::Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClientError::Ssh(e) => write!(f, "{}", e),
+            ClientError::HostKeyRejected(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+/// Whether trying the connection again could succeed; a rejected host key
+/// will be rejected again
+pub fn is_retryable(error: &anyhow::Error) -> bool {
+    !error
+        .chain()
+        .any(|cause| matches!(cause.downcast_ref::<ClientError>(), Some(ClientError::HostKeyRejected(_))))
+}
+
+/// Quote a string for safe use as a single POSIX shell word
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// What a remote command printed and how it ended
+#[derive(Debug, Default)]
+pub struct CommandOutput {
+    pub stdout: String,
+    pub stderr: String,
+    /// None when the server did not report one, e.g. after a signal
+    pub exit_status: Option<u32>,
+}
+
+pub struct Client {
+    host: String,
+    port: u16,
+    host_key_checking: StrictHostKeyChecking,
+    host_key_prompt: Option<HostKeyPromptSender>,
+    /// Set while the user is asked about the host key, which the connect
+    /// timeout does not count
+    prompting: watch::Sender<bool>,
+}
 
 #[async_trait::async_trait]
 impl client::Handler for Client {
-    type Error = russh::Error;
+    type Error = ClientError;
 
     async fn check_server_key(
         &mut self,
-        _server_public_key: &PublicKey,
+        server_public_key: &PublicKey,
     ) -> Result<bool, Self::Error> {
+        self.prompting.send_replace(self.host_key_prompt.is_some());
+        let verified = verify_host_key(
+            self.host_key_checking,
+            &self.host,
+            self.port,
+            server_public_key,
+            self.host_key_prompt.as_ref(),
+        )
+        .await;
+        self.prompting.send_replace(false);
+        verified.map_err(ClientError::HostKeyRejected)?;
+
         Ok(true)
     }
 }
 
 pub type SshSession = Handle<Client>;
 
+/// Run `future` for at most `timeout`, not counting the time `prompting`
+/// is set. None if it took longer.
+async fn with_timeout_unless_prompting<F: Future>(
+    timeout: Duration,
+    mut prompting: watch::Receiver<bool>,
+    future: F,
+) -> Option<F::Output> {
+    tokio::pin!(future);
+    let mut remaining = timeout;
+    loop {
+        let started = Instant::now();
+        let changed = if *prompting.borrow_and_update() {
+            tokio::select! {
+                output = &mut future => return Some(output),
+                changed = prompting.changed() => changed,
+            }
+        } else {
+            tokio::select! {
+                output = &mut future => return Some(output),
+                _ = tokio::time::sleep(remaining) => return None,
+                changed = prompting.changed() => {
+                    remaining = remaining.saturating_sub(started.elapsed());
+                    changed
+                }
+            }
+        };
+        // Nobody can prompt any more
+        if changed.is_err() {
+            return tokio::time::timeout(remaining, future).await.ok();
+        }
+    }
+}
+
+/// Run the keyboard-interactive exchange, asking the user to answer each
+/// round of server prompts (passwords, OTP codes, ...) in a TUI dialog
+async fn authenticate_keyboard_interactive(
+    session: &mut SshSession,
+    username: &str,
+    theme: &Theme,
+) -> Result<bool> {
+    let mut response = session
+        .authenticate_keyboard_interactive_start(username, None)
+        .await
+        .context("Keyboard-interactive authentication failed")?;
+
+    loop {
+        match response {
+            KeyboardInteractiveAuthResponse::Success => return Ok(true),
+            KeyboardInteractiveAuthResponse::Failure => return Ok(false),
+            KeyboardInteractiveAuthResponse::InfoRequest {
+                name,
+                instructions,
+                prompts,
+            } => {
+                // Servers may send an empty round purely as a banner
+                let answers = if prompts.is_empty() {
+                    Vec::new()
+                } else {
+                    let prompts = prompts
+                        .into_iter()
+                        .map(|p| AuthPrompt {
+                            prompt: p.prompt,
+                            echo: p.echo,
+                        })
+                        .collect();
+                    prompt_keyboard_interactive(&name, &instructions, prompts, theme)?
+                        .ok_or_else(|| anyhow::anyhow!("Authentication cancelled"))?
+                };
+
+                response = session
+                    .authenticate_keyboard_interactive_respond(answers)
+                    .await
+                    .context("Keyboard-interactive authentication failed")?;
+            }
+        }
+    }
+}
+
+/// Clones share the session and the directory listing session
+#[derive(Clone)]
 pub struct SshClient {
-    pub session: Handle<Client>,
+    pub session: Arc<SshSession>,
     pub connection_info: ConnectionInfo,
+    /// Raw SFTP session used for streaming directory listings
+    dir_lister: Arc<Mutex<Option<Arc<RawSftpSession>>>>,
 }
 
+/// Default time to wait for the TCP connect and SSH handshake
+pub const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 15;
+
+/// Transport-level settings applied when establishing a connection
+#[derive(Debug, Clone)]
+pub struct ConnectOptions {
+    pub connect_timeout: Duration,
+    /// Additional attempts after the first one fails
+    pub retries: u32,
+    /// Negotiate zlib compression of the transport
+    pub compression: bool,
+    pub strict_host_key_checking: StrictHostKeyChecking,
+    /// Colours of the dialogs shown while connecting
+    pub theme: Theme,
+    /// Time between keepalive messages; zero sends none
+    pub keepalive: Duration,
+}
+
+impl Default for ConnectOptions {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(DEFAULT_CONNECT_TIMEOUT_SECS),
+            retries: 0,
+            compression: false,
+            strict_host_key_checking: StrictHostKeyChecking::default(),
+            theme: Theme::default(),
+            keepalive: Duration::from_secs(DEFAULT_KEEPALIVE_SECS),
+        }
+    }
+}
+
+/// Delay between connection attempts
+pub const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Minimum attempts made when re-establishing a dropped connection
+const RECONNECT_RETRIES: u32 = 3;
+
+/// Default seconds between keepalive messages
+pub const DEFAULT_KEEPALIVE_SECS: u64 = 15;
+
 impl SshClient {
     pub async fn connect(
         host: &str,
         port: u16,
         username: &str,
         key_path: Option<&Path>,
+        options: &ConnectOptions,
     ) -> Result<Self> {
+        let mut attempt = 0;
+        let session = loop {
+            match Self::open_transport(host, port, options, None).await {
+                Ok(session) => break session,
+                Err(e) if attempt >= options.retries || !is_retryable(&e) => return Err(e),
+                Err(_) => {
+                    attempt += 1;
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+            }
+        };
+
+        Self::authenticate(session, host, port, username, key_path, options).await
+    }
+
+    /// Whether the underlying SSH session is still alive
+    pub fn is_connected(&self) -> bool {
+        !self.session.is_closed()
+    }
+
+    /// Open a fresh connection with the same target and settings, e.g. after
+    /// the network dropped
+    pub async fn reconnect(&self) -> Result<Self> {
+        let info = &self.connection_info;
+        let options = ConnectOptions {
+            retries: info.options.retries.max(RECONNECT_RETRIES),
+            ..info.options.clone()
+        };
+
+        Self::connect(
+            &info.host,
+            info.port,
+            &info.username,
+            info.key_path.as_deref(),
+            &options,
+        )
+        .await
+    }
+
+    /// Establish the TCP connection and SSH handshake, without authenticating.
+    /// Unknown host keys are offered on `host_key_prompt` when checking is `ask`.
+    pub async fn open_transport(
+        host: &str,
+        port: u16,
+        options: &ConnectOptions,
+        host_key_prompt: Option<HostKeyPromptSender>,
+    ) -> Result<SshSession> {
+        let mut preferred = Preferred::DEFAULT;
+        if options.compression {
+            // Listed before "none" so zlib wins when the server supports it
+            preferred.compression = Cow::Borrowed(&[
+                compression::ZLIB_LEGACY,
+                compression::ZLIB,
+                compression::NONE,
+            ]);
+        }
+
+        // Keepalives let us notice a dead link instead of hanging on it.
+        // Without them an idle session must not be dropped as inactive.
+        let keepalive = (!options.keepalive.is_zero()).then_some(options.keepalive);
         let config = client::Config {
-            inactivity_timeout: Some(std::time::Duration::from_secs(300)),
+            inactivity_timeout: keepalive.map(|_| Duration::from_secs(300)),
+            keepalive_interval: keepalive,
+            keepalive_max: 3,
+            preferred,
             ..<russh::client::Config as Default>::default()
         };
 
-        let sh = Client;
-        let mut session = client::connect(Arc::new(config), (host, port), sh)
-            .await
-            .context("Failed to connect to SSH server")?;
+        let (prompting, prompting_changes) = watch::channel(false);
+        let sh = Client {
+            host: host.to_string(),
+            port,
+            host_key_checking: options.strict_host_key_checking,
+            host_key_prompt,
+            prompting,
+        };
+        // The user may take their time deciding whether to trust the host
+        let session = with_timeout_unless_prompting(
+            options.connect_timeout,
+            prompting_changes,
+            client::connect(Arc::new(config), (host, port), sh),
+        )
+        .await
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Connection timed out after {}s",
+                options.connect_timeout.as_secs()
+            )
+        })?
+        .context("Failed to connect to SSH server")?;
+
+        Ok(session)
+    }
 
+    /// Authenticate an established transport, trying the identity file first
+    /// and falling back to keyboard-interactive
+    pub async fn authenticate(
+        mut session: SshSession,
+        host: &str,
+        port: u16,
+        username: &str,
+        key_path: Option<&Path>,
+        options: &ConnectOptions,
+    ) -> Result<Self> {
         let key_path_buf = key_path
             .map(|p| p.to_path_buf())
             .unwrap_or_else(|| {
@@ -59,15 +351,32 @@ impl SshClient {
                 home.join(".ssh/id_rsa")
             });
 
-        let key_pair = russh_keys::load_secret_key(&key_path_buf, None)
-            .context("Failed to load SSH key")?;
+        // A missing default key is fine as long as the server offers
+        // keyboard-interactive auth; an explicitly requested key is not
+        let key_pair = match russh_keys::load_secret_key(&key_path_buf, None) {
+            Ok(key_pair) => Some(key_pair),
+            Err(e) if key_path.is_some() => {
+                return Err(e).context("Failed to load SSH key");
+            }
+            Err(_) => None,
+        };
 
-        let auth_res = session
-            .authenticate_publickey(username, Arc::new(key_pair))
-            .await
-            .context("Authentication failed")?;
+        let used_key_path = key_pair.as_ref().map(|_| key_path_buf.clone());
 
-        if !auth_res {
+        let mut authenticated = false;
+        if let Some(key_pair) = key_pair {
+            authenticated = session
+                .authenticate_publickey(username, Arc::new(key_pair))
+                .await
+                .context("Authentication failed")?;
+        }
+
+        // Fall back to (or continue with, for MFA setups) keyboard-interactive
+        if !authenticated {
+            authenticated = authenticate_keyboard_interactive(&mut session, username, &options.theme).await?;
+        }
+
+        if !authenticated {
             anyhow::bail!("Authentication failed");
         }
 
@@ -75,10 +384,15 @@ impl SshClient {
             host: host.to_string(),
             port,
             username: username.to_string(),
-            key_path: Some(key_path_buf),
+            key_path: used_key_path,
+            options: options.clone(),
         };
 
-        Ok(Self { session, connection_info })
+        Ok(Self {
+            session: Arc::new(session),
+            connection_info,
+            dir_lister: Arc::default(),
+        })
     }
 
     pub async fn open_sftp(&mut self) -> Result<SftpSession> {
@@ -100,160 +414,116 @@ impl SshClient {
         Ok(sftp)
     }
 
-    pub async fn execute_command(&mut self, command: &str) -> Result<String> {
-        let mut channel = self
+    /// SFTP session exposing READDIR batches, opened on first use on its own
+    /// channel so long listings do not hold up other transfers
+    pub async fn dir_lister(&self) -> Result<Arc<RawSftpSession>> {
+        // Held while opening, so listings started together share one session
+        let mut dir_lister = self.dir_lister.lock().await;
+        if let Some(ref lister) = *dir_lister {
+            return Ok(lister.clone());
+        }
+
+        let channel = self
             .session
             .channel_open_session()
             .await
             .context("Failed to open channel")?;
 
         channel
-            .exec(true, command)
+            .request_subsystem(true, "sftp")
             .await
-            .context("Failed to execute command")?;
+            .context("Failed to request SFTP subsystem")?;
 
-        let mut output = String::new();
-        let mut code = None;
+        let lister = RawSftpSession::new(channel.into_stream());
+        lister
+            .init()
+            .await
+            .context("Failed to create SFTP session")?;
 
-        loop {
-            let Some(msg) = channel.wait().await else {
-                break;
-            };
+        let lister = Arc::new(lister);
+        *dir_lister = Some(lister.clone());
+        Ok(lister)
+    }
 
-            match msg {
-                ChannelMsg::Data { ref data } => {
-                    output.push_str(&String::from_utf8_lossy(data));
-                }
-                ChannelMsg::ExitStatus { exit_status } => {
-                    code = Some(exit_status);
-                }
-                _ => {}
-            }
-        }
+    /// Forget the listing session, e.g. after it failed; the next listing reopens it
+    pub async fn drop_dir_lister(&self) {
+        *self.dir_lister.lock().await = None;
+    }
 
-        if let Some(code) = code {
-            if code != 0 {
-                anyhow::bail!("Command exited with code {}: {}", code, output);
-            }
+    pub async fn execute_command(&mut self, command: &str) -> Result<String> {
+        let result = self.run_command(command).await?;
+        let output = result.stdout + &result.stderr;
+
+        if let Some(code) = result.exit_status
+            && code != 0
+        {
+            anyhow::bail!("Command exited with code {}: {}", code, output);
         }
 
         Ok(output)
     }
 
-    pub async fn execute_interactive(&mut self, command: &str) -> Result<()> {
-        use crossterm::terminal;
-
+    /// Start `command` and hand back its channel, to read the output as it comes
+    pub async fn start_command(&mut self, command: &str) -> Result<Channel<client::Msg>> {
         let channel = self
             .session
             .channel_open_session()
             .await
             .context("Failed to open channel")?;
 
-        // Get terminal size
-        let (cols, rows) = terminal::size().unwrap_or((80, 24));
-
-        // Request a PTY for interactive programs like vim
-        channel
-            .request_pty(
-                true,
-                "xterm-256color",
-                cols as u32,
-                rows as u32,
-                0,
-                0,
-                &[], // no terminal modes
-            )
-            .await
-            .context("Failed to request PTY")?;
-
         channel
             .exec(true, command)
             .await
             .context("Failed to execute command")?;
 
-        // Enable raw mode using crossterm (consistent with TUI)
-        terminal::enable_raw_mode()?;
-
-        // Channel stream for reading/writing
-        let stream = channel.into_stream();
-        let (mut read_half, mut write_half) = tokio::io::split(stream);
-
-        // Spawn task to forward stdin to remote
-        let stdin_task = tokio::spawn(async move {
-            let mut stdin = tokio::io::stdin();
-            let mut buf = [0u8; 4096];
-            loop {
-                match stdin.read(&mut buf).await {
-                    Ok(0) | Err(_) => break,
-                    Ok(n) => {
-                        if write_half.write_all(&buf[..n]).await.is_err() {
-                            break;
-                        }
-                    }
-                }
-            }
-        });
-
-        // Forward remote output to stdout
-        let mut stdout = tokio::io::stdout();
-        let mut buf = [0u8; 4096];
-        loop {
-            match read_half.read(&mut buf).await {
-                Ok(0) | Err(_) => break,
-                Ok(n) => {
-                    stdout.write_all(&buf[..n]).await?;
-                    stdout.flush().await?;
-                }
-            }
-        }
-
-        // Flush output one more time
-        stdout.flush().await?;
-
-        // Give stdin task a moment to finish processing
-        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-
-        // Abort stdin task
-        stdin_task.abort();
-
-        // Flush any pending input BEFORE disabling raw mode
-        use crossterm::event::{self};
-        while event::poll(std::time::Duration::from_millis(50))? {
-            let _ = event::read(); // Consume and discard
-        }
+        Ok(channel)
+    }
 
-        // Now disable raw mode (will be re-enabled by TUI::new())
-        terminal::disable_raw_mode()?;
+    /// Run `command` to completion, keeping its stdout and stderr apart.
+    /// Unlike `execute_command`, a failing exit status is not an error.
+    pub async fn run_command(&mut self, command: &str) -> Result<CommandOutput> {
+        let mut channel = self.start_command(command).await?;
 
-        // One more flush after disabling raw mode
-        while event::poll(std::time::Duration::from_millis(50))? {
-            let _ = event::read();
+        let mut output = CommandOutput::default();
+        while let Some(msg) = channel.wait().await {
+            match msg {
+                ChannelMsg::Data { ref data } => output.stdout.push_str(&String::from_utf8_lossy(data)),
+                ChannelMsg::ExtendedData { ref data, .. } => output.stderr.push_str(&String::from_utf8_lossy(data)),
+                ChannelMsg::ExitStatus { exit_status } => output.exit_status = Some(exit_status),
+                _ => {}
+            }
         }
 
-        Ok(())
+        Ok(output)
     }
+}
 
-    // Simpler approach: use system ssh command
-    pub fn execute_interactive_external(&self, command: &str) -> Result<()> {
-        use std::process::Command;
-
-        let mut cmd = Command::new("ssh");
-
-        cmd.arg("-p").arg(self.connection_info.port.to_string());
-
-        if let Some(ref key) = self.connection_info.key_path {
-            cmd.arg("-i").arg(key);
-        }
-
-        cmd.arg(format!("{}@{}", self.connection_info.username, self.connection_info.host));
-        cmd.arg(command);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let status = cmd.status()?;
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("/srv/my file"), "'/srv/my file'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+        assert_eq!(shell_quote("$(rm -rf /)"), "'$(rm -rf /)'");
+    }
 
-        if !status.success() {
-            anyhow::bail!("Command failed with exit code: {:?}", status.code());
-        }
+    #[tokio::test]
+    async fn test_host_key_prompt_does_not_count_towards_the_timeout() {
+        let (prompting, changes) = watch::channel(false);
+        let handshake = async {
+            prompting.send_replace(true);
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            prompting.send_replace(false);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            "connected"
+        };
+        let connected = with_timeout_unless_prompting(Duration::from_millis(100), changes, handshake).await;
+        assert_eq!(connected, Some("connected"));
 
-        Ok(())
+        let (_prompting, changes) = watch::channel(false);
+        let stuck = tokio::time::sleep(Duration::from_secs(5));
+        assert_eq!(with_timeout_unless_prompting(Duration::from_millis(50), changes, stuck).await, None);
     }
 }
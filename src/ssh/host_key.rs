@@ -0,0 +1,155 @@
+use russh_keys::key::PublicKey;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+
+/// Equivalent of OpenSSH's `StrictHostKeyChecking` option
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum StrictHostKeyChecking {
+    /// Only connect to hosts already in known_hosts
+    Yes,
+    /// Accept any host key without checking
+    No,
+    /// Record unknown hosts automatically, reject changed keys
+    AcceptNew,
+    /// Ask before trusting an unknown host, reject changed keys
+    #[default]
+    Ask,
+}
+
+/// A request for the user to decide whether to trust an unknown host key
+pub struct HostKeyPrompt {
+    pub host: String,
+    pub port: u16,
+    pub key_type: String,
+    pub fingerprint: String,
+    pub reply: oneshot::Sender<bool>,
+}
+
+pub type HostKeyPromptSender = mpsc::UnboundedSender<HostKeyPrompt>;
+
+/// What known_hosts says about a server key
+#[derive(Debug, Clone, PartialEq)]
+pub enum KnownHostStatus {
+    Known,
+    Unknown,
+    Changed { line: usize },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HostKeyDecision {
+    Accept,
+    /// Accept and record in known_hosts
+    Learn,
+    Prompt,
+    Reject(String),
+}
+
+pub fn decide(mode: StrictHostKeyChecking, status: &KnownHostStatus, host: &str) -> HostKeyDecision {
+    match (mode, status) {
+        (StrictHostKeyChecking::No, _) => HostKeyDecision::Accept,
+        (_, KnownHostStatus::Known) => HostKeyDecision::Accept,
+        (_, KnownHostStatus::Changed { line }) => HostKeyDecision::Reject(format!(
+            "Host key for {} has changed (known_hosts line {}); possible man-in-the-middle attack",
+            host, line
+        )),
+        (StrictHostKeyChecking::Yes, KnownHostStatus::Unknown) => HostKeyDecision::Reject(format!(
+            "No host key is known for {} and strict checking is enabled",
+            host
+        )),
+        (StrictHostKeyChecking::AcceptNew, KnownHostStatus::Unknown) => HostKeyDecision::Learn,
+        (StrictHostKeyChecking::Ask, KnownHostStatus::Unknown) => HostKeyDecision::Prompt,
+    }
+}
+
+pub fn known_host_status(host: &str, port: u16, key: &PublicKey) -> KnownHostStatus {
+    match russh_keys::check_known_hosts(host, port, key) {
+        Ok(true) => KnownHostStatus::Known,
+        Err(russh_keys::Error::KeyChanged { line }) => KnownHostStatus::Changed { line },
+        // Missing or unparseable entries mean we cannot vouch for the key
+        Ok(false) | Err(_) => KnownHostStatus::Unknown,
+    }
+}
+
+/// Apply the checking mode to a server key. Returns an error message when
+/// the connection must not proceed.
+pub async fn verify_host_key(
+    mode: StrictHostKeyChecking,
+    host: &str,
+    port: u16,
+    key: &PublicKey,
+    prompt: Option<&HostKeyPromptSender>,
+) -> Result<(), String> {
+    let status = known_host_status(host, port, key);
+
+    let learn = match decide(mode, &status, host) {
+        HostKeyDecision::Accept => false,
+        HostKeyDecision::Learn => true,
+        HostKeyDecision::Reject(reason) => return Err(reason),
+        HostKeyDecision::Prompt => {
+            // Without anyone to ask, fall back to refusing like `yes`
+            let Some(prompt) = prompt else {
+                return Err(format!("Host key for {} is not known and cannot be confirmed", host));
+            };
+
+            let (reply, answer) = oneshot::channel();
+            let request = HostKeyPrompt {
+                host: host.to_string(),
+                port,
+                key_type: key.name().to_string(),
+                fingerprint: key.fingerprint(),
+                reply,
+            };
+
+            if prompt.send(request).is_err() || !answer.await.unwrap_or(false) {
+                return Err(format!("Host key for {} was not accepted", host));
+            }
+            true
+        }
+    };
+
+    if learn {
+        // Failing to record the key should not block a key the user accepted
+        let _ = russh_keys::learn_known_hosts(host, port, key);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decide_no_accepts_changed_key() {
+        let decision = decide(StrictHostKeyChecking::No, &KnownHostStatus::Changed { line: 3 }, "h");
+        assert_eq!(decision, HostKeyDecision::Accept);
+    }
+
+    #[test]
+    fn test_decide_unknown_host_per_mode() {
+        let unknown = KnownHostStatus::Unknown;
+
+        assert!(matches!(decide(StrictHostKeyChecking::Yes, &unknown, "h"), HostKeyDecision::Reject(_)));
+        assert_eq!(decide(StrictHostKeyChecking::AcceptNew, &unknown, "h"), HostKeyDecision::Learn);
+        assert_eq!(decide(StrictHostKeyChecking::Ask, &unknown, "h"), HostKeyDecision::Prompt);
+    }
+
+    #[test]
+    fn test_decide_changed_key_rejected_unless_disabled() {
+        let changed = KnownHostStatus::Changed { line: 7 };
+
+        for mode in [StrictHostKeyChecking::Yes, StrictHostKeyChecking::AcceptNew, StrictHostKeyChecking::Ask] {
+            match decide(mode, &changed, "h") {
+                HostKeyDecision::Reject(reason) => assert!(reason.contains("line 7")),
+                other => panic!("expected rejection, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_mode_serializes_like_openssh() {
+        let json = serde_json::to_string(&StrictHostKeyChecking::AcceptNew).unwrap();
+        assert_eq!(json, "\"accept-new\"");
+    }
+}
@@ -1,3 +1,5 @@
 pub mod client;
+pub mod host_key;
+pub mod socks;
 
 pub use client::SshClient;
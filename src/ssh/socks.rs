@@ -0,0 +1,226 @@
+use anyhow::{Context, Result};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+
+use super::client::SshSession;
+
+const SOCKS_VERSION: u8 = 0x05;
+const METHOD_NO_AUTH: u8 = 0x00;
+const METHOD_NONE_ACCEPTABLE: u8 = 0xff;
+const CMD_CONNECT: u8 = 0x01;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+const REPLY_SUCCEEDED: u8 = 0x00;
+const REPLY_GENERAL_FAILURE: u8 = 0x01;
+const REPLY_COMMAND_NOT_SUPPORTED: u8 = 0x07;
+const REPLY_ADDRESS_NOT_SUPPORTED: u8 = 0x08;
+
+/// Pause after a failed accept, so that running out of file descriptors
+/// does not spin until clients close theirs
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(200);
+
+/// Destination requested by a SOCKS5 client
+#[derive(Debug, Clone, PartialEq)]
+pub struct SocksTarget {
+    pub host: String,
+    pub port: u16,
+}
+
+/// Reasons a SOCKS5 request is refused, mapped to protocol reply codes
+#[derive(Debug, PartialEq)]
+enum RequestError {
+    CommandNotSupported,
+    AddressNotSupported,
+}
+
+impl RequestError {
+    fn reply_code(&self) -> u8 {
+        match self {
+            RequestError::CommandNotSupported => REPLY_COMMAND_NOT_SUPPORTED,
+            RequestError::AddressNotSupported => REPLY_ADDRESS_NOT_SUPPORTED,
+        }
+    }
+}
+
+/// A local SOCKS5 listener (like `ssh -D`) whose connections are tunnelled
+/// as direct-tcpip channels over the SSH session
+pub struct SocksProxy {
+    pub port: u16,
+    session: watch::Sender<Arc<SshSession>>,
+}
+
+impl SocksProxy {
+    pub async fn start(session: Arc<SshSession>, port: u16) -> Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+            .await
+            .with_context(|| format!("Failed to bind SOCKS proxy on port {}", port))?;
+
+        let (sender, receiver) = watch::channel(session);
+
+        tokio::spawn(async move {
+            loop {
+                let Ok((stream, peer)) = listener.accept().await else {
+                    tokio::time::sleep(ACCEPT_BACKOFF).await;
+                    continue;
+                };
+                let session = receiver.borrow().clone();
+                tokio::spawn(async move {
+                    // Errors only affect this client; the TUI owns the terminal,
+                    // so there is nowhere useful to report them
+                    let _ = handle_client(session, stream, peer).await;
+                });
+            }
+        });
+
+        Ok(Self { port, session: sender })
+    }
+
+    /// Route new proxy connections over a replacement session after reconnecting
+    pub fn update_session(&self, session: Arc<SshSession>) {
+        let _ = self.session.send(session);
+    }
+}
+
+async fn handle_client(session: Arc<SshSession>, mut stream: TcpStream, peer: SocketAddr) -> Result<()> {
+    negotiate_method(&mut stream).await?;
+
+    let target = match read_request(&mut stream).await? {
+        Ok(target) => target,
+        Err(e) => {
+            send_reply(&mut stream, e.reply_code()).await?;
+            return Ok(());
+        }
+    };
+
+    let channel = match session
+        .channel_open_direct_tcpip(
+            target.host.clone(),
+            target.port as u32,
+            peer.ip().to_string(),
+            peer.port() as u32,
+        )
+        .await
+    {
+        Ok(channel) => channel,
+        Err(e) => {
+            send_reply(&mut stream, REPLY_GENERAL_FAILURE).await?;
+            return Err(e).context("Failed to open forwarding channel");
+        }
+    };
+
+    send_reply(&mut stream, REPLY_SUCCEEDED).await?;
+
+    let mut remote = channel.into_stream();
+    tokio::io::copy_bidirectional(&mut stream, &mut remote).await?;
+
+    Ok(())
+}
+
+/// Read the client greeting and select "no authentication"
+async fn negotiate_method<S: AsyncRead + AsyncWrite + Unpin>(stream: &mut S) -> Result<()> {
+    let mut header = [0u8; 2];
+    stream.read_exact(&mut header).await?;
+    if header[0] != SOCKS_VERSION {
+        anyhow::bail!("Unsupported SOCKS version {}", header[0]);
+    }
+
+    let mut methods = vec![0u8; header[1] as usize];
+    stream.read_exact(&mut methods).await?;
+
+    if methods.contains(&METHOD_NO_AUTH) {
+        stream.write_all(&[SOCKS_VERSION, METHOD_NO_AUTH]).await?;
+        Ok(())
+    } else {
+        stream.write_all(&[SOCKS_VERSION, METHOD_NONE_ACCEPTABLE]).await?;
+        anyhow::bail!("Client offered no supported authentication method")
+    }
+}
+
+/// Parse a CONNECT request. The outer Result is an I/O failure, the inner one
+/// a well-formed request we refuse.
+async fn read_request<R: AsyncRead + Unpin>(
+    stream: &mut R,
+) -> Result<std::result::Result<SocksTarget, RequestError>> {
+    let mut header = [0u8; 4];
+    stream.read_exact(&mut header).await?;
+    let [version, command, _reserved, address_type] = header;
+
+    if version != SOCKS_VERSION {
+        anyhow::bail!("Unsupported SOCKS version {}", version);
+    }
+
+    let host = match address_type {
+        ATYP_IPV4 => {
+            let mut octets = [0u8; 4];
+            stream.read_exact(&mut octets).await?;
+            Ipv4Addr::from(octets).to_string()
+        }
+        ATYP_IPV6 => {
+            let mut octets = [0u8; 16];
+            stream.read_exact(&mut octets).await?;
+            Ipv6Addr::from(octets).to_string()
+        }
+        ATYP_DOMAIN => {
+            let len = stream.read_u8().await? as usize;
+            let mut name = vec![0u8; len];
+            stream.read_exact(&mut name).await?;
+            String::from_utf8_lossy(&name).to_string()
+        }
+        _ => return Ok(Err(RequestError::AddressNotSupported)),
+    };
+
+    let port = stream.read_u16().await?;
+
+    if command != CMD_CONNECT {
+        return Ok(Err(RequestError::CommandNotSupported));
+    }
+
+    Ok(Ok(SocksTarget { host, port }))
+}
+
+async fn send_reply<W: AsyncWrite + Unpin>(stream: &mut W, code: u8) -> Result<()> {
+    // Bound address is not meaningful for a tunnelled connection
+    let reply = [SOCKS_VERSION, code, 0x00, ATYP_IPV4, 0, 0, 0, 0, 0, 0];
+    stream.write_all(&reply).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read_request_domain() {
+        let mut request: &[u8] = &[
+            0x05, 0x01, 0x00, 0x03, 11, b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'c',
+            b'o', b'm', 0x01, 0xbb,
+        ];
+
+        let target = read_request(&mut request).await.unwrap().unwrap();
+        assert_eq!(target.host, "example.com");
+        assert_eq!(target.port, 443);
+    }
+
+    #[tokio::test]
+    async fn test_read_request_ipv4() {
+        let mut request: &[u8] = &[0x05, 0x01, 0x00, 0x01, 10, 0, 0, 7, 0x00, 0x50];
+
+        let target = read_request(&mut request).await.unwrap().unwrap();
+        assert_eq!(target.host, "10.0.0.7");
+        assert_eq!(target.port, 80);
+    }
+
+    #[tokio::test]
+    async fn test_read_request_rejects_bind() {
+        let mut request: &[u8] = &[0x05, 0x02, 0x00, 0x01, 127, 0, 0, 1, 0x00, 0x50];
+
+        let result = read_request(&mut request).await.unwrap();
+        assert_eq!(result, Err(RequestError::CommandNotSupported));
+    }
+}
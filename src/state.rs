@@ -1,7 +1,13 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use crate::app::HistoryEntry;
+use crate::atomic_file::write_atomic;
+use crate::editor::FilePosition;
+use crate::tui::layout::PanelLayout;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SessionState {
@@ -10,47 +16,330 @@ pub struct SessionState {
     pub username: String,
     pub current_path: String,
     pub selected_index: usize,
+    /// Whether dotfiles are listed in the browser; the config file's setting
+    /// applies to sessions saved without one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub show_hidden: Option<bool>,
+    /// Where each directory tab was, the current directory being the active one
+    #[serde(default)]
+    pub tabs: Vec<HistoryEntry>,
+    #[serde(default)]
+    pub active_tab: usize,
+    /// Entry last selected in each directory left, most recent last
+    #[serde(default)]
+    pub selections: Vec<HistoryEntry>,
+    /// Editor cursor in each file edited lately, most recent last
+    #[serde(default)]
+    pub file_positions: Vec<FilePosition>,
+    /// Name of a session kept apart from the connection's default one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Panels open when the session was left; the config file's layout
+    /// applies to sessions saved without one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<PanelLayout>,
+    /// Whether the file list showed permissions, owner and mtime columns
+    #[serde(default)]
+    pub detailed_view: bool,
 }
 
 impl SessionState {
-    pub fn new(host: String, port: u16, username: String, current_path: String, selected_index: usize) -> Self {
+    pub fn new(
+        host: String,
+        port: u16,
+        username: String,
+        current_path: String,
+        selected_index: usize,
+        show_hidden: bool,
+    ) -> Self {
         Self {
             host,
             port,
             username,
             current_path,
             selected_index,
+            show_hidden: Some(show_hidden),
+            tabs: Vec::new(),
+            active_tab: 0,
+            selections: Vec::new(),
+            file_positions: Vec::new(),
+            name: None,
+            layout: None,
+            detailed_view: false,
+        }
+    }
+
+    pub fn with_tabs(mut self, tabs: Vec<HistoryEntry>, active_tab: usize) -> Self {
+        self.tabs = tabs;
+        self.active_tab = active_tab;
+        self
+    }
+
+    pub fn with_selections(mut self, selections: Vec<HistoryEntry>) -> Self {
+        self.selections = selections;
+        self
+    }
+
+    pub fn with_file_positions(mut self, file_positions: Vec<FilePosition>) -> Self {
+        self.file_positions = file_positions;
+        self
+    }
+
+    pub fn with_name(mut self, name: Option<String>) -> Self {
+        self.name = name;
+        self
+    }
+
+    /// Keep the panels and file list columns as they are
+    pub fn with_view(mut self, layout: PanelLayout, detailed_view: bool) -> Self {
+        self.layout = Some(layout);
+        self.detailed_view = detailed_view;
+        self
+    }
+
+    /// Session names become part of a file name, so they are kept simple
+    pub fn check_name(name: &str) -> Result<()> {
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+            bail!("Session names may only contain letters, digits, '-' and '_': '{}'", name);
         }
+        Ok(())
     }
 
-    fn get_state_file_path(host: &str, port: u16, username: &str) -> Result<PathBuf> {
+    fn get_state_dir() -> Result<PathBuf> {
         let config_dir = dirs::config_dir()
             .or_else(|| dirs::home_dir().map(|h| h.join(".config")))
             .ok_or_else(|| anyhow::anyhow!("Could not find config directory"))?;
 
         let bssh_dir = config_dir.join("bssh");
         fs::create_dir_all(&bssh_dir)?;
+        Ok(bssh_dir)
+    }
+
+    /// `session_user@host_port`, which each state file of the connection starts with
+    fn file_stem(host: &str, port: u16, username: &str) -> String {
+        format!("session_{}@{}_{}", username, host, port)
+    }
 
-        // Create a unique filename per connection
-        let filename = format!("session_{}@{}_{}.json", username, host, port);
-        Ok(bssh_dir.join(filename))
+    fn get_state_file_path(host: &str, port: u16, username: &str, name: Option<&str>) -> Result<PathBuf> {
+        // Create a unique filename per connection and session
+        let stem = Self::file_stem(host, port, username);
+        let filename = match name {
+            Some(name) => format!("{}.{}.json", stem, name),
+            None => format!("{}.json", stem),
+        };
+        Ok(Self::get_state_dir()?.join(filename))
+    }
+
+    /// Names of the connection's named sessions, sorted
+    pub fn named_sessions(host: &str, port: u16, username: &str) -> Vec<String> {
+        let Ok(entries) = Self::get_state_dir().and_then(|dir| Ok(fs::read_dir(dir)?)) else {
+            return Vec::new();
+        };
+        let prefix = format!("{}.", Self::file_stem(host, port, username));
+        let mut names: Vec<String> = entries
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter_map(|file| Some(file.strip_prefix(&prefix)?.strip_suffix(".json")?.to_string()))
+            .filter(|name| Self::check_name(name).is_ok())
+            .collect();
+        names.sort();
+        names
     }
 
     pub fn save(&self) -> Result<()> {
-        let state_file = Self::get_state_file_path(&self.host, self.port, &self.username)?;
+        let state_file = Self::get_state_file_path(&self.host, self.port, &self.username, self.name.as_deref())?;
         let json = serde_json::to_string_pretty(self)?;
-        fs::write(state_file, json)?;
+        write_atomic(&state_file, json)?;
         Ok(())
     }
 
-    pub fn load(host: &str, port: u16, username: &str) -> Option<Self> {
-        let state_file = Self::get_state_file_path(host, port, username).ok()?;
+    /// The saved state of the connection's default session, or of the one
+    /// called `name`
+    pub fn load(host: &str, port: u16, username: &str, name: Option<&str>) -> Option<Self> {
+        let state_file = Self::get_state_file_path(host, port, username, name).ok()?;
 
         if !state_file.exists() {
             return None;
         }
 
         let json = fs::read_to_string(state_file).ok()?;
-        serde_json::from_str(&json).ok()
+        let state: Self = serde_json::from_str(&json).ok()?;
+        Some(Self {
+            name: name.map(str::to_string),
+            ..state
+        })
+    }
+}
+
+/// What a file under `~/.config/bssh/` keeps for a connection
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StateKind {
+    Session,
+    ShellHistory,
+    VisitedDirs,
+}
+
+impl StateKind {
+    /// File name prefix, followed by `user@host_port`
+    fn prefix(self) -> &'static str {
+        match self {
+            StateKind::Session => "session_",
+            StateKind::ShellHistory => "shell_history_",
+            StateKind::VisitedDirs => "dirs_",
+        }
+    }
+
+    fn suffix(self) -> &'static str {
+        match self {
+            StateKind::ShellHistory => "",
+            StateKind::Session | StateKind::VisitedDirs => ".json",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            StateKind::Session => "session",
+            StateKind::ShellHistory => "shell history",
+            StateKind::VisitedDirs => "visited directories",
+        }
+    }
+}
+
+/// A file keeping something of one connection between runs
+#[derive(Debug, Clone)]
+pub struct StateFile {
+    pub path: PathBuf,
+    pub kind: StateKind,
+    /// `user@host:port`
+    pub connection: String,
+    /// Name of a named session
+    pub session: Option<String>,
+    pub modified: SystemTime,
+}
+
+impl StateFile {
+    /// Kind, connection and session name from a file name, if it is a state file
+    fn parse(file_name: &str) -> Option<(StateKind, String, Option<String>)> {
+        [StateKind::Session, StateKind::ShellHistory, StateKind::VisitedDirs]
+            .into_iter()
+            .find_map(|kind| {
+                let stem = file_name.strip_prefix(kind.prefix())?.strip_suffix(kind.suffix())?;
+                // The port follows the last `_` that has digits after it; a
+                // session name may hold `_` too
+                stem.match_indices('_').rev().find_map(|(at, _)| {
+                    let (user_host, rest) = (&stem[..at], &stem[at + 1..]);
+                    let (port, session) = match rest.split_once('.') {
+                        Some((port, session)) if kind == StateKind::Session => (port, Some(session.to_string())),
+                        Some(_) => return None,
+                        None => (rest, None),
+                    };
+                    let port: u16 = port.parse().ok()?;
+                    user_host
+                        .contains('@')
+                        .then(|| (kind, format!("{}:{}", user_host, port), session))
+                })
+            })
+    }
+}
+
+/// Every state file, sorted by connection
+pub fn state_files() -> Result<Vec<StateFile>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(SessionState::get_state_dir()?)? {
+        let entry = entry?;
+        let Some((kind, connection, session)) = entry.file_name().to_str().and_then(StateFile::parse) else {
+            continue;
+        };
+        files.push(StateFile {
+            path: entry.path(),
+            kind,
+            connection,
+            session,
+            modified: entry.metadata()?.modified()?,
+        });
+    }
+    files.sort_by(|a, b| (&a.connection, &a.session, a.path.as_path()).cmp(&(&b.connection, &b.session, b.path.as_path())));
+    Ok(files)
+}
+
+/// Delete state files unchanged for longer than `max_age`; returns how many
+pub fn prune_state(max_age: Duration) -> Result<usize> {
+    let now = SystemTime::now();
+    let mut removed = 0;
+    for file in state_files()? {
+        if now.duration_since(file.modified).is_ok_and(|age| age > max_age) {
+            fs::remove_file(&file.path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_old_state_files_leave_show_hidden_to_the_config() {
+        let json = r#"{
+            "host": "example.com",
+            "port": 22,
+            "username": "user",
+            "current_path": "/home/user",
+            "selected_index": 3
+        }"#;
+
+        let state: SessionState = serde_json::from_str(json).unwrap();
+        assert_eq!(state.show_hidden, None);
+        assert!(state.tabs.is_empty());
+        assert_eq!(state.name, None);
+        assert_eq!(state.layout, None);
+        assert!(!state.detailed_view);
+    }
+
+    #[test]
+    fn test_state_file_names() {
+        assert_eq!(
+            StateFile::parse("session_deploy@web-1.example.com_22.json"),
+            Some((StateKind::Session, String::from("deploy@web-1.example.com:22"), None))
+        );
+        assert_eq!(
+            StateFile::parse("session_deploy@web_1_2222.web_root.json"),
+            Some((StateKind::Session, String::from("deploy@web_1:2222"), Some(String::from("web_root"))))
+        );
+        assert_eq!(
+            StateFile::parse("shell_history_root@db_22"),
+            Some((StateKind::ShellHistory, String::from("root@db:22"), None))
+        );
+        assert_eq!(
+            StateFile::parse("dirs_root@db_22.json"),
+            Some((StateKind::VisitedDirs, String::from("root@db:22"), None))
+        );
+        assert_eq!(StateFile::parse("connections.json"), None);
+        assert_eq!(StateFile::parse("session_notes.json"), None);
+    }
+
+    #[test]
+    fn test_session_names() {
+        assert!(SessionState::check_name("logs").is_ok());
+        assert!(SessionState::check_name("web_root-2").is_ok());
+        assert!(SessionState::check_name("").is_err());
+        assert!(SessionState::check_name("../x").is_err());
+        assert!(SessionState::check_name("a.b").is_err());
+    }
+
+    #[test]
+    fn test_view_is_kept_with_the_session() {
+        let layout = PanelLayout {
+            preview: true,
+            preview_width: 30,
+            ..PanelLayout::default()
+        };
+        let state = SessionState::new("h".into(), 22, "u".into(), "/".into(), 0, false).with_view(layout, true);
+        let json = serde_json::to_string(&state).unwrap();
+
+        let restored: SessionState = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.layout, Some(layout));
+        assert!(restored.detailed_view);
+        assert_eq!(restored.show_hidden, Some(false));
     }
 }
@@ -0,0 +1,98 @@
+use anyhow::{bail, Result};
+use clap::Subcommand;
+use std::fs;
+use std::time::SystemTime;
+
+use crate::connections::load_connections;
+use crate::destination::parse_connection_string;
+use crate::state::state_files;
+
+/// `bssh state ...`: what is kept per connection between runs
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum StateCommand {
+    /// List sessions, shell histories and visited directories, one file per line
+    List,
+    /// Delete everything kept for one connection, or for all of them
+    Clear {
+        /// Saved connection name or [user@]host[:port]
+        #[arg(value_name = "DESTINATION", required_unless_present = "all", conflicts_with = "all")]
+        destination: Option<String>,
+        /// Clear the state of every connection
+        #[arg(long = "all")]
+        all: bool,
+    },
+}
+
+/// Carry out `command`, printing its result
+pub fn run(command: StateCommand) -> Result<()> {
+    let files = state_files()?;
+    match command {
+        StateCommand::List => {
+            let now = SystemTime::now();
+            for file in &files {
+                let what = match file.session {
+                    Some(ref name) => format!("{} {}", file.kind.label(), name),
+                    None => file.kind.label().to_string(),
+                };
+                let age = now.duration_since(file.modified).unwrap_or_default().as_secs();
+                println!("{}\t{}\t{}", file.connection, what, age_label(age));
+            }
+        }
+        StateCommand::Clear { destination, all } => {
+            let connection = match destination {
+                Some(destination) => Some(connection_key(&destination)?),
+                None if all => None,
+                None => bail!("Name a connection or pass --all"),
+            };
+            let mut removed = 0;
+            for file in files.iter().filter(|file| connection.as_ref().is_none_or(|c| *c == file.connection)) {
+                fs::remove_file(&file.path)?;
+                removed += 1;
+            }
+            match connection {
+                Some(connection) => println!("Removed {} file(s) of {}", removed, connection),
+                None => println!("Removed {} file(s)", removed),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `user@host:port` of a saved connection or a destination, as state files
+/// are named by it
+fn connection_key(destination: &str) -> Result<String> {
+    if let Some(saved) = load_connections()?.into_iter().find(|c| c.name == destination) {
+        return Ok(saved.display_name());
+    }
+    let (username, host, port, _) = parse_connection_string(destination)?;
+    Ok(format!("{}@{}:{}", username, host, port))
+}
+
+fn age_label(secs: u64) -> String {
+    match secs {
+        0..60 => String::from("just now"),
+        60..3600 => format!("{}m ago", secs / 60),
+        3600..86400 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(subcommand)]
+        command: StateCommand,
+    }
+
+    #[test]
+    fn test_clear_needs_a_connection_or_all() {
+        assert!(Cli::try_parse_from(["state", "clear"]).is_err());
+        assert!(Cli::try_parse_from(["state", "clear", "web", "--all"]).is_err());
+        let cli = Cli::try_parse_from(["state", "clear", "--all"]).unwrap();
+        assert_eq!(cli.command, StateCommand::Clear { destination: None, all: true });
+    }
+}
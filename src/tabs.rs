@@ -0,0 +1,160 @@
+use crate::app::{DirHistory, HistoryEntry};
+
+/// Most tabs open at once, so `Alt+1` to `Alt+9` reach them all
+pub const MAX_TABS: usize = 9;
+
+/// A directory view that is not on screen: where it was left and the
+/// history that led there
+#[derive(Debug, Default)]
+pub struct Tab {
+    pub place: HistoryEntry,
+    pub history: DirHistory,
+}
+
+/// Directory tabs over one connection. The tab on screen lives in `App`
+/// (current path, selection and history); its slot here is only filled in
+/// when switching away from it.
+#[derive(Debug)]
+pub struct Tabs {
+    tabs: Vec<Tab>,
+    active: usize,
+}
+
+impl Default for Tabs {
+    fn default() -> Self {
+        Self {
+            tabs: vec![Tab::default()],
+            active: 0,
+        }
+    }
+}
+
+impl Tabs {
+    /// Tabs saved with the session; the active one is where the browser opens
+    pub fn restore(places: Vec<HistoryEntry>, active: usize) -> Self {
+        if places.is_empty() || active >= places.len() {
+            return Self::default();
+        }
+        let tabs = places
+            .into_iter()
+            .take(MAX_TABS)
+            .map(|place| Tab {
+                place,
+                history: DirHistory::default(),
+            })
+            .collect();
+        Self {
+            tabs,
+            active: active.min(MAX_TABS - 1),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tabs.len()
+    }
+
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    /// Add a tab after the shown one and make it active. `current` is the
+    /// tab being left; the new one starts where it is, with no history.
+    pub fn open(&mut self, current: Tab) -> bool {
+        if self.tabs.len() >= MAX_TABS {
+            return false;
+        }
+        self.tabs[self.active] = current;
+        self.active += 1;
+        self.tabs.insert(self.active, Tab::default());
+        true
+    }
+
+    /// Make tab `index` active, storing `current` in the slot being left.
+    /// Returns the tab to show, or None if `index` is already shown or does
+    /// not exist.
+    pub fn switch(&mut self, index: usize, current: Tab) -> Option<Tab> {
+        if index == self.active || index >= self.tabs.len() {
+            return None;
+        }
+        self.tabs[self.active] = current;
+        self.active = index;
+        Some(std::mem::take(&mut self.tabs[index]))
+    }
+
+    /// The tab `step` places to the right, wrapping around
+    pub fn neighbour(&self, step: isize) -> usize {
+        let len = self.tabs.len() as isize;
+        (self.active as isize + step).rem_euclid(len) as usize
+    }
+
+    /// Drop the shown tab. Returns the one to show instead, or None if it
+    /// is the last tab.
+    pub fn close(&mut self) -> Option<Tab> {
+        if self.tabs.len() == 1 {
+            return None;
+        }
+        self.tabs.remove(self.active);
+        self.active = self.active.min(self.tabs.len() - 1);
+        Some(std::mem::take(&mut self.tabs[self.active]))
+    }
+
+    /// Where each tab is, `current` being the shown one, for the header and
+    /// the session file
+    pub fn places(&self, current: &HistoryEntry) -> Vec<HistoryEntry> {
+        self.tabs
+            .iter()
+            .enumerate()
+            .map(|(i, tab)| if i == self.active { current.clone() } else { tab.place.clone() })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn place(path: &str) -> HistoryEntry {
+        HistoryEntry {
+            path: path.to_string(),
+            selected: None,
+        }
+    }
+
+    fn tab(path: &str) -> Tab {
+        Tab {
+            place: place(path),
+            history: DirHistory::default(),
+        }
+    }
+
+    fn paths(tabs: &Tabs, current: &str) -> Vec<String> {
+        tabs.places(&place(current)).into_iter().map(|p| p.path).collect()
+    }
+
+    #[test]
+    fn test_open_switch_and_close() {
+        let mut tabs = Tabs::default();
+        assert!(tabs.open(tab("/etc/nginx")));
+        assert_eq!(tabs.active(), 1);
+        assert_eq!(paths(&tabs, "/var/log/nginx"), ["/etc/nginx", "/var/log/nginx"]);
+
+        let shown = tabs.switch(0, tab("/var/log/nginx")).unwrap();
+        assert_eq!(shown.place.path, "/etc/nginx");
+        assert!(tabs.switch(0, tab("/etc/nginx")).is_none());
+        assert_eq!(tabs.neighbour(-1), 1);
+
+        let shown = tabs.close().unwrap();
+        assert_eq!(shown.place.path, "/var/log/nginx");
+        assert!(tabs.close().is_none());
+    }
+
+    #[test]
+    fn test_restore_checks_the_saved_tabs() {
+        let tabs = Tabs::restore(vec![place("/etc"), place("/srv")], 1);
+        assert_eq!((tabs.len(), tabs.active()), (2, 1));
+        assert_eq!(paths(&tabs, "/tmp"), ["/etc", "/tmp"]);
+
+        let tabs = Tabs::restore(vec![place("/etc")], 3);
+        assert_eq!((tabs.len(), tabs.active()), (1, 0));
+    }
+}
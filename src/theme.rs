@@ -0,0 +1,211 @@
+use ratatui::style::Color;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Colours used across the UI, by role
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    /// Titles, prompt labels and the connection name
+    pub accent: Color,
+    /// Key names in hints, marked entries and the path label
+    pub key: Color,
+    /// Secondary text such as sizes, separators and line numbers
+    pub muted: Color,
+    pub success: Color,
+    pub warning: Color,
+    /// Errors, broken links and destructive actions
+    pub error: Color,
+    /// Indicators that stand apart from the others, e.g. the SOCKS proxy
+    pub special: Color,
+    pub directory: Color,
+    pub symlink: Color,
+    /// The selected row
+    pub selection_fg: Color,
+    pub selection_bg: Color,
+    /// A row being edited in place, e.g. while renaming
+    pub editing_fg: Color,
+    pub editing_bg: Color,
+    /// Entries that appeared on refresh
+    pub new_entry_fg: Color,
+    pub new_entry_bg: Color,
+    /// Search and substitution matches
+    pub highlight_fg: Color,
+    pub highlight_bg: Color,
+}
+
+/// Names accepted for the `theme` config key
+pub const THEME_NAMES: &[&str] = &["dark", "light", "solarized"];
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            accent: Color::Cyan,
+            key: Color::Yellow,
+            muted: Color::DarkGray,
+            success: Color::Green,
+            warning: Color::Yellow,
+            error: Color::Red,
+            special: Color::Magenta,
+            directory: Color::Blue,
+            symlink: Color::Cyan,
+            selection_fg: Color::White,
+            selection_bg: Color::DarkGray,
+            editing_fg: Color::White,
+            editing_bg: Color::Blue,
+            new_entry_fg: Color::Black,
+            new_entry_bg: Color::Green,
+            highlight_fg: Color::Black,
+            highlight_bg: Color::Yellow,
+        }
+    }
+
+    /// For terminals with a light background, where yellow text and a dark
+    /// gray selection are hard to read
+    pub fn light() -> Self {
+        Self {
+            accent: Color::Blue,
+            key: Color::Magenta,
+            muted: Color::DarkGray,
+            success: Color::Rgb(0, 128, 0),
+            warning: Color::Rgb(175, 95, 0),
+            error: Color::Red,
+            special: Color::Rgb(135, 0, 175),
+            directory: Color::Blue,
+            symlink: Color::Rgb(0, 128, 128),
+            selection_fg: Color::Black,
+            selection_bg: Color::Rgb(200, 200, 200),
+            editing_fg: Color::White,
+            editing_bg: Color::Blue,
+            new_entry_fg: Color::Black,
+            new_entry_bg: Color::Rgb(175, 255, 175),
+            highlight_fg: Color::Black,
+            highlight_bg: Color::Rgb(255, 255, 135),
+        }
+    }
+
+    /// Solarized accents, readable on both its light and dark backgrounds
+    pub fn solarized() -> Self {
+        const YELLOW: Color = Color::Rgb(0xb5, 0x89, 0x00);
+        const BLUE: Color = Color::Rgb(0x26, 0x8b, 0xd2);
+        const CYAN: Color = Color::Rgb(0x2a, 0xa1, 0x98);
+        const GREEN: Color = Color::Rgb(0x85, 0x99, 0x00);
+        const BASE3: Color = Color::Rgb(0xfd, 0xf6, 0xe3);
+        const BASE02: Color = Color::Rgb(0x07, 0x36, 0x42);
+        Self {
+            accent: CYAN,
+            key: YELLOW,
+            muted: Color::Rgb(0x58, 0x6e, 0x75),
+            success: GREEN,
+            warning: Color::Rgb(0xcb, 0x4b, 0x16),
+            error: Color::Rgb(0xdc, 0x32, 0x2f),
+            special: Color::Rgb(0xd3, 0x36, 0x82),
+            directory: BLUE,
+            symlink: CYAN,
+            selection_fg: BASE3,
+            selection_bg: BLUE,
+            editing_fg: BASE3,
+            editing_bg: Color::Rgb(0x6c, 0x71, 0xc4),
+            new_entry_fg: BASE3,
+            new_entry_bg: GREEN,
+            highlight_fg: BASE02,
+            highlight_bg: YELLOW,
+        }
+    }
+
+    pub fn named(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "solarized" => Some(Self::solarized()),
+            _ => None,
+        }
+    }
+
+    /// The built-in theme `name` with `colors` overriding single roles.
+    /// Colours are names (`red`, `lightblue`), `#rrggbb` or a 0-255 index.
+    /// Unknown themes, roles and colours are skipped and described in the
+    /// returned warnings.
+    pub fn from_config(name: &str, colors: &HashMap<String, String>) -> (Self, Vec<String>) {
+        let mut warnings = Vec::new();
+        let mut theme = Self::named(name).unwrap_or_else(|| {
+            warnings.push(format!("Unknown theme '{}' (try {})", name, THEME_NAMES.join(", ")));
+            Self::dark()
+        });
+
+        let mut roles: Vec<_> = colors.iter().collect();
+        roles.sort();
+        for (role, value) in roles {
+            match (theme.role_mut(role), Color::from_str(value)) {
+                (Some(slot), Ok(color)) => *slot = color,
+                (None, _) => warnings.push(format!("Unknown theme colour '{}'", role)),
+                (_, Err(_)) => warnings.push(format!("Invalid colour '{}' for {}", value, role)),
+            }
+        }
+        (theme, warnings)
+    }
+
+    fn role_mut(&mut self, role: &str) -> Option<&mut Color> {
+        Some(match role {
+            "accent" => &mut self.accent,
+            "key" => &mut self.key,
+            "muted" => &mut self.muted,
+            "success" => &mut self.success,
+            "warning" => &mut self.warning,
+            "error" => &mut self.error,
+            "special" => &mut self.special,
+            "directory" => &mut self.directory,
+            "symlink" => &mut self.symlink,
+            "selection_fg" => &mut self.selection_fg,
+            "selection_bg" => &mut self.selection_bg,
+            "editing_fg" => &mut self.editing_fg,
+            "editing_bg" => &mut self.editing_bg,
+            "new_entry_fg" => &mut self.new_entry_fg,
+            "new_entry_bg" => &mut self.new_entry_bg,
+            "highlight_fg" => &mut self.highlight_fg,
+            "highlight_bg" => &mut self.highlight_bg,
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overrides_apply_to_the_named_theme() {
+        let colors = HashMap::from([
+            (String::from("selection_bg"), String::from("#264f78")),
+            (String::from("key"), String::from("lightblue")),
+        ]);
+        let (theme, warnings) = Theme::from_config("light", &colors);
+        assert!(warnings.is_empty());
+        assert_eq!(theme.selection_bg, Color::Rgb(0x26, 0x4f, 0x78));
+        assert_eq!(theme.key, Color::LightBlue);
+        assert_eq!(theme.accent, Theme::light().accent);
+    }
+
+    #[test]
+    fn test_bad_entries_are_reported() {
+        let colors = HashMap::from([
+            (String::from("selection"), String::from("red")),
+            (String::from("error"), String::from("reddish")),
+        ]);
+        let (theme, warnings) = Theme::from_config("neon", &colors);
+        assert_eq!(theme, Theme::dark());
+        assert_eq!(
+            warnings,
+            [
+                "Unknown theme 'neon' (try dark, light, solarized)",
+                "Invalid colour 'reddish' for error",
+                "Unknown theme colour 'selection'",
+            ]
+        );
+    }
+}
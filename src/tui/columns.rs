@@ -0,0 +1,176 @@
+use serde::{Deserialize, Serialize};
+
+/// Narrowest the name column gets when it takes the room left over
+const MIN_NAME_WIDTH: usize = 10;
+
+/// What a file list column shows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnKind {
+    Icon,
+    Name,
+    Size,
+    Mtime,
+    Permissions,
+    Owner,
+}
+
+/// A file list column and its width in characters. Written in the config
+/// file as the kind with an optional width, e.g. `"name:60"` or `"size"`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct Column {
+    pub kind: ColumnKind,
+    /// None for the kind's usual width; for the name, whatever the other
+    /// columns leave
+    pub width: Option<u16>,
+}
+
+impl ColumnKind {
+    const ALL: [(ColumnKind, &'static str); 6] = [
+        (ColumnKind::Icon, "icon"),
+        (ColumnKind::Name, "name"),
+        (ColumnKind::Size, "size"),
+        (ColumnKind::Mtime, "mtime"),
+        (ColumnKind::Permissions, "permissions"),
+        (ColumnKind::Owner, "owner"),
+    ];
+
+    fn name(self) -> &'static str {
+        Self::ALL.iter().find(|(kind, _)| *kind == self).map_or("", |(_, name)| name)
+    }
+
+    /// Width when the config gives none; the icon is one glyph, which may
+    /// be drawn two cells wide
+    fn default_width(self) -> usize {
+        match self {
+            ColumnKind::Icon => 2,
+            ColumnKind::Name => 40,
+            ColumnKind::Size => 10,
+            ColumnKind::Mtime => 16,
+            ColumnKind::Permissions => 10,
+            ColumnKind::Owner => 17,
+        }
+    }
+}
+
+impl Column {
+    fn new(kind: ColumnKind) -> Self {
+        Self { kind, width: None }
+    }
+}
+
+impl TryFrom<String> for Column {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, String> {
+        let (name, width) = match text.split_once(':') {
+            Some((name, width)) => {
+                let width = width.trim().parse().map_err(|_| format!("Bad width in column {:?}", text))?;
+                (name, Some(width))
+            }
+            None => (text.as_str(), None),
+        };
+        let kind = ColumnKind::ALL
+            .iter()
+            .find(|(_, known)| *known == name.trim())
+            .map(|(kind, _)| *kind)
+            .ok_or_else(|| format!("Unknown column {:?}", name))?;
+        Ok(Self { kind, width })
+    }
+}
+
+impl From<Column> for String {
+    fn from(column: Column) -> Self {
+        match column.width {
+            Some(width) => format!("{}:{}", column.kind.name(), width),
+            None => column.kind.name().to_string(),
+        }
+    }
+}
+
+/// Icon, name and size, as the file list has always shown
+pub fn default_columns() -> Vec<Column> {
+    vec![
+        Column::new(ColumnKind::Icon),
+        Column::new(ColumnKind::Name),
+        Column::new(ColumnKind::Size),
+    ]
+}
+
+/// The columns to draw: the configured ones, plus in the detailed view the
+/// permissions, owner and mtime if they are not among them
+pub fn shown_columns(configured: &[Column], detailed: bool) -> Vec<Column> {
+    let mut columns = if configured.is_empty() {
+        default_columns()
+    } else {
+        configured.to_vec()
+    };
+    if detailed {
+        for kind in [ColumnKind::Permissions, ColumnKind::Owner, ColumnKind::Mtime] {
+            if !columns.iter().any(|column| column.kind == kind) {
+                columns.push(Column::new(kind));
+            }
+        }
+    }
+    columns
+}
+
+/// Width of each of `columns` in a row `total` characters wide, with one
+/// space between columns. A name without a set width gets the rest.
+pub fn column_widths(columns: &[Column], total: usize) -> Vec<usize> {
+    let fixed: usize = columns
+        .iter()
+        .filter(|column| column.kind != ColumnKind::Name || column.width.is_some())
+        .map(|column| column.width.map_or(column.kind.default_width(), usize::from))
+        .sum();
+    let spacing = columns.len().saturating_sub(1);
+    let rest = total.saturating_sub(fixed + spacing).max(MIN_NAME_WIDTH);
+
+    columns
+        .iter()
+        .map(|column| match (column.kind, column.width) {
+            (_, Some(width)) => width as usize,
+            (ColumnKind::Name, None) => rest,
+            (kind, None) => kind.default_width(),
+        })
+        .collect()
+}
+
+/// `text` padded or cut to `width` characters, cut text ending in `…`
+pub fn fit(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return format!("{:<width$}", text);
+    }
+    let mut cut: String = text.chars().take(width.saturating_sub(1)).collect();
+    if width > 0 {
+        cut.push('…');
+    }
+    cut
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_columns_from_config() {
+        let columns: Vec<Column> = serde_json::from_str(r#"["icon", "name:60", "mtime"]"#).unwrap();
+        assert_eq!(columns[1], Column { kind: ColumnKind::Name, width: Some(60) });
+        assert_eq!(serde_json::to_string(&columns).unwrap(), r#"["icon","name:60","mtime"]"#);
+        assert!(serde_json::from_str::<Vec<Column>>(r#"["colour"]"#).is_err());
+        assert!(serde_json::from_str::<Vec<Column>>(r#"["size:wide"]"#).is_err());
+
+        let detailed = shown_columns(&columns, true);
+        let kinds: Vec<ColumnKind> = detailed.iter().map(|column| column.kind).collect();
+        assert_eq!(kinds[3..], [ColumnKind::Permissions, ColumnKind::Owner]);
+    }
+
+    #[test]
+    fn test_name_takes_the_rest_of_the_row() {
+        // 2 + 10 fixed and 2 spaces leave 66 of 80 for the name
+        assert_eq!(column_widths(&default_columns(), 80), [2, 66, 10]);
+        assert_eq!(column_widths(&default_columns(), 20), [2, MIN_NAME_WIDTH, 10]);
+        assert_eq!(fit("release-notes.txt", 10), "release-n…");
+        assert_eq!(fit("a.txt", 7), "a.txt  ");
+    }
+}
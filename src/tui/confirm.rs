@@ -0,0 +1,145 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+    Frame,
+};
+
+use super::centered_rect;
+use crate::theme::Theme;
+
+/// How a confirmation dialog was answered
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Answer {
+    /// The key of one of the dialog's choices
+    Choice(char),
+    /// Esc, Ctrl+c, or `n` when it is not a choice of its own
+    Cancel,
+}
+
+/// Modal question answered with a single key, y/n by default. Esc always
+/// cancels.
+pub struct Confirm {
+    title: String,
+    question: String,
+    details: Vec<Line<'static>>,
+    choices: Vec<(char, String)>,
+    /// Show the question in the error colour, for actions that lose data
+    destructive: bool,
+}
+
+impl Confirm {
+    pub fn new(title: &str, question: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            question: question.to_string(),
+            details: Vec::new(),
+            choices: vec![('y', String::from("Yes"))],
+            destructive: false,
+        }
+    }
+
+    /// Lines shown under the question
+    pub fn with_details(mut self, details: Vec<Line<'static>>) -> Self {
+        self.details = details;
+        self
+    }
+
+    pub fn with_choices(mut self, choices: &[(char, &str)]) -> Self {
+        self.choices = choices.iter().map(|&(key, label)| (key, label.to_string())).collect();
+        self
+    }
+
+    pub fn destructive(mut self) -> Self {
+        self.destructive = true;
+        self
+    }
+
+    /// The answer `key` gives, if any. Letters match either case.
+    pub fn answer(&self, key: KeyEvent) -> Option<Answer> {
+        match key.code {
+            KeyCode::Esc => Some(Answer::Cancel),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => Some(Answer::Cancel),
+            KeyCode::Char(c) => {
+                let c = c.to_ascii_lowercase();
+                if self.choices.iter().any(|&(key, _)| key == c) {
+                    Some(Answer::Choice(c))
+                } else if c == 'n' {
+                    Some(Answer::Cancel)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether `key` picks the choice `choice`, for plain yes/no questions
+    pub fn accepts(&self, key: KeyEvent, choice: char) -> Option<bool> {
+        self.answer(key).map(|answer| answer == Answer::Choice(choice))
+    }
+
+    fn key_hints(&self, theme: &Theme) -> Line<'static> {
+        let key = Style::default().fg(theme.key);
+        let mut spans = Vec::new();
+        for (c, label) in &self.choices {
+            spans.push(Span::styled(c.to_string(), key));
+            spans.push(Span::raw(format!(": {}  ", label)));
+        }
+        let cancel = if self.choices.iter().any(|&(c, _)| c == 'n') { "Esc" } else { "n/Esc" };
+        spans.push(Span::styled(cancel, key));
+        spans.push(Span::raw(": Cancel"));
+        Line::from(spans)
+    }
+
+    pub fn render(&self, f: &mut Frame, area: Rect, theme: &Theme) {
+        let color = if self.destructive { theme.error } else { theme.accent };
+        let mut lines = vec![Line::from(Span::styled(
+            self.question.clone(),
+            Style::default().fg(color).add_modifier(Modifier::BOLD),
+        ))];
+        if !self.details.is_empty() {
+            lines.push(Line::default());
+            lines.extend(self.details.iter().cloned());
+        }
+        lines.push(Line::default());
+        lines.push(self.key_hints(theme));
+
+        let area = centered_rect(64, lines.len() as u16 + 2, area);
+        let block = Block::default()
+            .title(format!(" {} ", self.title))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(color));
+        f.render_widget(Clear, area);
+        f.render_widget(Paragraph::new(lines).block(block).wrap(Wrap { trim: false }), area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(c: char) -> KeyEvent {
+        KeyEvent::from(KeyCode::Char(c))
+    }
+
+    #[test]
+    fn test_yes_no_answers() {
+        let confirm = Confirm::new("Delete", "Delete 'a'?");
+        assert_eq!(confirm.answer(key('Y')), Some(Answer::Choice('y')));
+        assert_eq!(confirm.answer(key('n')), Some(Answer::Cancel));
+        assert_eq!(confirm.answer(KeyEvent::from(KeyCode::Esc)), Some(Answer::Cancel));
+        assert_eq!(confirm.answer(key('x')), None);
+        assert_eq!(confirm.accepts(key('n'), 'y'), Some(false));
+    }
+
+    #[test]
+    fn test_custom_choices() {
+        let confirm = Confirm::new("Exists", "'a' exists").with_choices(&[('o', "Overwrite"), ('n', "New name")]);
+        assert_eq!(confirm.answer(key('n')), Some(Answer::Choice('n')));
+        assert_eq!(confirm.answer(key('y')), None);
+        assert_eq!(confirm.key_hints(&Theme::dark()).to_string(), "o: Overwrite  n: New name  Esc: Cancel");
+    }
+}
@@ -0,0 +1,128 @@
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph, Wrap},
+    Frame,
+};
+
+use super::{format_permissions, format_size};
+use crate::app::FileEntry;
+use crate::theme::Theme;
+
+/// Rows of the details panel when it shares the side with the preview
+pub const DETAILS_HEIGHT: u16 = 12;
+
+/// Label and value rows describing `file`; `checksum` is its SHA-256 if it
+/// has been computed
+fn detail_rows(file: &FileEntry, checksum: Option<&str>) -> Vec<(&'static str, String)> {
+    let kind = match (&file.link_target, file.is_dir) {
+        (Some(_), _) if file.broken_link => "Broken symlink",
+        (Some(_), true) => "Symlink to a directory",
+        (Some(_), false) => "Symlink to a file",
+        (None, true) => "Directory",
+        (None, false) => "File",
+    };
+
+    let mut rows = vec![("Path", file.path.clone()), ("Type", kind.to_string())];
+    if let Some(ref target) = file.link_target {
+        rows.push(("Target", target.clone()));
+    }
+    if !file.is_dir {
+        rows.push(("Size", format!("{} ({} bytes)", format_size(file.size), group_digits(file.size))));
+    }
+    if let Some(mode) = file.permissions {
+        rows.push(("Mode", format!("{} ({:04o})", format_permissions(mode), mode & 0o7777)));
+    }
+    if file.owner.is_some() || file.group.is_some() {
+        let owner = format!("{}:{}", file.owner.as_deref().unwrap_or("?"), file.group.as_deref().unwrap_or("?"));
+        rows.push(("Owner", owner));
+    }
+    rows.push(("Modified", file.modified.map_or_else(|| String::from("-"), format_timestamp)));
+    rows.push(("Accessed", file.accessed.map_or_else(|| String::from("-"), format_timestamp)));
+    if !file.is_dir {
+        rows.push(("SHA-256", checksum.map_or_else(|| String::from("press # to compute"), str::to_string)));
+    }
+    rows
+}
+
+/// `1234567` as `1,234,567`
+fn group_digits(value: u64) -> String {
+    let digits = value.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    grouped
+}
+
+pub(super) fn format_timestamp(secs: i64) -> String {
+    chrono::DateTime::from_timestamp(secs, 0)
+        .map(|time| time.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S %z").to_string())
+        .unwrap_or_default()
+}
+
+pub fn render_details(f: &mut Frame, area: Rect, file: Option<&FileEntry>, checksum: Option<&str>, theme: &Theme) {
+    let title = file.map_or_else(|| String::from("Details"), |file| format!("Details: {}", file.name));
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(Span::styled(title, Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)));
+
+    let lines: Vec<Line> = file
+        .filter(|file| file.name != "..")
+        .map(|file| {
+            detail_rows(file, checksum)
+                .into_iter()
+                .map(|(label, value)| {
+                    let style = if checksum.is_none() && label == "SHA-256" {
+                        Style::default().fg(theme.muted)
+                    } else {
+                        Style::default()
+                    };
+                    Line::from(vec![
+                        Span::styled(format!("{:<9}", label), Style::default().fg(theme.key)),
+                        Span::styled(value, style),
+                    ])
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    f.render_widget(Paragraph::new(lines).block(block).wrap(Wrap { trim: false }), area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rows_for_a_file() {
+        let mut file = FileEntry::new(String::from("app.log"), String::from("/var/log/app.log"), false);
+        file.size = 1_234_567;
+        file.permissions = Some(0o100640);
+        file.owner = Some(String::from("root"));
+
+        let rows = detail_rows(&file, None);
+        let value = |label: &str| rows.iter().find(|(l, _)| *l == label).map(|(_, v)| v.as_str());
+        assert_eq!(value("Size"), Some("1.2 MB (1,234,567 bytes)"));
+        assert_eq!(value("Mode"), Some("-rw-r----- (0640)"));
+        assert_eq!(value("Owner"), Some("root:?"));
+        assert_eq!(value("Modified"), Some("-"));
+        assert_eq!(value("SHA-256"), Some("press # to compute"));
+        assert_eq!(value("Target"), None);
+    }
+
+    #[test]
+    fn test_rows_for_a_link_to_a_directory() {
+        let mut dir = FileEntry::new(String::from("current"), String::from("/srv/current"), true);
+        dir.link_target = Some(String::from("releases/42"));
+
+        let labels: Vec<&str> = detail_rows(&dir, None).iter().map(|(label, _)| *label).collect();
+        assert_eq!(labels, ["Path", "Type", "Target", "Modified", "Accessed"]);
+        assert_eq!(group_digits(999), "999");
+        assert_eq!(group_digits(1000), "1,000");
+    }
+}
@@ -0,0 +1,117 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use crate::theme::Theme;
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+/// Keys and what they do, under a heading
+pub type HelpSection = (&'static str, Vec<(String, String)>);
+
+/// Full-screen, scrollable list of keybindings, or of anything else that
+/// pairs a short label with a description
+pub struct HelpOverlay {
+    title: &'static str,
+    sections: Vec<HelpSection>,
+    scroll: usize,
+}
+
+impl HelpOverlay {
+    pub fn new(sections: Vec<HelpSection>) -> Self {
+        Self { title: "Help", sections, scroll: 0 }
+    }
+
+    pub fn with_title(mut self, title: &'static str) -> Self {
+        self.title = title;
+        self
+    }
+
+    fn lines(&self, theme: &Theme) -> Vec<Line<'static>> {
+        let key_width = self
+            .sections
+            .iter()
+            .flat_map(|(_, entries)| entries.iter().map(|(keys, _)| keys.chars().count()))
+            .max()
+            .unwrap_or(0);
+
+        let mut lines = Vec::new();
+        for (title, entries) in &self.sections {
+            if !lines.is_empty() {
+                lines.push(Line::default());
+            }
+            lines.push(Line::from(Span::styled(
+                *title,
+                Style::default().fg(theme.key).add_modifier(Modifier::BOLD),
+            )));
+            for (keys, description) in entries {
+                lines.push(Line::from(vec![
+                    Span::styled(format!("  {:<width$}  ", keys, width = key_width), Style::default().fg(theme.accent)),
+                    Span::raw(description.clone()),
+                ]));
+            }
+        }
+        lines
+    }
+
+    /// Rows `lines` produces: a heading per section, its entries, and a
+    /// blank row between sections
+    fn line_count(&self) -> usize {
+        let rows: usize = self.sections.iter().map(|(_, entries)| entries.len() + 1).sum();
+        rows + self.sections.len().saturating_sub(1)
+    }
+
+    /// Scroll on `key`, with `page` rows on screen. Returns false once the
+    /// overlay should close.
+    pub fn handle_key(&mut self, key: KeyEvent, page: usize) -> bool {
+        let max_scroll = self.line_count().saturating_sub(page);
+        self.scroll = match key.code {
+            KeyCode::Char('q' | '?') | KeyCode::Esc => return false,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Up | KeyCode::Char('k') => self.scroll.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.scroll + 1,
+            KeyCode::PageUp => self.scroll.saturating_sub(page),
+            KeyCode::PageDown | KeyCode::Char(' ') => self.scroll + page,
+            KeyCode::Home | KeyCode::Char('g') => 0,
+            KeyCode::End | KeyCode::Char('G') => max_scroll,
+            _ => self.scroll,
+        }
+        .min(max_scroll);
+        true
+    }
+
+    pub fn render(&self, f: &mut Frame, area: Rect, theme: &Theme) {
+        let block = Block::default()
+            .title(format!(" {}: j/k scroll, q or Esc to close ", self.title))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.key));
+        let lines: Vec<Line> = self.lines(theme).into_iter().skip(self.scroll).collect();
+        f.render_widget(Clear, area);
+        f.render_widget(Paragraph::new(lines).block(block), area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scrolling_stays_in_range() {
+        let entries = (0..30).map(|i| (format!("k{}", i), String::from("does a thing"))).collect();
+        let mut help = HelpOverlay::new(vec![("Keys", entries)]);
+        let press = |help: &mut HelpOverlay, code| help.handle_key(KeyEvent::from(code), 10);
+
+        assert!(press(&mut help, KeyCode::PageDown));
+        assert_eq!(help.scroll, 10);
+        press(&mut help, KeyCode::End);
+        // One heading and 30 entries, 10 on screen
+        assert_eq!(help.scroll, 21);
+        press(&mut help, KeyCode::Char('j'));
+        assert_eq!(help.scroll, 21);
+        press(&mut help, KeyCode::Char('g'));
+        assert_eq!(help.scroll, 0);
+        assert!(!press(&mut help, KeyCode::Esc));
+    }
+}
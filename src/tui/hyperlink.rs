@@ -0,0 +1,102 @@
+use ratatui::buffer::Buffer;
+
+/// Whether the terminal turns OSC 8 sequences into clickable links.
+/// Terminals without support may print them, so this only says yes for
+/// ones known to handle them.
+pub fn supported() -> bool {
+    let var = |name| std::env::var(name).unwrap_or_default();
+    supported_from(
+        &var("TERM"),
+        &var("TERM_PROGRAM"),
+        &var("VTE_VERSION"),
+        std::env::var_os("WT_SESSION").is_some(),
+    )
+}
+
+fn supported_from(term: &str, term_program: &str, vte_version: &str, windows_terminal: bool) -> bool {
+    // GNOME Terminal and other VTE terminals since 0.50
+    let vte = vte_version.parse::<u32>().is_ok_and(|version| version >= 5000);
+    windows_terminal
+        || vte
+        || matches!(term_program, "iTerm.app" | "WezTerm" | "vscode" | "ghostty")
+        || ["kitty", "foot", "alacritty"].iter().any(|name| term.contains(name))
+}
+
+/// `sftp://user@host:port`, which every entry's link starts with
+pub fn sftp_base(username: &str, host: &str, port: u16) -> String {
+    let host = if host.contains(':') { format!("[{}]", host) } else { host.to_string() };
+    format!("sftp://{}@{}:{}", percent_encode(username, false), host, port)
+}
+
+/// The link for remote `path`
+pub fn sftp_url(base: &str, path: &str) -> String {
+    format!("{}{}", base, percent_encode(path, true))
+}
+
+/// Escape everything but unreserved characters, and `/` if `keep_slash`
+fn percent_encode(text: &str, keep_slash: bool) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+            b'/' if keep_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Make `len` cells from (`x`, `y`) a link to `url`, keeping their text and
+/// style. Ratatui counts the escape sequence as printable, so each cell
+/// pair gets its own sequence holding both characters, and the diff skips
+/// the cell after it. Text other than ASCII is left unlinked, as a wide
+/// character would throw the pairs out of step.
+pub fn link_cells(buf: &mut Buffer, x: u16, y: u16, len: u16, url: &str) {
+    let area = buf.area;
+    let len = len.min(area.right().saturating_sub(x));
+    // A lone last character would make the diff skip the cell after the text
+    let len = len - len % 2;
+    if len == 0 || y >= area.bottom() || x < area.left() {
+        return;
+    }
+
+    let text: Vec<String> = (x..x + len).map(|x| buf[(x, y)].symbol().to_string()).collect();
+    if !text.iter().all(|symbol| symbol.len() == 1 && symbol.is_ascii()) {
+        return;
+    }
+
+    for (i, pair) in text.chunks(2).enumerate() {
+        let symbol = format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, pair.concat());
+        buf[(x + i as u16 * 2, y)].set_symbol(&symbol);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::layout::Rect;
+
+    #[test]
+    fn test_urls_are_encoded() {
+        let base = sftp_base("deploy", "web-1", 22);
+        assert_eq!(sftp_url(&base, "/srv/my app/#1.log"), "sftp://deploy@web-1:22/srv/my%20app/%231.log");
+        assert_eq!(sftp_base("a@b", "::1", 2222), "sftp://a%40b@[::1]:2222");
+        assert!(supported_from("xterm-kitty", "", "", false));
+        assert!(supported_from("xterm-256color", "", "6003", false));
+        assert!(!supported_from("xterm-256color", "Apple_Terminal", "", false));
+    }
+
+    #[test]
+    fn test_cells_are_linked_in_pairs() {
+        let mut buf = Buffer::with_lines(["notes.txt  ", "données    "]);
+        link_cells(&mut buf, 0, 0, 9, "sftp://h/notes.txt");
+        assert_eq!(buf[(0, 0)].symbol(), "\x1b]8;;sftp://h/notes.txt\x1b\\no\x1b]8;;\x1b\\");
+        assert_eq!(buf[(6, 0)].symbol(), "\x1b]8;;sftp://h/notes.txt\x1b\\tx\x1b]8;;\x1b\\");
+        // The odd last character stays as it was
+        assert_eq!(buf[(8, 0)].symbol(), "t");
+
+        link_cells(&mut buf, 0, 1, 7, "sftp://h/donnees");
+        assert_eq!(buf[(0, 1)].symbol(), "d");
+        assert_eq!(buf.area, Rect::new(0, 0, 11, 2));
+    }
+}
@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+use std::env;
+
+use crate::app::FileEntry;
+use crate::filetype::FileKind;
+
+/// Which glyphs mark entries in the file list
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum IconMode {
+    /// Emoji where the terminal is likely to show them, else ASCII
+    #[default]
+    Auto,
+    Emoji,
+    /// Glyphs from a patched Nerd Font, which cannot be detected
+    Nerd,
+    /// Letters like `ls -l` uses, for any terminal and font
+    Ascii,
+}
+
+/// One glyph per kind of entry
+struct Glyphs {
+    directory: &'static str,
+    link: &'static str,
+    file: &'static str,
+    log: &'static str,
+    image: &'static str,
+    archive: &'static str,
+    binary: &'static str,
+}
+
+const EMOJI: Glyphs = Glyphs {
+    directory: "📁",
+    link: "🔗",
+    file: "📄",
+    log: "📜",
+    image: "🖼",
+    archive: "📦",
+    binary: "⚙",
+};
+
+const NERD: Glyphs = Glyphs {
+    directory: "\u{f07b}",
+    link: "\u{f0c1}",
+    file: "\u{f15b}",
+    log: "\u{f15c}",
+    image: "\u{f1c5}",
+    archive: "\u{f1c6}",
+    binary: "\u{f013}",
+};
+
+const ASCII: Glyphs = Glyphs {
+    directory: "d",
+    link: "l",
+    file: "-",
+    log: "L",
+    image: "i",
+    archive: "z",
+    binary: "x",
+};
+
+impl IconMode {
+    /// The mode to draw with: `Auto` becomes what the environment suggests
+    pub fn resolve(self) -> Self {
+        match self {
+            IconMode::Auto => Self::detect(|name| env::var(name).ok()),
+            mode => mode,
+        }
+    }
+
+    /// ASCII on the Linux console and without a UTF-8 locale, where emoji
+    /// show as boxes or question marks; emoji everywhere else
+    fn detect(var: impl Fn(&str) -> Option<String>) -> Self {
+        // The first of these that is set decides the character set
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .find_map(|name| var(name).filter(|value| !value.is_empty()))
+            .unwrap_or_default()
+            .to_lowercase();
+        let utf8 = locale.contains("utf-8") || locale.contains("utf8");
+        if !utf8 || var("TERM").as_deref() == Some("linux") {
+            IconMode::Ascii
+        } else {
+            IconMode::Emoji
+        }
+    }
+
+    fn glyphs(self) -> &'static Glyphs {
+        match self {
+            IconMode::Auto | IconMode::Emoji => &EMOJI,
+            IconMode::Nerd => &NERD,
+            IconMode::Ascii => &ASCII,
+        }
+    }
+
+    pub fn for_entry(self, file: &FileEntry) -> &'static str {
+        let glyphs = self.glyphs();
+        if file.link_target.is_some() {
+            return glyphs.link;
+        }
+        if file.is_dir {
+            return glyphs.directory;
+        }
+        match FileKind::from_name(&file.name) {
+            Some(FileKind::Log) => glyphs.log,
+            Some(FileKind::Image) => glyphs.image,
+            Some(FileKind::Archive) => glyphs.archive,
+            Some(FileKind::Binary) => glyphs.binary,
+            Some(FileKind::Text) | None => glyphs.file,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(vars: &[(&str, &str)]) -> IconMode {
+        IconMode::detect(|name| vars.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string()))
+    }
+
+    #[test]
+    fn test_detect_falls_back_to_ascii() {
+        assert_eq!(detect(&[("LANG", "en_US.UTF-8"), ("TERM", "xterm-256color")]), IconMode::Emoji);
+        assert_eq!(detect(&[("LANG", "en_US.UTF-8"), ("TERM", "linux")]), IconMode::Ascii);
+        assert_eq!(detect(&[("LC_ALL", "C"), ("LANG", "en_US.UTF-8")]), IconMode::Ascii);
+        assert_eq!(detect(&[]), IconMode::Ascii);
+        assert_eq!(IconMode::Nerd.resolve(), IconMode::Nerd);
+    }
+
+    #[test]
+    fn test_glyph_for_entry() {
+        assert_eq!(IconMode::Ascii.for_entry(&FileEntry::parent()), "d");
+        let mut file = FileEntry::new(String::from("backup.tar.gz"), String::from("/srv/backup.tar.gz"), false);
+        assert_eq!(IconMode::Ascii.for_entry(&file), "z");
+        file.link_target = Some(String::from("/srv/backup.tar.gz"));
+        assert_eq!(IconMode::Emoji.for_entry(&file), "🔗");
+    }
+}
@@ -0,0 +1,264 @@
+use base64ct::{Base64, Encoding};
+use image::{DynamicImage, ImageReader, RgbaImage};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::io::Cursor;
+
+/// Largest image downloaded in full for inline display
+pub const IMAGE_PREVIEW_MAX_BYTES: u64 = 8 * 1024 * 1024;
+
+/// Kitty limits each graphics escape to 4096 bytes of payload
+const KITTY_CHUNK: usize = 4096;
+
+/// Cell size to assume when the terminal does not report pixel dimensions
+const DEFAULT_CELL_PIXELS: (u32, u32) = (8, 16);
+
+/// Terminal graphics protocols bssh can draw images with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GraphicsProtocol {
+    Kitty,
+    Iterm2,
+    Sixel,
+}
+
+/// Format and size of an image, read from its header
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageInfo {
+    pub format: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub fn is_image_name(name: &str) -> bool {
+    let lower = name.to_lowercase();
+    [".png", ".jpg", ".jpeg"].iter().any(|ext| lower.ends_with(ext))
+}
+
+/// Guess the graphics protocol from the environment
+pub fn detect_protocol() -> Option<GraphicsProtocol> {
+    let var = |name| std::env::var(name).unwrap_or_default();
+    detect_from(&var("TERM"), &var("TERM_PROGRAM"), std::env::var_os("KITTY_WINDOW_ID").is_some())
+}
+
+fn detect_from(term: &str, term_program: &str, kitty_window: bool) -> Option<GraphicsProtocol> {
+    if kitty_window || term.contains("kitty") || term_program == "ghostty" {
+        return Some(GraphicsProtocol::Kitty);
+    }
+    if term_program == "iTerm.app" || term_program == "WezTerm" {
+        return Some(GraphicsProtocol::Iterm2);
+    }
+    if term.contains("sixel") || term == "foot" || term.starts_with("mlterm") {
+        return Some(GraphicsProtocol::Sixel);
+    }
+    None
+}
+
+/// Read the image format and dimensions without decoding pixels
+pub fn probe(bytes: &[u8]) -> Option<ImageInfo> {
+    let reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format().ok()?;
+    let format = format!("{:?}", reader.format()?).to_uppercase();
+    let (width, height) = reader.into_dimensions().ok()?;
+    Some(ImageInfo { format, width, height })
+}
+
+/// Pixel size of one terminal cell
+pub fn cell_pixels() -> (u32, u32) {
+    match crossterm::terminal::window_size() {
+        Ok(size) if size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0 => (
+            size.width as u32 / size.columns as u32,
+            size.height as u32 / size.rows as u32,
+        ),
+        _ => DEFAULT_CELL_PIXELS,
+    }
+}
+
+/// Escape sequence that draws the image at the cursor, scaled to fit
+/// within `cols` x `rows` cells
+pub fn encode(protocol: GraphicsProtocol, bytes: &[u8], cols: u16, rows: u16) -> Option<String> {
+    match protocol {
+        GraphicsProtocol::Iterm2 => Some(iterm2_sequence(bytes, cols, rows)),
+        GraphicsProtocol::Kitty => Some(kitty_sequence(&fit_image(bytes, cols, rows)?)),
+        GraphicsProtocol::Sixel => Some(sixel_sequence(&fit_image(bytes, cols, rows)?)),
+    }
+}
+
+/// Escape sequence that removes previously drawn images, if the protocol
+/// keeps them separate from the text
+pub fn clear_sequence(protocol: GraphicsProtocol) -> Option<&'static str> {
+    match protocol {
+        GraphicsProtocol::Kitty => Some("\x1b_Ga=d,q=2\x1b\\"),
+        GraphicsProtocol::Iterm2 | GraphicsProtocol::Sixel => None,
+    }
+}
+
+fn fit_image(bytes: &[u8], cols: u16, rows: u16) -> Option<RgbaImage> {
+    let image: DynamicImage = image::load_from_memory(bytes).ok()?;
+    let (cell_width, cell_height) = cell_pixels();
+    let max_width = (cols as u32 * cell_width).max(1);
+    let max_height = (rows as u32 * cell_height).max(1);
+
+    let image = if image.width() > max_width || image.height() > max_height {
+        image.thumbnail(max_width, max_height)
+    } else {
+        image
+    };
+    Some(image.to_rgba8())
+}
+
+fn iterm2_sequence(bytes: &[u8], cols: u16, rows: u16) -> String {
+    format!(
+        "\x1b]1337;File=inline=1;size={};width={};height={};preserveAspectRatio=1:{}\x07",
+        bytes.len(),
+        cols,
+        rows,
+        Base64::encode_string(bytes)
+    )
+}
+
+fn kitty_sequence(image: &RgbaImage) -> String {
+    let payload = Base64::encode_string(image.as_raw());
+    let chunks: Vec<&[u8]> = payload.as_bytes().chunks(KITTY_CHUNK).collect();
+
+    let mut sequence = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = if i + 1 < chunks.len() { 1 } else { 0 };
+        let chunk = std::str::from_utf8(chunk).unwrap_or_default();
+        if i == 0 {
+            let _ = write!(
+                sequence,
+                "\x1b_Ga=T,f=32,s={},v={},q=2,C=1,m={};{}\x1b\\",
+                image.width(),
+                image.height(),
+                more,
+                chunk
+            );
+        } else {
+            let _ = write!(sequence, "\x1b_Gm={};{}\x1b\\", more, chunk);
+        }
+    }
+    sequence
+}
+
+/// Map a channel to one of 6 levels of the 6x6x6 color cube
+fn cube_level(value: u8) -> usize {
+    (value as usize * 5 + 127) / 255
+}
+
+fn palette_index(pixel: &image::Rgba<u8>) -> Option<usize> {
+    let [r, g, b, a] = pixel.0;
+    if a < 128 {
+        return None;
+    }
+    Some(cube_level(r) * 36 + cube_level(g) * 6 + cube_level(b))
+}
+
+fn push_run(sequence: &mut String, sixel: u8, count: usize) {
+    let c = (b'?' + sixel) as char;
+    if count > 3 {
+        let _ = write!(sequence, "!{}{}", count, c);
+    } else {
+        for _ in 0..count {
+            sequence.push(c);
+        }
+    }
+}
+
+fn sixel_sequence(image: &RgbaImage) -> String {
+    let (width, height) = image.dimensions();
+    let indices: Vec<Option<usize>> = image.pixels().map(palette_index).collect();
+    let used: BTreeSet<usize> = indices.iter().flatten().copied().collect();
+
+    let mut sequence = format!("\x1bPq\"1;1;{};{}", width, height);
+    for &color in &used {
+        let (r, g, b) = (color / 36, color / 6 % 6, color % 6);
+        let _ = write!(sequence, "#{};2;{};{};{}", color, r * 20, g * 20, b * 20);
+    }
+
+    for band_top in (0..height).step_by(6) {
+        let band_height = (height - band_top).min(6);
+        let band_colors: BTreeSet<usize> = (band_top..band_top + band_height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter_map(|(x, y)| indices[(y * width + x) as usize])
+            .collect();
+
+        for &color in &band_colors {
+            let _ = write!(sequence, "#{}", color);
+            let mut run: Option<(u8, usize)> = None;
+
+            for x in 0..width {
+                let mut sixel = 0u8;
+                for dy in 0..band_height {
+                    if indices[((band_top + dy) * width + x) as usize] == Some(color) {
+                        sixel |= 1 << dy;
+                    }
+                }
+                run = match run {
+                    Some((value, count)) if value == sixel => Some((value, count + 1)),
+                    Some((value, count)) => {
+                        push_run(&mut sequence, value, count);
+                        Some((sixel, 1))
+                    }
+                    None => Some((sixel, 1)),
+                };
+            }
+            if let Some((value, count)) = run {
+                push_run(&mut sequence, value, count);
+            }
+            // Return to the start of the band for the next color
+            sequence.push('$');
+        }
+        sequence.push('-');
+    }
+
+    sequence.push_str("\x1b\\");
+    sequence
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png_bytes(width: u32, height: u32) -> Vec<u8> {
+        let image = RgbaImage::from_pixel(width, height, image::Rgba([255, 0, 0, 255]));
+        let mut bytes = Vec::new();
+        DynamicImage::ImageRgba8(image)
+            .write_to(&mut Cursor::new(&mut bytes), image::ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_detect_from_environment() {
+        assert_eq!(detect_from("xterm-kitty", "", false), Some(GraphicsProtocol::Kitty));
+        assert_eq!(detect_from("xterm-256color", "iTerm.app", false), Some(GraphicsProtocol::Iterm2));
+        assert_eq!(detect_from("foot", "", false), Some(GraphicsProtocol::Sixel));
+        assert_eq!(detect_from("xterm-256color", "Apple_Terminal", false), None);
+    }
+
+    #[test]
+    fn test_probe_reads_dimensions() {
+        let info = probe(&png_bytes(3, 2)).unwrap();
+        assert_eq!(info, ImageInfo { format: "PNG".to_string(), width: 3, height: 2 });
+        assert!(probe(b"not an image").is_none());
+    }
+
+    #[test]
+    fn test_sixel_sequence_for_solid_image() {
+        let image = RgbaImage::from_pixel(5, 6, image::Rgba([255, 0, 0, 255]));
+        let sequence = sixel_sequence(&image);
+
+        // Pure red is cube index 5*36 = 180, all six rows set for five columns
+        assert!(sequence.starts_with("\x1bPq\"1;1;5;6#180;2;100;0;0"));
+        assert!(sequence.contains("#180!5~$-"));
+        assert!(sequence.ends_with("\x1b\\"));
+    }
+
+    #[test]
+    fn test_kitty_sequence_chunks_payload() {
+        let image = RgbaImage::from_pixel(40, 40, image::Rgba([0, 0, 0, 255]));
+        let sequence = kitty_sequence(&image);
+
+        assert!(sequence.starts_with("\x1b_Ga=T,f=32,s=40,v=40,q=2,C=1,m=1;"));
+        assert!(sequence.contains("\x1b_Gm=0;"));
+    }
+}
@@ -0,0 +1,121 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use super::centered_rect;
+use crate::app::TextInput;
+use crate::frecency::jump_match;
+use crate::theme::Theme;
+
+/// Most directories listed at once; typing narrows the rest down
+const VISIBLE_DIRS: usize = 12;
+
+/// What a key press did to the jump prompt
+#[derive(Debug, Clone, PartialEq)]
+pub enum JumpResult {
+    Picking,
+    Go(String),
+    Cancelled,
+}
+
+/// `z`-style prompt over the directories visited on the connection
+pub struct JumpPrompt {
+    /// Most frecent first
+    dirs: Vec<String>,
+    query: TextInput,
+    /// Index into the matches
+    selected: usize,
+}
+
+impl JumpPrompt {
+    pub fn new(dirs: Vec<String>) -> Self {
+        Self {
+            dirs,
+            query: TextInput::new(""),
+            selected: 0,
+        }
+    }
+
+    fn matches(&self) -> Vec<&String> {
+        self.dirs.iter().filter(|dir| jump_match(dir, &self.query.value)).collect()
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> JumpResult {
+        match key.code {
+            KeyCode::Esc => return JumpResult::Cancelled,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return JumpResult::Cancelled,
+            KeyCode::Enter => {
+                if let Some(dir) = self.matches().get(self.selected) {
+                    return JumpResult::Go((*dir).clone());
+                }
+            }
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(self.matches().len().saturating_sub(1)),
+            _ => {
+                self.query.edit(key);
+                self.selected = 0;
+            }
+        }
+        JumpResult::Picking
+    }
+
+    pub fn render(&self, f: &mut Frame, area: Rect, theme: &Theme) {
+        let matches = self.matches();
+        let shown = matches.len().clamp(1, VISIBLE_DIRS);
+        let area = centered_rect(70, shown as u16 + 3, area);
+        let block = Block::default()
+            .title(Span::styled(
+                " Jump to: type part of a path, Enter go, Esc cancel ",
+                Style::default().fg(theme.accent).add_modifier(Modifier::BOLD),
+            ))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.accent));
+
+        let mut lines = vec![Line::from(format!("z {}", self.query.with_cursor()))];
+        if matches.is_empty() {
+            lines.push(Line::from(Span::styled("No visited directory matches", Style::default().fg(theme.muted))));
+        }
+        // Keep the selection in the window of listed directories
+        let first = self.selected.saturating_sub(VISIBLE_DIRS - 1);
+        for (i, dir) in matches.iter().enumerate().skip(first).take(VISIBLE_DIRS) {
+            let style = if i == self.selected {
+                Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+            } else {
+                Style::default()
+            };
+            lines.push(Line::from(Span::styled(dir.as_str(), style)));
+        }
+
+        f.render_widget(Clear, area);
+        f.render_widget(Paragraph::new(lines).block(block), area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_text(prompt: &mut JumpPrompt, text: &str) {
+        for c in text.chars() {
+            prompt.handle_key(KeyEvent::from(KeyCode::Char(c)));
+        }
+    }
+
+    #[test]
+    fn test_typing_narrows_to_the_best_match() {
+        let dirs = ["/etc/nginx/conf.d", "/etc/nginx", "/var/log/nginx"].map(String::from).to_vec();
+        let mut prompt = JumpPrompt::new(dirs);
+        type_text(&mut prompt, "ngx");
+        assert_eq!(prompt.matches(), ["/etc/nginx", "/var/log/nginx"]);
+        assert_eq!(prompt.handle_key(KeyEvent::from(KeyCode::Enter)), JumpResult::Go(String::from("/etc/nginx")));
+
+        type_text(&mut prompt, " zzz");
+        assert_eq!(prompt.handle_key(KeyEvent::from(KeyCode::Enter)), JumpResult::Picking);
+        assert_eq!(prompt.handle_key(KeyEvent::from(KeyCode::Esc)), JumpResult::Cancelled);
+    }
+}
@@ -0,0 +1,237 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use super::InputAction;
+
+/// A key a binding answers to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Key {
+    /// Matches with or without Shift, so shifted letters work as typed, but
+    /// not with Ctrl or Alt, which belong to `Ctrl` and `Alt` bindings
+    Code(KeyCode),
+    Ctrl(char),
+    Alt(KeyCode),
+    /// `1` to `9`, passed on as the index of an `Ancestor`
+    Digits,
+    /// `Alt+1` to `Alt+9`, passed on as the index of a tab
+    AltDigits,
+    /// Two keys pressed one after the other, like `gg`
+    Seq(char, char),
+}
+
+impl Key {
+    /// Whether `key` completes this binding, `pending` being the first key
+    /// of a sequence pressed just before
+    fn matches(&self, key: &KeyEvent, pending: Option<char>) -> bool {
+        if let Key::Seq(first, second) = *self {
+            return pending == Some(first) && key.code == KeyCode::Char(second);
+        }
+        if pending.is_some() {
+            return false;
+        }
+        match *self {
+            Key::Code(code) => {
+                key.code == code && !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+            }
+            Key::Ctrl(c) => key.code == KeyCode::Char(c) && key.modifiers.contains(KeyModifiers::CONTROL),
+            Key::Alt(code) => key.code == code && key.modifiers.contains(KeyModifiers::ALT),
+            Key::Digits => matches!(key.code, KeyCode::Char('1'..='9')) && !key.modifiers.contains(KeyModifiers::ALT),
+            Key::AltDigits => matches!(key.code, KeyCode::Char('1'..='9')) && key.modifiers.contains(KeyModifiers::ALT),
+            Key::Seq(..) => false,
+        }
+    }
+
+    /// How the key is written in help
+    pub fn label(&self) -> String {
+        match *self {
+            Key::Code(code) => code_label(code),
+            Key::Ctrl(c) => format!("Ctrl+{}", c),
+            Key::Alt(code) => format!("Alt+{}", code_label(code)),
+            Key::Digits => String::from("1-9"),
+            Key::AltDigits => String::from("Alt+1-9"),
+            Key::Seq(first, second) => format!("{}{}", first, second),
+        }
+    }
+}
+
+fn code_label(code: KeyCode) -> String {
+    match code {
+        KeyCode::Char(' ') => String::from("Space"),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Up => String::from("↑"),
+        KeyCode::Down => String::from("↓"),
+        KeyCode::Left => String::from("←"),
+        KeyCode::Right => String::from("→"),
+        KeyCode::PageUp => String::from("PgUp"),
+        KeyCode::PageDown => String::from("PgDn"),
+        KeyCode::Delete => String::from("Del"),
+        code => code.to_string(),
+    }
+}
+
+/// Keys bound to one browser action
+pub struct Binding {
+    pub keys: &'static [Key],
+    pub action: InputAction,
+    pub description: &'static str,
+}
+
+const fn bind(keys: &'static [Key], action: InputAction, description: &'static str) -> Binding {
+    Binding { keys, action, description }
+}
+
+/// Every file browser key, in the order they are matched and listed in help
+pub const BROWSER_KEYMAP: &[Binding] = &[
+    bind(&[Key::Ctrl('s')], InputAction::ToggleShell, "Toggle shell mode"),
+    bind(&[Key::Code(KeyCode::Char('S'))], InputAction::Shells, "List shells: open, start another or close one"),
+    bind(&[Key::Code(KeyCode::Char('E'))], InputAction::SelectionToShell, "Type the selected paths into the shell"),
+    bind(&[Key::Code(KeyCode::Char('q')), Key::Ctrl('c')], InputAction::Quit, "Quit"),
+    bind(&[Key::Code(KeyCode::Up), Key::Code(KeyCode::Char('k'))], InputAction::MoveUp, "Move selection up"),
+    bind(&[Key::Code(KeyCode::Down), Key::Code(KeyCode::Char('j'))], InputAction::MoveDown, "Move selection down"),
+    bind(&[Key::Code(KeyCode::PageUp)], InputAction::PageUp, "Move selection up a page"),
+    bind(&[Key::Code(KeyCode::PageDown)], InputAction::PageDown, "Move selection down a page"),
+    bind(&[Key::Ctrl('d')], InputAction::HalfPageDown, "Move selection down half a page"),
+    bind(&[Key::Ctrl('u')], InputAction::HalfPageUp, "Move selection up half a page"),
+    bind(&[Key::Code(KeyCode::Home), Key::Seq('g', 'g')], InputAction::First, "Jump to the first entry"),
+    bind(&[Key::Code(KeyCode::End), Key::Code(KeyCode::Char('G'))], InputAction::Last, "Jump to the last entry"),
+    bind(&[Key::Code(KeyCode::Enter)], InputAction::Enter, "Open directory, or open file by type"),
+    bind(
+        &[Key::Code(KeyCode::Backspace), Key::Code(KeyCode::Char('h')), Key::Alt(KeyCode::Left)],
+        InputAction::Back,
+        "Go back to the previously visited directory",
+    ),
+    bind(
+        &[Key::Code(KeyCode::Char('l')), Key::Alt(KeyCode::Right)],
+        InputAction::Forward,
+        "Go forward again in the directory history",
+    ),
+    bind(&[Key::Digits], InputAction::Ancestor(0), "Jump to the numbered ancestor in the breadcrumb path"),
+    bind(&[Key::Code(KeyCode::Char('t'))], InputAction::NewTab, "Open a new tab on the current directory"),
+    bind(&[Key::AltDigits], InputAction::SwitchTab(0), "Switch to the numbered tab"),
+    bind(&[Key::Seq('g', 't')], InputAction::NextTab, "Next tab"),
+    bind(&[Key::Seq('g', 'T')], InputAction::PreviousTab, "Previous tab"),
+    bind(&[Key::Ctrl('w')], InputAction::CloseTab, "Close the current tab"),
+    bind(&[Key::Code(KeyCode::Char('z'))], InputAction::Jump, "Jump to a visited directory by part of its path"),
+    bind(&[Key::Code(KeyCode::Char('/'))], InputAction::Filter, "Filter the file list as you type"),
+    bind(&[Key::Code(KeyCode::Char('.'))], InputAction::ToggleHidden, "Show/hide dotfiles"),
+    bind(&[Key::Code(KeyCode::Char('R'))], InputAction::Refresh, "Refresh the current directory"),
+    bind(&[Key::Code(KeyCode::Char('L'))], InputAction::ToggleDetails, "Toggle detailed view"),
+    bind(&[Key::Code(KeyCode::Char(' '))], InputAction::ToggleMark, "Mark/unmark entry for batch operations"),
+    bind(&[Key::Code(KeyCode::Char('V'))], InputAction::VisualSelect, "Start/commit a visual range selection"),
+    bind(&[Key::Code(KeyCode::Char('v'))], InputAction::TogglePreview, "Toggle the preview pane"),
+    bind(&[Key::Code(KeyCode::Char('i'))], InputAction::ToggleDetailsPanel, "Toggle the details panel"),
+    bind(&[Key::Code(KeyCode::Char('#'))], InputAction::Checksum, "Compute the SHA-256 of the selected file"),
+    bind(&[Key::Code(KeyCode::Char('>'))], InputAction::WidenPreview, "Widen the preview pane"),
+    bind(&[Key::Code(KeyCode::Char('<'))], InputAction::NarrowPreview, "Narrow the preview pane"),
+    bind(&[Key::Code(KeyCode::Char('H'))], InputAction::ToggleHeaderHints, "Show/hide the actions line in the header"),
+    bind(&[Key::Code(KeyCode::Esc)], InputAction::ClearSelection, "Cancel a running download or refresh, or clear selection"),
+    bind(&[Key::Code(KeyCode::Char('d'))], InputAction::Download, "Download selected file(s)"),
+    bind(&[Key::Code(KeyCode::Char('u'))], InputAction::Upload, "Upload a local file or directory here"),
+    bind(
+        &[Key::Code(KeyCode::Char('y')), Key::Code(KeyCode::F(5))],
+        InputAction::Yank,
+        "Yank selected entries for copying",
+    ),
+    bind(
+        &[Key::Code(KeyCode::Char('m')), Key::Code(KeyCode::F(6))],
+        InputAction::Cut,
+        "Yank selected entries for moving",
+    ),
+    bind(&[Key::Code(KeyCode::Char('p'))], InputAction::Paste, "Paste yanked entries into the current directory"),
+    bind(&[Key::Code(KeyCode::Char('n'))], InputAction::NewDirectory, "Create new directory (coming soon)"),
+    bind(&[Key::Code(KeyCode::Char('r'))], InputAction::Rename, "Rename file/directory inline"),
+    bind(&[Key::Code(KeyCode::Delete), Key::Code(KeyCode::Char('x'))], InputAction::Delete, "Delete selected entries"),
+    bind(&[Key::Code(KeyCode::Char('c'))], InputAction::Chmod, "Change permissions"),
+    bind(&[Key::Code(KeyCode::Char('o'))], InputAction::Chown, "Change owner/group"),
+    bind(&[Key::Seq('g', 'l')], InputAction::GotoLinkTarget, "Jump to a symlink's target"),
+    bind(&[Key::Code(KeyCode::Char('X'))], InputAction::Extract, "Extract the selected archive"),
+    bind(&[Key::Code(KeyCode::Char('Z'))], InputAction::Compress, "Compress the selection into a new archive"),
+    bind(&[Key::Code(KeyCode::Char('f'))], InputAction::Follow, "Follow the selected file like tail -f"),
+    bind(&[Key::Code(KeyCode::Char(':'))], InputAction::Command, "Batch command on glob matches (rm, get, mark)"),
+    bind(&[Key::Code(KeyCode::Char('Y'))], InputAction::CopyPath, "Copy the absolute path of the selection"),
+    bind(&[Key::Code(KeyCode::Char('U'))], InputAction::CopyUrl, "Copy an sftp:// URL of the selection"),
+    bind(&[Key::Code(KeyCode::Char('C'))], InputAction::CopyContents, "Copy the contents of a small text file"),
+    bind(&[Key::Code(KeyCode::Char('P'))], InputAction::PasteText, "Write the clipboard text to a remote file"),
+    bind(&[Key::Code(KeyCode::Char('e'))], InputAction::Execute, "Run a command on the selected entry"),
+    bind(&[Key::Code(KeyCode::Char('!'))], InputAction::RunScript, "Run the selected script, showing its output live"),
+    bind(&[Key::Ctrl('p')], InputAction::Palette, "Pick a saved command to run"),
+    bind(&[Key::Code(KeyCode::Char('M'))], InputAction::NotificationLog, "Show recent status messages"),
+    bind(&[Key::Code(KeyCode::Char('?'))], InputAction::Help, "Show this help"),
+];
+
+/// The browser action bound to `key`, `pending` being the start of a key
+/// sequence pressed just before. A key that starts a sequence comes back as
+/// `KeyPrefix`; a sequence with no binding does nothing.
+pub fn browser_action(key: KeyEvent, pending: Option<char>) -> InputAction {
+    if pending.is_none()
+        && !key.modifiers.contains(KeyModifiers::CONTROL)
+        && let KeyCode::Char(c) = key.code
+        && BROWSER_KEYMAP.iter().flat_map(|b| b.keys).any(|k| matches!(k, Key::Seq(first, _) if *first == c))
+    {
+        return InputAction::KeyPrefix(c);
+    }
+    let Some(binding) = BROWSER_KEYMAP.iter().find(|b| b.keys.iter().any(|k| k.matches(&key, pending))) else {
+        return InputAction::None;
+    };
+    match (binding.action, key.code) {
+        (InputAction::Ancestor(_), KeyCode::Char(digit)) => InputAction::Ancestor(digit as usize - '1' as usize),
+        (InputAction::SwitchTab(_), KeyCode::Char(digit)) => InputAction::SwitchTab(digit as usize - '1' as usize),
+        (action, _) => action,
+    }
+}
+
+/// Help lines for the browser: the keys of each binding and what it does
+pub fn browser_help() -> Vec<(String, String)> {
+    BROWSER_KEYMAP
+        .iter()
+        .map(|b| {
+            let keys: Vec<String> = b.keys.iter().map(Key::label).collect();
+            (keys.join(" / "), b.description.to_string())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn test_browser_action_lookup() {
+        assert_eq!(browser_action(key(KeyCode::Char('s'), KeyModifiers::CONTROL), None), InputAction::ToggleShell);
+        assert_eq!(browser_action(key(KeyCode::Char('s'), KeyModifiers::NONE), None), InputAction::None);
+        assert_eq!(browser_action(key(KeyCode::Char('3'), KeyModifiers::NONE), None), InputAction::Ancestor(2));
+        assert_eq!(browser_action(key(KeyCode::Char('3'), KeyModifiers::ALT), None), InputAction::SwitchTab(2));
+        assert_eq!(browser_action(key(KeyCode::Char('V'), KeyModifiers::SHIFT), None), InputAction::VisualSelect);
+        assert_eq!(browser_action(key(KeyCode::Left, KeyModifiers::ALT), None), InputAction::Back);
+        assert_eq!(browser_action(key(KeyCode::Left, KeyModifiers::NONE), None), InputAction::None);
+    }
+
+    #[test]
+    fn test_key_sequences() {
+        let g = key(KeyCode::Char('g'), KeyModifiers::NONE);
+        assert_eq!(browser_action(g, None), InputAction::KeyPrefix('g'));
+        assert_eq!(browser_action(g, Some('g')), InputAction::First);
+        assert_eq!(browser_action(key(KeyCode::Char('l'), KeyModifiers::NONE), Some('g')), InputAction::GotoLinkTarget);
+        assert_eq!(browser_action(key(KeyCode::Char('j'), KeyModifiers::NONE), Some('g')), InputAction::None);
+        assert_eq!(browser_action(key(KeyCode::Char('d'), KeyModifiers::CONTROL), None), InputAction::HalfPageDown);
+        assert_eq!(browser_action(key(KeyCode::Char('d'), KeyModifiers::NONE), None), InputAction::Download);
+    }
+
+    #[test]
+    fn test_ctrl_keys_do_not_fall_through_to_plain_letters() {
+        assert_eq!(browser_action(key(KeyCode::Char('p'), KeyModifiers::CONTROL), None), InputAction::Palette);
+        assert_eq!(browser_action(key(KeyCode::Char('p'), KeyModifiers::NONE), None), InputAction::Paste);
+        assert_eq!(browser_action(key(KeyCode::Char('y'), KeyModifiers::ALT), None), InputAction::None);
+    }
+
+    #[test]
+    fn test_every_binding_is_listed_in_help() {
+        let help = browser_help();
+        assert_eq!(help.len(), BROWSER_KEYMAP.len());
+        assert!(help.contains(&("Backspace / h / Alt+←".into(), "Go back to the previously visited directory".into())));
+        assert!(help.contains(&("y / F5".into(), "Yank selected entries for copying".into())));
+    }
+}
@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+
+/// Narrowest and widest the preview pane can be made, in percent
+const PREVIEW_WIDTH_RANGE: (u16, u16) = (20, 80);
+
+/// Step for resizing the preview pane, in percent
+const PREVIEW_WIDTH_STEP: u16 = 10;
+
+/// Which browser panels are shown and how much room they get. Stored under
+/// `layout` in the config file whenever it changes.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct PanelLayout {
+    /// Preview pane next to the file list
+    pub preview: bool,
+    /// Width of the preview pane, in percent of the screen
+    pub preview_width: u16,
+    /// The line of common actions in the header
+    pub header_hints: bool,
+    /// Stat details of the selected entry, above the preview when both are shown
+    pub details: bool,
+}
+
+impl Default for PanelLayout {
+    fn default() -> Self {
+        Self {
+            preview: false,
+            preview_width: 50,
+            header_hints: true,
+            details: false,
+        }
+    }
+}
+
+impl PanelLayout {
+    /// Widen (positive `steps`) or narrow the preview pane, within limits.
+    /// Returns the status line message.
+    pub fn resize_preview(&mut self, steps: i16) -> String {
+        let (min, max) = PREVIEW_WIDTH_RANGE;
+        let width = self.preview_percent() as i16 + steps * PREVIEW_WIDTH_STEP as i16;
+        self.preview_width = (width.max(0) as u16).clamp(min, max);
+        format!("Preview width {}%", self.preview_width)
+    }
+
+    /// Whether the file list shares the screen with the preview or details
+    pub fn side_pane(&self) -> bool {
+        self.preview || self.details
+    }
+
+    /// Width of the preview pane in percent, whatever the config file says
+    pub fn preview_percent(&self) -> u16 {
+        let (min, max) = PREVIEW_WIDTH_RANGE;
+        self.preview_width.clamp(min, max)
+    }
+
+    /// Rows taken by the header
+    pub fn header_height(&self) -> u16 {
+        if self.header_hints { 5 } else { 4 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview_width_stays_in_range() {
+        let mut layout = PanelLayout::default();
+        assert_eq!(layout.resize_preview(1), "Preview width 60%");
+        layout.resize_preview(10);
+        assert_eq!(layout.preview_width, 80);
+        layout.resize_preview(-20);
+        assert_eq!(layout.preview_width, 20);
+    }
+
+    #[test]
+    fn test_out_of_range_config_is_clamped() {
+        let layout: PanelLayout = serde_json::from_str(r#"{"preview_width": 95}"#).unwrap();
+        assert!(layout.header_hints);
+        assert_eq!(layout.preview_percent(), 80);
+    }
+}
@@ -1,38 +1,184 @@
-use crate::app::App;
+use crate::app::{
+    breadcrumbs, App, ChmodState, ClipboardMode, ConnectionHealth, FileEntry, PreviewContent, TransferBatch,
+    PREVIEW_BYTES, SLOW_LATENCY,
+};
+use crate::notifications::Level;
+use crate::tui::columns::{column_widths, fit, shown_columns, ColumnKind};
+use crate::theme::Theme;
 use anyhow::Result;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
+    event::{
+        self, DisableBracketedPaste, DisableFocusChange, DisableMouseCapture, EnableBracketedPaste, EnableFocusChange,
+        EnableMouseCapture, Event, KeyEvent,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Alignment, Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style},
+    layout::{Alignment, Constraint, Direction, Layout, Margin, Rect},
+    style::{Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, Paragraph},
+    widgets::{
+        Block, Borders, Clear, List, ListItem, ListState, Paragraph, Scrollbar, ScrollbarOrientation,
+        ScrollbarState,
+    },
     Frame, Terminal,
 };
 use std::io;
+use std::time::Instant;
+
+pub mod columns;
+pub mod confirm;
+pub mod details;
+pub mod help;
+pub mod hyperlink;
+pub mod icons;
+pub mod image;
+pub mod jump;
+pub mod keymap;
+pub mod layout;
+pub mod palette;
+pub mod prompt;
+pub mod shell_switcher;
+
+use confirm::Confirm;
+use details::{render_details, DETAILS_HEIGHT};
+use image::GraphicsProtocol;
+use prompt::Prompt;
 
 pub struct Tui {
     pub terminal: Terminal<CrosstermBackend<io::Stdout>>,
     restored: bool,
+    graphics: Option<GraphicsProtocol>,
+    /// Path and area of the image currently drawn in the preview pane
+    shown_image: Option<(String, Rect)>,
+    file_list: FileListView,
+    pub theme: Theme,
+}
+
+/// Scroll position of the file list, kept between frames
+#[derive(Default)]
+struct FileListView {
+    state: ListState,
+    /// Entries that fit in the list in the last frame
+    height: usize,
 }
 
 impl Tui {
-    pub fn new() -> Result<Self> {
+    pub fn new(theme: Theme) -> Result<Self> {
         enable_raw_mode()?;
         let mut stdout = io::stdout();
-        execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+        execute!(stdout, EnterAlternateScreen, EnableMouseCapture, EnableFocusChange)?;
         let backend = CrosstermBackend::new(stdout);
         let terminal = Terminal::new(backend)?;
 
-        Ok(Self { terminal, restored: false })
+        Ok(Self {
+            terminal,
+            restored: false,
+            graphics: image::detect_protocol(),
+            shown_image: None,
+            file_list: FileListView::default(),
+            theme,
+        })
     }
 
     pub fn draw(&mut self, app: &App) -> Result<()> {
-        self.terminal.draw(|f| ui(f, app))?;
+        let inline_images = self.graphics.is_some();
+        let mut image_area = None;
+        self.terminal
+            .draw(|f| image_area = ui(f, app, &mut self.file_list, inline_images, &self.theme))?;
+        self.update_inline_image(app, image_area)
+    }
+
+    /// Start over after the terminal changed size: the inline image and
+    /// the last frame were laid out for the old size, so both are redone
+    pub fn resize(&mut self) -> Result<()> {
+        self.clear_image()?;
+        self.terminal.autoresize()?;
+        self.terminal.clear()?;
+        Ok(())
+    }
+
+    /// Entries moved by PageUp/PageDown in the file list
+    pub fn list_page_size(&self) -> usize {
+        self.file_list.height.max(1)
+    }
+
+    /// Draw, replace or remove the preview image. Images are written straight
+    /// to the terminal, outside ratatui's buffer, so only on change.
+    fn update_inline_image(&mut self, app: &App, area: Option<Rect>) -> Result<()> {
+        let Some(protocol) = self.graphics else {
+            return Ok(());
+        };
+
+        let wanted = match (area, &app.preview) {
+            (Some(area), Some(preview)) => match preview.content {
+                PreviewContent::Image { data: Some(ref data), .. } => Some((preview.path.clone(), area, data)),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        let wanted_key = wanted.as_ref().map(|(path, area, _)| (path.clone(), *area));
+        if wanted_key == self.shown_image {
+            return Ok(());
+        }
+
+        if self.shown_image.is_some() {
+            self.clear_image()?;
+            // Repaint the cells the image covered
+            self.terminal.draw(|f| {
+                ui(f, app, &mut self.file_list, true, &self.theme);
+            })?;
+        }
+
+        if let Some((_, area, data)) = wanted {
+            if let Some(sequence) = image::encode(protocol, data, area.width, area.height) {
+                execute!(
+                    self.terminal.backend_mut(),
+                    crossterm::cursor::MoveTo(area.x, area.y),
+                    crossterm::style::Print(sequence)
+                )?;
+            }
+            self.shown_image = wanted_key;
+        }
+
+        Ok(())
+    }
+
+    /// Remove any inline image, e.g. before handing the screen to the editor
+    pub fn clear_image(&mut self) -> Result<()> {
+        if self.shown_image.take().is_none() {
+            return Ok(());
+        }
+
+        match self.graphics.and_then(image::clear_sequence) {
+            Some(sequence) => execute!(self.terminal.backend_mut(), crossterm::style::Print(sequence))?,
+            // Sixel and iTerm2 images live in the cells; force a full repaint
+            None => self.terminal.clear()?,
+        }
+        Ok(())
+    }
+
+    /// Have the terminal deliver a paste as one event rather than as
+    /// keystrokes; only the editor turns this on
+    pub fn set_bracketed_paste(&mut self, enabled: bool) -> Result<()> {
+        if enabled {
+            execute!(self.terminal.backend_mut(), EnableBracketedPaste)?;
+        } else {
+            execute!(self.terminal.backend_mut(), DisableBracketedPaste)?;
+        }
+        Ok(())
+    }
+
+    /// Take the screen back after `restore`, e.g. when leaving the shell.
+    /// Unlike a new `Tui`, the file list keeps its scroll position.
+    pub fn resume(&mut self) -> Result<()> {
+        enable_raw_mode()?;
+        execute!(self.terminal.backend_mut(), EnterAlternateScreen, EnableMouseCapture, EnableFocusChange)?;
+        self.terminal.clear()?;
+        self.restored = false;
         Ok(())
     }
 
@@ -45,7 +191,9 @@ impl Tui {
         execute!(
             self.terminal.backend_mut(),
             LeaveAlternateScreen,
-            DisableMouseCapture
+            DisableMouseCapture,
+            DisableFocusChange,
+            DisableBracketedPaste
         )?;
         self.terminal.show_cursor()?;
         Ok(())
@@ -58,78 +206,473 @@ impl Drop for Tui {
     }
 }
 
-fn ui(f: &mut Frame, app: &App) {
+/// Render the browser. Returns the area reserved for an inline image, if any.
+fn ui(f: &mut Frame, app: &App, file_list: &mut FileListView, inline_images: bool, theme: &Theme) -> Option<Rect> {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Length(5),
+            Constraint::Length(app.layout.header_height()),
             Constraint::Min(0),
             Constraint::Length(3),
         ])
         .split(f.area());
 
-    render_header(f, chunks[0], app);
-    render_file_list(f, chunks[1], app);
-    render_footer(f, chunks[2], app);
-}
+    render_header(f, chunks[0], app, theme);
+    let mut image_area = None;
+    if app.layout.side_pane() {
+        let preview_width = app.layout.preview_percent();
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(100 - preview_width), Constraint::Percentage(preview_width)])
+            .split(chunks[1]);
+        render_file_list(f, panes[0], app, file_list, theme);
 
-fn render_header(f: &mut Frame, area: Rect, app: &App) {
-    let shell_indicator = if app.has_background_shell {
-        " [shell]"
+        let mut side = panes[1];
+        if app.layout.details {
+            let height = if app.layout.preview { DETAILS_HEIGHT } else { side.height };
+            let parts = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(height), Constraint::Min(0)])
+                .split(side);
+            let selected = app.get_selected_file();
+            render_details(f, parts[0], selected, app.checksum_of(selected), theme);
+            side = parts[1];
+        }
+        if app.layout.preview {
+            image_area = render_preview(f, side, app, inline_images, theme);
+        }
     } else {
-        ""
+        render_file_list(f, chunks[1], app, file_list, theme);
+    }
+    render_footer(f, chunks[2], app, theme);
+
+    if let Some(ref entries) = app.pending_delete {
+        entries_confirm("Delete", entries).destructive().render(f, f.area(), theme);
+    }
+
+    if let Some(ref entries) = app.pending_download {
+        entries_confirm("Download", entries).render(f, f.area(), theme);
+    }
+
+    if let Some(confirm) = app.pending_overwrite.as_ref().and_then(overwrite_confirm) {
+        confirm.render(f, f.area(), theme);
+    }
+
+    if app.confirm_quit {
+        quit_confirm(app).destructive().render(f, f.area(), theme);
+    }
+
+    if let Some(ref chmod) = app.chmod {
+        render_chmod(f, chmod, theme);
+    }
+
+    if let Some(ref command) = app.command {
+        command.render(f, f.area(), theme);
+    }
+
+    if let Some(ref execute) = app.execute {
+        execute.render(f, f.area(), theme);
+    }
+
+    if let Some((_, ref prompt)) = app.run_script {
+        prompt.render(f, f.area(), theme);
+    }
+
+    let prompt = match (&app.rename, &app.chown, &app.compress, &app.paste_text, &app.upload) {
+        (Some(rename), ..) => Some(&rename.prompt),
+        (_, Some(chown), ..) => Some(&chown.prompt),
+        (_, _, Some(compress), ..) => Some(&compress.prompt),
+        (_, _, _, Some(paste), _) => Some(&paste.prompt),
+        (.., Some(upload)) => Some(upload),
+        _ => None,
     };
+    if let Some(prompt) = prompt {
+        prompt.render(f, f.area(), theme);
+    }
 
-    let header = Paragraph::new(vec![
-        Line::from(vec![
-            Span::styled(&app.connection_string, Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
-            Span::styled(shell_indicator, Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
-        ]),
+    if let Some(ref filter) = app.filter {
+        // Over the footer, so the matches stay in view
+        let footer = chunks[2];
+        let height = Prompt::HEIGHT.min(footer.bottom());
+        let area = Rect {
+            y: footer.bottom() - height,
+            height,
+            ..footer
+        };
+        filter.prompt.render_in(f, area, theme);
+    }
+
+    if let Some(ref palette) = app.palette {
+        palette.render(f, f.area(), theme);
+    }
+
+    if let Some(ref switcher) = app.shell_switcher {
+        switcher.render(f, f.area(), theme);
+    }
+
+    if let Some(ref jump) = app.jump {
+        jump.render(f, f.area(), theme);
+    }
+
+    if app.reconnecting {
+        render_reconnecting(f, app, theme);
+    }
+
+    if let Some(ref help) = app.help {
+        help.render(f, f.area(), theme);
+    }
+
+    // Dialogs would be drawn underneath an image
+    let modal_open = app.pending_delete.is_some()
+        || app.pending_download.is_some()
+        || app.pending_overwrite.is_some()
+        || app.confirm_quit
+        || app.chmod.is_some()
+        || app.command.is_some()
+        || app.execute.is_some()
+        || app.palette.is_some()
+        || app.run_script.is_some()
+        || app.rename.is_some()
+        || app.chown.is_some()
+        || app.compress.is_some()
+        || app.paste_text.is_some()
+        || app.upload.is_some()
+        || app.shell_switcher.is_some()
+        || app.jump.is_some()
+        || app.help.is_some()
+        || app.reconnecting;
+    image_area.filter(|_| !modal_open)
+}
+
+/// A rectangle of the given size centered within `area`
+fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+fn render_reconnecting(f: &mut Frame, app: &App, theme: &Theme) {
+    let area = centered_rect(50, 5, f.area());
+
+    let overlay = Paragraph::new(vec![
+        Line::from(Span::styled(
+            "Connection lost. Reconnecting...",
+            Style::default().fg(theme.key).add_modifier(Modifier::BOLD),
+        )),
+        Line::from(Span::raw(&app.connection_string)),
+    ])
+    .alignment(Alignment::Center)
+    .block(Block::default().borders(Borders::ALL).title("Reconnecting"));
+
+    f.render_widget(Clear, area);
+    f.render_widget(overlay, area);
+}
+
+/// Most entries listed by name in a confirmation dialog
+const CONFIRM_MAX_NAMES: usize = 5;
+
+/// Question whether to apply `verb` (e.g. "Delete") to the entries
+pub fn entries_confirm(verb: &str, entries: &[FileEntry]) -> Confirm {
+    let (question, details) = match entries {
+        [file] => {
+            let (kind, size) = if file.is_dir {
+                ("directory", String::from("-"))
+            } else {
+                ("file", format_size(file.size))
+            };
+            (
+                format!("{} {} '{}'?", verb, kind, file.name),
+                vec![Line::from(format!("Type: {}", kind)), Line::from(format!("Size: {}", size))],
+            )
+        }
+        _ => {
+            let total: u64 = entries.iter().filter(|f| !f.is_dir).map(|f| f.size).sum();
+            let mut details = Vec::new();
+            for file in entries.iter().take(CONFIRM_MAX_NAMES) {
+                let suffix = if file.is_dir { "/" } else { "" };
+                details.push(Line::from(format!("  {}{}", file.name, suffix)));
+            }
+            if entries.len() > CONFIRM_MAX_NAMES {
+                details.push(Line::from(format!(
+                    "  ... and {} more",
+                    entries.len() - CONFIRM_MAX_NAMES
+                )));
+            }
+            (format!("{} {} items ({})?", verb, entries.len(), format_size(total)), details)
+        }
+    };
+    Confirm::new(&format!("Confirm {}", verb), &question)
+        .with_details(details)
+        .with_choices(&[('y', verb)])
+}
+
+/// Question what to do with the transfer `batch` is paused on, comparing
+/// the file with the one it would replace
+pub fn overwrite_confirm(batch: &TransferBatch) -> Option<Confirm> {
+    let (file, existing) = batch.conflict()?;
+    let describe = |label: &str, file: &FileEntry| {
+        let mut line = format!("{:<7}{}", label, format_size(file.size));
+        if let Some(modified) = file.modified {
+            line.push_str(&format!(", modified {}", details::format_timestamp(modified)));
+        }
+        if let (Some(owner), Some(group)) = (&file.owner, &file.group) {
+            line.push_str(&format!(", {}:{}", owner, group));
+        }
+        Line::from(line)
+    };
+    let ((local, remote), place) = match batch.direction {
+        // Not ratatui's layout direction
+        crate::app::Direction::Download => ((existing, file), "locally"),
+        crate::app::Direction::Upload { .. } => ((file, existing), "on the server"),
+    };
+    let details = vec![describe("Local", local), describe("Remote", remote)];
+    let confirm = Confirm::new("File exists", &format!("'{}' exists {}", file.name, place))
+        .with_details(details)
+        .with_choices(&[
+        ('o', "Overwrite"),
+        ('r', "Rename"),
+        ('s', "Skip"),
+        ('a', "Overwrite all"),
+    ]);
+    Some(confirm)
+}
+
+/// Question whether to quit while jobs are running, listing them
+pub fn quit_confirm(app: &App) -> Confirm {
+    let details = app
+        .loading_labels()
+        .map(|label| Line::from(format!("  {}", label)))
+        .chain([
+            Line::from(""),
+            Line::from("An unfinished transfer is kept as .part and resumes next time;"),
+            Line::from("a file change may be left partly done."),
+        ])
+        .collect();
+    Confirm::new("Quit", "Operations are still running")
+        .with_details(details)
+        .with_choices(&[('y', "Quit")])
+}
+
+fn render_chmod(f: &mut Frame, chmod: &ChmodState, theme: &Theme) {
+    let target = match chmod.entries.as_slice() {
+        [file] => file.name.clone(),
+        entries => format!("{} items", entries.len()),
+    };
+
+    let mut lines = vec![
         Line::from(vec![
-            Span::styled("Path: ", Style::default().fg(Color::Yellow)),
-            Span::raw(&app.current_path),
+            Span::raw("Permissions for "),
+            Span::styled(target, Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)),
         ]),
-        Line::from(vec![
-            Span::styled("Actions: ", Style::default().fg(Color::Green)),
+        Line::from(""),
+        Line::from(Span::styled("         r  w  x", Style::default().fg(theme.muted))),
+    ];
+
+    for (row, label) in ["Owner", "Group", "Other"].iter().enumerate() {
+        let mut spans = vec![Span::raw(format!("{:<8}", label))];
+        for col in 0..3 {
+            let mark = if chmod.is_set(row, col) { "[x]" } else { "[ ]" };
+            let style = if row == chmod.row && col == chmod.col {
+                Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+            } else {
+                Style::default()
+            };
+            spans.push(Span::styled(mark, style));
+        }
+        lines.push(Line::from(spans));
+    }
+
+    lines.push(Line::from(""));
+    lines.push(Line::from(vec![
+        Span::raw("Mode: "),
+        Span::styled(
+            format!("{:03o}", chmod.mode),
+            Style::default().fg(theme.key).add_modifier(Modifier::BOLD),
+        ),
+        Span::raw(format!("  {}", &format_permissions(chmod.mode)[1..])),
+    ]));
+    lines.push(Line::from(""));
+    lines.push(Line::from(vec![
+        Span::styled("Space", Style::default().fg(theme.key)),
+        Span::raw(": Toggle  "),
+        Span::styled("0-7", Style::default().fg(theme.key)),
+        Span::raw(": Octal  "),
+        Span::styled("Enter", Style::default().fg(theme.key)),
+        Span::raw(": Apply  "),
+        Span::styled("Esc", Style::default().fg(theme.key)),
+        Span::raw(": Cancel"),
+    ]));
+
+    let area = centered_rect(50, lines.len() as u16 + 2, f.area());
+    let dialog = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title("Change Permissions"));
+
+    f.render_widget(Clear, area);
+    f.render_widget(dialog, area);
+}
+
+fn render_header(f: &mut Frame, area: Rect, app: &App, theme: &Theme) {
+    let shell_indicator = match app.background_shells {
+        0 => String::new(),
+        1 => String::from(" [shell]"),
+        count => format!(" [{} shells]", count),
+    };
+
+    let clipboard_indicator = app
+        .clipboard
+        .as_ref()
+        .map(|clipboard| {
+            let verb = match clipboard.mode {
+                ClipboardMode::Copy => "copy",
+                ClipboardMode::Move => "move",
+            };
+            format!(" [{} to {}]", clipboard.entries.len(), verb)
+        })
+        .unwrap_or_default();
+
+    let session_indicator = app
+        .session_name
+        .as_ref()
+        .map(|name| format!(" [session {}]", name))
+        .unwrap_or_default();
+
+    let socks_indicator = app
+        .socks_port
+        .map(|port| format!(" [socks :{}]", port))
+        .unwrap_or_default();
+
+    let connection_color = app.connection_color.unwrap_or(theme.accent);
+    let mut status_line = vec![
+        Span::styled(&app.connection_string, Style::default().fg(connection_color).add_modifier(Modifier::BOLD)),
+        Span::styled(session_indicator, Style::default().fg(theme.special)),
+        Span::styled(shell_indicator, Style::default().fg(theme.success).add_modifier(Modifier::BOLD)),
+        Span::styled(socks_indicator, Style::default().fg(theme.special).add_modifier(Modifier::BOLD)),
+        Span::styled(clipboard_indicator, Style::default().fg(theme.key).add_modifier(Modifier::BOLD)),
+    ];
+    if let Some(label) = app.health.label() {
+        let color = match app.health {
+            ConnectionHealth::Responding(latency) if latency < SLOW_LATENCY => theme.success,
+            ConnectionHealth::Responding(_) => theme.warning,
+            _ => theme.error,
+        };
+        status_line.insert(1, Span::styled(label, Style::default().fg(color)));
+    }
+    if let Some(usage) = app.disk_usage {
+        let percent = usage.used_percent();
+        let color = match percent {
+            0..80 => theme.success,
+            80..90 => theme.warning,
+            _ => theme.error,
+        };
+        status_line.push(Span::styled(
+            format!(" [disk {}% used, {} free]", percent, format_size(usage.available)),
+            Style::default().fg(color),
+        ));
+    }
+
+    let mut lines = vec![Line::from(status_line), breadcrumb_line(&app.current_path, theme)];
+    if app.layout.header_hints {
+        lines.push(Line::from(vec![
+            Span::styled("Actions: ", Style::default().fg(theme.success)),
             Span::raw("Enter=Open  d=Download  Del=Delete  Ctrl+s=Shell  q=Quit"),
-        ]),
-    ])
-    .block(Block::default().borders(Borders::ALL).title("bssh"));
+        ]));
+    }
+    let mut block = Block::default().borders(Borders::ALL).title(tab_title(app, theme));
+    if let Some(color) = app.connection_color {
+        block = block.border_style(Style::default().fg(color));
+    }
+    let header = Paragraph::new(lines).block(block);
 
     f.render_widget(header, area);
 }
 
-fn render_file_list(f: &mut Frame, area: Rect, app: &App) {
+/// `bssh`, followed by the open tabs when there is more than one
+fn tab_title(app: &App, theme: &Theme) -> Line<'static> {
+    let mut spans = vec![Span::raw("bssh")];
+    if app.tabs.len() > 1 {
+        for (i, place) in app.tabs.places(&app.history_entry()).iter().enumerate() {
+            let name = place.path.rsplit('/').find(|segment| !segment.is_empty()).unwrap_or("/");
+            let style = if i == app.tabs.active() {
+                Style::default().fg(theme.selection_fg).bg(theme.selection_bg).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(theme.muted)
+            };
+            spans.push(Span::raw(" "));
+            spans.push(Span::styled(format!(" {}:{} ", i + 1, name), style));
+        }
+    }
+    Line::from(spans)
+}
+
+/// The path as numbered segments; the number keys jump to an ancestor
+fn breadcrumb_line(path: &str, theme: &Theme) -> Line<'static> {
+    let crumbs = breadcrumbs(path);
+    let last = crumbs.len() - 1;
+    let dim = Style::default().fg(theme.muted);
+
+    let mut spans = vec![Span::styled("Path: ", Style::default().fg(theme.key))];
+    for (i, (label, _)) in crumbs.into_iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::styled(" › ", dim));
+        }
+        if i == last {
+            spans.push(Span::styled(label, Style::default().add_modifier(Modifier::BOLD)));
+        } else {
+            if i < 9 {
+                spans.push(Span::styled(format!("{} ", i + 1), dim));
+            }
+            spans.push(Span::raw(label));
+        }
+    }
+    Line::from(spans)
+}
+
+fn render_file_list(f: &mut Frame, area: Rect, app: &App, view: &mut FileListView, theme: &Theme) {
+    let columns = shown_columns(&app.columns, app.detailed_view);
+    // Inside the border, after the mark column
+    let widths = column_widths(&columns, area.width.saturating_sub(3) as usize);
+
+    // Where each entry's name starts in its row and how much of it to link
+    let mut links: Vec<Option<(u16, u16)>> = Vec::with_capacity(app.files.len());
     let items: Vec<ListItem> = app
         .files
         .iter()
         .enumerate()
         .map(|(i, file)| {
-            let icon = if file.is_dir { "📁" } else { "📄" };
-            let size = if file.is_dir {
-                String::from("<DIR>")
-            } else {
-                format_size(file.size)
-            };
+            let renaming = app.rename.as_ref().is_some_and(|rename| rename.index == i);
+            let marker = if app.is_marked(i) { "*" } else { " " };
 
-            let content = Line::from(vec![
-                Span::raw(format!("{} ", icon)),
-                Span::styled(
-                    format!("{:<40}", file.name),
-                    if file.is_dir {
-                        Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD)
-                    } else {
-                        Style::default()
-                    },
-                ),
-                Span::styled(
-                    format!("{:>10}", size),
-                    Style::default().fg(Color::DarkGray),
-                ),
-            ]);
-
-            let style = if i == app.selected_index {
-                Style::default().bg(Color::DarkGray).fg(Color::White)
+            let mut spans = vec![Span::styled(marker, Style::default().fg(theme.key).add_modifier(Modifier::BOLD))];
+            let mut link = None;
+            for (n, (column, &width)) in columns.iter().zip(&widths).enumerate() {
+                if n > 0 {
+                    spans.push(Span::raw(" "));
+                }
+                if column.kind == ColumnKind::Name && file.name != ".." {
+                    let x = spans.iter().map(Span::width).sum::<usize>();
+                    // Round up into the padding so no character is left out of a pair
+                    let len = file.name.chars().count().min(width);
+                    let len = if len < width { len + len % 2 } else { len };
+                    link = Some((x as u16, len as u16));
+                }
+                spans.push(column_cell(column.kind, file, width, app, theme));
+            }
+            links.push(link);
+            let content = Line::from(spans);
+
+            let style = if renaming {
+                Style::default().bg(theme.editing_bg).fg(theme.editing_fg)
+            } else if i == app.selected_index {
+                Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+            } else if app.is_marked(i) {
+                Style::default().fg(theme.key)
+            } else if app.is_new_entry(i) {
+                Style::default().bg(theme.new_entry_bg).fg(theme.new_entry_fg)
             } else {
                 Style::default()
             };
@@ -138,53 +681,280 @@ fn render_file_list(f: &mut Frame, area: Rect, app: &App) {
         })
         .collect();
 
+    let mut title = String::from("Files");
+    if !app.show_hidden {
+        title.push_str(&format!(" ({} hidden)", app.hidden_count()));
+    }
+    if app.filter.is_some() {
+        title.push_str(&format!(" [{} matching]", app.files.len()));
+    }
+
     let list = List::new(items)
-        .block(Block::default().borders(Borders::ALL).title("Files"));
+        .block(Block::default().borders(Borders::ALL).title(title));
+
+    // The list scrolls just enough to keep the selection in view
+    view.height = area.height.saturating_sub(2) as usize;
+    view.state.select((!app.files.is_empty()).then_some(app.selected_index));
+    f.render_stateful_widget(list, area, &mut view.state);
+
+    if let Some(ref base) = app.hyperlink_base {
+        let rows = links.iter().enumerate().skip(view.state.offset()).take(view.height);
+        for (row, (i, link)) in rows.enumerate() {
+            if let Some((x, len)) = *link {
+                let url = hyperlink::sftp_url(base, &app.files[i].path);
+                hyperlink::link_cells(f.buffer_mut(), area.x + 1 + x, area.y + 1 + row as u16, len, &url);
+            }
+        }
+    }
 
-    f.render_widget(list, area);
+    if app.files.len() > view.height {
+        let mut scrollbar = ScrollbarState::new(app.files.len().saturating_sub(view.height))
+            .position(view.state.offset());
+        f.render_stateful_widget(
+            Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                .begin_symbol(None)
+                .end_symbol(None),
+            area.inner(Margin { vertical: 1, horizontal: 0 }),
+            &mut scrollbar,
+        );
+    }
 }
 
-fn render_footer(f: &mut Frame, area: Rect, app: &App) {
-    let help_text = if app.status_message.is_empty() {
+fn render_footer(f: &mut Frame, area: Rect, app: &App, theme: &Theme) {
+    let notification = app.notifications.current(Instant::now());
+    let help_text = if let Some(loading) = app.loading() {
+        vec![Line::from(Span::styled(
+            format!("{} {}", loading.spinner(Instant::now()), loading.label),
+            Style::default().fg(theme.accent).add_modifier(Modifier::BOLD),
+        ))]
+    } else if notification.is_none() && app.has_selection() {
+        let (count, size) = app.selection_summary();
+        vec![Line::from(vec![
+            Span::styled(
+                format!("{} selected, {}  ", count, format_size(size)),
+                Style::default().fg(theme.accent).add_modifier(Modifier::BOLD),
+            ),
+            Span::styled("Space", Style::default().fg(theme.key)),
+            Span::raw(": Toggle  "),
+            Span::styled("V", Style::default().fg(theme.key)),
+            Span::raw(": Range  "),
+            Span::styled("d", Style::default().fg(theme.key)),
+            Span::raw(": Download  "),
+            Span::styled("Del", Style::default().fg(theme.key)),
+            Span::raw(": Delete  "),
+            Span::styled("Esc", Style::default().fg(theme.key)),
+            Span::raw(": Clear"),
+        ])]
+    } else if let Some((notification, others)) = notification {
+        let color = match notification.level {
+            Level::Info => theme.success,
+            Level::Warn => theme.warning,
+            Level::Error => theme.error,
+        };
+        let mut spans = vec![Span::styled(notification.message.as_str(), Style::default().fg(color))];
+        if others > 0 {
+            spans.push(Span::styled(format!("  (+{} more, M to view)", others), Style::default().fg(theme.muted)));
+        }
+        vec![Line::from(spans)]
+    } else {
         vec![
             Line::from(vec![
-                Span::styled("↑/↓", Style::default().fg(Color::Yellow)),
+                Span::styled("↑/↓", Style::default().fg(theme.key)),
                 Span::raw(": Navigate  "),
-                Span::styled("Enter", Style::default().fg(Color::Yellow)),
+                Span::styled("Enter", Style::default().fg(theme.key)),
                 Span::raw(": Open  "),
-                Span::styled("d", Style::default().fg(Color::Yellow)),
+                Span::styled("d", Style::default().fg(theme.key)),
                 Span::raw(": Download  "),
-                Span::styled("u", Style::default().fg(Color::Yellow)),
+                Span::styled("u", Style::default().fg(theme.key)),
                 Span::raw(": Upload  "),
-                Span::styled("n", Style::default().fg(Color::Yellow)),
+                Span::styled("n", Style::default().fg(theme.key)),
                 Span::raw(": New Dir  "),
-                Span::styled("r", Style::default().fg(Color::Yellow)),
+                Span::styled("r", Style::default().fg(theme.key)),
                 Span::raw(": Rename  "),
             ]),
             Line::from(vec![
-                Span::styled("Del", Style::default().fg(Color::Yellow)),
+                Span::styled("Del", Style::default().fg(theme.key)),
                 Span::raw(": Delete  "),
-                Span::styled("e", Style::default().fg(Color::Yellow)),
+                Span::styled("e", Style::default().fg(theme.key)),
                 Span::raw(": Execute  "),
-                Span::styled("q", Style::default().fg(Color::Yellow)),
+                Span::styled("q", Style::default().fg(theme.key)),
                 Span::raw(": Quit"),
             ]),
         ]
-    } else {
-        vec![Line::from(Span::styled(
-            &app.status_message,
-            Style::default().fg(Color::Green),
-        ))]
     };
 
+    let totals = Line::from(Span::styled(format!(" {} ", totals_text(app)), Style::default().fg(theme.muted)));
     let footer = Paragraph::new(help_text)
-        .block(Block::default().borders(Borders::ALL).title("Help"))
+        .block(Block::default().borders(Borders::ALL).title("Help").title(totals.right_aligned()))
         .alignment(Alignment::Left);
 
     f.render_widget(footer, area);
 }
 
-fn format_size(bytes: u64) -> String {
+/// Counts and sizes of the listing and the selection, for the footer
+/// border: `12 entries (3 dirs, 9 files), total 1.2 GB; 2 selected (300 MB)`
+fn totals_text(app: &App) -> String {
+    let (dirs, files, size) = app.listing_summary();
+    let plural = |count: usize, one: &str, many: &str| format!("{} {}", count, if count == 1 { one } else { many });
+    let mut text = format!(
+        "{} ({}, {}), total {}",
+        plural(dirs + files, "entry", "entries"),
+        plural(dirs, "dir", "dirs"),
+        plural(files, "file", "files"),
+        format_size(size)
+    );
+    if app.has_selection() {
+        let (count, size) = app.selection_summary();
+        text.push_str(&format!("; {} selected ({})", count, format_size(size)));
+    }
+    text
+}
+
+/// Lines of image metadata shown under an inline image
+const IMAGE_INFO_LINES: u16 = 2;
+
+/// Render the preview pane. Returns the area to draw an inline image in.
+fn render_preview(f: &mut Frame, area: Rect, app: &App, inline_images: bool, theme: &Theme) -> Option<Rect> {
+    let selected = app.get_selected_file();
+    let mut title = selected
+        .map(|file| format!("Preview: {}", file.name))
+        .unwrap_or_else(|| String::from("Preview"));
+
+    let block = Block::default().borders(Borders::ALL);
+    let inner = block.inner(area);
+    let mut image_area = None;
+
+    let dim = Style::default().fg(theme.muted);
+    let lines = match (selected, &app.preview) {
+        (Some(file), Some(preview)) if preview.path == file.path => {
+            if preview.truncated {
+                title.push_str(&format!(" (first {})", format_size(PREVIEW_BYTES as u64)));
+            }
+            match preview.content {
+                PreviewContent::Text(ref text) => text
+                    .lines()
+                    .map(|line| Line::from(line.replace('\t', "    ")))
+                    .collect(),
+                PreviewContent::Binary => vec![Line::from(Span::styled(
+                    format!("Binary file ({})", format_size(file.size)),
+                    dim,
+                ))],
+                PreviewContent::Image { ref info, ref data } => {
+                    let summary = Line::from(format!(
+                        "{} image, {}x{}, {}",
+                        info.format,
+                        info.width,
+                        info.height,
+                        format_size(file.size)
+                    ));
+                    let note = if data.is_none() {
+                        "Too large to display inline"
+                    } else if !inline_images {
+                        "Terminal does not support inline images"
+                    } else {
+                        ""
+                    };
+
+                    if data.is_some() && inline_images && inner.height > IMAGE_INFO_LINES {
+                        image_area = Some(Rect {
+                            height: inner.height - IMAGE_INFO_LINES,
+                            ..inner
+                        });
+                        // Metadata goes below the image
+                        let mut lines = vec![Line::from(""); (inner.height - IMAGE_INFO_LINES) as usize];
+                        lines.push(summary);
+                        lines
+                    } else {
+                        vec![summary, Line::from(Span::styled(note, dim))]
+                    }
+                }
+                PreviewContent::Directory => vec![Line::from(Span::styled("Directory", dim))],
+                PreviewContent::Error(ref e) => vec![Line::from(Span::styled(e.clone(), Style::default().fg(theme.error)))],
+            }
+        }
+        (Some(_), _) => vec![Line::from(Span::styled("Loading...", dim))],
+        (None, _) => Vec::new(),
+    };
+
+    let preview = Paragraph::new(lines).block(block.title(title));
+
+    f.render_widget(preview, area);
+    image_area
+}
+
+/// One column of a file list row, `width` characters wide
+fn column_cell(kind: ColumnKind, file: &FileEntry, width: usize, app: &App, theme: &Theme) -> Span<'static> {
+    match kind {
+        ColumnKind::Icon => Span::raw(app.icons.for_entry(file)),
+        ColumnKind::Name => {
+            let style = if file.broken_link {
+                Style::default().fg(theme.error)
+            } else if file.is_dir {
+                Style::default().fg(theme.directory).add_modifier(Modifier::BOLD)
+            } else if file.link_target.is_some() {
+                Style::default().fg(theme.symlink)
+            } else {
+                Style::default()
+            };
+            let name = match file.link_target {
+                Some(ref target) => fit(&format!("{} -> {}", file.name, target), width),
+                None => fit(&file.name, width),
+            };
+            Span::styled(name, style)
+        }
+        ColumnKind::Size => {
+            let size = if file.is_dir { String::from("<DIR>") } else { format_size(file.size) };
+            Span::styled(format!("{:>width$}", size), Style::default().fg(theme.muted))
+        }
+        ColumnKind::Mtime => {
+            let modified = file.modified.map(format_mtime).unwrap_or_default();
+            Span::styled(fit(&modified, width), Style::default().fg(theme.muted))
+        }
+        ColumnKind::Permissions => {
+            let permissions = file.permissions.map(format_permissions).unwrap_or_else(|| "-".repeat(10));
+            Span::styled(fit(&permissions, width), Style::default().fg(theme.success))
+        }
+        ColumnKind::Owner => {
+            let owner = format!("{}:{}", file.owner.as_deref().unwrap_or("?"), file.group.as_deref().unwrap_or("?"));
+            Span::styled(fit(&owner, width), Style::default().fg(theme.accent))
+        }
+    }
+}
+
+/// Render Unix mode bits like `ls -l`, e.g. `drwxr-xr-x`
+fn format_permissions(mode: u32) -> String {
+    let file_type = match mode & 0o170000 {
+        0o040000 => 'd',
+        0o120000 => 'l',
+        0o020000 => 'c',
+        0o060000 => 'b',
+        0o010000 => 'p',
+        0o140000 => 's',
+        _ => '-',
+    };
+
+    let mut result = String::with_capacity(10);
+    result.push(file_type);
+    for shift in [6, 3, 0] {
+        let bits = (mode >> shift) & 0o7;
+        result.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        result.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        result.push(if bits & 0o1 != 0 { 'x' } else { '-' });
+    }
+    result
+}
+
+fn format_mtime(secs: i64) -> String {
+    chrono::DateTime::from_timestamp(secs, 0)
+        .map(|time| {
+            time.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+        .unwrap_or_default()
+}
+
+pub fn format_size(bytes: u64) -> String {
     const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
     let mut size = bytes as f64;
     let mut unit_index = 0;
@@ -201,9 +971,14 @@ fn format_size(bytes: u64) -> String {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum InputAction {
     MoveUp,
     MoveDown,
+    PageUp,
+    PageDown,
+    First,
+    Last,
     Enter,
     Download,
     Upload,
@@ -212,33 +987,116 @@ pub enum InputAction {
     Delete,
     Execute,
     ToggleShell,
+    Shells,
+    SelectionToShell,
+    ToggleMark,
+    VisualSelect,
+    ClearSelection,
+    ToggleHidden,
+    Filter,
+    ToggleDetails,
+    Chmod,
+    Chown,
+    GotoLinkTarget,
+    TogglePreview,
+    ToggleDetailsPanel,
+    NewTab,
+    SwitchTab(usize),
+    NextTab,
+    PreviousTab,
+    CloseTab,
+    Checksum,
+    WidenPreview,
+    NarrowPreview,
+    ToggleHeaderHints,
+    Extract,
+    Compress,
+    Follow,
+    Command,
+    Refresh,
+    CopyPath,
+    CopyUrl,
+    CopyContents,
+    PasteText,
+    /// Jump to the breadcrumb with this index
+    Ancestor(usize),
+    Back,
+    Forward,
+    Yank,
+    Cut,
+    Paste,
+    Help,
+    NotificationLog,
+    Palette,
+    Jump,
     Quit,
+    RunScript,
+    HalfPageUp,
+    HalfPageDown,
+    /// First key of a sequence such as `gg`, waiting for the second
+    KeyPrefix(char),
+    /// Raw key for an active inline prompt
+    PromptKey(KeyEvent),
+    /// The terminal changed size
+    Resize,
     None,
 }
 
-pub fn handle_input() -> Result<InputAction> {
-    if event::poll(std::time::Duration::from_millis(100))? {
-        if let Event::Key(key) = event::read()? {
-            return Ok(match key.code {
-                KeyCode::Up | KeyCode::Char('k') => InputAction::MoveUp,
-                KeyCode::Down | KeyCode::Char('j') => InputAction::MoveDown,
-                KeyCode::Enter => InputAction::Enter,
-                KeyCode::Char('d') => InputAction::Download,
-                KeyCode::Char('u') => InputAction::Upload,
-                KeyCode::Char('n') => InputAction::NewDirectory,
-                KeyCode::Char('r') => InputAction::Rename,
-                KeyCode::Delete | KeyCode::Char('x') => InputAction::Delete,
-                KeyCode::Char('e') => InputAction::Execute,
-                KeyCode::Char('q') => InputAction::Quit,
-                KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                    InputAction::ToggleShell
-                }
-                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                    InputAction::Quit
-                }
-                _ => InputAction::None,
-            });
+pub fn handle_input(app: &mut App) -> Result<InputAction> {
+    if !event::poll(std::time::Duration::from_millis(100))? {
+        return Ok(InputAction::None);
+    }
+    let event = event::read()?;
+    if let Event::Resize(..) = event {
+        return Ok(InputAction::Resize);
+    }
+    if let Event::Key(key) = event {
+        if app.prompt_active() {
+            return Ok(InputAction::PromptKey(key));
+        }
+
+        let action = keymap::browser_action(key, app.pending_key.take());
+        if let InputAction::KeyPrefix(c) = action {
+            app.pending_key = Some(c);
         }
+        return Ok(action);
     }
     Ok(InputAction::None)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_permissions() {
+        assert_eq!(format_permissions(0o040755), "drwxr-xr-x");
+        assert_eq!(format_permissions(0o100644), "-rw-r--r--");
+        assert_eq!(format_permissions(0o120777), "lrwxrwxrwx");
+        assert_eq!(format_permissions(0o100700), "-rwx------");
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MB");
+    }
+
+    #[test]
+    fn test_totals_text() {
+        let mut app = App::new("user@host:22".to_string());
+        let mut log = FileEntry::new(String::from("app.log"), String::from("/var/log/app.log"), false);
+        log.size = 1536;
+        app.files = vec![
+            FileEntry::parent(),
+            FileEntry::new(String::from("nginx"), String::from("/var/log/nginx"), true),
+            log,
+        ];
+        assert_eq!(totals_text(&app), "2 entries (1 dir, 1 file), total 1.5 KB");
+
+        app.selected_index = 2;
+        app.toggle_mark();
+        assert_eq!(totals_text(&app), "2 entries (1 dir, 1 file), total 1.5 KB; 1 selected (1.5 KB)");
+    }
+}
@@ -0,0 +1,146 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+use serde::{Deserialize, Serialize};
+
+use super::centered_rect;
+use crate::app::{fuzzy_match, TextInput};
+use crate::theme::Theme;
+
+/// Most commands listed at once; typing narrows the rest down
+const VISIBLE_COMMANDS: usize = 12;
+
+/// A frequent remote command, from the config file or a saved connection.
+/// `{}` in the command stands for the selected entry, as at the `e` prompt.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PaletteCommand {
+    pub name: String,
+    pub command: String,
+}
+
+/// What a key press did to the palette
+#[derive(Debug, Clone, PartialEq)]
+pub enum PaletteResult {
+    Picking,
+    Run(PaletteCommand),
+    Cancelled,
+}
+
+/// Fuzzy picker over the configured commands
+pub struct Palette {
+    commands: Vec<PaletteCommand>,
+    query: TextInput,
+    /// Index into the matches
+    selected: usize,
+}
+
+impl Palette {
+    pub fn new(commands: Vec<PaletteCommand>) -> Self {
+        Self {
+            commands,
+            query: TextInput::new(""),
+            selected: 0,
+        }
+    }
+
+    /// Commands whose name or command line fuzzily match the query
+    fn matches(&self) -> Vec<&PaletteCommand> {
+        let query = &self.query.value;
+        self.commands
+            .iter()
+            .filter(|c| fuzzy_match(&c.name, query) || fuzzy_match(&c.command, query))
+            .collect()
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> PaletteResult {
+        match key.code {
+            KeyCode::Esc => return PaletteResult::Cancelled,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return PaletteResult::Cancelled,
+            KeyCode::Enter => {
+                if let Some(command) = self.matches().get(self.selected) {
+                    return PaletteResult::Run((*command).clone());
+                }
+            }
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(self.matches().len().saturating_sub(1)),
+            _ => {
+                self.query.edit(key);
+                self.selected = 0;
+            }
+        }
+        PaletteResult::Picking
+    }
+
+    pub fn render(&self, f: &mut Frame, area: Rect, theme: &Theme) {
+        let matches = self.matches();
+        let shown = matches.len().clamp(1, VISIBLE_COMMANDS);
+        let area = centered_rect(70, shown as u16 + 3, area);
+        let block = Block::default()
+            .title(Span::styled(
+                " Commands: ↑/↓ pick, Enter run, Esc cancel ",
+                Style::default().fg(theme.accent).add_modifier(Modifier::BOLD),
+            ))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.accent));
+
+        let mut lines = vec![Line::from(format!("> {}", self.query.with_cursor()))];
+        if matches.is_empty() {
+            lines.push(Line::from(Span::styled("No matching commands", Style::default().fg(theme.muted))));
+        }
+        // Keep the selection in the window of listed commands
+        let first = self.selected.saturating_sub(VISIBLE_COMMANDS - 1);
+        for (i, command) in matches.iter().enumerate().skip(first).take(VISIBLE_COMMANDS) {
+            let style = if i == self.selected {
+                Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+            } else {
+                Style::default()
+            };
+            lines.push(Line::from(vec![
+                Span::styled(format!("{}  ", command.name), style.add_modifier(Modifier::BOLD)),
+                Span::styled(command.command.clone(), style.fg(theme.muted)),
+            ]));
+        }
+
+        f.render_widget(Clear, area);
+        f.render_widget(Paragraph::new(lines).block(block), area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(name: &str, command: &str) -> PaletteCommand {
+        PaletteCommand {
+            name: name.to_string(),
+            command: command.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_typing_narrows_and_enter_runs_the_pick() {
+        let mut palette = Palette::new(vec![
+            command("restart nginx", "sudo systemctl restart nginx"),
+            command("disk usage", "df -h"),
+            command("containers", "docker ps"),
+        ]);
+        palette.handle_key(KeyEvent::from(KeyCode::Down));
+        for c in "dk".chars() {
+            palette.handle_key(KeyEvent::from(KeyCode::Char(c)));
+        }
+        // "dk" matches "disk usage" by name and "docker ps" by command
+        assert_eq!(palette.matches().len(), 2);
+        palette.handle_key(KeyEvent::from(KeyCode::Down));
+        palette.handle_key(KeyEvent::from(KeyCode::Down));
+        assert_eq!(
+            palette.handle_key(KeyEvent::from(KeyCode::Enter)),
+            PaletteResult::Run(command("containers", "docker ps"))
+        );
+        assert_eq!(palette.handle_key(KeyEvent::from(KeyCode::Esc)), PaletteResult::Cancelled);
+    }
+}
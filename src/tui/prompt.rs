@@ -0,0 +1,214 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use super::centered_rect;
+use crate::app::TextInput;
+use crate::theme::Theme;
+
+/// Most answers kept in a prompt's history
+const HISTORY_LIMIT: usize = 50;
+
+/// Checks a prompt's text before it is accepted, returning why it is not
+pub type Validator = Box<dyn Fn(&str) -> Result<(), String>>;
+
+/// What a key press did to a prompt
+#[derive(Debug, Clone, PartialEq)]
+pub enum PromptResult {
+    Editing,
+    /// Enter on text that passed validation
+    Submitted(String),
+    Cancelled,
+}
+
+/// Earlier answers to one kind of prompt, oldest first
+#[derive(Debug, Clone, Default)]
+pub struct PromptHistory {
+    entries: Vec<String>,
+}
+
+impl PromptHistory {
+    /// Remember `value`, moving it to the end if it was already there
+    pub fn push(&mut self, value: &str) {
+        if value.trim().is_empty() {
+            return;
+        }
+        self.entries.retain(|entry| entry != value);
+        self.entries.push(value.to_string());
+        if self.entries.len() > HISTORY_LIMIT {
+            self.entries.remove(0);
+        }
+    }
+}
+
+/// Modal single-line text prompt with cursor editing, validation and
+/// history recalled with ↑/↓
+pub struct Prompt {
+    title: String,
+    hint: String,
+    pub input: TextInput,
+    validate: Option<Validator>,
+    history: Vec<String>,
+    /// History entry shown, and the text typed before browsing started
+    browsing: Option<(usize, String)>,
+    error: Option<String>,
+}
+
+impl Prompt {
+    /// Rows the prompt takes, with its border
+    pub const HEIGHT: u16 = 4;
+
+    pub fn new(title: &str, value: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            hint: String::new(),
+            input: TextInput::new(value),
+            validate: None,
+            history: Vec::new(),
+            browsing: None,
+            error: None,
+        }
+    }
+
+    /// Muted text under the input, e.g. the accepted syntax
+    pub fn with_hint(mut self, hint: &str) -> Self {
+        self.hint = hint.to_string();
+        self
+    }
+
+    pub fn with_validator(mut self, validate: impl Fn(&str) -> Result<(), String> + 'static) -> Self {
+        self.validate = Some(Box::new(validate));
+        self
+    }
+
+    pub fn with_history(mut self, history: &PromptHistory) -> Self {
+        self.history = history.entries.clone();
+        self
+    }
+
+    /// Change the title while the prompt is open, e.g. to show a mode
+    pub fn set_title(&mut self, title: &str) {
+        self.title = title.to_string();
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> PromptResult {
+        match key.code {
+            KeyCode::Esc => return PromptResult::Cancelled,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return PromptResult::Cancelled,
+            KeyCode::Enter => {
+                match self.validate.as_ref().map_or(Ok(()), |validate| validate(&self.input.value)) {
+                    Ok(()) => return PromptResult::Submitted(self.input.value.clone()),
+                    Err(e) => self.error = Some(e),
+                }
+            }
+            KeyCode::Up => self.recall(true),
+            KeyCode::Down => self.recall(false),
+            _ => {
+                self.input.edit(key);
+                self.error = None;
+            }
+        }
+        PromptResult::Editing
+    }
+
+    /// Step to an older or newer history entry; past the newest is the text
+    /// that was being typed
+    fn recall(&mut self, older: bool) {
+        let len = self.history.len();
+        let current = self.browsing.as_ref().map_or(len, |(index, _)| *index);
+        let next = if older { current.checked_sub(1) } else { (current < len).then_some(current + 1) };
+        let Some(next) = next else {
+            return;
+        };
+
+        let (_, draft) = self.browsing.take().unwrap_or_else(|| (len, self.input.value.clone()));
+        if next == len {
+            self.input = TextInput::new(&draft);
+        } else {
+            self.input = TextInput::new(&self.history[next]);
+            self.browsing = Some((next, draft));
+        }
+        self.error = None;
+    }
+
+    /// Draw the prompt in the middle of `area`
+    pub fn render(&self, f: &mut Frame, area: Rect, theme: &Theme) {
+        self.render_in(f, centered_rect(60, Self::HEIGHT, area), theme);
+    }
+
+    /// Draw the prompt filling `area`
+    pub fn render_in(&self, f: &mut Frame, area: Rect, theme: &Theme) {
+        let below = match self.error {
+            Some(ref error) => Span::styled(error.clone(), Style::default().fg(theme.error)),
+            None => Span::styled(self.hint.clone(), Style::default().fg(theme.muted)),
+        };
+        let block = Block::default()
+            .title(Span::styled(
+                format!(" {} ", self.title),
+                Style::default().fg(theme.accent).add_modifier(Modifier::BOLD),
+            ))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.accent));
+        let lines = vec![Line::from(self.input.with_cursor()), Line::from(below)];
+        f.render_widget(Clear, area);
+        f.render_widget(Paragraph::new(lines).block(block), area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(prompt: &mut Prompt, code: KeyCode) -> PromptResult {
+        prompt.handle_key(KeyEvent::from(code))
+    }
+
+    fn type_text(prompt: &mut Prompt, text: &str) {
+        for c in text.chars() {
+            press(prompt, KeyCode::Char(c));
+        }
+    }
+
+    #[test]
+    fn test_validation_blocks_submit_until_fixed() {
+        let mut prompt = Prompt::new("Name", "").with_validator(|value| {
+            if value.is_empty() { Err(String::from("Name is empty")) } else { Ok(()) }
+        });
+        assert_eq!(press(&mut prompt, KeyCode::Enter), PromptResult::Editing);
+        assert_eq!(prompt.error.as_deref(), Some("Name is empty"));
+
+        type_text(&mut prompt, "ab");
+        assert_eq!(prompt.error, None);
+        press(&mut prompt, KeyCode::Left);
+        type_text(&mut prompt, "x");
+        assert_eq!(press(&mut prompt, KeyCode::Enter), PromptResult::Submitted(String::from("axb")));
+        assert_eq!(press(&mut prompt, KeyCode::Esc), PromptResult::Cancelled);
+    }
+
+    #[test]
+    fn test_history_recall_keeps_the_draft() {
+        let mut history = PromptHistory::default();
+        for value in ["one", "two", "one", " "] {
+            history.push(value);
+        }
+        assert_eq!(history.entries, ["two", "one"]);
+
+        let mut prompt = Prompt::new("Run", "").with_history(&history);
+        type_text(&mut prompt, "dra");
+        press(&mut prompt, KeyCode::Up);
+        assert_eq!(prompt.input.value, "one");
+        press(&mut prompt, KeyCode::Up);
+        press(&mut prompt, KeyCode::Up);
+        assert_eq!(prompt.input.value, "two");
+        press(&mut prompt, KeyCode::Down);
+        press(&mut prompt, KeyCode::Down);
+        assert_eq!(prompt.input.value, "dra");
+        press(&mut prompt, KeyCode::Down);
+        assert_eq!(prompt.input.value, "dra");
+    }
+}
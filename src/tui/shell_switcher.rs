@@ -0,0 +1,137 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use super::centered_rect;
+use crate::theme::Theme;
+
+/// A shell as listed in the switcher
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShellEntry {
+    /// Directory it was started in
+    pub title: String,
+    /// Output arrived since it was last on screen
+    pub has_output: bool,
+}
+
+/// What a key press in the switcher asks for
+#[derive(Debug, Clone, PartialEq)]
+pub enum SwitcherResult {
+    Picking,
+    Open(usize),
+    New,
+    Close(usize),
+    Cancelled,
+}
+
+/// List of the connection's shells, to enter one, start another or close one
+pub struct ShellSwitcher {
+    pub shells: Vec<ShellEntry>,
+    pub selected: usize,
+}
+
+impl ShellSwitcher {
+    pub fn new(shells: Vec<ShellEntry>, active: usize) -> Self {
+        Self {
+            selected: active.min(shells.len().saturating_sub(1)),
+            shells,
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> SwitcherResult {
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => SwitcherResult::Cancelled,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => SwitcherResult::Cancelled,
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.selected = self.selected.saturating_sub(1);
+                SwitcherResult::Picking
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected = (self.selected + 1).min(self.shells.len().saturating_sub(1));
+                SwitcherResult::Picking
+            }
+            KeyCode::Enter if !self.shells.is_empty() => SwitcherResult::Open(self.selected),
+            KeyCode::Char(c @ '1'..='9') => {
+                let index = c as usize - '1' as usize;
+                if index < self.shells.len() { SwitcherResult::Open(index) } else { SwitcherResult::Picking }
+            }
+            KeyCode::Char('n') => SwitcherResult::New,
+            KeyCode::Char('x') | KeyCode::Delete if !self.shells.is_empty() => SwitcherResult::Close(self.selected),
+            _ => SwitcherResult::Picking,
+        }
+    }
+
+    /// The shell at `index` was closed
+    pub fn remove(&mut self, index: usize) {
+        if index < self.shells.len() {
+            self.shells.remove(index);
+            self.selected = self.selected.min(self.shells.len().saturating_sub(1));
+        }
+    }
+
+    pub fn render(&self, f: &mut Frame, area: Rect, theme: &Theme) {
+        let area = centered_rect(60, self.shells.len().max(1) as u16 + 2, area);
+        let block = Block::default()
+            .title(Span::styled(
+                " Shells: Enter open, n new, x close, Esc cancel ",
+                Style::default().fg(theme.accent).add_modifier(Modifier::BOLD),
+            ))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.accent));
+
+        let mut lines = Vec::new();
+        if self.shells.is_empty() {
+            lines.push(Line::from(Span::styled("No shells open; n starts one", Style::default().fg(theme.muted))));
+        }
+        for (i, shell) in self.shells.iter().enumerate() {
+            let style = if i == self.selected {
+                Style::default().bg(theme.selection_bg).fg(theme.selection_fg)
+            } else {
+                Style::default()
+            };
+            let mut spans = vec![
+                Span::styled(format!("{}  ", i + 1), style.fg(theme.key)),
+                Span::styled(shell.title.clone(), style),
+            ];
+            if shell.has_output {
+                spans.push(Span::styled("  new output", style.fg(theme.muted)));
+            }
+            lines.push(Line::from(spans));
+        }
+
+        f.render_widget(Clear, area);
+        f.render_widget(Paragraph::new(lines).block(block), area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shell(title: &str) -> ShellEntry {
+        ShellEntry {
+            title: title.to_string(),
+            has_output: false,
+        }
+    }
+
+    #[test]
+    fn test_pick_and_close_shells() {
+        let mut switcher = ShellSwitcher::new(vec![shell("/var/log"), shell("/srv/app")], 1);
+        assert_eq!(switcher.handle_key(KeyEvent::from(KeyCode::Enter)), SwitcherResult::Open(1));
+        assert_eq!(switcher.handle_key(KeyEvent::from(KeyCode::Char('1'))), SwitcherResult::Open(0));
+        assert_eq!(switcher.handle_key(KeyEvent::from(KeyCode::Char('5'))), SwitcherResult::Picking);
+
+        assert_eq!(switcher.handle_key(KeyEvent::from(KeyCode::Char('x'))), SwitcherResult::Close(1));
+        switcher.remove(1);
+        assert_eq!(switcher.selected, 0);
+        switcher.remove(0);
+        assert_eq!(switcher.handle_key(KeyEvent::from(KeyCode::Enter)), SwitcherResult::Picking);
+        assert_eq!(switcher.handle_key(KeyEvent::from(KeyCode::Char('n'))), SwitcherResult::New);
+    }
+}
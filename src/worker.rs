@@ -0,0 +1,718 @@
+use anyhow::{bail, Result};
+use russh_sftp::client::SftpSession;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+
+use crate::app::{unique_name, ClipboardMode, ConnectionHealth, FileEntry, Preview, PreviewContent, PREVIEW_BYTES};
+use crate::file_ops::archive::{self, ArchiveKind};
+use crate::file_ops::{self, DirectoryStream, DiskUsage};
+use crate::filetype::{self, FileKind, OpenAction};
+use crate::local_fs;
+use crate::ssh::SshClient;
+use crate::tui::image;
+
+/// Tells jobs apart, e.g. to cancel one of them
+pub type JobId = u64;
+
+/// An SFTP operation for the worker
+#[derive(Debug, Clone)]
+pub enum Job {
+    /// List `path` again; `quiet` for the periodic auto-refresh
+    Refresh { path: String, quiet: bool },
+    /// Open `path` to make it the current directory, sending its entries
+    /// batch by batch as they arrive
+    List { path: String },
+    Preview { file: FileEntry },
+    Download {
+        file: FileEntry,
+        local_path: PathBuf,
+        concurrency: usize,
+        on_conflict: OnConflict,
+    },
+    /// Upload the local `file` to `remote_path`
+    Upload {
+        file: FileEntry,
+        remote_path: String,
+        concurrency: usize,
+        on_conflict: OnConflict,
+    },
+    /// Find the local files to upload for `path` as typed: the file, or
+    /// the files directly inside a directory
+    LocalFiles { path: String },
+    /// Change files in the directory `path`, then list it again
+    Change { path: String, change: Change },
+    Checksum { file: FileEntry },
+    /// Time a round trip to the server
+    Health,
+    DiskUsage { path: String },
+    /// Read up to `limit` bytes of `file` for the clipboard
+    ReadText { file: FileEntry, limit: usize },
+    /// Decide how Enter opens `file` by its name and, when that is not
+    /// enough, its first bytes
+    Resolve { file: FileEntry },
+}
+
+/// What a transfer does when its target already exists
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OnConflict {
+    /// Stop and hand back the existing file, to ask the user
+    Ask,
+    Overwrite,
+    /// Pick a free name next to it
+    Rename,
+}
+
+/// Something done to files on the server
+#[derive(Debug, Clone)]
+pub enum Change {
+    Delete(Vec<FileEntry>),
+    /// Each entry with the mode to give it; `mode` is the one picked
+    Chmod { entries: Vec<(FileEntry, u32)>, mode: u32 },
+    /// `spec` is the owner and group as typed, for the report
+    Chown {
+        entries: Vec<FileEntry>,
+        owner: Option<String>,
+        group: Option<String>,
+        spec: String,
+    },
+    Rename { from: String, to: String },
+    /// Each entry with its destination path; `errors` are the entries that
+    /// could not be given one
+    Paste {
+        entries: Vec<(FileEntry, String)>,
+        mode: ClipboardMode,
+        errors: Vec<String>,
+    },
+    Compress { name: String, names: Vec<String>, kind: ArchiveKind },
+    Extract { file: FileEntry, kind: ArchiveKind },
+    /// Write `text` to the file `name`, replacing or appending to it
+    WriteText { name: String, text: String, append: bool },
+}
+
+/// What a job came back with
+#[derive(Debug)]
+pub enum JobResult {
+    Refreshed {
+        path: String,
+        quiet: bool,
+        files: Result<Vec<FileEntry>>,
+    },
+    /// The directory of a `List` job could be opened; `files` is what is
+    /// known of it so far. More follow.
+    Opened { path: String, files: Vec<FileEntry> },
+    /// Entries of an opened directory. More follow.
+    Batch { path: String, files: Vec<FileEntry> },
+    /// The `List` job is done, or why it stopped
+    Listed { result: Result<()> },
+    Previewed(Preview),
+    /// The offset the download resumed from, or why it failed
+    Downloaded {
+        file: FileEntry,
+        local_path: PathBuf,
+        result: Result<u64>,
+    },
+    /// The offset the upload resumed from, or why it failed
+    Uploaded {
+        file: FileEntry,
+        remote_path: String,
+        result: Result<u64>,
+    },
+    LocalFiles { files: Result<Vec<FileEntry>> },
+    /// The target of a transfer asked about exists; `existing` is it
+    Conflict { file: FileEntry, existing: FileEntry },
+    /// The report of a change, the entry to select and the listing after it
+    Changed {
+        path: String,
+        report: Result<String, String>,
+        select: Option<String>,
+        files: Result<Vec<FileEntry>>,
+    },
+    Checksummed { file: FileEntry, result: Result<String> },
+    Health(ConnectionHealth),
+    DiskUsage { path: String, usage: Result<DiskUsage> },
+    TextRead { file: FileEntry, result: Result<Vec<u8>> },
+    Resolved { file: FileEntry, action: OpenAction },
+    /// The job was cancelled before it finished
+    Cancelled(Job),
+}
+
+/// Runs SFTP jobs on background tasks, so the UI keeps drawing and reading
+/// keys while they run. Jobs run side by side and are cancelled one by one.
+pub struct Worker {
+    sftp: Arc<SftpSession>,
+    ssh: SshClient,
+    result_tx: mpsc::UnboundedSender<(JobId, JobResult)>,
+    results: mpsc::UnboundedReceiver<(JobId, JobResult)>,
+    /// Jobs whose last result has not been received
+    running: HashMap<JobId, Running>,
+    next_id: JobId,
+}
+
+struct Running {
+    cancel: watch::Sender<bool>,
+    task: JoinHandle<()>,
+    /// Started by the app rather than the user, so not counted as busy
+    background: bool,
+}
+
+impl Job {
+    /// Whether the app runs this on its own, e.g. the periodic health check
+    fn is_background(&self) -> bool {
+        matches!(self, Job::Health | Job::DiskUsage { .. })
+    }
+}
+
+impl JobResult {
+    /// Path of the preview this is the answer to, if it is one
+    pub fn preview_path(&self) -> Option<&str> {
+        match self {
+            JobResult::Previewed(preview) => Some(&preview.path),
+            JobResult::Cancelled(Job::Preview { file }) => Some(&file.path),
+            _ => None,
+        }
+    }
+
+    /// Whether this is the last result of its job
+    fn is_last(&self) -> bool {
+        !matches!(self, JobResult::Opened { .. } | JobResult::Batch { .. })
+    }
+}
+
+impl Worker {
+    pub fn spawn(sftp: Arc<SftpSession>, ssh: SshClient) -> Self {
+        let (result_tx, results) = mpsc::unbounded_channel();
+        Self {
+            sftp,
+            ssh,
+            result_tx,
+            results,
+            running: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn submit(&mut self, job: Job) -> JobId {
+        self.next_id += 1;
+        let id = self.next_id;
+        let (cancel, cancelled) = watch::channel(false);
+        let results = Results { id, sender: self.result_tx.clone() };
+        let background = job.is_background();
+        let task = tokio::spawn(run(job, self.sftp.clone(), self.ssh.clone(), cancelled, results));
+        self.running.insert(id, Running { cancel, task, background });
+        id
+    }
+
+    /// A result that has arrived, if any, without waiting
+    pub fn try_result(&mut self) -> Option<(JobId, JobResult)> {
+        let (id, result) = self.results.try_recv().ok()?;
+        if result.is_last() {
+            self.running.remove(&id);
+        }
+        Some((id, result))
+    }
+
+    /// Whether a job other than a background check is running
+    pub fn busy(&self) -> bool {
+        self.running.values().any(|running| !running.background)
+    }
+
+    pub fn is_running(&self, id: JobId) -> bool {
+        self.running.contains_key(&id)
+    }
+
+    /// Stop job `id`; it comes back as `JobResult::Cancelled` unless it
+    /// finished first
+    pub fn cancel(&self, id: JobId) {
+        if let Some(running) = self.running.get(&id) {
+            let _ = running.cancel.send(true);
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        for running in self.running.values() {
+            running.task.abort();
+        }
+    }
+}
+
+/// Where a job sends its results
+struct Results {
+    id: JobId,
+    sender: mpsc::UnboundedSender<(JobId, JobResult)>,
+}
+
+impl Results {
+    fn send(&self, result: JobResult) {
+        let _ = self.sender.send((self.id, result));
+    }
+}
+
+async fn run(
+    job: Job,
+    sftp: Arc<SftpSession>,
+    mut ssh: SshClient,
+    mut cancelled: watch::Receiver<bool>,
+    results: Results,
+) {
+    let cancelled_job = job.clone();
+    let result = tokio::select! {
+        result = job.run(&sftp, &mut ssh, &results) => result,
+        _ = cancelled.wait_for(|cancelled| *cancelled) => JobResult::Cancelled(cancelled_job),
+    };
+    results.send(result);
+}
+
+impl Job {
+    async fn run(self, sftp: &SftpSession, ssh: &mut SshClient, results: &Results) -> JobResult {
+        match self {
+            Job::Refresh { path, quiet } => {
+                let files = file_ops::list_directory(sftp, &path).await;
+                JobResult::Refreshed { path, quiet, files }
+            }
+            Job::List { path } => list(sftp, ssh, path, results).await,
+            Job::Preview { file } => {
+                let (content, truncated) = preview_content(sftp, &file).await;
+                JobResult::Previewed(Preview {
+                    path: file.path,
+                    content,
+                    truncated,
+                })
+            }
+            Job::Download {
+                file,
+                mut local_path,
+                concurrency,
+                on_conflict,
+            } => {
+                match on_conflict {
+                    OnConflict::Ask => {
+                        if let Some(existing) = local_fs::stat(&local_path).await {
+                            return JobResult::Conflict { file, existing };
+                        }
+                    }
+                    OnConflict::Rename => local_path = free_local_path(&local_path).await,
+                    OnConflict::Overwrite => {}
+                }
+                let result = file_ops::download_file(sftp, &file.path, &local_path, concurrency).await;
+                JobResult::Downloaded {
+                    file,
+                    local_path,
+                    result,
+                }
+            }
+            Job::Upload {
+                file,
+                mut remote_path,
+                concurrency,
+                on_conflict,
+            } => {
+                match on_conflict {
+                    OnConflict::Ask => {
+                        if let Some(existing) = file_ops::stat(sftp, &remote_path).await {
+                            return JobResult::Conflict { file, existing };
+                        }
+                    }
+                    OnConflict::Rename => remote_path = free_remote_path(sftp, &remote_path).await,
+                    OnConflict::Overwrite => {}
+                }
+                let result = file_ops::upload_file(sftp, Path::new(&file.path), &remote_path, concurrency).await;
+                JobResult::Uploaded {
+                    file,
+                    remote_path,
+                    result,
+                }
+            }
+            Job::LocalFiles { path } => JobResult::LocalFiles {
+                files: local_files(&local_fs::expand_home(&path)).await,
+            },
+            Job::Change { path, change } => {
+                let (report, select) = change.apply(sftp, ssh, &path).await;
+                let files = file_ops::list_directory(sftp, &path).await;
+                JobResult::Changed {
+                    path,
+                    report,
+                    select,
+                    files,
+                }
+            }
+            Job::Checksum { file } => {
+                let result = file_ops::checksum(ssh, &file.path).await;
+                JobResult::Checksummed { file, result }
+            }
+            Job::Health => JobResult::Health(file_ops::check_health(ssh, sftp).await),
+            Job::DiskUsage { path } => {
+                let usage = file_ops::disk_usage(ssh, sftp, &path).await;
+                JobResult::DiskUsage { path, usage }
+            }
+            Job::ReadText { file, limit } => {
+                let result = file_ops::read_head(sftp, &file.path, limit).await;
+                JobResult::TextRead { file, result }
+            }
+            Job::Resolve { file } => {
+                let action = sniff_open_action(sftp, &file).await;
+                JobResult::Resolved { file, action }
+            }
+        }
+    }
+}
+
+/// Stream the listing of `path` on the listing session, or read it in one
+/// go if that session cannot be opened, e.g. because the server limits
+/// SFTP sessions per connection
+async fn list(sftp: &SftpSession, ssh: &SshClient, path: String, results: &Results) -> JobResult {
+    let stream = match ssh.dir_lister().await {
+        Ok(lister) => DirectoryStream::open(lister, &path).await,
+        Err(e) => Err(e),
+    };
+    let mut stream = match stream {
+        Ok(stream) => stream,
+        Err(e) if !file_ops::is_session_error(&e) => return JobResult::Listed { result: Err(e) },
+        Err(_) => {
+            ssh.drop_dir_lister().await;
+            let result = file_ops::list_directory(sftp, &path).await.map(|files| {
+                results.send(JobResult::Opened { path: path.clone(), files });
+            });
+            return JobResult::Listed { result };
+        }
+    };
+
+    let parent = (path != "/").then(FileEntry::parent);
+    results.send(JobResult::Opened {
+        path: path.clone(),
+        files: parent.into_iter().collect(),
+    });
+    loop {
+        match stream.next_batch(sftp).await {
+            Ok(Some(files)) => results.send(JobResult::Batch { path: path.clone(), files }),
+            Ok(None) => return JobResult::Listed { result: Ok(()) },
+            Err(e) => {
+                if file_ops::is_session_error(&e) {
+                    ssh.drop_dir_lister().await;
+                }
+                return JobResult::Listed { result: Err(e) };
+            }
+        }
+    }
+}
+
+impl Change {
+    /// Make the change in `directory`. Returns the report for the status
+    /// line, an error if anything failed, and the entry to select after.
+    async fn apply(
+        self,
+        sftp: &SftpSession,
+        ssh: &mut SshClient,
+        directory: &str,
+    ) -> (Result<String, String>, Option<String>) {
+        match self {
+            Change::Delete(entries) => {
+                let mut failed = Vec::new();
+                let mut last_error = None;
+                for file in &entries {
+                    // Links to directories are removed like files
+                    let result = if file.is_dir && file.link_target.is_none() {
+                        file_ops::delete_directory(sftp, &file.path).await
+                    } else {
+                        file_ops::delete_file(sftp, &file.path).await
+                    };
+                    if let Err(e) = result {
+                        failed.push(file.name.clone());
+                        last_error = Some(e);
+                    }
+                }
+                (delete_report(&entries, &failed, last_error), None)
+            }
+            Change::Chmod { entries, mode } => {
+                let mut errors = Vec::new();
+                for (entry, mode) in &entries {
+                    if let Err(e) = file_ops::set_permissions(sftp, &entry.path, *mode).await {
+                        errors.push(format!("{}: {}", entry.name, e));
+                    }
+                }
+                let report = if errors.is_empty() {
+                    Ok(format!("Changed mode to {:03o} on {} item(s)", mode, entries.len()))
+                } else {
+                    Err(format!("chmod failed: {}", errors.join("; ")))
+                };
+                (report, None)
+            }
+            Change::Chown {
+                entries,
+                owner,
+                group,
+                spec,
+            } => {
+                let result = async {
+                    let uid = match owner {
+                        Some(ref name) => Some(file_ops::lookup_id(ssh, "passwd", name).await?),
+                        None => None,
+                    };
+                    let gid = match group {
+                        Some(ref name) => Some(file_ops::lookup_id(ssh, "group", name).await?),
+                        None => None,
+                    };
+
+                    let mut errors = Vec::new();
+                    for entry in &entries {
+                        if let Err(e) = file_ops::set_owner(sftp, &entry.path, uid, gid).await {
+                            errors.push(format!("{}: {}", entry.name, e));
+                        }
+                    }
+                    Ok::<_, anyhow::Error>(errors)
+                }
+                .await;
+                let report = match result {
+                    Ok(errors) if errors.is_empty() => {
+                        Ok(format!("Changed ownership of {} item(s) to {}", entries.len(), spec))
+                    }
+                    Ok(errors) => Err(format!("chown failed: {}", errors.join("; "))),
+                    Err(e) => Err(format!("chown failed: {}", e)),
+                };
+                (report, None)
+            }
+            Change::Rename { from, to } => {
+                let old_path = file_ops::join_path(directory, &from);
+                let new_path = file_ops::join_path(directory, &to);
+                match file_ops::rename(sftp, &old_path, &new_path).await {
+                    Ok(_) => (Ok(format!("Renamed: {} -> {}", from, to)), Some(to)),
+                    Err(e) => (Err(format!("Rename failed: {}", e)), None),
+                }
+            }
+            Change::Paste {
+                entries,
+                mode,
+                mut errors,
+            } => {
+                let mut pasted = Vec::new();
+                for (entry, destination) in &entries {
+                    let result = match mode {
+                        ClipboardMode::Move => file_ops::rename(sftp, &entry.path, destination).await,
+                        ClipboardMode::Copy => {
+                            file_ops::copy_entry(ssh, sftp, &entry.path, destination, entry.is_dir).await
+                        }
+                    };
+                    match result {
+                        Ok(_) => pasted.push(entry.name.clone()),
+                        Err(e) => errors.push(format!("{}: {}", entry.name, e)),
+                    }
+                }
+                let verb = match mode {
+                    ClipboardMode::Copy => "Copied",
+                    ClipboardMode::Move => "Moved",
+                };
+                let report = if errors.is_empty() {
+                    Ok(format!("{} {} item(s)", verb, pasted.len()))
+                } else {
+                    Err(format!("{} {} item(s); {}", verb, pasted.len(), errors.join("; ")))
+                };
+                (report, pasted.into_iter().next())
+            }
+            Change::Compress { name, names, kind } => {
+                match archive::create_archive(ssh, directory, &name, &names, kind).await {
+                    Ok(_) => (Ok(format!("Created {} from {} item(s)", name, names.len())), Some(name)),
+                    Err(e) => (Err(format!("Compress failed: {:#}", e)), Some(name)),
+                }
+            }
+            Change::Extract { file, kind } => {
+                let report = match archive::extract_archive(ssh, sftp, &file.path, directory, kind).await {
+                    Ok(_) => Ok(format!("Extracted: {}", file.name)),
+                    Err(e) => Err(format!("Extract failed: {:#}", e)),
+                };
+                (report, Some(file.name))
+            }
+            Change::WriteText { name, text, append } => {
+                let path = file_ops::join_path(directory, &name);
+                match file_ops::write_text(sftp, &path, &text, append).await {
+                    Ok(_) => {
+                        let verb = if append { "Appended" } else { "Wrote" };
+                        (Ok(format!("{} {} bytes to {}", verb, text.len(), name)), Some(name))
+                    }
+                    Err(e) => (Err(format!("Write failed: {:#}", e)), None),
+                }
+            }
+        }
+    }
+}
+
+/// The file at `path`, or the files directly inside it if it is a directory
+async fn local_files(path: &Path) -> Result<Vec<FileEntry>> {
+    let Some(entry) = local_fs::stat(path).await else {
+        bail!("No such file: {}", path.display());
+    };
+    if !entry.is_dir {
+        return Ok(vec![entry]);
+    }
+    let files: Vec<FileEntry> = local_fs::list_directory(path)
+        .await?
+        .into_iter()
+        .filter(|f| !f.is_dir)
+        .collect();
+    if files.is_empty() {
+        bail!("No files in {}", path.display());
+    }
+    Ok(files)
+}
+
+/// `path`, or the first `stem (n).ext` next to it that is free. One
+/// listing rather than a lookup per candidate name.
+async fn free_local_path(path: &Path) -> PathBuf {
+    let Some(name) = path.file_name() else {
+        return path.to_path_buf();
+    };
+    let dir = path.parent().filter(|parent| !parent.as_os_str().is_empty());
+    let taken: HashSet<String> = local_fs::list_directory(dir.unwrap_or(Path::new(".")))
+        .await
+        .map(|files| files.into_iter().map(|f| f.name).collect())
+        .unwrap_or_default();
+    path.with_file_name(unique_name(&name.to_string_lossy(), |name| taken.contains(name)))
+}
+
+/// `path`, or the first `stem (n).ext` next to it that is free on the
+/// server
+async fn free_remote_path(sftp: &SftpSession, path: &str) -> String {
+    let (dir, name) = file_ops::split_path(path);
+    let taken: HashSet<String> = file_ops::list_directory(sftp, dir)
+        .await
+        .map(|files| files.into_iter().map(|f| f.name).collect())
+        .unwrap_or_default();
+    file_ops::join_path(dir, &unique_name(name, |name| taken.contains(name)))
+}
+
+/// How Enter opens `file` when no `open_with` rule names it: by its type,
+/// sniffing the first bytes when the name says nothing
+async fn sniff_open_action(sftp: &SftpSession, file: &FileEntry) -> OpenAction {
+    let head = || file_ops::read_head(sftp, &file.path, filetype::SNIFF_BYTES);
+    let kind = match FileKind::from_name(&file.name) {
+        // A text name on binary content, e.g. a compressed `app.log`
+        Some(kind @ (FileKind::Text | FileKind::Log)) => match head().await {
+            Ok(bytes) if filetype::looks_binary(&bytes) => FileKind::Binary,
+            _ => kind,
+        },
+        Some(kind) => kind,
+        None => match head().await {
+            Ok(bytes) => FileKind::sniff(&bytes),
+            // Let the editor report why the file cannot be read
+            Err(_) => FileKind::Text,
+        },
+    };
+    kind.default_action()
+}
+
+/// Status line after deleting `entries`, of which `failed` could not be
+fn delete_report(entries: &[FileEntry], failed: &[String], last_error: Option<anyhow::Error>) -> Result<String, String> {
+    match (entries, last_error) {
+        ([file], None) => Ok(format!("Deleted: {}", file.name)),
+        ([_], Some(e)) => Err(format!("Delete failed: {}", e)),
+        (_, None) => Ok(format!("Deleted {} items", entries.len())),
+        (_, Some(_)) => Err(format!(
+            "Deleted {} of {} items; failed: {}",
+            entries.len() - failed.len(),
+            entries.len(),
+            failed.join(", ")
+        )),
+    }
+}
+
+/// What the preview pane shows for `file`, and whether it was cut short
+async fn preview_content(sftp: &SftpSession, file: &FileEntry) -> (PreviewContent, bool) {
+    if file.is_dir {
+        (PreviewContent::Directory, false)
+    } else if image::is_image_name(&file.name) {
+        (image_preview(sftp, &file.path, file.size).await, false)
+    } else {
+        let truncated = file.size > PREVIEW_BYTES as u64;
+        match file_ops::read_head(sftp, &file.path, PREVIEW_BYTES).await {
+            Ok(bytes) => (PreviewContent::from_bytes(&bytes), truncated),
+            Err(e) => (PreviewContent::Error(format!("{:#}", e)), false),
+        }
+    }
+}
+
+/// Read an image for the preview pane, keeping only its header when it is
+/// too large to transfer for display
+async fn image_preview(sftp: &SftpSession, path: &str, size: u64) -> PreviewContent {
+    let fits = size <= image::IMAGE_PREVIEW_MAX_BYTES;
+    let limit = if fits { size as usize } else { PREVIEW_BYTES };
+
+    let bytes = match file_ops::read_head(sftp, path, limit).await {
+        Ok(bytes) => bytes,
+        Err(e) => return PreviewContent::Error(format!("{:#}", e)),
+    };
+
+    match image::probe(&bytes) {
+        Some(info) => PreviewContent::Image {
+            info,
+            data: fits.then(|| Arc::new(bytes)),
+        },
+        None => PreviewContent::from_bytes(&bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delete_report() {
+        let entry = |name: &str| FileEntry::new(name.to_string(), format!("/srv/{}", name), false);
+        let one = [entry("a.log")];
+        let three = [entry("a.log"), entry("b.log"), entry("c.log")];
+
+        assert_eq!(delete_report(&one, &[], None), Ok(String::from("Deleted: a.log")));
+        assert_eq!(
+            delete_report(&one, &[String::from("a.log")], Some(anyhow::anyhow!("Permission denied"))),
+            Err(String::from("Delete failed: Permission denied"))
+        );
+        assert_eq!(delete_report(&three, &[], None), Ok(String::from("Deleted 3 items")));
+        assert_eq!(
+            delete_report(&three, &[String::from("b.log")], Some(anyhow::anyhow!("Permission denied"))),
+            Err(String::from("Deleted 2 of 3 items; failed: b.log"))
+        );
+    }
+
+    #[test]
+    fn test_only_checks_run_in_the_background() {
+        assert!(Job::Health.is_background());
+        assert!(Job::DiskUsage { path: String::from("/srv") }.is_background());
+        assert!(!Job::Refresh { path: String::from("/srv"), quiet: true }.is_background());
+    }
+
+    #[test]
+    fn test_listing_batches_are_not_the_last_result() {
+        let batch = JobResult::Batch { path: String::from("/srv"), files: Vec::new() };
+        assert!(!batch.is_last());
+        let listed = JobResult::Listed { result: Ok(()) };
+        assert!(listed.is_last());
+    }
+
+    #[tokio::test]
+    async fn test_renamed_downloads_take_a_free_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        assert_eq!(free_local_path(&path).await, path);
+
+        std::fs::write(&path, "").unwrap();
+        std::fs::write(dir.path().join("a (1).txt"), "").unwrap();
+        assert_eq!(free_local_path(&path).await, dir.path().join("a (2).txt"));
+    }
+
+    #[tokio::test]
+    async fn test_uploading_a_directory_takes_the_files_in_it() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(local_files(dir.path()).await.is_err());
+
+        std::fs::write(dir.path().join("a.txt"), "").unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        let names: Vec<String> = local_files(dir.path()).await.unwrap().into_iter().map(|f| f.name).collect();
+        assert_eq!(names, ["a.txt"]);
+
+        let file = local_files(&dir.path().join("a.txt")).await.unwrap();
+        assert_eq!(file[0].path, dir.path().join("a.txt").to_string_lossy());
+        assert!(local_files(&dir.path().join("missing")).await.is_err());
+    }
+}
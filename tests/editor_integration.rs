@@ -1,3 +1,5 @@
+#![allow(clippy::assertions_on_constants)]
+
 // Integration tests for the editor module
 // These tests simulate realistic editing workflows
 